@@ -0,0 +1,900 @@
+//! Typed async client for the pods API. One method per route in `routes.md`,
+//! taking and returning the server's own types from [`pods_types`].
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), pods_client::Error> {
+//! let pods = pods_client::Client::new("http://localhost:3000")?;
+//! let me = pods.create_user("alice").await?;
+//! pods.login(me.id).await?;
+//! pods.subscribe("https://example.com/feed.xml").await?;
+//! for podcast in pods.subscriptions(me.id).await? {
+//!     println!("{}", podcast.name);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use pods_types::{
+    admin::{StorageReport, UserPage, UserQuery},
+    annotations::{Annotation, CreateAnnotation},
+    archive::{ArchiveStatus, SetArchive},
+    bundles::{Bundle, CreateBundle},
+    chapters::ChapterProgress,
+    discovery::{DirectoryHit, DirectorySearch, TrendingQuery},
+    downloads::{Download, DownloadLatest, EnqueueDownload},
+    engagement::{EngagementQuery, PodcastEngagement, PodcastEngagementReport},
+    gpodder::{EpisodeAction, GpodderExport},
+    health::FeedHealth,
+    inbox::Inbox,
+    instance::{InstanceSettings, InstanceSettingsReport},
+    integrity::VerifyReport,
+    media_links::{CreateMediaLink, MediaLink},
+    merge::{MergeReport, MergeRequest},
+    poll::{Poll, PollQuery},
+    profiles::CreateProfile,
+    public::{PodcastPage, PodcastQuery},
+    quota::{QuotaOverride, Usage},
+    refresh::{RefreshOverride, RefreshRun, RefreshRunReport, RefreshSchedule},
+    request_quota::RequestQuota,
+    resume::{Playback, ResumePosition},
+    scrobble::{ScrobbleTarget, Scrobbler},
+    service_accounts::{
+        AddPodcast, CreateServiceAccount, CreatedServiceAccount, Scope, ServiceAccount,
+    },
+    settings::UserSettings,
+    stats::{ListeningStats, StatsQuery},
+    stream::{AudioQuery, Quality},
+    subscriptions::{Reorder, SubscriptionOverride},
+    tags::EmbeddedTags,
+    tenants::{CreateTenant, NewTenant, Tenant},
+    transcripts::{Transcript, TranscriptHit, TranscriptQuery, TranscriptionJob},
+    voice::{VoiceRequest, VoiceResponse},
+    ApiError, CreateUser, Episode, EpisodeFilter, PodcastChannel, Subscribe, SubscribeQuery,
+    Subscribed, Today, User, UserStatus,
+};
+use reqwest::{header, Method, RequestBuilder, Url};
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+
+pub use pods_types as types;
+pub use pods_types::profiles::HEADER as PROFILE_HEADER;
+pub use pods_types::signing::{SignatureError, HEADER as SIGNATURE_HEADER};
+pub use reqwest::StatusCode;
+
+/// Checks the [`SIGNATURE_HEADER`] of an event the server sent, against the
+/// endpoint's secret and the raw body, allowing five minutes of clock skew.
+pub fn verify_signature(secret: &[u8], header: &str, body: &[u8]) -> Result<(), SignatureError> {
+    pods_types::signing::verify(
+        secret,
+        header,
+        body,
+        Utc::now().timestamp(),
+        pods_types::signing::DEFAULT_TOLERANCE_SECS,
+    )
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The base URL couldn't be parsed.
+    Url(url::ParseError),
+    /// The request failed, or the response wasn't what the route returns.
+    Http(reqwest::Error),
+    /// The server answered with a non-success status. `body` is whatever it
+    /// sent, often `null`.
+    Status { status: StatusCode, body: String },
+}
+
+impl Error {
+    /// The error code and translated message, if the server sent them.
+    pub fn api_error(&self) -> Option<ApiError> {
+        match self {
+            Error::Status { body, .. } => serde_json::from_str(body).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Url(e) => write!(f, "invalid base URL: {}", e),
+            Error::Http(e) => write!(f, "{}", e),
+            Error::Status { status, body } => write!(f, "{}: {}", status, body),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Url(e) => Some(e),
+            Error::Http(e) => Some(e),
+            Error::Status { .. } => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Error {
+        Error::Http(e)
+    }
+}
+
+/// A pods server. The server tracks who is logged in itself, so every
+/// client talking to it shares the login.
+#[derive(Clone, Debug)]
+pub struct Client {
+    base: Url,
+    http: reqwest::Client,
+    profile: Option<Uuid>,
+    token: Option<String>,
+}
+
+impl Client {
+    /// `base` is where the API is mounted, e.g. `http://localhost:3000`.
+    pub fn new(base: &str) -> Result<Client, Error> {
+        Client::with_http(base, reqwest::Client::new())
+    }
+
+    /// Like [`Client::new`], reusing a configured `reqwest` client (for
+    /// timeouts, proxies, ...).
+    pub fn with_http(base: &str, http: reqwest::Client) -> Result<Client, Error> {
+        let mut base = Url::parse(base).map_err(Error::Url)?;
+        // Join paths under the base rather than replacing its last segment
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        Ok(Client {
+            base,
+            http,
+            profile: None,
+            token: None,
+        })
+    }
+
+    /// A copy of this client whose requests act as one of the logged in
+    /// account's profiles. Routes under the account's `users/<user ID>` are
+    /// answered for the profile.
+    pub fn as_profile(&self, profile: Uuid) -> Client {
+        Client {
+            profile: Some(profile),
+            ..self.clone()
+        }
+    }
+
+    /// A copy of this client that sends a service account's token. Only
+    /// the routes of the account's scopes answer it.
+    pub fn with_token(&self, token: &str) -> Client {
+        Client {
+            token: Some(token.to_string()),
+            ..self.clone()
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let url = self.base.join(path).expect("route paths are relative");
+        let mut req = self.http.request(method, url);
+        if let Some(p) = self.profile {
+            req = req.header(PROFILE_HEADER, p.to_string());
+        }
+        if let Some(token) = &self.token {
+            req = req.bearer_auth(token);
+        }
+        req
+    }
+
+    async fn send(req: RequestBuilder) -> Result<reqwest::Response, Error> {
+        let resp = req.send().await?;
+        let status = resp.status();
+        if status.is_success() {
+            Ok(resp)
+        } else {
+            let body = resp.text().await.unwrap_or_default();
+            Err(Error::Status { status, body })
+        }
+    }
+
+    async fn json<T: DeserializeOwned>(req: RequestBuilder) -> Result<T, Error> {
+        Ok(Client::send(req).await?.json().await?)
+    }
+
+    /// `GET /`
+    pub async fn hello(&self) -> Result<String, Error> {
+        Client::json(self.request(Method::GET, "")).await
+    }
+
+    /// `POST /users`. The first user created is the admin. When registration
+    /// is closed only admins can add users.
+    pub async fn create_user(&self, name: &str) -> Result<User, Error> {
+        let body = CreateUser {
+            name: name.to_string(),
+        };
+        Client::json(self.request(Method::POST, "users").json(&body)).await
+    }
+
+    /// `GET /users/<user ID>`
+    pub async fn user(&self, user: Uuid) -> Result<User, Error> {
+        Client::json(self.request(Method::GET, &format!("users/{}", user))).await
+    }
+
+    /// `GET /login`
+    pub async fn login_status(&self) -> Result<UserStatus, Error> {
+        Client::json(self.request(Method::GET, "login")).await
+    }
+
+    /// `POST /login/<user ID>`
+    pub async fn login(&self, user: Uuid) -> Result<(), Error> {
+        Client::send(self.request(Method::POST, &format!("login/{}", user))).await?;
+        Ok(())
+    }
+
+    /// `POST /users/<user ID>/episode_actions`
+    pub async fn upload_episode_actions(
+        &self,
+        user: Uuid,
+        actions: &[EpisodeAction],
+    ) -> Result<(), Error> {
+        let path = format!("users/{}/episode_actions", user);
+        Client::send(self.request(Method::POST, &path).json(actions)).await?;
+        Ok(())
+    }
+
+    /// `GET /users/<user ID>/export/gpodder`
+    pub async fn export_gpodder(&self, user: Uuid) -> Result<GpodderExport, Error> {
+        let path = format!("users/{}/export/gpodder", user);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /users/<user ID>/export/markdown`, whose body is a zip of
+    /// Markdown notes.
+    pub async fn export_markdown(&self, user: Uuid) -> Result<reqwest::Response, Error> {
+        let path = format!("users/{}/export/markdown", user);
+        Client::send(self.request(Method::GET, &path)).await
+    }
+
+    /// `POST /export/bundle`: starts zipping the episodes for offline use.
+    /// Poll [`Client::bundle`] until it's ready.
+    pub async fn create_bundle(&self, episodes: Vec<Uuid>) -> Result<Bundle, Error> {
+        let body = CreateBundle { episodes };
+        Client::json(self.request(Method::POST, "export/bundle").json(&body)).await
+    }
+
+    /// `GET /export/bundle/<bundle ID>`
+    pub async fn bundle(&self, bundle: Uuid) -> Result<Bundle, Error> {
+        let path = format!("export/bundle/{}", bundle);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /export/bundle/<bundle ID>/download`, whose body is the zip.
+    pub async fn download_bundle(&self, bundle: Uuid) -> Result<reqwest::Response, Error> {
+        let path = format!("export/bundle/{}/download", bundle);
+        Client::send(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /users/<user ID>/episodes/<episode ID>/annotations`
+    pub async fn annotations(&self, user: Uuid, episode: Uuid) -> Result<Vec<Annotation>, Error> {
+        let path = format!("users/{}/episodes/{}/annotations", user, episode);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `POST /users/<user ID>/episodes/<episode ID>/annotations`
+    pub async fn annotate(
+        &self,
+        user: Uuid,
+        episode: Uuid,
+        annotation: &CreateAnnotation,
+    ) -> Result<Annotation, Error> {
+        let path = format!("users/{}/episodes/{}/annotations", user, episode);
+        Client::json(self.request(Method::POST, &path).json(annotation)).await
+    }
+
+    /// `DELETE /users/<user ID>/annotations/<annotation ID>`
+    pub async fn delete_annotation(&self, user: Uuid, annotation: Uuid) -> Result<(), Error> {
+        let path = format!("users/{}/annotations/{}", user, annotation);
+        Client::send(self.request(Method::DELETE, &path)).await?;
+        Ok(())
+    }
+
+    /// `POST /podcast`: subscribes the logged in user, returning the podcast,
+    /// their subscribed feeds and any problems found in the feed.
+    pub async fn subscribe(&self, rss: &str) -> Result<Subscribed, Error> {
+        let body = Subscribe::Rss {
+            rss: rss.to_string(),
+        };
+        self.subscribe_to(&body).await
+    }
+
+    /// `POST /podcast` with a feed URL or a directory ID.
+    pub async fn subscribe_to(&self, what: &Subscribe) -> Result<Subscribed, Error> {
+        self.subscribe_with(what, &SubscribeQuery::default()).await
+    }
+
+    /// [`Client::subscribe_to`], also downloading the newest episodes if
+    /// `options` asks.
+    pub async fn subscribe_with(
+        &self,
+        what: &Subscribe,
+        options: &SubscribeQuery,
+    ) -> Result<Subscribed, Error> {
+        let req = self.request(Method::POST, "podcast").query(options);
+        Client::json(req.json(what)).await
+    }
+
+    /// `GET /discover/search`: podcasts, or episodes with their podcasts,
+    /// from the instance's directories. Subscribe to a hit with its `id`.
+    pub async fn search_directory(
+        &self,
+        search: &DirectorySearch,
+    ) -> Result<Vec<DirectoryHit>, Error> {
+        Client::json(self.request(Method::GET, "discover/search").query(search)).await
+    }
+
+    /// `GET /discover/trending`
+    pub async fn trending(&self, query: &TrendingQuery) -> Result<Vec<DirectoryHit>, Error> {
+        Client::json(self.request(Method::GET, "discover/trending").query(query)).await
+    }
+
+    /// `GET /users/<user ID>/podcasts`
+    pub async fn subscriptions(&self, user: Uuid) -> Result<Vec<PodcastChannel>, Error> {
+        let path = format!("users/{}/podcasts", user);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `PUT /users/<user ID>/podcasts/order`
+    pub async fn reorder_subscriptions(
+        &self,
+        user: Uuid,
+        podcasts: &[Uuid],
+    ) -> Result<Vec<PodcastChannel>, Error> {
+        let path = format!("users/{}/podcasts/order", user);
+        let body = Reorder {
+            podcasts: podcasts.to_vec(),
+        };
+        Client::json(self.request(Method::PUT, &path).json(&body)).await
+    }
+
+    /// `PUT /users/<user ID>/podcasts/<podcast ID>`
+    pub async fn set_subscription_override(
+        &self,
+        user: Uuid,
+        podcast: Uuid,
+        custom: &SubscriptionOverride,
+    ) -> Result<PodcastChannel, Error> {
+        let path = format!("users/{}/podcasts/{}", user, podcast);
+        Client::json(self.request(Method::PUT, &path).json(custom)).await
+    }
+
+    /// `GET /podcasts/<podcast ID>/episodes`, newest first.
+    pub async fn episodes(
+        &self,
+        podcast: Uuid,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Episode>, Error> {
+        let path = format!("podcasts/{}/episodes", podcast);
+        let query = EpisodeFilter { since };
+        Client::json(self.request(Method::GET, &path).query(&query)).await
+    }
+
+    /// `GET /podcasts/<podcast ID>/health`
+    pub async fn feed_health(&self, podcast: Uuid) -> Result<FeedHealth, Error> {
+        let path = format!("podcasts/{}/health", podcast);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /public/podcasts`, which needs no login: the catalog by name,
+    /// filtered by `query.q`.
+    pub async fn public_podcasts(&self, query: &PodcastQuery) -> Result<PodcastPage, Error> {
+        Client::json(self.request(Method::GET, "public/podcasts").query(query)).await
+    }
+
+    /// `GET /public/podcasts/<podcast ID>`
+    pub async fn public_podcast(&self, podcast: Uuid) -> Result<PodcastChannel, Error> {
+        Client::json(self.request(Method::GET, &format!("public/podcasts/{}", podcast))).await
+    }
+
+    /// `GET /public/podcasts/<podcast ID>/episodes`, newest first.
+    pub async fn public_episodes(&self, podcast: Uuid) -> Result<Vec<Episode>, Error> {
+        let path = format!("public/podcasts/{}/episodes", podcast);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /users/<user ID>/today`
+    pub async fn today(&self, user: Uuid) -> Result<Today, Error> {
+        Client::json(self.request(Method::GET, &format!("users/{}/today", user))).await
+    }
+
+    /// `GET /users/<user ID>/stats`: the last `weeks` weeks of listening,
+    /// 12 if unset, and streaks.
+    pub async fn stats(&self, user: Uuid, weeks: Option<u32>) -> Result<ListeningStats, Error> {
+        let path = format!("users/{}/stats", user);
+        let query = StatsQuery { weeks };
+        Client::json(self.request(Method::GET, &path).query(&query)).await
+    }
+
+    /// `GET /users/<user ID>/episodes/<episode ID>/resume`: where to pick the
+    /// episode back up, with the user's rewind rules applied.
+    pub async fn resume_position(
+        &self,
+        user: Uuid,
+        episode: Uuid,
+    ) -> Result<ResumePosition, Error> {
+        let path = format!("users/{}/episodes/{}/resume", user, episode);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /users/<user ID>/episodes/<episode ID>/playback`: where to
+    /// start and what to skip.
+    pub async fn playback(&self, user: Uuid, episode: Uuid) -> Result<Playback, Error> {
+        let path = format!("users/{}/episodes/{}/playback", user, episode);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /users/<user ID>/episodes/<episode ID>/chapters`: how much of
+    /// each chapter the user has heard.
+    pub async fn chapter_progress(
+        &self,
+        user: Uuid,
+        episode: Uuid,
+    ) -> Result<ChapterProgress, Error> {
+        let path = format!("users/{}/episodes/{}/chapters", user, episode);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /users/<user ID>/profiles`
+    pub async fn profiles(&self, user: Uuid) -> Result<Vec<User>, Error> {
+        Client::json(self.request(Method::GET, &format!("users/{}/profiles", user))).await
+    }
+
+    /// `POST /users/<user ID>/profiles`
+    pub async fn create_profile(&self, user: Uuid, name: &str) -> Result<User, Error> {
+        let body = CreateProfile {
+            name: name.to_string(),
+        };
+        let path = format!("users/{}/profiles", user);
+        Client::json(self.request(Method::POST, &path).json(&body)).await
+    }
+
+    /// `DELETE /users/<user ID>/profiles/<profile ID>`
+    pub async fn delete_profile(&self, user: Uuid, profile: Uuid) -> Result<(), Error> {
+        let path = format!("users/{}/profiles/{}", user, profile);
+        Client::send(self.request(Method::DELETE, &path)).await?;
+        Ok(())
+    }
+
+    /// `GET /users/<user ID>/inbox`
+    pub async fn inbox(&self, user: Uuid) -> Result<Inbox, Error> {
+        Client::json(self.request(Method::GET, &format!("users/{}/inbox", user))).await
+    }
+
+    /// `POST /users/<user ID>/inbox/<episode ID>/queue`
+    pub async fn queue_from_inbox(&self, user: Uuid, episode: Uuid) -> Result<Inbox, Error> {
+        let path = format!("users/{}/inbox/{}/queue", user, episode);
+        Client::json(self.request(Method::POST, &path)).await
+    }
+
+    /// `POST /users/<user ID>/inbox/<episode ID>/played`
+    pub async fn mark_played(&self, user: Uuid, episode: Uuid) -> Result<Inbox, Error> {
+        let path = format!("users/{}/inbox/{}/played", user, episode);
+        Client::json(self.request(Method::POST, &path)).await
+    }
+
+    /// `POST /users/<user ID>/inbox/<episode ID>/dismiss`
+    pub async fn dismiss(&self, user: Uuid, episode: Uuid) -> Result<Inbox, Error> {
+        let path = format!("users/{}/inbox/{}/dismiss", user, episode);
+        Client::json(self.request(Method::POST, &path)).await
+    }
+
+    /// `GET /users/<user ID>/queue`
+    pub async fn queue(&self, user: Uuid) -> Result<Vec<Episode>, Error> {
+        Client::json(self.request(Method::GET, &format!("users/{}/queue", user))).await
+    }
+
+    /// `GET /poll`: the logged in user's events after `since`, waiting up to
+    /// `wait` seconds (25 if unset) for some. A `reqwest` client timeout has
+    /// to allow for the wait.
+    pub async fn poll(&self, since: Option<u64>, wait: Option<u32>) -> Result<Poll, Error> {
+        let query = PollQuery { since, wait };
+        Client::json(self.request(Method::GET, "poll").query(&query)).await
+    }
+
+    /// `GET /episodes/<episode ID>/audio`. `range` is passed on as the
+    /// `Range` header; read the body from the returned response.
+    pub async fn audio(
+        &self,
+        episode: Uuid,
+        range: Option<&str>,
+    ) -> Result<reqwest::Response, Error> {
+        self.audio_at(episode, Quality::Original, range).await
+    }
+
+    /// [`Client::audio`] in another quality.
+    pub async fn audio_at(
+        &self,
+        episode: Uuid,
+        quality: Quality,
+        range: Option<&str>,
+    ) -> Result<reqwest::Response, Error> {
+        let query = AudioQuery {
+            quality,
+            trim: false,
+        };
+        self.audio_with(episode, &query, range).await
+    }
+
+    /// [`Client::audio`] with every option of the query, like a `low`
+    /// rendition with the user's intro cut out.
+    pub async fn audio_with(
+        &self,
+        episode: Uuid,
+        query: &AudioQuery,
+        range: Option<&str>,
+    ) -> Result<reqwest::Response, Error> {
+        let mut req = self
+            .request(Method::GET, &format!("episodes/{}/audio", episode))
+            .query(query);
+        if let Some(range) = range {
+            req = req.header(header::RANGE, range);
+        }
+        Client::send(req).await
+    }
+
+    /// `POST /episodes/<episode ID>/audio/link`, a signed link to the audio
+    /// for players that can't log in.
+    pub async fn audio_link(
+        &self,
+        episode: Uuid,
+        req: &CreateMediaLink,
+    ) -> Result<MediaLink, Error> {
+        let path = format!("episodes/{}/audio/link", episode);
+        Client::json(self.request(Method::POST, &path).json(req)).await
+    }
+
+    /// `POST /voice`, a voice-assistant skill's intent for a linked user.
+    pub async fn voice(&self, req: &VoiceRequest) -> Result<VoiceResponse, Error> {
+        Client::json(self.request(Method::POST, "voice").json(req)).await
+    }
+
+    /// `GET /episodes/<episode ID>/tags`, once the episode was downloaded.
+    pub async fn episode_tags(&self, episode: Uuid) -> Result<EmbeddedTags, Error> {
+        let path = format!("episodes/{}/tags", episode);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /episodes/<episode ID>/artwork`: the cover embedded in the
+    /// downloaded file.
+    pub async fn episode_artwork(&self, episode: Uuid) -> Result<reqwest::Response, Error> {
+        let path = format!("episodes/{}/artwork", episode);
+        Client::send(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /episodes/<episode ID>/transcript`
+    pub async fn transcript(&self, episode: Uuid) -> Result<Transcript, Error> {
+        let path = format!("episodes/{}/transcript", episode);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /transcripts/search`: episodes whose transcripts have every word
+    /// of `q`, optionally only from podcasts in `lang`.
+    pub async fn search_transcripts(
+        &self,
+        q: &str,
+        lang: Option<&str>,
+    ) -> Result<Vec<TranscriptHit>, Error> {
+        let query = TranscriptQuery {
+            q: q.to_string(),
+            lang: lang.map(|l| l.to_string()),
+        };
+        Client::json(
+            self.request(Method::GET, "transcripts/search")
+                .query(&query),
+        )
+        .await
+    }
+
+    /// `POST /users/<user ID>/podcasts/<podcast ID>/downloads`
+    pub async fn download_latest(
+        &self,
+        user: Uuid,
+        podcast: Uuid,
+        latest: usize,
+    ) -> Result<Vec<Download>, Error> {
+        let path = format!("users/{}/podcasts/{}/downloads", user, podcast);
+        let body = DownloadLatest { latest };
+        Client::json(self.request(Method::POST, &path).json(&body)).await
+    }
+
+    /// `POST /users/<user ID>/downloads`
+    pub async fn enqueue_download(
+        &self,
+        user: Uuid,
+        episode: Uuid,
+        background: bool,
+    ) -> Result<Download, Error> {
+        let path = format!("users/{}/downloads", user);
+        let body = EnqueueDownload {
+            episode,
+            background,
+        };
+        Client::json(self.request(Method::POST, &path).json(&body)).await
+    }
+
+    /// `GET /users/<user ID>/downloads`
+    pub async fn downloads(&self, user: Uuid) -> Result<Vec<Download>, Error> {
+        let path = format!("users/{}/downloads", user);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /users/<user ID>/usage`
+    pub async fn usage(&self, user: Uuid) -> Result<Usage, Error> {
+        Client::json(self.request(Method::GET, &format!("users/{}/usage", user))).await
+    }
+
+    /// `GET /users/<ID>/quota`, for a user or, as an admin, a service
+    /// account.
+    pub async fn request_quota(&self, id: Uuid) -> Result<RequestQuota, Error> {
+        Client::json(self.request(Method::GET, &format!("users/{}/quota", id))).await
+    }
+
+    /// `GET /users/<user ID>/settings`
+    pub async fn settings(&self, user: Uuid) -> Result<UserSettings, Error> {
+        let path = format!("users/{}/settings", user);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `PUT /users/<user ID>/settings`
+    pub async fn set_settings(
+        &self,
+        user: Uuid,
+        settings: &UserSettings,
+    ) -> Result<UserSettings, Error> {
+        let path = format!("users/{}/settings", user);
+        Client::json(self.request(Method::PUT, &path).json(settings)).await
+    }
+
+    /// `GET /users/<user ID>/scrobbler`
+    pub async fn scrobbler(&self, user: Uuid) -> Result<Scrobbler, Error> {
+        let path = format!("users/{}/scrobbler", user);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `PUT /users/<user ID>/scrobbler`
+    pub async fn set_scrobbler(
+        &self,
+        user: Uuid,
+        target: &ScrobbleTarget,
+    ) -> Result<Scrobbler, Error> {
+        let path = format!("users/{}/scrobbler", user);
+        Client::json(self.request(Method::PUT, &path).json(target)).await
+    }
+
+    /// `DELETE /users/<user ID>/scrobbler`
+    pub async fn delete_scrobbler(&self, user: Uuid) -> Result<(), Error> {
+        let path = format!("users/{}/scrobbler", user);
+        Client::send(self.request(Method::DELETE, &path)).await?;
+        Ok(())
+    }
+
+    /// `PUT /admin/users/<user ID>/quota`. `None` goes back to the default.
+    pub async fn set_quota(&self, user: Uuid, bytes: Option<u64>) -> Result<Usage, Error> {
+        let path = format!("admin/users/{}/quota", user);
+        let body = QuotaOverride { bytes };
+        Client::json(self.request(Method::PUT, &path).json(&body)).await
+    }
+
+    /// `GET /admin/users`. `limit` defaults to 50 on the server.
+    pub async fn users(
+        &self,
+        q: Option<&str>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<UserPage, Error> {
+        let query = UserQuery {
+            q: q.map(str::to_string),
+            offset,
+            limit,
+        };
+        Client::json(self.request(Method::GET, "admin/users").query(&query)).await
+    }
+
+    /// `POST /admin/users/<user ID>/merge`
+    pub async fn merge_user(
+        &self,
+        from: Uuid,
+        into: Uuid,
+        dry_run: bool,
+    ) -> Result<MergeReport, Error> {
+        let path = format!("admin/users/{}/merge", from);
+        let body = MergeRequest { into, dry_run };
+        Client::json(self.request(Method::POST, &path).json(&body)).await
+    }
+
+    /// `GET /admin/engagement`: plays since `since` (or ever) by podcast.
+    pub async fn engagement(
+        &self,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<PodcastEngagement>, Error> {
+        let query = EngagementQuery { since };
+        Client::json(self.request(Method::GET, "admin/engagement").query(&query)).await
+    }
+
+    /// `GET /admin/podcasts/<podcast ID>/engagement`
+    pub async fn podcast_engagement(
+        &self,
+        podcast: Uuid,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<PodcastEngagementReport, Error> {
+        let path = format!("admin/podcasts/{}/engagement", podcast);
+        let query = EngagementQuery { since };
+        Client::json(self.request(Method::GET, &path).query(&query)).await
+    }
+
+    /// `GET /admin/storage`
+    pub async fn storage(&self) -> Result<StorageReport, Error> {
+        Client::json(self.request(Method::GET, "admin/storage")).await
+    }
+
+    /// `POST /admin/podcasts`
+    pub async fn add_podcast(&self, rss: &str) -> Result<PodcastChannel, Error> {
+        let body = AddPodcast {
+            rss: rss.to_string(),
+        };
+        Client::json(self.request(Method::POST, "admin/podcasts").json(&body)).await
+    }
+
+    /// `GET /admin/service_accounts`
+    pub async fn service_accounts(&self) -> Result<Vec<ServiceAccount>, Error> {
+        Client::json(self.request(Method::GET, "admin/service_accounts")).await
+    }
+
+    /// `POST /admin/service_accounts`
+    pub async fn create_service_account(
+        &self,
+        name: &str,
+        scopes: Vec<Scope>,
+    ) -> Result<CreatedServiceAccount, Error> {
+        let body = CreateServiceAccount {
+            name: name.to_string(),
+            scopes,
+        };
+        Client::json(
+            self.request(Method::POST, "admin/service_accounts")
+                .json(&body),
+        )
+        .await
+    }
+
+    /// `DELETE /admin/service_accounts/<service account ID>`
+    pub async fn delete_service_account(&self, id: Uuid) -> Result<(), Error> {
+        let path = format!("admin/service_accounts/{}", id);
+        Client::send(self.request(Method::DELETE, &path)).await?;
+        Ok(())
+    }
+
+    /// `GET /admin/settings`
+    pub async fn instance_settings(&self) -> Result<InstanceSettingsReport, Error> {
+        Client::json(self.request(Method::GET, "admin/settings")).await
+    }
+
+    /// `PUT /admin/settings`. `None` fields go back to the config file.
+    pub async fn set_instance_settings(
+        &self,
+        settings: &InstanceSettings,
+    ) -> Result<InstanceSettingsReport, Error> {
+        Client::json(self.request(Method::PUT, "admin/settings").json(settings)).await
+    }
+
+    /// `GET /admin/podcasts/<podcast ID>/refresh`
+    pub async fn refresh_schedule(&self, podcast: Uuid) -> Result<RefreshSchedule, Error> {
+        let path = format!("admin/podcasts/{}/refresh", podcast);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `PUT /admin/podcasts/<podcast ID>/refresh`; `None` removes the override.
+    pub async fn set_refresh_interval(
+        &self,
+        podcast: Uuid,
+        interval_mins: Option<u32>,
+    ) -> Result<RefreshSchedule, Error> {
+        let path = format!("admin/podcasts/{}/refresh", podcast);
+        let body = RefreshOverride { interval_mins };
+        Client::json(self.request(Method::PUT, &path).json(&body)).await
+    }
+
+    /// `GET /admin/podcasts/<podcast ID>/archive`
+    pub async fn archive(&self, podcast: Uuid) -> Result<ArchiveStatus, Error> {
+        let path = format!("admin/podcasts/{}/archive", podcast);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `PUT /admin/podcasts/<podcast ID>/archive`
+    pub async fn set_archive(&self, podcast: Uuid, archive: bool) -> Result<ArchiveStatus, Error> {
+        let path = format!("admin/podcasts/{}/archive", podcast);
+        let body = SetArchive { archive };
+        Client::json(self.request(Method::PUT, &path).json(&body)).await
+    }
+
+    /// `GET /admin/podcasts/<podcast ID>/archive/snapshots/<snapshot ID>`,
+    /// whose body is the feed as it was.
+    pub async fn feed_snapshot(
+        &self,
+        podcast: Uuid,
+        snapshot: Uuid,
+    ) -> Result<reqwest::Response, Error> {
+        let path = format!("admin/podcasts/{}/archive/snapshots/{}", podcast, snapshot);
+        Client::send(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /admin/podcasts/<podcast ID>/archive/torrent`, whose body is the
+    /// `.torrent` file.
+    pub async fn archive_torrent(&self, podcast: Uuid) -> Result<reqwest::Response, Error> {
+        let path = format!("admin/podcasts/{}/archive/torrent", podcast);
+        Client::send(self.request(Method::GET, &path)).await
+    }
+
+    /// `GET /admin/tenants`
+    pub async fn tenants(&self) -> Result<Vec<Tenant>, Error> {
+        Client::json(self.request(Method::GET, "admin/tenants")).await
+    }
+
+    /// `POST /admin/tenants`. Reach the tenant with a client whose base URL
+    /// ends in `/t/<slug>/`.
+    pub async fn create_tenant(&self, req: &CreateTenant) -> Result<NewTenant, Error> {
+        Client::json(self.request(Method::POST, "admin/tenants").json(req)).await
+    }
+
+    /// `GET /admin/tenants/<slug>`
+    pub async fn tenant(&self, slug: &str) -> Result<Tenant, Error> {
+        let path = format!("admin/tenants/{}", slug);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `DELETE /admin/tenants/<slug>`
+    pub async fn delete_tenant(&self, slug: &str) -> Result<(), Error> {
+        let path = format!("admin/tenants/{}", slug);
+        Client::send(self.request(Method::DELETE, &path)).await?;
+        Ok(())
+    }
+
+    /// `GET /admin/refresh-runs`, newest first.
+    pub async fn refresh_runs(&self) -> Result<Vec<RefreshRun>, Error> {
+        Client::json(self.request(Method::GET, "admin/refresh-runs")).await
+    }
+
+    /// `GET /admin/refresh-runs/<run ID>`
+    pub async fn refresh_run(&self, run: Uuid) -> Result<RefreshRunReport, Error> {
+        let path = format!("admin/refresh-runs/{}", run);
+        Client::json(self.request(Method::GET, &path)).await
+    }
+
+    /// `POST /admin/media/verify`
+    pub async fn start_verify(&self) -> Result<VerifyReport, Error> {
+        Client::json(self.request(Method::POST, "admin/media/verify")).await
+    }
+
+    /// `GET /admin/media/verify`
+    pub async fn verify_status(&self) -> Result<VerifyReport, Error> {
+        Client::json(self.request(Method::GET, "admin/media/verify")).await
+    }
+
+    /// `POST /admin/episodes/<episode ID>/transcribe`
+    pub async fn transcribe_episode(&self, episode: Uuid) -> Result<Vec<TranscriptionJob>, Error> {
+        let path = format!("admin/episodes/{}/transcribe", episode);
+        Client::json(self.request(Method::POST, &path)).await
+    }
+
+    /// `POST /admin/podcasts/<podcast ID>/transcribe`: queues the episodes
+    /// that have no transcript yet.
+    pub async fn transcribe_podcast(&self, podcast: Uuid) -> Result<Vec<TranscriptionJob>, Error> {
+        let path = format!("admin/podcasts/{}/transcribe", podcast);
+        Client::json(self.request(Method::POST, &path)).await
+    }
+
+    /// `GET /admin/transcriptions`
+    pub async fn transcription_jobs(&self) -> Result<Vec<TranscriptionJob>, Error> {
+        Client::json(self.request(Method::GET, "admin/transcriptions")).await
+    }
+}
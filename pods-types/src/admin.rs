@@ -0,0 +1,77 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::DbStats;
+
+/// Query of `GET /admin/users`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UserQuery {
+    /// Case-insensitive substring of the name.
+    pub q: Option<String>,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserPage {
+    /// Matching users across all pages.
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub users: Vec<UserSummary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub admin: bool,
+    pub subscriptions: usize,
+    pub last_active: Option<DateTime<Utc>>,
+    pub flagged_idle: Option<DateTime<Utc>>,
+    pub archived: bool,
+    /// The account this is a profile of.
+    pub profile_of: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StorageReport {
+    pub media_bytes: u64,
+    pub by_user: Vec<UsageLine>,
+    pub by_podcast: Vec<UsageLine>,
+    /// Files in the media directory we couldn't match to a user or episode.
+    pub unattributed_bytes: u64,
+    /// Bytes not stored again because several downloads share a file. Each
+    /// user's line counts shared files in full.
+    pub shared_bytes: u64,
+    pub database: DbStats,
+    /// The latest nightly maintenance run. Unset until the first one, or
+    /// when maintenance is off.
+    pub maintenance: Option<MaintenanceReport>,
+}
+
+/// What a maintenance run did.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MaintenanceReport {
+    pub started: DateTime<Utc>,
+    pub finished: DateTime<Utc>,
+    /// Rows the database dropped because nothing refers to them any more,
+    /// like past days' request counts.
+    pub database_rows: usize,
+    pub expired_bundles: usize,
+    /// Media files no download refers to that were deleted.
+    pub orphaned_files: usize,
+    pub orphaned_bytes: u64,
+    /// Steps that failed; the rest still ran.
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UsageLine {
+    pub key: String,
+    pub name: Option<String>,
+    pub bytes: u64,
+    pub files: u64,
+}
@@ -0,0 +1,24 @@
+//! Bookmarks and notes a user keeps on episodes.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A bookmark at a point in an episode, a note on it, or both.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Annotation {
+    pub id: Uuid,
+    pub episode: Uuid,
+    /// Seconds into the episode. Unset for a note on the whole episode.
+    pub position: Option<u32>,
+    pub note: Option<String>,
+    pub created: DateTime<Utc>,
+}
+
+/// Body of `POST /users/<user ID>/episodes/<episode ID>/annotations`. At
+/// least one of the two is needed.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CreateAnnotation {
+    pub position: Option<u32>,
+    pub note: Option<String>,
+}
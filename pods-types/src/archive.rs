@@ -0,0 +1,46 @@
+//! Archive mode, for keeping everything a podcast has published in case it
+//! disappears: its whole back catalog, every version of its feed and every
+//! enclosure.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Body of `PUT /admin/podcasts/<podcast ID>/archive`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SetArchive {
+    pub archive: bool,
+}
+
+/// Answer to `GET /admin/podcasts/<podcast ID>/archive`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ArchiveStatus {
+    pub podcast: Uuid,
+    /// `None` when archive mode is off.
+    pub archive: Option<Archive>,
+    pub episodes: usize,
+    /// Episodes with an enclosure that the server has a finished download of.
+    pub downloaded: usize,
+    /// Every distinct version of the feed seen since archiving started,
+    /// newest first.
+    pub snapshots: Vec<FeedSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Archive {
+    /// The admin who turned it on. Downloads are made under their account.
+    pub by: Uuid,
+    pub since: DateTime<Utc>,
+    /// Whether a crawl has followed the feed's pages back to the oldest.
+    pub crawled: bool,
+    pub last_crawl: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FeedSnapshot {
+    pub id: Uuid,
+    pub fetched: DateTime<Utc>,
+    /// Hex SHA-256 of the document.
+    pub sha256: String,
+    pub bytes: u64,
+}
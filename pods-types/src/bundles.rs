@@ -0,0 +1,41 @@
+//! Zips of episodes with their metadata and artwork, for carrying to
+//! devices that can't reach the server.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Body of `POST /export/bundle`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CreateBundle {
+    pub episodes: Vec<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum BundleStatus {
+    Queued,
+    Building,
+    Ready,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Bundle {
+    pub id: Uuid,
+    pub user: Uuid,
+    pub episodes: Vec<Uuid>,
+    pub status: BundleStatus,
+    pub created: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+    /// When the zip is deleted.
+    pub expires: DateTime<Utc>,
+    /// Size of the zip once it's ready.
+    pub bytes: Option<u64>,
+    /// Why it failed.
+    pub error: Option<String>,
+    /// Where to get the zip once it's ready, relative to where the API is
+    /// mounted.
+    pub download: Option<String>,
+}
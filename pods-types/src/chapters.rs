@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::tags::Chapter;
+
+/// How much of each chapter a user has heard, from
+/// `GET /users/<user ID>/episodes/<episode ID>/chapters`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChapterProgress {
+    pub episode: Uuid,
+    pub chapters: Vec<HeardChapter>,
+    /// Index in `chapters` of the first one not yet heard, to jump to.
+    /// Unset once all have been.
+    pub first_unheard: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HeardChapter {
+    #[serde(flatten)]
+    pub chapter: Chapter,
+    /// Milliseconds of the chapter covered by `play` actions.
+    pub heard_ms: u64,
+    /// Percent of the chapter heard. Unset if where it ends isn't known.
+    pub completion: Option<f32>,
+    pub heard: bool,
+}
@@ -0,0 +1,65 @@
+//! Searching podcast directories for feeds to subscribe to.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::instance::DiscoveryProvider;
+
+/// Query of `GET /discover/search`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DirectorySearch {
+    pub q: String,
+    /// Unset means every directory enabled in `discovery`.
+    pub provider: Option<DiscoveryProvider>,
+    #[serde(default)]
+    pub kind: SearchKind,
+    /// Only podcasts in this language (`en` includes `en-us`) or of unknown
+    /// language.
+    pub lang: Option<String>,
+}
+
+/// Query of `GET /discover/trending`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TrendingQuery {
+    /// Unset means every directory enabled in `discovery`.
+    pub provider: Option<DiscoveryProvider>,
+    /// As in [`DirectorySearch`].
+    pub lang: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum SearchKind {
+    #[default]
+    Podcast,
+    /// Episodes whose title or notes match, with the podcast each is from.
+    Episode,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DirectoryHit {
+    pub provider: DiscoveryProvider,
+    /// The directory's ID of the podcast, for the `POST /podcast` body.
+    pub id: String,
+    pub title: String,
+    pub author: Option<String>,
+    /// Feed URL, when the directory hands it out with search results.
+    pub rss: Option<String>,
+    pub artwork: Option<String>,
+    /// Lowercase BCP 47 tag, if the directory says.
+    pub language: Option<String>,
+    /// Whether the directory marks the podcast, or on episode searches the
+    /// episode, explicit.
+    pub explicit: bool,
+    /// The matching episode, on episode searches.
+    pub episode: Option<DirectoryEpisode>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DirectoryEpisode {
+    pub id: String,
+    pub title: String,
+    pub audio: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+}
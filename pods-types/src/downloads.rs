@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::integrity::Integrity;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum DownloadStatus {
+    Queued,
+    Downloading,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Download {
+    pub id: Uuid,
+    pub user: Uuid,
+    pub episode: Uuid,
+    pub url: String,
+    pub status: DownloadStatus,
+    /// The enclosure length advertised by the feed, if any.
+    pub expected_bytes: Option<u64>,
+    /// Bytes written to disk so far. For an interrupted download this is
+    /// where it resumes from.
+    pub bytes: u64,
+    /// Background downloads only run inside the configured download window.
+    pub background: bool,
+    pub attempts: u32,
+    /// A queued download waits until then before its next attempt.
+    #[serde(default)]
+    pub retry_after: Option<DateTime<Utc>>,
+    /// Hex SHA-256 of the finished file.
+    pub sha256: Option<String>,
+    pub integrity: Option<Integrity>,
+    /// Where the server keeps the file. Never sent over the API.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Download {
+    /// Bytes this download counts against the user's quota: what is on disk,
+    /// or what the feed told us to expect if we haven't finished yet.
+    pub fn reserved_bytes(&self) -> u64 {
+        match self.status {
+            DownloadStatus::Failed => 0,
+            DownloadStatus::Done => self.bytes,
+            _ => self.bytes.max(self.expected_bytes.unwrap_or(0)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EnqueueDownload {
+    pub episode: Uuid,
+    #[serde(default)]
+    pub background: bool,
+}
+
+/// Body of `POST /users/<user ID>/podcasts/<podcast ID>/downloads`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DownloadLatest {
+    /// How many of the newest episodes to have downloaded.
+    pub latest: usize,
+}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::gpodder::Connection;
+
+/// Query of the admin engagement routes.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EngagementQuery {
+    /// Only count plays after this.
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// How much a podcast is listened to, from every user's `play` actions.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PodcastEngagement {
+    pub podcast: Uuid,
+    pub rss: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub totals: Engagement,
+    pub by_client: Vec<ClientShare>,
+    pub by_connection: Vec<ConnectionShare>,
+}
+
+/// A podcast's engagement with a line per episode.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PodcastEngagementReport {
+    pub podcast: PodcastEngagement,
+    pub episodes: Vec<EpisodeEngagement>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EpisodeEngagement {
+    /// `None` for media URLs no longer in the feed.
+    pub episode: Option<Uuid>,
+    pub audio: String,
+    pub title: Option<String>,
+    #[serde(flatten)]
+    pub totals: Engagement,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Engagement {
+    /// Users, profiles included, who played any of it.
+    pub listeners: usize,
+    pub plays: usize,
+    pub listened_secs: u64,
+    /// Mean percentage of an episode listeners got through, over the
+    /// listener and episode pairs where it's known.
+    pub avg_completion: Option<f32>,
+    /// Listener and episode pairs that got through at least 90%.
+    pub completed: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClientShare {
+    /// `None` for plays that didn't say.
+    pub client: Option<String>,
+    pub plays: usize,
+    pub listened_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConnectionShare {
+    pub connection: Option<Connection>,
+    pub plays: usize,
+    pub listened_secs: u64,
+}
@@ -0,0 +1,32 @@
+//! What one pods server shares with another that mirrors it, so a group of
+//! small servers only fetch each feed once between them.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Episode, PodcastChannel};
+
+/// Query of `GET /federation/catalog`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CatalogQuery {
+    /// Only podcasts refreshed after this, usually the previous catalog's
+    /// `cursor`. Unset means every podcast.
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Catalog {
+    /// The latest `last_refreshed` in `podcasts`, or `since` if there are
+    /// none; pass it back as `since`.
+    pub cursor: Option<DateTime<Utc>>,
+    pub podcasts: Vec<CatalogEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CatalogEntry {
+    pub podcast: PodcastChannel,
+    /// When the sharing server last fetched the feed.
+    pub last_refreshed: Option<DateTime<Utc>>,
+    /// Every episode it knows, with its own IDs.
+    pub episodes: Vec<Episode>,
+}
@@ -0,0 +1,72 @@
+//! gPodder's formats. See
+//! <https://gpoddernet.readthedocs.io/en/latest/api/reference/events.html>
+//! for episode actions.
+
+use chrono::{NaiveDateTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum ActionKind {
+    Download,
+    Delete,
+    Play,
+    New,
+}
+
+/// A single gPodder episode action. `started`, `position` and `total` are in
+/// seconds, and they and the playback details after them are only
+/// meaningful for `play` actions.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EpisodeAction {
+    pub podcast: String,
+    pub episode: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    pub action: ActionKind,
+    #[serde(default = "now")]
+    pub timestamp: NaiveDateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u32>,
+    /// Percent of the episode played through, for clients that don't send
+    /// `total`. This and the fields below are pods extensions to the format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion: Option<f32>,
+    /// The app that played, e.g. `"AntennaPod/3.4"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<Connection>,
+}
+
+/// How the device was online while playing, as the client saw it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Connection {
+    Wifi,
+    Cellular,
+    Wired,
+    /// Played from a download without a connection.
+    Offline,
+    /// Anything else a client sends.
+    #[serde(other)]
+    Other,
+}
+
+fn now() -> NaiveDateTime {
+    // gPodder timestamps have second precision
+    let now = Utc::now().naive_utc();
+    now.with_nanosecond(0).unwrap_or(now)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GpodderExport {
+    pub subscriptions: Vec<String>,
+    pub episode_actions: Vec<EpisodeAction>,
+}
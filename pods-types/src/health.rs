@@ -0,0 +1,99 @@
+//! How a podcast's feed has been fetching, for working out why a show
+//! isn't updating.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Answer to `GET /podcasts/<podcast ID>/health`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FeedHealth {
+    pub podcast: Uuid,
+    pub rss: String,
+    /// Time of the latest recent fetch that gave a usable feed, or found it
+    /// unchanged.
+    pub last_success: Option<DateTime<Utc>>,
+    /// Recent fetches, newest first.
+    pub fetches: Vec<FeedFetch>,
+    /// Mean response time of the recent fetches that got an answer.
+    pub average_ms: Option<u64>,
+    /// Validators from the latest answer. A host that sends neither can't
+    /// tell feed readers the feed is unchanged, so every check downloads
+    /// all of it.
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Problems in the latest usable copy of the feed.
+    pub warnings: Vec<FeedWarning>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FeedFetch {
+    pub at: DateTime<Utc>,
+    pub outcome: FetchOutcome,
+    /// HTTP status of the final response.
+    pub status: Option<u16>,
+    /// Until the whole body was read, or the fetch failed.
+    pub elapsed_ms: u64,
+    pub bytes: Option<u64>,
+    /// Redirects followed on the way, in order.
+    pub redirects: Vec<Redirect>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum FetchOutcome {
+    Ok,
+    /// A refresh sent the validators of the last fetch and the host said
+    /// nothing changed.
+    NotModified,
+    /// The host couldn't be reached or stopped answering.
+    Unreachable,
+    /// It answered with an error status.
+    HttpError,
+    /// Bigger than the instance's `max_feed_bytes`.
+    TooLarge,
+    /// Not an RSS feed, or missing its title or description.
+    Invalid,
+    /// The URL, or a redirect, points at a non-public address.
+    Blocked,
+    TooManyRedirects,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Redirect {
+    /// The URL that answered with the redirect.
+    pub url: String,
+    /// `301` and `308` mean the feed has moved for good.
+    pub status: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FeedWarning {
+    pub kind: WarningKind,
+    /// How many items have the problem.
+    pub count: usize,
+    /// Titles of the first few of them.
+    pub examples: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// Without a `<guid>` an item is matched by its enclosure URL, so moving
+    /// the audio makes it look new.
+    MissingGuid,
+    /// Items sharing a `<guid>` are taken for one episode.
+    DuplicateGuid,
+    /// A `<pubDate>` that couldn't be read.
+    BadDate,
+    MissingDate,
+    /// Nothing to play.
+    MissingEnclosure,
+    /// An enclosure without a `length`, so download sizes and quotas can't
+    /// be known up front.
+    MissingLength,
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Episode;
+
+/// Episodes ingested since the user subscribed that they haven't triaged
+/// yet, newest first.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Inbox {
+    /// `episodes.len()`, for an unread badge.
+    pub count: usize,
+    pub episodes: Vec<Episode>,
+}
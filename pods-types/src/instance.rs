@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+/// Instance options admins can change while the server runs. `null` fields
+/// fall back to the config file.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct InstanceSettings {
+    /// Whether `POST /users` takes new accounts from anyone. Admins can
+    /// always add users, and the first account can always be created.
+    pub registration_open: Option<bool>,
+    /// Minutes between checks of each feed for new episodes, at most; rarely
+    /// published feeds are checked less often, and admins can set any feed's
+    /// interval.
+    pub refresh_interval_mins: Option<u32>,
+    /// Largest feed document the server will fetch.
+    pub max_feed_bytes: Option<u64>,
+    /// Podcast directories used to find feeds.
+    pub discovery: Option<Vec<DiscoveryProvider>>,
+    /// Whether users who haven't chosen are spared explicit content.
+    pub hide_explicit: Option<bool>,
+}
+
+/// The settings in force: the stored overrides over the config file.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EffectiveSettings {
+    pub registration_open: bool,
+    pub refresh_interval_mins: u32,
+    pub max_feed_bytes: u64,
+    pub discovery: Vec<DiscoveryProvider>,
+    pub hide_explicit: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InstanceSettingsReport {
+    pub overrides: InstanceSettings,
+    pub effective: EffectiveSettings,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum DiscoveryProvider {
+    /// Apple Podcasts' lookup API.
+    Itunes,
+    /// <https://podcastindex.org>
+    PodcastIndex,
+    /// <https://www.listennotes.com/api/>
+    ListenNotes,
+}
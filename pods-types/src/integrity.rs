@@ -0,0 +1,27 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Integrity {
+    /// Every check we had something to compare against passed.
+    Ok,
+    /// Size differs from the feed's enclosure length, or from what we wrote.
+    /// Feeds often get the length wrong, so on its own this is a warning.
+    LengthMismatch,
+    /// Content doesn't match the feed's hash, or has changed since we wrote it.
+    HashMismatch,
+    Missing,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VerifyReport {
+    pub started: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+    pub checked: usize,
+    pub ok: usize,
+    /// Downloads whose file failed a check, with the result.
+    pub problems: Vec<(Uuid, Integrity)>,
+}
@@ -0,0 +1,207 @@
+//! The models the pods server stores and the bodies its API takes and
+//! returns, shared by the server and its clients.
+//!
+//! Enums are `#[non_exhaustive]`: the server may add variants (a language, a
+//! sort order, ...) in a minor release, so match them with a wildcard arm.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub mod admin;
+pub mod annotations;
+pub mod archive;
+pub mod bundles;
+pub mod chapters;
+pub mod discovery;
+pub mod downloads;
+pub mod engagement;
+pub mod federation;
+pub mod gpodder;
+pub mod health;
+pub mod i18n;
+pub mod inbox;
+pub mod instance;
+pub mod integrity;
+pub mod media_links;
+pub mod merge;
+pub mod poll;
+pub mod profiles;
+pub mod public;
+pub mod quota;
+pub mod refresh;
+pub mod request_quota;
+pub mod resume;
+pub mod scrobble;
+pub mod service_accounts;
+pub mod settings;
+pub mod signing;
+pub mod stats;
+pub mod stream;
+pub mod subscriptions;
+pub mod tags;
+pub mod tenants;
+pub mod transcripts;
+pub mod voice;
+
+use settings::UserSettings;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct User {
+    pub name: String,
+    pub id: Uuid,
+    pub subscribed: Vec<String>,
+    pub admin: bool,
+    pub settings: UserSettings,
+    pub last_active: Option<DateTime<Utc>>,
+    pub created: DateTime<Utc>,
+    /// When the idle account policy flagged this account.
+    pub flagged_idle: Option<DateTime<Utc>>,
+    /// Downloads were removed for inactivity. Using the account again clears
+    /// this and the flag.
+    pub archived: bool,
+    /// The account this is a profile of. Profiles share its login.
+    pub profile_of: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PodcastChannel {
+    pub name: String,
+    pub description: String,
+    pub rss: String,
+    pub id: Uuid,
+    pub artwork: Option<String>,
+    /// Lowercase BCP 47 tag from the feed's `<language>`, or guessed from
+    /// its text when there's none.
+    pub language: Option<String>,
+    /// `<itunes:explicit>` on the channel.
+    pub explicit: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Episode {
+    pub id: Uuid,
+    /// RSS link of the podcast this episode belongs to
+    pub podcast: String,
+    pub guid: Option<String>,
+    pub title: String,
+    /// Publish date normalized to UTC, if the feed gave a readable one
+    pub published: Option<DateTime<Utc>>,
+    pub enclosure: Option<Enclosure>,
+    /// The item's `<itunes:explicit>`, or the channel's if it has none.
+    pub explicit: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Enclosure {
+    pub url: String,
+    pub length: Option<u64>,
+    pub mime_type: Option<String>,
+    /// Subresource Integrity hash from `<podcast:integrity type="sri">`
+    pub integrity: Option<String>,
+}
+
+/// Query of `POST /podcast`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SubscribeQuery {
+    /// Also queues downloads of this many of the newest episodes, to have
+    /// the show ready offline.
+    pub download_latest: Option<usize>,
+}
+
+/// Body of `POST /podcast`: a feed URL, or a directory ID to look one up by.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+#[non_exhaustive]
+pub enum Subscribe {
+    Rss {
+        rss: String,
+    },
+    /// Apple Podcasts' collection ID, from `podcasts.apple.com/.../id<ID>`.
+    Itunes {
+        itunes_id: u64,
+    },
+    PodcastIndex {
+        podcastindex_id: u64,
+    },
+    /// From `listennotes.com/podcasts/.../<ID>/`.
+    ListenNotes {
+        listennotes_id: String,
+    },
+}
+
+/// Answer to `POST /podcast`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Subscribed {
+    pub podcast: PodcastChannel,
+    /// RSS links of all the user's subscriptions.
+    pub subscriptions: Vec<String>,
+    /// Problems found in the feed. It's stored anyway, but episodes may be
+    /// missing, merged or misdated.
+    pub warnings: Vec<health::FeedWarning>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CreateUser {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserStatus {
+    pub user: Option<Uuid>,
+    pub logged_in: bool,
+    /// The profile the request switched to, if any.
+    pub profile: Option<Uuid>,
+}
+
+/// Query of `GET /podcasts/<podcast ID>/episodes`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EpisodeFilter {
+    /// Only episodes published after this time.
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Today {
+    pub date: NaiveDate,
+    /// The zone `date` is in; `null` means the server's.
+    pub timezone: Option<Tz>,
+    pub episodes: Vec<Episode>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DbStats {
+    /// On-disk size, for backends that have one.
+    pub bytes: Option<u64>,
+    pub users: usize,
+    pub podcasts: usize,
+    pub episodes: usize,
+    pub downloads: usize,
+}
+
+/// Body of error responses that have one.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiError {
+    /// Stable code to match on, like `quota_exceeded`.
+    pub error: String,
+    /// `error` in the user's language.
+    pub message: String,
+    /// Set on `timeout` errors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// What's wrong with the request, field by field, on `invalid` errors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+/// A field of an `invalid` request and the rule it broke.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FieldError {
+    /// As named in the body or query string, like `name` or `limit`.
+    pub field: String,
+    /// Stable code to match on, like `too_long`.
+    pub error: String,
+    /// `error` in the user's language.
+    pub message: String,
+}
@@ -0,0 +1,26 @@
+//! Signed links to an episode's audio that work without logging in until
+//! they expire, for handing to players like smart speakers.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::stream::Quality;
+
+/// Body of `POST /episodes/<episode ID>/audio/link`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct CreateMediaLink {
+    /// How long the link works, up to the server's `max_ttl_secs`. `None`
+    /// means the server's default.
+    pub ttl_secs: Option<u64>,
+    pub quality: Quality,
+    /// Cuts the acting user's intro skip out, as with `trim=true` on the
+    /// audio route.
+    pub trim: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MediaLink {
+    pub url: String,
+    pub expires: DateTime<Utc>,
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MergeRequest {
+    /// The account to keep.
+    pub into: Uuid,
+    /// Only report what would happen. Defaults to on, so a merge has to be
+    /// asked for explicitly.
+    #[serde(default = "yes")]
+    pub dry_run: bool,
+}
+
+fn yes() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MergeReport {
+    pub from: Uuid,
+    pub into: Uuid,
+    pub dry_run: bool,
+    /// Feeds `into` wasn't already subscribed to. Their overrides come along.
+    pub subscriptions_added: Vec<String>,
+    pub episode_actions_moved: usize,
+    /// Episodes where `from` had listened further; `into` gets a play action
+    /// at this position.
+    pub positions_raised: Vec<Position>,
+    pub downloads_moved: usize,
+    /// Downloads of episodes `into` already has.
+    pub downloads_dropped: usize,
+    /// Inbox and queue entries `into` didn't already have, appended to its
+    /// own.
+    pub inbox_moved: usize,
+    pub queue_moved: usize,
+    /// Bookmarks and notes, which all move.
+    pub annotations_moved: usize,
+    /// Whether `from`'s scrobbler moved. It's dropped if `into` has its own.
+    pub scrobbler_moved: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Position {
+    pub podcast: String,
+    pub episode: String,
+    pub position: u32,
+}
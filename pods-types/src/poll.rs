@@ -0,0 +1,67 @@
+//! Long polling for clients that can't keep a connection open: the request
+//! is held until there's something new for the user.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+
+/// Query of `GET /poll`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PollQuery {
+    /// The `cursor` of the previous poll. Unset means from now on.
+    pub since: Option<u64>,
+    /// Seconds to hold the request for, written `25s` or `25`. Defaults to
+    /// 25 and is at most 60.
+    #[serde(default, with = "secs")]
+    pub wait: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Poll {
+    /// Pass as `since` next time.
+    pub cursor: u64,
+    /// Oldest first. Empty if the wait ran out.
+    pub events: Vec<Event>,
+    /// Events after `since` were dropped (the server restarted or the client
+    /// stayed away too long); refetch the inbox to catch up.
+    pub missed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Event {
+    pub cursor: u64,
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum EventKind {
+    /// A refresh put episodes of a subscribed podcast in the inbox.
+    NewEpisodes { podcast: Uuid, episodes: Vec<Uuid> },
+}
+
+/// Seconds with an optional `s` suffix.
+mod secs {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(secs: &Option<u32>, s: S) -> Result<S::Ok, S::Error> {
+        match secs {
+            Some(secs) => s.serialize_str(&format!("{}s", secs)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u32>, D::Error> {
+        let Some(text) = Option::<String>::deserialize(d)? else {
+            return Ok(None);
+        };
+        let digits = text.strip_suffix('s').unwrap_or(&text);
+        digits
+            .parse()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("not a number of seconds: {}", text)))
+    }
+}
@@ -0,0 +1,18 @@
+//! Profiles: users under one account that share its login but keep their
+//! own subscriptions, queue, history and settings.
+//!
+//! A request acts as a profile when it sends the profile's ID in the
+//! [`HEADER`] header or the [`PARAM`] query parameter.
+
+use serde::{Deserialize, Serialize};
+
+pub const HEADER: &str = "X-Pods-Profile";
+
+/// The query parameter doing what [`HEADER`] does, for players that can't
+/// set headers (e.g. on audio URLs).
+pub const PARAM: &str = "profile";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CreateProfile {
+    pub name: String,
+}
@@ -0,0 +1,26 @@
+//! The read-only API under `/public`, for a public website in front of the
+//! instance. It only covers the catalog, never anything about users.
+
+use serde::{Deserialize, Serialize};
+
+use crate::PodcastChannel;
+
+/// Query of `GET /public/podcasts`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PodcastQuery {
+    /// Case-insensitive words that must all appear in the name or
+    /// description.
+    pub q: Option<String>,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PodcastPage {
+    /// Matching podcasts across all pages.
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub podcasts: Vec<PodcastChannel>,
+}
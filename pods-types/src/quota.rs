@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Usage {
+    pub user: Uuid,
+    pub used_bytes: u64,
+    pub quota_bytes: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QuotaOverride {
+    /// `null` removes the override, falling back to the configured default.
+    pub bytes: Option<u64>,
+}
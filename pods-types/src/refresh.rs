@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::health::FetchOutcome;
+
+/// Body of `PUT /admin/podcasts/<podcast ID>/refresh`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct RefreshOverride {
+    /// Minutes between checks of this feed; `null` removes the override.
+    pub interval_mins: Option<u32>,
+}
+
+/// Where a feed's refresh interval comes from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum IntervalSource {
+    /// An admin set it for this podcast.
+    Override,
+    /// Derived from how often the feed publishes, and backed off outside
+    /// its publish windows.
+    Cadence,
+    /// Inside one of the feed's publish windows.
+    Window,
+    /// The instance's `refresh_interval_mins`.
+    Instance,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RefreshSchedule {
+    pub podcast: Uuid,
+    pub interval_mins: u32,
+    pub source: IntervalSource,
+    pub last_refreshed: Option<DateTime<Utc>>,
+    /// When the feed usually publishes, if it has a pattern.
+    pub windows: Option<PublishWindows>,
+}
+
+/// Hours of the day (UTC) a feed usually publishes in, and the days it has
+/// published at those hours.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PublishWindows {
+    pub weekdays: Vec<Weekday>,
+    pub hours: Vec<u32>,
+}
+
+/// One pass of the refresh scheduler over the feeds that were due.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RefreshRun {
+    pub id: Uuid,
+    pub started: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// Feeds fetched.
+    pub attempted: usize,
+    /// Feeds whose host answered `304 Not Modified`.
+    pub not_modified: usize,
+    /// Feeds that couldn't be fetched or read.
+    pub failed: usize,
+    pub new_episodes: usize,
+}
+
+/// Answer to `GET /admin/refresh-runs/<run ID>`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RefreshRunReport {
+    #[serde(flatten)]
+    pub run: RefreshRun,
+    /// In the order they were fetched.
+    pub feeds: Vec<FeedRun>,
+}
+
+/// How one feed went in a refresh run.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FeedRun {
+    pub podcast: Uuid,
+    pub rss: String,
+    pub outcome: FetchOutcome,
+    pub status: Option<u16>,
+    pub elapsed_ms: u64,
+    pub new_episodes: usize,
+}
@@ -0,0 +1,32 @@
+//! Daily API request quotas, per user and per service account, so shared
+//! instances can spot and cap integrations that call too much.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Answer to `GET /users/<ID>/quota`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RequestQuota {
+    /// The user or service account.
+    pub id: Uuid,
+    /// The UTC day being counted.
+    pub day: NaiveDate,
+    pub used: u64,
+    /// Requests allowed a day in all, `None` if unlimited.
+    pub limit: Option<u64>,
+    /// When the counts start over.
+    pub resets: DateTime<Utc>,
+    /// Today's requests by route, most used first, with each route's own
+    /// limit if it has one.
+    pub routes: Vec<RouteUsage>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RouteUsage {
+    pub method: String,
+    /// The route pattern, like `/podcasts/:id/episodes`.
+    pub route: String,
+    pub used: u64,
+    pub limit: Option<u64>,
+}
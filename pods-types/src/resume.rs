@@ -0,0 +1,43 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Resuming at least `after_mins` after playback stopped starts `secs`
+/// earlier. Of a user's rules, the one with the longest `after_mins` that
+/// has passed applies.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RewindRule {
+    pub after_mins: u32,
+    pub secs: u32,
+}
+
+/// Where to pick an episode back up, from the latest `play` action on any
+/// device.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ResumePosition {
+    pub episode: Uuid,
+    /// Seconds into the episode to start at, rewound by the user's rules.
+    pub position: u32,
+    /// Where playback stopped.
+    pub stopped_at: u32,
+    pub rewound_secs: u32,
+    /// When it stopped, in UTC like gPodder timestamps.
+    pub stopped: NaiveDateTime,
+    pub total: Option<u32>,
+}
+
+/// How to play an episode so every client does the same, from
+/// `GET /users/<user ID>/episodes/<episode ID>/playback`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Playback {
+    pub episode: Uuid,
+    /// From the user's `skip_intro_secs` for the podcast, or 0.
+    pub skip_intro_secs: u32,
+    /// From the user's `skip_outro_secs` for the podcast, or 0.
+    pub skip_outro_secs: u32,
+    /// Seconds in to start at: the resume position, but never inside the
+    /// skipped intro.
+    pub start_at: u32,
+    /// Unset until the episode has been played.
+    pub resume: Option<ResumePosition>,
+}
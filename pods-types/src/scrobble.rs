@@ -0,0 +1,62 @@
+//! Scrobbling: sending each episode a user finishes to ListenBrainz or a
+//! webhook of their own, for people who track everything they listen to.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Body of `PUT /users/<user ID>/scrobbler`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScrobbleTarget {
+    /// The instance's ListenBrainz server, with the user token from the
+    /// user's ListenBrainz settings.
+    ListenBrainz { token: String },
+    /// Any URL, sent each listen as a [`Scrobble`]. With `secret`, requests
+    /// are signed as described in [`crate::signing`].
+    Webhook {
+        url: String,
+        #[serde(default)]
+        secret: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrobbleKind {
+    ListenBrainz,
+    Webhook,
+}
+
+/// A user's scrobbler, without its token or secret.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Scrobbler {
+    pub kind: ScrobbleKind,
+    /// The webhook's URL.
+    pub url: Option<String>,
+    /// Listens finished after this are sent; it moves forward as they are.
+    /// In UTC like gPodder timestamps.
+    pub since: NaiveDateTime,
+    /// Listens sent so far.
+    pub submitted: u64,
+    pub last_submitted: Option<DateTime<Utc>>,
+    /// Why the latest attempt failed, until one succeeds. Failed listens are
+    /// tried again.
+    pub last_error: Option<String>,
+}
+
+/// What a webhook is sent, as JSON, for each finished episode.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Scrobble {
+    pub user: Uuid,
+    /// The `play` action that reached the end, in UTC.
+    pub listened_at: NaiveDateTime,
+    pub episode: Uuid,
+    pub title: String,
+    pub podcast: String,
+    pub rss: String,
+    pub audio: String,
+    pub published: Option<DateTime<Utc>>,
+    /// The episode's length, from the play action.
+    pub duration_secs: Option<u32>,
+}
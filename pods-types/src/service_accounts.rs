@@ -0,0 +1,52 @@
+//! Accounts for cron jobs and dashboards: they can't log in, and their
+//! tokens only open the routes their scopes name.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Scope {
+    /// `POST /admin/podcasts`: add feeds to the catalog.
+    Ingest,
+    /// `GET /admin/engagement`, `GET /admin/podcasts/<podcast ID>/engagement`,
+    /// `GET /admin/refresh-runs` with each run, and `GET /admin/storage`.
+    Stats,
+    /// `POST /voice`: a voice-assistant skill's intents, for any user.
+    Voice,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ServiceAccount {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created: DateTime<Utc>,
+    /// Time of the latest request made with the token.
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+/// Body of `POST /admin/service_accounts`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CreateServiceAccount {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// Answer to `POST /admin/service_accounts`, the only time the token is
+/// shown.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CreatedServiceAccount {
+    #[serde(flatten)]
+    pub account: ServiceAccount,
+    /// Sent as `Authorization: Bearer <token>`.
+    pub token: String,
+}
+
+/// Body of `POST /admin/podcasts`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AddPodcast {
+    pub rss: String,
+}
@@ -0,0 +1,28 @@
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::{i18n::Lang, resume::RewindRule, subscriptions::SubscriptionOrder};
+
+/// Per-user preferences. Unset fields fall back to instance or client
+/// defaults.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct UserSettings {
+    /// Language for messages, taking precedence over `Accept-Language`.
+    pub language: Option<Lang>,
+    /// IANA zone for "today" and other calendar views, overriding the
+    /// instance timezone.
+    pub timezone: Option<Tz>,
+    pub subscription_order: SubscriptionOrder,
+    /// Where to send account notices.
+    pub email: Option<String>,
+    /// Minutes of listening to aim for each week, tracked in
+    /// `/users/<user ID>/stats`.
+    pub weekly_goal_mins: Option<u32>,
+    /// Leave explicit podcasts and episodes out of listings, search and
+    /// discovery. Unset follows the instance's `hide_explicit`.
+    pub hide_explicit: Option<bool>,
+    /// How far `/users/<user ID>/episodes/<episode ID>/resume` jumps back
+    /// depending on how long playback has been stopped.
+    pub rewind_on_resume: Vec<RewindRule>,
+}
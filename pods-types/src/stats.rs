@@ -0,0 +1,40 @@
+//! Listening time from a user's play history, against their weekly goal.
+
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// Query of `GET /users/<user ID>/stats`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StatsQuery {
+    /// Weeks to list, current one included. Defaults to 12.
+    pub weeks: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ListeningStats {
+    /// The zone days and weeks are counted in; `null` means the server's.
+    pub timezone: Option<Tz>,
+    /// From the user's `weekly_goal_mins` setting.
+    pub weekly_goal_mins: Option<u32>,
+    /// Newest first, starting with the current week.
+    pub weeks: Vec<WeekStats>,
+    /// Weeks in a row the goal was met, up to last week, plus the current
+    /// one once it's met. `0` without a goal.
+    pub goal_streak: u32,
+    pub longest_goal_streak: u32,
+    /// Days in a row with any listening, up to yesterday, plus today once
+    /// there is some.
+    pub day_streak: u32,
+    pub longest_day_streak: u32,
+    pub total_mins: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WeekStats {
+    /// The Monday the week starts on.
+    pub start: NaiveDate,
+    pub minutes: u64,
+    /// `null` without a goal.
+    pub goal_met: Option<bool>,
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// Query of `GET /episodes/<episode ID>/audio`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AudioQuery {
+    pub quality: Quality,
+    /// Cuts the acting user's `skip_intro_secs` for the podcast out of a
+    /// `low` rendition.
+    pub trim: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Quality {
+    /// The enclosure as the host serves it.
+    #[default]
+    Original,
+    /// A low-bitrate mono MP3 made by the server, for slow connections.
+    Low,
+}
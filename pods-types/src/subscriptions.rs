@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::PodcastChannel;
+
+/// How a user's subscription list is sorted.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum SubscriptionOrder {
+    /// Subscription order, or whatever order was last set with the reorder
+    /// endpoint.
+    #[default]
+    Manual,
+    /// By newest episode, newest first.
+    RecentEpisode,
+    /// By displayed name.
+    Alphabetical,
+    /// By seconds played according to the user's episode actions.
+    MostListened,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Reorder {
+    /// Every subscribed podcast's ID, in the new order.
+    pub podcasts: Vec<Uuid>,
+}
+
+/// One user's replacements for a podcast's display fields, and the parts of
+/// its episodes they skip. `null` fields show the feed's own value.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct SubscriptionOverride {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub artwork: Option<String>,
+    /// Seconds to skip at the start of every episode, like a recurring
+    /// intro.
+    pub skip_intro_secs: Option<u32>,
+    /// Seconds to skip at the end.
+    pub skip_outro_secs: Option<u32>,
+}
+
+impl SubscriptionOverride {
+    pub fn apply(&self, mut channel: PodcastChannel) -> PodcastChannel {
+        if let Some(name) = &self.name {
+            channel.name = name.clone();
+        }
+        if let Some(description) = &self.description {
+            channel.description = description.clone();
+        }
+        if let Some(artwork) = &self.artwork {
+            channel.artwork = Some(artwork.clone());
+        }
+        channel
+    }
+}
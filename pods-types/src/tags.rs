@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// What a downloaded file's embedded ID3 or Vorbis comment tags say about
+/// the episode, from `GET /episodes/<episode ID>/tags`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EmbeddedTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    /// MIME type of the embedded cover, which
+    /// `GET /episodes/<episode ID>/artwork` serves.
+    pub artwork: Option<String>,
+    pub chapters: Vec<Chapter>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Chapter {
+    pub start_ms: u64,
+    /// Vorbis comment chapters only mark where they start.
+    pub end_ms: Option<u64>,
+    pub title: Option<String>,
+}
@@ -0,0 +1,34 @@
+//! Tenants: separate groups sharing one server, each with its own users,
+//! subscriptions and catalog under `/t/<slug>/`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{CreateUser, User};
+
+/// Body of `POST /admin/tenants`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CreateTenant {
+    /// Lowercase letters, digits and `-`; the tenant's routes are under
+    /// `/t/<slug>/`.
+    pub slug: String,
+    pub name: String,
+    /// The tenant's first user, who is its admin.
+    pub admin: CreateUser,
+}
+
+/// Answer to `POST /admin/tenants`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewTenant {
+    pub tenant: Tenant,
+    pub admin: User,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Tenant {
+    pub slug: String,
+    pub name: String,
+    pub created: DateTime<Utc>,
+    pub users: usize,
+    pub podcasts: usize,
+}
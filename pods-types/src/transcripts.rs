@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Which kind of transcription backend made a transcript.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum TranscriptionBackend {
+    /// A local whisper.cpp command.
+    WhisperCpp,
+    /// A remote speech-to-text API.
+    Api,
+}
+
+/// `GET /episodes/<episode ID>/transcript`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Transcript {
+    pub episode: Uuid,
+    pub backend: TranscriptionBackend,
+    pub created: DateTime<Utc>,
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Segment {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum TranscriptionStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+/// An episode waiting for, or done with, transcription. There is at most
+/// one per episode.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TranscriptionJob {
+    pub episode: Uuid,
+    pub status: TranscriptionStatus,
+    pub queued: DateTime<Utc>,
+    /// Why a failed job failed.
+    pub error: Option<String>,
+}
+
+/// Query of `GET /transcripts/search`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TranscriptQuery {
+    /// Words that must all appear in an episode's transcript, in any order.
+    pub q: String,
+    /// Only podcasts in this language (`en` includes `en-us`) or of unknown
+    /// language.
+    pub lang: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TranscriptHit {
+    pub episode: Uuid,
+    /// RSS link of the episode's podcast.
+    pub podcast: String,
+    pub title: String,
+    /// The first segment mentioning one of the words.
+    pub segment: Segment,
+}
@@ -0,0 +1,51 @@
+//! The contract voice-assistant skills (Alexa, Google Assistant) talk to
+//! `POST /voice` with: an intent the skill recognized, and what to say and
+//! play back.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::media_links::MediaLink;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "intent", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Intent {
+    /// "Play the latest episode of ...", with the podcast as it was heard.
+    PlayLatest { podcast: String },
+    /// "Resume my queue": the first queued episode not yet finished.
+    ResumeQueue,
+}
+
+/// Body of `POST /voice`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VoiceRequest {
+    /// The user whose account the skill is linked to.
+    pub user: Uuid,
+    #[serde(flatten)]
+    pub intent: Intent,
+    /// The assistant's locale, like `de-DE`, for `speech`. Unset means the
+    /// user's language.
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VoiceResponse {
+    /// What the assistant says, in the request's language.
+    pub speech: String,
+    /// Unset when there's nothing to play, and `speech` says why.
+    pub play: Option<VoicePlay>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VoicePlay {
+    pub episode: Uuid,
+    pub title: String,
+    pub podcast: String,
+    /// Where to start, from the user's resume position and intro skip.
+    pub offset_secs: u32,
+    /// A signed link to the audio that works without logging in.
+    #[serde(flatten)]
+    pub link: MediaLink,
+}
@@ -0,0 +1,44 @@
+//! Synthetic feeds shaped like real ones: a channel header followed by
+//! `episodes` items, each with a GUID, enclosure and the usual filler.
+
+use std::fmt::Write;
+
+pub fn url(n: usize) -> String {
+    format!("https://feeds.example.com/show-{}/rss", n)
+}
+
+pub fn rss(url: &str, episodes: usize) -> String {
+    let mut xml = String::new();
+    write!(
+        xml,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd" xmlns:podcast="https://podcastindex.org/namespace/1.0">
+<channel>
+<title>Synthetic Show</title>
+<link>{url}</link>
+<description>A feed generated for benchmarks.</description>
+<language>en-us</language>
+<itunes:author>Benchmarks</itunes:author>
+"#
+    )
+    .unwrap();
+    for i in (0..episodes).rev() {
+        write!(
+            xml,
+            r#"<item>
+<title>Episode {i}: Something Happened</title>
+<guid isPermaLink="false">{url}#{i}</guid>
+<pubDate>Mon, 02 Jan 2023 10:00:00 +0000</pubDate>
+<description><![CDATA[<p>Show notes for episode {i}, with <a href="https://example.com">links</a>.</p>]]></description>
+<itunes:duration>01:02:03</itunes:duration>
+<enclosure url="https://media.example.com/ep{i}.mp3" length="{length}" type="audio/mpeg"/>
+<podcast:integrity type="sri" value="sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="/>
+</item>
+"#,
+            length = 40_000_000 + i,
+        )
+        .unwrap();
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
@@ -0,0 +1,19 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+mod feeds;
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_feed");
+    for episodes in [10, 100, 1000] {
+        let url = feeds::url(0);
+        let xml = feeds::rss(&url, episodes);
+        group.throughput(Throughput::Bytes(xml.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(episodes), &xml, |b, xml| {
+            b.iter(|| pods::parse_feed(&url, black_box(xml)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,70 @@
+//! Storage hot paths, run against every `DB` backend.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use pods::{CreateUser, InMemoryStore, DB};
+
+mod feeds;
+
+fn backends(c: &mut Criterion) {
+    store(c, "memory", InMemoryStore::new);
+}
+
+fn store<D: DB>(c: &mut Criterion, backend: &str, new: fn() -> D) {
+    let mut group = c.benchmark_group(format!("{}/add_episodes", backend));
+    for episodes in [10, 100, 1000] {
+        let url = feeds::url(0);
+        let feed = pods::parse_feed(&url, &feeds::rss(&url, episodes)).unwrap();
+        group.bench_function(BenchmarkId::from_parameter(episodes), |b| {
+            b.iter_batched(
+                || {
+                    let mut db = new();
+                    db.create_podcast(
+                        url.clone(),
+                        feed.title.clone(),
+                        feed.description.clone(),
+                        feed.artwork.clone(),
+                        feed.language.clone(),
+                        feed.explicit,
+                    )
+                    .unwrap();
+                    (db, feed.episodes.clone())
+                },
+                |(mut db, episodes)| db.add_episodes(url.clone(), episodes).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group(format!("{}/subscriptions", backend));
+    for podcasts in [10, 100, 500] {
+        let mut db = new();
+        let user = db
+            .create_user(CreateUser {
+                name: "bench".to_string(),
+            })
+            .unwrap();
+        for n in 0..podcasts {
+            let url = feeds::url(n);
+            let feed = pods::parse_feed(&url, &feeds::rss(&url, 20)).unwrap();
+            db.create_podcast(
+                url.clone(),
+                feed.title,
+                feed.description,
+                feed.artwork,
+                feed.language,
+                feed.explicit,
+            )
+            .unwrap();
+            db.add_episodes(url.clone(), feed.episodes).unwrap();
+            db.subscribe(user.id, url).unwrap();
+        }
+        group.bench_function(BenchmarkId::from_parameter(podcasts), |b| {
+            b.iter(|| pods::subscriptions(&db, user.id).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, backends);
+criterion_main!(benches);
@@ -0,0 +1,144 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    net::SocketAddr,
+    sync::Arc,
+    time::Instant,
+};
+
+use axum::{
+    body::HttpBody,
+    extract::{ConnectInfo, State},
+    http::{header, Request},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{media_links::redact, AppState, DB};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AccessLogConfig {
+    pub format: Format,
+    /// `stdout`, `stderr`, or a file path to append to.
+    pub sink: String,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> AccessLogConfig {
+        AccessLogConfig {
+            format: Format::Json,
+            sink: "stdout".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Json,
+    /// Common Log Format, with the latency in milliseconds appended.
+    Clf,
+}
+
+pub struct AccessLog {
+    format: Format,
+    sink: std::sync::Mutex<Box<dyn Write + Send>>,
+}
+
+impl AccessLog {
+    pub fn open(config: &AccessLogConfig) -> io::Result<AccessLog> {
+        let sink: Box<dyn Write + Send> = match config.sink.as_str() {
+            "stdout" => Box::new(io::stdout()),
+            "stderr" => Box::new(io::stderr()),
+            path => Box::new(OpenOptions::new().create(true).append(true).open(path)?),
+        };
+        Ok(AccessLog {
+            format: config.format,
+            sink: std::sync::Mutex::new(sink),
+        })
+    }
+
+    fn write(&self, line: &Line) {
+        let text = match self.format {
+            Format::Json => serde_json::to_string(line).unwrap_or_default(),
+            Format::Clf => format!(
+                "{} - {} [{}] \"{} {} {}\" {} {} {}",
+                line.remote.map_or("-".to_string(), |r| r.ip().to_string()),
+                line.user.map_or("-".to_string(), |u| u.to_string()),
+                line.time.format("%d/%b/%Y:%H:%M:%S %z"),
+                line.method,
+                line.path,
+                line.version,
+                line.status,
+                line.bytes.map_or("-".to_string(), |b| b.to_string()),
+                line.latency_ms,
+            ),
+        };
+        if let Ok(mut sink) = self.sink.lock() {
+            let _ = writeln!(sink, "{}", text);
+            let _ = sink.flush();
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Line {
+    time: DateTime<Utc>,
+    remote: Option<SocketAddr>,
+    method: String,
+    path: String,
+    version: String,
+    status: u16,
+    latency_ms: u128,
+    user: Option<Uuid>,
+    /// Response body size, when known up front.
+    bytes: Option<u64>,
+}
+
+/// The log, plus app state for the logged in user.
+type LogState<D> = (Arc<AccessLog>, Arc<Mutex<AppState<D>>>);
+
+pub async fn log<D: DB, B>(
+    State((log, state)): State<LogState<D>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let start = Instant::now();
+    let time = Utc::now();
+    let user = state.lock().await.current_user;
+    let remote = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|c| c.0);
+    let method = req.method().to_string();
+    let version = format!("{:?}", req.version());
+    let path = req
+        .uri()
+        .path_and_query()
+        .map_or_else(|| req.uri().path().to_string(), |p| redact(p.as_str()));
+
+    let resp = next.run(req).await;
+
+    let bytes = resp.body().size_hint().exact().or_else(|| {
+        resp.headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|l| l.to_str().ok()?.parse().ok())
+    });
+    log.write(&Line {
+        time,
+        remote,
+        method,
+        path,
+        version,
+        status: resp.status().as_u16(),
+        latency_ms: start.elapsed().as_millis(),
+        user,
+        bytes,
+    });
+    resp
+}
@@ -0,0 +1,181 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use tokio::{fs, sync::Mutex};
+
+pub use pods_types::admin::{StorageReport, UsageLine, UserPage, UserQuery, UserSummary};
+
+use crate::{
+    current_admin, media,
+    service_accounts::{self, Service},
+    validation::Valid,
+    AppState, DB,
+};
+
+const DEFAULT_PAGE: usize = 50;
+pub(crate) const MAX_PAGE: usize = 200;
+
+/// Users sorted by name, a page at a time.
+pub async fn users<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Valid(Query(query)): Valid<Query<UserQuery>>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    if let Err(status) = current_admin(&s) {
+        return (status, Json(None));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE);
+    match s.db.users(query.q.as_deref(), query.offset, limit) {
+        Ok((total, users)) => {
+            let users = users
+                .into_iter()
+                .map(|u| UserSummary {
+                    id: u.id,
+                    name: u.name,
+                    admin: u.admin,
+                    subscriptions: u.subscribed.len(),
+                    last_active: u.last_active,
+                    flagged_idle: u.flagged_idle,
+                    archived: u.archived,
+                    profile_of: u.profile_of,
+                })
+                .collect();
+            let page = UserPage {
+                total,
+                offset: query.offset,
+                limit,
+                users,
+            };
+            (StatusCode::OK, Json(Some(page)))
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Media is stored as `<media dir>/blobs/<sha256>` once finished, and as
+/// `<media dir>/<user id>/<episode id>` while downloading.
+pub async fn storage<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    service: Option<Extension<Service>>,
+) -> impl IntoResponse {
+    let media_dir = {
+        let s = state.lock().await;
+        if let Err(status) = service_accounts::admin_or_service(&s, service) {
+            return (status, Json(None));
+        }
+        s.config.media_dir.clone()
+    };
+    let files = match media_files(&media_dir).await {
+        Ok(f) => f,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    };
+
+    let s = state.lock().await;
+    let database = match s.db.stats() {
+        Ok(d) => d,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    };
+    let mut by_user: HashMap<String, UsageLine> = HashMap::new();
+    let mut by_podcast: HashMap<String, UsageLine> = HashMap::new();
+    let mut media_bytes = 0;
+    let mut unattributed_bytes = 0;
+    let mut shared_bytes = 0;
+    let downloads = s.db.all_downloads();
+    for (dir, file, bytes) in files {
+        media_bytes += bytes;
+        let holders: Vec<(String, String)> = if dir == media::BLOBS {
+            let path = media_dir.join(media::BLOBS).join(&file);
+            downloads
+                .iter()
+                .filter(|d| d.path.as_ref() == Some(&path))
+                .map(|d| (d.user.to_string(), d.episode.to_string()))
+                .collect()
+        } else {
+            vec![(dir, file)]
+        };
+        let holders: Vec<_> = holders
+            .into_iter()
+            .filter_map(|(user, episode)| {
+                let user = s.db.get_user(user.parse().ok()?).ok()?;
+                Some((user, s.db.get_episode(episode.parse().ok()?).ok()?))
+            })
+            .collect();
+        let Some((_, episode)) = holders.first() else {
+            unattributed_bytes += bytes;
+            continue;
+        };
+        let podcast_name =
+            s.db.get_podcast(episode.podcast.clone())
+                .ok()
+                .map(|p| p.name);
+        add(
+            &mut by_podcast,
+            episode.podcast.clone(),
+            podcast_name,
+            bytes,
+        );
+        shared_bytes += bytes * (holders.len() as u64 - 1);
+        for (user, _) in holders {
+            add(&mut by_user, user.id.to_string(), Some(user.name), bytes);
+        }
+    }
+
+    let report = StorageReport {
+        media_bytes,
+        by_user: sorted(by_user),
+        by_podcast: sorted(by_podcast),
+        unattributed_bytes,
+        shared_bytes,
+        database,
+        maintenance: s.maintenance.clone(),
+    };
+    (StatusCode::OK, Json(Some(report)))
+}
+
+fn add(lines: &mut HashMap<String, UsageLine>, key: String, name: Option<String>, bytes: u64) {
+    let line = lines.entry(key.clone()).or_insert(UsageLine {
+        key,
+        name,
+        bytes: 0,
+        files: 0,
+    });
+    line.bytes += bytes;
+    line.files += 1;
+}
+
+fn sorted(lines: HashMap<String, UsageLine>) -> Vec<UsageLine> {
+    let mut lines: Vec<_> = lines.into_values().collect();
+    lines.sort_by_key(|l| std::cmp::Reverse(l.bytes));
+    lines
+}
+
+/// Every file two levels under `dir`, as (user dir, file name, size).
+pub(crate) async fn media_files(dir: &Path) -> std::io::Result<Vec<(String, String, u64)>> {
+    let mut files = vec![];
+    let mut users = match fs::read_dir(dir).await {
+        Ok(u) => u,
+        // Nothing has been downloaded yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e),
+    };
+    while let Some(user) = users.next_entry().await? {
+        if !user.file_type().await?.is_dir() {
+            files.push((String::new(), name(&user), user.metadata().await?.len()));
+            continue;
+        }
+        let mut episodes = fs::read_dir(user.path()).await?;
+        while let Some(episode) = episodes.next_entry().await? {
+            files.push((name(&user), name(&episode), episode.metadata().await?.len()));
+        }
+    }
+    Ok(files)
+}
+
+fn name(entry: &fs::DirEntry) -> String {
+    entry.file_name().to_string_lossy().into_owned()
+}
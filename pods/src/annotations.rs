@@ -0,0 +1,208 @@
+//! Bookmarks and notes on episodes, and their export as a folder of
+//! Markdown notes (one file per episode, in a folder per podcast) that can
+//! be unzipped into an Obsidian vault.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{Cursor, Write},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+pub use pods_types::annotations::{Annotation, CreateAnnotation};
+
+use crate::{validation::Valid, AppState, Episode, Error, DB};
+
+/// Longest file name for an episode's note, in characters.
+const FILE_NAME_MAX: usize = 100;
+
+/// The episode's annotations, by position with whole-episode notes first.
+pub async fn list<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((uid, episode)): Path<(Uuid, Uuid)>,
+) -> impl IntoResponse {
+    let db = &state.lock().await.db;
+    let listed = db.get_episode(episode).and_then(|_| {
+        let mut annotations: Vec<Annotation> = db
+            .annotations(uid)?
+            .into_iter()
+            .filter(|a| a.episode == episode)
+            .collect();
+        annotations.sort_by_key(|a| (a.position, a.created));
+        Ok(annotations)
+    });
+    match listed {
+        Ok(a) => (StatusCode::OK, Json(Some(a))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn create<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((uid, episode)): Path<(Uuid, Uuid)>,
+    Valid(Json(req)): Valid<Json<CreateAnnotation>>,
+) -> impl IntoResponse {
+    let db = &mut state.lock().await.db;
+    let annotation = Annotation {
+        id: Uuid::new_v4(),
+        episode,
+        position: req.position,
+        note: req.note.filter(|n| !n.trim().is_empty()),
+        created: Utc::now(),
+    };
+    let created = db
+        .get_episode(episode)
+        .and_then(|_| db.add_annotation(uid, annotation));
+    match created {
+        Ok(a) => (StatusCode::CREATED, Json(Some(a))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn delete<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((uid, annotation)): Path<(Uuid, Uuid)>,
+) -> StatusCode {
+    match state.lock().await.db.delete_annotation(uid, annotation) {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(Error::NotFound) => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// A zip of the user's annotated episodes as Markdown.
+pub async fn export_markdown<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> Response {
+    let notes = match notes(&state.lock().await.db, uid) {
+        Ok(n) => n,
+        Err(Error::NotFound) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    match zip(notes) {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, "application/zip"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"podcast-notes.zip\"",
+                ),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Path in the zip and contents of each annotated episode's note.
+fn notes<D: DB>(db: &D, uid: Uuid) -> Result<Vec<(String, String)>, Error> {
+    let mut by_episode: BTreeMap<Uuid, Vec<Annotation>> = BTreeMap::new();
+    for a in db.annotations(uid)? {
+        by_episode.entry(a.episode).or_default().push(a);
+    }
+    let mut taken = HashSet::new();
+    let mut notes = vec![];
+    for (episode, mut annotations) in by_episode {
+        // Annotations outlive episodes a feed stopped listing
+        let Ok(e) = db.get_episode(episode) else {
+            continue;
+        };
+        let podcast = db.get_podcast(e.podcast.clone())?.name;
+        annotations.sort_by_key(|a| (a.position, a.created));
+        let date = e.published.map(|p| p.format("%Y-%m-%d ").to_string());
+        let stem = format!(
+            "{}/{}{}",
+            file_name(&podcast),
+            date.unwrap_or_default(),
+            file_name(&e.title)
+        );
+        let mut path = format!("{}.md", stem);
+        let mut n = 2;
+        while !taken.insert(path.clone()) {
+            path = format!("{} ({}).md", stem, n);
+            n += 1;
+        }
+        notes.push((path, markdown(&podcast, &e, &annotations)));
+    }
+    Ok(notes)
+}
+
+/// Leaves out what file systems or Obsidian links can't hold.
+pub(crate) fn file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .take(FILE_NAME_MAX)
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.').trim();
+    match cleaned.is_empty() {
+        true => "Untitled".to_string(),
+        false => cleaned.to_string(),
+    }
+}
+
+/// `1:02:03`, or `2:03` under an hour.
+fn timestamp(secs: u32) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    match h {
+        0 => format!("{}:{:02}", m, s),
+        _ => format!("{}:{:02}:{:02}", h, m, s),
+    }
+}
+
+/// Front matter for Obsidian's properties, then the annotations as a list.
+/// Positions link into the audio with a media fragment, which browsers
+/// start playback at.
+fn markdown(podcast: &str, e: &Episode, annotations: &[Annotation]) -> String {
+    // A JSON string is a valid YAML one
+    let quoted = |s: &str| serde_json::to_string(s).unwrap_or_default();
+    let audio = e.enclosure.as_ref().map(|enc| enc.url.as_str());
+    let mut md = String::from("---\n");
+    md += &format!("podcast: {}\n", quoted(podcast));
+    md += &format!("episode: {}\n", quoted(&e.title));
+    if let Some(published) = e.published {
+        md += &format!("published: {}\n", published.format("%Y-%m-%d"));
+    }
+    if let Some(audio) = audio {
+        md += &format!("audio: {}\n", quoted(audio));
+    }
+    md += "tags: [podcast]\n---\n\n";
+    md += &format!("# {}\n\n", e.title);
+    for a in annotations {
+        let note = a.note.as_deref().unwrap_or_default().replace('\n', "\n  ");
+        let line = match (a.position, audio) {
+            (Some(p), Some(audio)) => format!("[{}](<{}#t={}>) {}", timestamp(p), audio, p, note),
+            (Some(p), None) => format!("{} {}", timestamp(p), note),
+            (None, _) => note,
+        };
+        md += &format!("- {}\n", line.trim_end());
+    }
+    md
+}
+
+fn zip(notes: Vec<(String, String)>) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    for (path, contents) in notes {
+        zip.start_file(path, SimpleFileOptions::default())?;
+        zip.write_all(contents.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
@@ -0,0 +1,365 @@
+//! Archive mode: for a show at risk of disappearing, crawl its paged feed
+//! back to the oldest episode, keep a copy of every version of the feed and
+//! download every enclosure. Archived podcasts are left alone by episode
+//! retention.
+//!
+//! Snapshots are kept as `<dir>/<podcast ID>/<snapshot ID>.xml`.
+
+use std::{
+    collections::HashSet,
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::{fs, sync::Mutex};
+use uuid::Uuid;
+
+pub use pods_types::archive::{Archive, ArchiveStatus, FeedSnapshot, SetArchive};
+
+use crate::{
+    current_admin,
+    downloads::{Download, DownloadStatus},
+    fetcher::Fetcher,
+    instance, parse_feed, refresh, AppState, Episode, Error, PodcastChannel, DB,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// Where feed snapshots, and torrents of archived podcasts, are kept.
+    pub dir: PathBuf,
+    /// How torrent clients reach this server, for the web seed in torrents,
+    /// e.g. `https://pods.example.org`. Unset means the `Host` the torrent
+    /// was asked for under, over HTTP.
+    pub public_url: Option<String>,
+    /// Announce URLs put in torrents. Without any, clients find peers through
+    /// the DHT and the web seed.
+    pub trackers: Vec<String>,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> ArchiveConfig {
+        ArchiveConfig {
+            dir: PathBuf::from("archive"),
+            public_url: None,
+            trackers: vec![],
+        }
+    }
+}
+
+/// Feed versions are caught at this granularity.
+const CRAWL_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Older pages followed per crawl, against feeds that link in circles.
+const MAX_PAGES: usize = 1000;
+const ATOM: &str = "http://www.w3.org/2005/Atom";
+
+pub fn is_on<D: DB>(db: &D, rss: &str) -> bool {
+    matches!(db.archive(rss.to_string()), Ok(Some(_)))
+}
+
+pub async fn worker<D: DB + Send + 'static>(state: Arc<Mutex<AppState<D>>>) {
+    loop {
+        let archived: Vec<PodcastChannel> = {
+            let s = state.lock().await;
+            let podcasts = s.db.podcasts().unwrap_or_default();
+            podcasts
+                .into_iter()
+                .filter(|p| is_on(&s.db, &p.rss))
+                .collect()
+        };
+        for p in archived {
+            crawl(&state, p).await;
+        }
+        tokio::time::sleep(CRAWL_INTERVAL).await;
+    }
+}
+
+/// Snapshots the feed if it changed, adds episodes only its older pages
+/// list, and queues downloads of everything not yet downloaded. Episodes on
+/// the feed itself are left to the refresh, which delivers them to inboxes.
+///
+/// Until a crawl has reached the oldest page, each one follows every page;
+/// after that it stops at the first older page with nothing new.
+async fn crawl<D: DB>(state: &Mutex<AppState<D>>, p: PodcastChannel) {
+    let (http, max_bytes, dir, crawled) = {
+        let s = state.lock().await;
+        let Ok(Some(archive)) = s.db.archive(p.rss.clone()) else {
+            return;
+        };
+        let max_bytes = match instance::effective(&s) {
+            Ok(i) => i.max_feed_bytes,
+            Err(_) => s.config.max_feed_bytes,
+        };
+        let dir = s.config.archive.dir.join(p.id.to_string());
+        (s.http.clone(), max_bytes, dir, archive.crawled)
+    };
+    let mut url = p.rss.clone();
+    let mut seen = HashSet::new();
+    let mut complete = false;
+    for page in 0..MAX_PAGES {
+        if !seen.insert(url.clone()) {
+            complete = true;
+            break;
+        }
+        let Some(body) = fetch(&http, &url, max_bytes).await else {
+            break;
+        };
+        if page == 0 {
+            snapshot(state, &p.rss, &dir, &body).await;
+        }
+        let Ok(feed) = parse_feed(&p.rss, &body) else {
+            break;
+        };
+        let added = match page {
+            0 => None,
+            _ => Some(add_older(&mut state.lock().await.db, &p.rss, feed.episodes)),
+        };
+        match older_page(&url, &body) {
+            None => {
+                complete = true;
+                break;
+            }
+            Some(_) if crawled && added == Some(0) => break,
+            Some(older) => url = older,
+        }
+    }
+
+    let s = &mut *state.lock().await;
+    let Ok(Some(mut archive)) = s.db.archive(p.rss.clone()) else {
+        return;
+    };
+    download_all(s, &p.rss, archive.by);
+    archive.crawled |= complete;
+    archive.last_crawl = Some(Utc::now());
+    let _ = s.db.set_archive(p.rss, Some(archive));
+}
+
+async fn fetch(http: &Fetcher, url: &str, max_bytes: u64) -> Option<String> {
+    let mut resp = http.get(url).ok()?.send().await.ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    let mut body = vec![];
+    while let Some(chunk) = resp.chunk().await.ok()? {
+        body.extend_from_slice(&chunk);
+        if body.len() as u64 > max_bytes {
+            return None;
+        }
+    }
+    Some(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Keeps the document if it differs from the latest snapshot.
+async fn snapshot<D: DB>(state: &Mutex<AppState<D>>, rss: &str, dir: &FsPath, body: &str) {
+    let sha256: String = Sha256::digest(body.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let latest = state.lock().await.db.feed_snapshots(rss.to_string());
+    if latest.is_ok_and(|l| l.first().is_some_and(|s| s.sha256 == sha256)) {
+        return;
+    }
+    let snapshot = FeedSnapshot {
+        id: Uuid::new_v4(),
+        fetched: Utc::now(),
+        sha256,
+        bytes: body.len() as u64,
+    };
+    if fs::create_dir_all(dir).await.is_err()
+        || fs::write(snapshot_path(dir, snapshot.id), body)
+            .await
+            .is_err()
+    {
+        return;
+    }
+    let _ = state
+        .lock()
+        .await
+        .db
+        .add_feed_snapshot(rss.to_string(), snapshot);
+}
+
+pub(crate) fn snapshot_path(dir: &FsPath, id: Uuid) -> PathBuf {
+    dir.join(format!("{}.xml", id))
+}
+
+/// The next older page of an RFC 5005 paged or archived feed, from the
+/// channel's `<atom:link rel="next">` or `rel="prev-archive"`.
+fn older_page(url: &str, body: &str) -> Option<String> {
+    let xml = roxmltree::Document::parse(body).ok()?;
+    let channel = xml
+        .descendants()
+        .find(|n| n.tag_name().name() == "channel")?;
+    let href = channel
+        .children()
+        .filter(|n| n.tag_name().name() == "link" && n.tag_name().namespace() == Some(ATOM))
+        .find(|n| matches!(n.attribute("rel"), Some("next" | "prev-archive")))?
+        .attribute("href")?;
+    Url::parse(url)
+        .ok()?
+        .join(href.trim())
+        .ok()
+        .map(String::from)
+}
+
+/// Stores the page's episodes the podcast doesn't have, without delivering
+/// them to inboxes: they're the back catalog, not new. Returns how many.
+fn add_older<D: DB>(db: &mut D, rss: &str, episodes: Vec<Episode>) -> usize {
+    let known = db.episodes(rss.to_string()).unwrap_or_default();
+    let older: Vec<Episode> = episodes
+        .into_iter()
+        .filter(|e| !known.iter().any(|k| refresh::same_episode(k, e)))
+        .collect();
+    let added = older.len();
+    match older.is_empty() || db.add_episodes(rss.to_string(), older).is_err() {
+        true => 0,
+        false => added,
+    }
+}
+
+/// Queues background downloads, under `by`, of every episode with an
+/// enclosure that nobody has downloaded. They aren't held to `by`'s quota.
+fn download_all<D: DB>(s: &mut AppState<D>, rss: &str, by: Uuid) {
+    // A failed archive download isn't retried on every crawl
+    let have: HashSet<Uuid> =
+        s.db.all_downloads()
+            .into_iter()
+            .filter(|d| d.status != DownloadStatus::Failed || d.user == by)
+            .map(|d| d.episode)
+            .collect();
+    let mut queued = false;
+    for e in s.db.episodes(rss.to_string()).unwrap_or_default() {
+        let Some(enclosure) = e.enclosure.filter(|_| !have.contains(&e.id)) else {
+            continue;
+        };
+        let download = Download {
+            id: Uuid::new_v4(),
+            user: by,
+            episode: e.id,
+            url: enclosure.url,
+            status: DownloadStatus::Queued,
+            expected_bytes: enclosure.length,
+            bytes: 0,
+            background: true,
+            attempts: 0,
+            retry_after: None,
+            sha256: None,
+            integrity: None,
+            path: None,
+        };
+        queued |= s.db.save_download(download).is_ok();
+    }
+    if queued {
+        s.download_notify.notify_one();
+    }
+}
+
+fn status<D: DB>(s: &AppState<D>, podcast: Uuid) -> Result<ArchiveStatus, Error> {
+    let p = s.db.get_podcast_by_id(podcast)?;
+    let episodes = s.db.episodes(p.rss.clone())?;
+    let done: HashSet<Uuid> =
+        s.db.all_downloads()
+            .into_iter()
+            .filter(|d| d.status == DownloadStatus::Done)
+            .map(|d| d.episode)
+            .collect();
+    Ok(ArchiveStatus {
+        podcast,
+        archive: s.db.archive(p.rss.clone())?,
+        episodes: episodes.len(),
+        downloaded: episodes.iter().filter(|e| done.contains(&e.id)).count(),
+        snapshots: s.db.feed_snapshots(p.rss)?,
+    })
+}
+
+pub async fn get_archive<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(podcast): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    if let Err(status) = current_admin(&s) {
+        return (status, Json(None));
+    }
+    match status(&s, podcast) {
+        Ok(a) => (StatusCode::OK, Json(Some(a))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Turning archive mode on starts a crawl straight away. Turning it off
+/// keeps what was archived.
+pub async fn put_archive<D: DB + Send + 'static>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(podcast): Path<Uuid>,
+    Json(payload): Json<SetArchive>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    let admin = match current_admin(s) {
+        Ok(uid) => uid,
+        Err(status) => return (status, Json(None)),
+    };
+    let updated = s.db.get_podcast_by_id(podcast).and_then(|p| {
+        match (payload.archive, s.db.archive(p.rss.clone())?) {
+            (true, None) => {
+                let archive = Archive {
+                    by: admin,
+                    since: Utc::now(),
+                    crawled: false,
+                    last_crawl: None,
+                };
+                s.db.set_archive(p.rss.clone(), Some(archive))?;
+                let state = state.clone();
+                tokio::spawn(async move { crawl(&state, p).await });
+            }
+            (false, Some(_)) => s.db.set_archive(p.rss, None)?,
+            _ => {}
+        }
+        status(s, podcast)
+    });
+    match updated {
+        Ok(a) => (StatusCode::OK, Json(Some(a))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// The feed document as it was when the snapshot was taken.
+pub async fn get_snapshot<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((podcast, snapshot)): Path<(Uuid, Uuid)>,
+) -> Response {
+    let path = {
+        let s = state.lock().await;
+        if let Err(status) = current_admin(&s) {
+            return status.into_response();
+        }
+        let p = match s.db.get_podcast_by_id(podcast) {
+            Ok(p) => p,
+            Err(Error::NotFound) => return StatusCode::NOT_FOUND.into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+        match s.db.feed_snapshots(p.rss) {
+            Ok(snapshots) if snapshots.iter().any(|x| x.id == snapshot) => {
+                snapshot_path(&s.config.archive.dir.join(podcast.to_string()), snapshot)
+            }
+            Ok(_) => return StatusCode::NOT_FOUND.into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    };
+    match fs::read(&path).await {
+        Ok(body) => ([(header::CONTENT_TYPE, "application/rss+xml")], body).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
@@ -0,0 +1,103 @@
+use std::{sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use serde::Deserialize;
+use tokio::{
+    sync::Mutex,
+    time::{sleep_until, Instant},
+};
+
+/// A byte-rate cap, in bytes per second. `None` means unlimited.
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(default)]
+pub struct Caps {
+    pub global: Option<u64>,
+    pub per_connection: Option<u64>,
+}
+
+/// Paces callers so that, on average, no more than `rate` bytes per second
+/// get through. Short bursts of one chunk are allowed.
+#[derive(Debug)]
+struct RateLimit {
+    rate: u64,
+    next: Mutex<Instant>,
+}
+
+impl RateLimit {
+    fn new(rate: u64) -> RateLimit {
+        RateLimit {
+            rate: rate.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    async fn take(&self, bytes: usize) {
+        let start = {
+            let mut next = self.next.lock().await;
+            let start = (*next).max(Instant::now());
+            *next = start + Duration::from_secs_f64(bytes as f64 / self.rate as f64);
+            start
+        };
+        sleep_until(start).await;
+    }
+}
+
+/// Bandwidth limits for one kind of traffic (downloads, streaming). The
+/// global limit is shared by every connection made through it.
+#[derive(Clone, Debug)]
+pub struct Throttle {
+    global: Option<Arc<RateLimit>>,
+    per_connection: Option<u64>,
+}
+
+impl Throttle {
+    pub fn new(caps: Caps) -> Throttle {
+        Throttle {
+            global: caps.global.map(|r| Arc::new(RateLimit::new(r))),
+            per_connection: caps.per_connection,
+        }
+    }
+
+    /// Limits for a single new connection.
+    pub fn connection(&self) -> Connection {
+        Connection {
+            global: self.global.clone(),
+            own: self.per_connection.map(RateLimit::new),
+        }
+    }
+}
+
+pub struct Connection {
+    global: Option<Arc<RateLimit>>,
+    own: Option<RateLimit>,
+}
+
+impl Connection {
+    /// Waits until `bytes` more may be sent.
+    pub async fn take(&self, bytes: usize) {
+        if let Some(own) = &self.own {
+            own.take(bytes).await;
+        }
+        if let Some(global) = &self.global {
+            global.take(bytes).await;
+        }
+    }
+
+    /// Throttles a byte stream through this connection's limits.
+    pub fn wrap<S, E>(self, stream: S) -> impl Stream<Item = Result<Bytes, E>>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+    {
+        let conn = Arc::new(self);
+        stream.then(move |chunk| {
+            let conn = conn.clone();
+            async move {
+                if let Ok(bytes) = &chunk {
+                    conn.take(bytes.len()).await;
+                }
+                chunk
+            }
+        })
+    }
+}
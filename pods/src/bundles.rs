@@ -0,0 +1,440 @@
+//! Offline bundles: a zip of chosen episodes' audio, their metadata and
+//! podcast artwork, with an M3U playlist and an RSS feed pointing at the
+//! files inside, for copying to a device with no connection. Bundles are
+//! built in the background from finished downloads where there are some,
+//! fetching the rest, and deleted after `keep_hours`.
+//!
+//! ```text
+//! playlist.m3u
+//! feed.xml
+//! episodes.json
+//! <podcast>/cover.jpg
+//! <podcast>/<date> <episode title>.mp3
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    body::StreamBody,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use quick_xml::escape::escape;
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex, task};
+use uuid::Uuid;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+pub use pods_types::bundles::{Bundle, BundleStatus, CreateBundle};
+
+use crate::{
+    annotations::file_name, fetcher::Fetcher, media, profiles::Acting, stream_cache,
+    validation::Valid, AppState, Episode, Error, DB,
+};
+
+/// Most episodes in one bundle.
+pub(crate) const MAX_EPISODES: usize = 100;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct BundleConfig {
+    /// Where bundles are built and kept.
+    pub dir: PathBuf,
+    pub keep_hours: u32,
+}
+
+impl Default for BundleConfig {
+    fn default() -> BundleConfig {
+        BundleConfig {
+            dir: PathBuf::from("bundles"),
+            keep_hours: 24,
+        }
+    }
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// An episode to put in a bundle, and where its audio comes from.
+struct Item {
+    episode: Episode,
+    podcast: String,
+    artwork: Option<String>,
+    local: Option<PathBuf>,
+}
+
+/// One entry of `episodes.json`.
+#[derive(Serialize)]
+struct Entry<'a> {
+    podcast: &'a str,
+    file: &'a str,
+    episode: &'a Episode,
+}
+
+fn zip_path(dir: &FsPath, id: Uuid) -> PathBuf {
+    dir.join(format!("{}.zip", id))
+}
+
+/// Forgets expired bundles and deletes their zips.
+pub(crate) fn purge<D: DB>(s: &mut AppState<D>) {
+    let now = Utc::now();
+    let dir = s.config.bundles.dir.clone();
+    s.bundles.retain(|id, b| {
+        let keep = b.expires > now;
+        if !keep {
+            let path = zip_path(&dir, *id);
+            tokio::spawn(async move {
+                let _ = fs::remove_file(path).await;
+            });
+        }
+        keep
+    });
+}
+
+/// The acting user's bundle.
+fn owned<D: DB>(s: &AppState<D>, user: Option<Uuid>, id: Uuid) -> Result<Bundle, StatusCode> {
+    let user = user.ok_or(StatusCode::UNAUTHORIZED)?;
+    match s.bundles.get(&id) {
+        Some(b) if b.user == user && b.expires > Utc::now() => Ok(b.clone()),
+        _ => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// Starts building a bundle for the acting user, answering `202` with the
+/// job to poll.
+pub async fn create<D: DB + Send + 'static>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Acting(user): Acting,
+    Valid(Json(req)): Valid<Json<CreateBundle>>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    let Some(user) = user else {
+        return (StatusCode::UNAUTHORIZED, Json(None));
+    };
+    purge(s);
+    let mut episodes = vec![];
+    for id in req.episodes {
+        match s.db.get_episode(id) {
+            Ok(_) if episodes.contains(&id) => {}
+            Ok(_) => episodes.push(id),
+            Err(Error::NotFound) => return (StatusCode::NOT_FOUND, Json(None)),
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+        }
+    }
+    let created = Utc::now();
+    let bundle = Bundle {
+        id: Uuid::new_v4(),
+        user,
+        episodes,
+        status: BundleStatus::Queued,
+        created,
+        finished: None,
+        expires: created + Duration::hours(i64::from(s.config.bundles.keep_hours)),
+        bytes: None,
+        error: None,
+        download: None,
+    };
+    s.bundles.insert(bundle.id, bundle.clone());
+    tokio::spawn(build(state.clone(), bundle.id));
+    (StatusCode::ACCEPTED, Json(Some(bundle)))
+}
+
+pub async fn status<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Acting(user): Acting,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match owned(&*state.lock().await, user, id) {
+        Ok(b) => (StatusCode::OK, Json(Some(b))),
+        Err(status) => (status, Json(None)),
+    }
+}
+
+/// The finished zip. `409` while it's still being built.
+pub async fn download<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Acting(user): Acting,
+    Path(id): Path<Uuid>,
+) -> Response {
+    let (bundle, dir) = {
+        let s = state.lock().await;
+        (owned(&s, user, id), s.config.bundles.dir.clone())
+    };
+    match bundle {
+        Ok(b) if b.status == BundleStatus::Ready => {}
+        Ok(b) if b.status == BundleStatus::Failed => return StatusCode::NOT_FOUND.into_response(),
+        Ok(_) => return StatusCode::CONFLICT.into_response(),
+        Err(status) => return status.into_response(),
+    }
+    let path = zip_path(&dir, id);
+    let Ok(meta) = fs::metadata(&path).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let body = match stream_cache::read_file(&path, 0, meta.len().saturating_sub(1)).await {
+        Ok(b) => b,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let disposition = format!("attachment; filename=\"podcasts-{}.zip\"", id);
+    (
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_LENGTH, meta.len().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        StreamBody::new(body),
+    )
+        .into_response()
+}
+
+async fn build<D: DB>(state: Arc<Mutex<AppState<D>>>, id: Uuid) {
+    let items = {
+        let s = &mut *state.lock().await;
+        let Some(b) = s.bundles.get_mut(&id) else {
+            return;
+        };
+        b.status = BundleStatus::Building;
+        let episodes = b.episodes.clone();
+        let items = episodes
+            .into_iter()
+            .map(|e| {
+                let episode = s.db.get_episode(e)?;
+                let podcast = s.db.get_podcast(episode.podcast.clone())?;
+                Ok(Item {
+                    local: media::find(&s.db, e).and_then(|d| d.path),
+                    podcast: podcast.name,
+                    artwork: podcast.artwork,
+                    episode,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>();
+        items.map(|i| (i, s.http.clone(), s.config.bundles.dir.clone()))
+    };
+    let built = match items {
+        Ok((items, http, dir)) => {
+            let built = assemble(&http, &dir, id, items).await;
+            // Fetched episodes, and the zip if it failed half way
+            let _ = fs::remove_dir_all(dir.join(id.to_string())).await;
+            let _ = fs::remove_file(dir.join(format!("{}.zip.part", id))).await;
+            built
+        }
+        Err(e) => Err(format!("{:?}", e).into()),
+    };
+    let s = &mut *state.lock().await;
+    let Some(b) = s.bundles.get_mut(&id) else {
+        return;
+    };
+    b.finished = Some(Utc::now());
+    match built {
+        Ok(bytes) => {
+            b.status = BundleStatus::Ready;
+            b.bytes = Some(bytes);
+            b.download = Some(format!("export/bundle/{}/download", id));
+        }
+        Err(e) => {
+            eprintln!("building bundle {} failed: {}", id, e);
+            b.status = BundleStatus::Failed;
+            b.error = Some(e.to_string());
+        }
+    }
+}
+
+/// Writes the zip, returning its size.
+async fn assemble(
+    http: &Fetcher,
+    dir: &FsPath,
+    id: Uuid,
+    items: Vec<Item>,
+) -> Result<u64, BoxError> {
+    let temp = dir.join(id.to_string());
+    fs::create_dir_all(&temp).await?;
+    let part = dir.join(format!("{}.zip.part", id));
+    let mut zip = ZipWriter::new(std::fs::File::create(&part)?);
+    // Audio and images are compressed already
+    let stored = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+    let mut taken = HashSet::new();
+    let mut covers: HashMap<String, Option<String>> = HashMap::new();
+    let mut files = vec![];
+    for (n, item) in items.iter().enumerate() {
+        let folder = file_name(&item.podcast);
+        if !covers.contains_key(&folder) {
+            let cover = match &item.artwork {
+                Some(url) => fetch_cover(http, url, &temp.join(format!("{}-cover", n))).await,
+                None => None,
+            };
+            let cover = match cover {
+                Some((path, ext)) => {
+                    let name = format!("{}/cover.{}", folder, ext);
+                    zip = add_file(zip, name.clone(), path.clone(), stored).await?;
+                    let _ = fs::remove_file(path).await;
+                    Some(name)
+                }
+                None => None,
+            };
+            covers.insert(folder.clone(), cover);
+        }
+
+        let e = &item.episode;
+        let date = e.published.map(|p| p.format("%Y-%m-%d ").to_string());
+        let stem = format!(
+            "{}/{}{}",
+            folder,
+            date.unwrap_or_default(),
+            file_name(&e.title)
+        );
+        let ext = extension(e);
+        let mut name = format!("{}.{}", stem, ext);
+        let mut copy = 2;
+        while !taken.insert(name.clone()) {
+            name = format!("{} ({}).{}", stem, copy, ext);
+            copy += 1;
+        }
+        let source = match &item.local {
+            Some(path) => path.clone(),
+            None => {
+                let url = &e.enclosure.as_ref().ok_or("episode has no audio")?.url;
+                let to = temp.join(n.to_string());
+                fetch(http, url, &to).await?;
+                to
+            }
+        };
+        zip = add_file(zip, name.clone(), source.clone(), stored).await?;
+        if item.local.is_none() {
+            let _ = fs::remove_file(source).await;
+        }
+        files.push(name);
+    }
+
+    let text = SimpleFileOptions::default();
+    zip.start_file("playlist.m3u", text)?;
+    io::Write::write_all(&mut zip, m3u(&items, &files).as_bytes())?;
+    zip.start_file("feed.xml", text)?;
+    io::Write::write_all(&mut zip, rss(&items, &files, &covers).as_bytes())?;
+    let entries: Vec<Entry> = items
+        .iter()
+        .zip(&files)
+        .map(|(i, file)| Entry {
+            podcast: &i.podcast,
+            file,
+            episode: &i.episode,
+        })
+        .collect();
+    zip.start_file("episodes.json", text)?;
+    serde_json::to_writer_pretty(&mut zip, &entries)?;
+    let file = task::spawn_blocking(move || zip.finish()).await??;
+    let bytes = file.metadata()?.len();
+    fs::rename(&part, zip_path(dir, id)).await?;
+    Ok(bytes)
+}
+
+/// Copies a file into the zip off the async threads.
+async fn add_file(
+    mut zip: ZipWriter<std::fs::File>,
+    name: String,
+    source: PathBuf,
+    options: SimpleFileOptions,
+) -> Result<ZipWriter<std::fs::File>, BoxError> {
+    let zip = task::spawn_blocking(move || -> Result<_, BoxError> {
+        zip.start_file(name, options)?;
+        io::copy(&mut std::fs::File::open(source)?, &mut zip)?;
+        Ok(zip)
+    });
+    zip.await?
+}
+
+async fn fetch(http: &Fetcher, url: &str, to: &FsPath) -> Result<(), BoxError> {
+    let mut resp = http.get(url)?.send().await?.error_for_status()?;
+    let mut file = fs::File::create(to).await?;
+    while let Some(chunk) = resp.chunk().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(())
+}
+
+/// The podcast's artwork and its file extension. Artwork served by this
+/// server itself (a relative URL) is left out.
+async fn fetch_cover(http: &Fetcher, url: &str, to: &FsPath) -> Option<(PathBuf, &'static str)> {
+    let ext = match url.rsplit('.').next()?.to_ascii_lowercase().as_str() {
+        "png" => "png",
+        "webp" => "webp",
+        _ => "jpg",
+    };
+    fetch(http, url, to).await.ok()?;
+    Some((to.to_path_buf(), ext))
+}
+
+/// From the enclosure URL's file name, or else its MIME type.
+pub(crate) fn extension(e: &Episode) -> String {
+    let enclosure = e.enclosure.as_ref();
+    let from_url = enclosure.and_then(|enc| {
+        let path = enc.url.split(['?', '#']).next()?;
+        let ext = path.rsplit('/').next()?.rsplit_once('.')?.1;
+        let ok = (1..=4).contains(&ext.len()) && ext.chars().all(|c| c.is_ascii_alphanumeric());
+        ok.then(|| ext.to_ascii_lowercase())
+    });
+    let from_mime = || match enclosure?.mime_type.as_deref()? {
+        "audio/mp4" | "audio/x-m4a" | "audio/aac" => Some("m4a".to_string()),
+        "audio/ogg" | "audio/opus" => Some("ogg".to_string()),
+        "audio/flac" => Some("flac".to_string()),
+        _ => None,
+    };
+    from_url
+        .or_else(from_mime)
+        .unwrap_or_else(|| "mp3".to_string())
+}
+
+fn m3u(items: &[Item], files: &[String]) -> String {
+    let mut m3u = String::from("#EXTM3U\n");
+    for (item, file) in items.iter().zip(files) {
+        m3u += &format!(
+            "#EXTINF:-1,{} - {}\n{}\n",
+            item.podcast, item.episode.title, file
+        );
+    }
+    m3u
+}
+
+/// A feed of the bundle's episodes with relative enclosure URLs, for
+/// players that import a feed from a folder.
+fn rss(items: &[Item], files: &[String], covers: &HashMap<String, Option<String>>) -> String {
+    let mut xml = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">"#,
+        "\n<channel>\n<title>Offline bundle</title>\n<description>Episodes exported from pods</description>\n",
+    ));
+    for (item, file) in items.iter().zip(files) {
+        let e = &item.episode;
+        let mime = e.enclosure.as_ref().and_then(|enc| enc.mime_type.clone());
+        xml += "<item>\n";
+        xml += &format!("<title>{}</title>\n", escape(e.title.as_str()));
+        xml += &format!(
+            "<itunes:author>{}</itunes:author>\n",
+            escape(item.podcast.as_str())
+        );
+        xml += &format!("<guid>{}</guid>\n", e.id);
+        if let Some(published) = e.published {
+            xml += &format!("<pubDate>{}</pubDate>\n", published.to_rfc2822());
+        }
+        if let Some(Some(cover)) = covers.get(&file_name(&item.podcast)) {
+            xml += &format!("<itunes:image href=\"{}\"/>\n", escape(cover.as_str()));
+        }
+        xml += &format!(
+            "<enclosure url=\"{}\" type=\"{}\"/>\n",
+            escape(file.as_str()),
+            escape(mime.as_deref().unwrap_or("audio/mpeg"))
+        );
+        xml += "</item>\n";
+    }
+    xml += "</channel>\n</rss>\n";
+    xml
+}
@@ -0,0 +1,119 @@
+//! Which chapters of an episode a user has heard, worked out from the
+//! stretches their `play` actions cover (`started` to `position`) against
+//! the chapter marks embedded in the downloaded file.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::chapters::{ChapterProgress, HeardChapter};
+
+use crate::{gpodder::ActionKind, tags::Chapter, AppState, Error, DB};
+
+/// At least this much of a chapter counts as having heard it, so skipping
+/// the last seconds into the next one doesn't leave it unheard.
+const HEARD_PERCENT: f32 = 90.0;
+
+/// Merges overlapping `(start, end)` stretches.
+fn merge(mut stretches: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    stretches.sort();
+    let mut merged: Vec<(u64, u64)> = vec![];
+    for (start, end) in stretches {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn heard(
+    chapters: Vec<Chapter>,
+    played: &[(u64, u64)],
+    total_ms: Option<u64>,
+) -> Vec<HeardChapter> {
+    let starts: Vec<u64> = chapters.iter().map(|c| c.start_ms).collect();
+    chapters
+        .into_iter()
+        .enumerate()
+        .map(|(i, chapter)| {
+            // Vorbis comment chapters run until the next one starts
+            let end = chapter
+                .end_ms
+                .or_else(|| starts.get(i + 1).copied())
+                .or(total_ms);
+            let until = end.unwrap_or(u64::MAX);
+            let heard_ms = played
+                .iter()
+                .map(|(from, to)| to.min(&until).saturating_sub(*from.max(&chapter.start_ms)))
+                .sum();
+            let completion = end
+                .map(|end| end.saturating_sub(chapter.start_ms))
+                .filter(|len| *len > 0)
+                .map(|len| (heard_ms as f32 * 100.0 / len as f32).min(100.0));
+            HeardChapter {
+                chapter,
+                heard_ms,
+                completion,
+                heard: completion.is_some_and(|c| c >= HEARD_PERCENT),
+            }
+        })
+        .collect()
+}
+
+fn progress<D: DB>(db: &D, user: Uuid, episode: Uuid) -> Result<ChapterProgress, Error> {
+    let audio = db
+        .get_episode(episode)?
+        .enclosure
+        .ok_or(Error::NotFound)?
+        .url;
+    let mut chapters = db.episode_tags(episode)?.ok_or(Error::NotFound)?.chapters;
+    chapters.sort_by_key(|c| c.start_ms);
+    let plays: Vec<_> = db
+        .episode_actions(user)?
+        .into_iter()
+        .filter(|a| a.action == ActionKind::Play && a.episode == audio)
+        .collect();
+    let played = merge(
+        plays
+            .iter()
+            .filter_map(|a| Some((u64::from(a.started?) * 1000, u64::from(a.position?) * 1000)))
+            .filter(|(start, end)| start < end)
+            .collect(),
+    );
+    let total_ms = plays
+        .iter()
+        .filter_map(|a| a.total)
+        .max()
+        .map(|t| u64::from(t) * 1000);
+    let chapters = heard(chapters, &played, total_ms);
+    Ok(ChapterProgress {
+        episode,
+        first_unheard: chapters.iter().position(|c| !c.heard),
+        chapters,
+    })
+}
+
+/// `404` unless the episode's download had chapter marks.
+pub async fn get_chapters<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((uid, episode)): Path<(Uuid, Uuid)>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    match s
+        .db
+        .get_user(uid)
+        .and_then(|_| progress(&s.db, uid, episode))
+    {
+        Ok(p) => (StatusCode::OK, Json(Some(p))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
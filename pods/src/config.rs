@@ -0,0 +1,242 @@
+use std::{env, fs, path::PathBuf};
+
+use chrono::NaiveTime;
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    access_log::AccessLogConfig,
+    archive::ArchiveConfig,
+    bandwidth::Caps,
+    bundles::BundleConfig,
+    discovery::{ItunesConfig, ListenNotesConfig, PodcastIndexConfig},
+    dlna::DlnaConfig,
+    error_reporting::ErrorReportingConfig,
+    federation::FederationConfig,
+    fetcher::FetchConfig,
+    idle::IdlePolicy,
+    instance::DiscoveryProvider,
+    mail::MailConfig,
+    maintenance::MaintenanceConfig,
+    media_links::MediaLinkConfig,
+    metrics_export::MetricsExportConfig,
+    public::PublicApiConfig,
+    request_quota::RequestQuotaConfig,
+    retention::EpisodeRetention,
+    scrobble::ScrobblingConfig,
+    stream_cache::StreamCacheConfig,
+    tenancy::TenancyConfig,
+    timeout::TimeoutConfig,
+    transcode::TranscodeConfig,
+    transcription::TranscriptionConfig,
+};
+
+/// Instance configuration, read from the TOML file named by `PODS_CONFIG`
+/// (default `pods.toml`). Every field has a default, so a missing file just
+/// means a default instance.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Config {
+    pub listen: String,
+    /// Connection handling for the API's own listener.
+    pub server: ServerConfig,
+    pub media_dir: PathBuf,
+    /// IANA zone for the download window and users without their own
+    /// timezone setting, e.g. `Europe/Berlin`. Unset means the server's.
+    pub timezone: Option<Tz>,
+    /// Whether anyone can create an account. Admins can change this and the
+    /// next four at runtime through `/admin/settings`.
+    pub registration_open: bool,
+    /// Minutes between checks of each feed for new episodes, at most. See
+    /// `refresh` for per-feed intervals.
+    pub refresh_interval_mins: u32,
+    /// Largest feed document to fetch.
+    pub max_feed_bytes: u64,
+    /// Podcast directories used to find feeds.
+    pub discovery: Vec<DiscoveryProvider>,
+    /// Default for users without their own `hide_explicit` setting.
+    pub hide_explicit: bool,
+    pub itunes: ItunesConfig,
+    /// Needed for Podcast Index lookups.
+    pub podcast_index: Option<PodcastIndexConfig>,
+    /// Needed for Listen Notes lookups and search.
+    pub listen_notes: Option<ListenNotesConfig>,
+    pub quota: QuotaConfig,
+    pub bandwidth: BandwidthConfig,
+    /// Hours background downloads may run in, in the instance timezone. Unset means any
+    /// time; user-triggered downloads always start immediately.
+    pub download_window: Option<DownloadWindow>,
+    pub fetch: FetchConfig,
+    /// Where the stream proxy keeps enclosure bytes it fetched.
+    pub stream_cache: StreamCacheConfig,
+    /// How `?quality=low` renditions are made.
+    pub transcode: TranscodeConfig,
+    /// Speech-to-text for episodes. Off unless configured.
+    pub transcription: Option<TranscriptionConfig>,
+    pub timeouts: TimeoutConfig,
+    /// One line per request, separate from any debug output. Off unless
+    /// configured.
+    pub access_log: Option<AccessLogConfig>,
+    /// Where to send panics and internal errors. Off unless configured.
+    pub error_reporting: Option<ErrorReportingConfig>,
+    /// SMTP server for notices to users. Off unless configured.
+    pub mail: Option<MailConfig>,
+    /// What to do about accounts nobody uses. Off unless configured.
+    pub idle_accounts: Option<IdlePolicy>,
+    /// How many old episodes to keep in the database. Off unless configured.
+    pub episode_retention: Option<EpisodeRetention>,
+    /// Where to push listening and ingestion metrics. Off unless configured.
+    pub metrics_export: Option<MetricsExportConfig>,
+    /// Catalog sharing with other pods servers. Off unless configured.
+    pub federation: FederationConfig,
+    /// The read-only catalog API under `/public`. Off unless configured.
+    pub public_api: Option<PublicApiConfig>,
+    /// Where offline bundles are built and how long they're kept.
+    pub bundles: BundleConfig,
+    /// Where archived podcasts' feed snapshots are kept.
+    pub archive: ArchiveConfig,
+    /// Separate groups of users under `/t/<slug>/`. Off unless configured.
+    pub tenancy: Option<TenancyConfig>,
+    /// Daily limits on API requests per user and service account. Requests
+    /// are counted either way.
+    pub request_quota: RequestQuotaConfig,
+    /// Signed links to episodes' audio for players that can't log in. Off
+    /// unless configured.
+    pub media_links: Option<MediaLinkConfig>,
+    /// A UPnP media server of one user's subscriptions for players on the
+    /// network. Off unless configured.
+    pub dlna: Option<DlnaConfig>,
+    /// Sending the episodes users finish to ListenBrainz or their own
+    /// webhooks. Off unless configured.
+    pub scrobbling: Option<ScrobblingConfig>,
+    /// The nightly database and media upkeep. Off unless configured.
+    pub maintenance: Option<MaintenanceConfig>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct DownloadWindow {
+    #[serde(deserialize_with = "hh_mm")]
+    pub start: NaiveTime,
+    #[serde(deserialize_with = "hh_mm")]
+    pub end: NaiveTime,
+}
+
+impl DownloadWindow {
+    /// Windows may wrap past midnight, e.g. 22:00 to 06:00.
+    pub fn contains(&self, t: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= t && t < self.end
+        } else {
+            t >= self.start || t < self.end
+        }
+    }
+}
+
+pub(crate) fn hh_mm<'de, D: Deserializer<'de>>(d: D) -> Result<NaiveTime, D::Error> {
+    let s = String::deserialize(d)?;
+    NaiveTime::parse_from_str(&s, "%H:%M").map_err(serde::de::Error::custom)
+}
+
+/// Tuning for the inbound HTTP server, e.g. for many small requests from
+/// mobile clients.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Accept HTTP/2 (cleartext, with prior knowledge, as reverse proxies
+    /// speak it) next to HTTP/1.1.
+    pub http2: bool,
+    /// Keep HTTP/1.1 connections open between requests.
+    pub keep_alive: bool,
+    /// TCP keepalive probes on idle connections, every this many seconds.
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Seconds between HTTP/2 pings that keep a connection alive. Unset
+    /// means no pings.
+    pub http2_keep_alive_interval_secs: Option<u64>,
+    /// Seconds to wait for a ping's answer before closing the connection.
+    pub http2_keep_alive_timeout_secs: u64,
+    /// Requests a client may have in flight on one HTTP/2 connection.
+    pub http2_max_concurrent_streams: u32,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            http2: true,
+            keep_alive: true,
+            tcp_keepalive_secs: None,
+            http2_keep_alive_interval_secs: None,
+            http2_keep_alive_timeout_secs: 20,
+            http2_max_concurrent_streams: 200,
+        }
+    }
+}
+
+/// Bandwidth caps for the download worker and the streaming proxy.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct BandwidthConfig {
+    pub download: Caps,
+    pub stream: Caps,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct QuotaConfig {
+    /// Downloaded media bytes allowed per user, unless an admin has set an
+    /// override for them. `None` means unlimited.
+    pub default_bytes: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            listen: "0.0.0.0:3000".to_string(),
+            server: ServerConfig::default(),
+            media_dir: PathBuf::from("media"),
+            timezone: None,
+            registration_open: true,
+            refresh_interval_mins: 60,
+            max_feed_bytes: 20 * 1024 * 1024,
+            discovery: vec![DiscoveryProvider::Itunes, DiscoveryProvider::PodcastIndex],
+            hide_explicit: false,
+            itunes: ItunesConfig::default(),
+            podcast_index: None,
+            listen_notes: None,
+            quota: QuotaConfig::default(),
+            bandwidth: BandwidthConfig::default(),
+            download_window: None,
+            fetch: FetchConfig::default(),
+            stream_cache: StreamCacheConfig::default(),
+            transcode: TranscodeConfig::default(),
+            transcription: None,
+            timeouts: TimeoutConfig::default(),
+            access_log: None,
+            error_reporting: None,
+            mail: None,
+            idle_accounts: None,
+            episode_retention: None,
+            metrics_export: None,
+            federation: FederationConfig::default(),
+            public_api: None,
+            bundles: BundleConfig::default(),
+            archive: ArchiveConfig::default(),
+            tenancy: None,
+            request_quota: RequestQuotaConfig::default(),
+            media_links: None,
+            dlna: None,
+            scrobbling: None,
+            maintenance: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Config {
+        let path = env::var("PODS_CONFIG").unwrap_or_else(|_| "pods.toml".to_string());
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .unwrap_or_else(|e| panic!("invalid config file {}: {}", path, e)),
+            Err(_) => Config::default(),
+        }
+    }
+}
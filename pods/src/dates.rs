@@ -0,0 +1,130 @@
+//! Publish dates as feeds actually write them: RFC 822 with whatever zone
+//! abbreviation the publisher's CMS likes, or ISO 8601.
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Parses a feed date, normalized to UTC. Dates without a zone are taken as
+/// UTC.
+pub fn parse(raw: &str) -> Option<DateTime<Utc>> {
+    let s = raw.trim();
+    if let Ok(d) = DateTime::parse_from_rfc2822(s) {
+        return Some(d.with_timezone(&Utc));
+    }
+    if let Ok(d) = DateTime::parse_from_rfc3339(s) {
+        return Some(d.with_timezone(&Utc));
+    }
+    iso8601(s).or_else(|| rfc822(s))
+}
+
+/// Wall-clock time in `tz`, or in the server's own zone if unset.
+pub fn local(t: DateTime<Utc>, tz: Option<Tz>) -> NaiveDateTime {
+    match tz {
+        Some(tz) => t.with_timezone(&tz).naive_local(),
+        None => t.with_timezone(&Local).naive_local(),
+    }
+}
+
+fn iso8601(s: &str) -> Option<DateTime<Utc>> {
+    for format in [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+    ] {
+        if let Ok(d) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(d.and_utc());
+        }
+    }
+    if let Ok(d) = DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S %z") {
+        return Some(d.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .map(|d| d.and_time(NaiveTime::MIN).and_utc())
+}
+
+/// The forgiving version: optional weekday, full or abbreviated month names,
+/// two-digit years, missing seconds, and zone abbreviations beyond the few
+/// RFC 822 defines.
+fn rfc822(s: &str) -> Option<DateTime<Utc>> {
+    let mut tokens: Vec<&str> = s
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|t| !t.is_empty())
+        .collect();
+    if tokens.first()?.chars().all(|c| c.is_ascii_alphabetic()) {
+        tokens.remove(0);
+    }
+    let [day, month, year, time, rest @ ..] = tokens.as_slice() else {
+        return None;
+    };
+
+    let day: u32 = day.parse().ok()?;
+    let month = month_number(month)?;
+    let year: i32 = match year.parse().ok()? {
+        y @ 0..=49 => 2000 + y,
+        y @ 50..=99 => 1900 + y,
+        y => y,
+    };
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .ok()?;
+    // An unknown zone costs a few hours of accuracy, not the whole date
+    let offset = rest.first().and_then(|z| zone_offset(z)).unwrap_or(0);
+
+    let local = NaiveDate::from_ymd_opt(year, month, day)?.and_time(time);
+    let d = FixedOffset::east_opt(offset)?
+        .from_local_datetime(&local)
+        .single()?;
+    Some(d.with_timezone(&Utc))
+}
+
+fn month_number(name: &str) -> Option<u32> {
+    let prefix = name.get(..3)?.to_lowercase();
+    let months = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    months
+        .iter()
+        .position(|m| *m == prefix)
+        .map(|i| i as u32 + 1)
+}
+
+/// Seconds east of UTC for `+hhmm`, `+hh:mm`, or a zone abbreviation.
+fn zone_offset(zone: &str) -> Option<i32> {
+    if let Some(sign) = zone.chars().next().filter(|c| *c == '+' || *c == '-') {
+        let digits: String = zone[1..].chars().filter(|c| *c != ':').collect();
+        if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let hours: i32 = digits[..2].parse().ok()?;
+        let minutes: i32 = digits[2..].parse().ok()?;
+        let offset = hours * 3600 + minutes * 60;
+        return Some(if sign == '-' { -offset } else { offset });
+    }
+    let hours = match zone.to_uppercase().as_str() {
+        "UT" | "UTC" | "GMT" | "Z" | "WET" => 0.0,
+        "BST" | "CET" | "MET" | "WEST" => 1.0,
+        "CEST" | "MEST" | "EET" | "SAST" => 2.0,
+        "EEST" | "MSK" => 3.0,
+        "IST" => 5.5,
+        "SGT" | "HKT" | "AWST" => 8.0,
+        "JST" | "KST" => 9.0,
+        "ACST" => 9.5,
+        "AEST" => 10.0,
+        "ACDT" => 10.5,
+        "AEDT" => 11.0,
+        "NZST" => 12.0,
+        "NZDT" => 13.0,
+        "NST" => -3.5,
+        "ADT" => -3.0,
+        "AST" | "EDT" => -4.0,
+        "EST" | "CDT" => -5.0,
+        "CST" | "MDT" => -6.0,
+        "MST" | "PDT" => -7.0,
+        "PST" | "AKDT" => -8.0,
+        "AKST" => -9.0,
+        "HST" => -10.0,
+        _ => return None,
+    };
+    Some((hours * 3600.0) as i32)
+}
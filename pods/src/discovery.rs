@@ -0,0 +1,596 @@
+//! Podcast directories: looking up feed URLs for share links that only carry
+//! a directory ID, and searching for podcasts and episodes to subscribe to.
+//!
+//! Each directory implements [`Directory`]. Searches and charts ask every
+//! enabled one at once and merge the answers, dropping podcasts a directory
+//! earlier in `discovery` already returned.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures_util::future;
+use serde::Deserialize;
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::discovery::{
+    DirectoryEpisode, DirectoryHit, DirectorySearch, SearchKind, TrendingQuery,
+};
+
+use crate::{
+    config::Config,
+    explicit,
+    fetcher::Fetcher,
+    instance::{self, DiscoveryProvider},
+    language,
+    profiles::Acting,
+    AppState, Error, DB,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ItunesConfig {
+    pub api: String,
+    /// Apple's top charts feed for a storefront, for trending podcasts.
+    pub charts: String,
+}
+
+impl Default for ItunesConfig {
+    fn default() -> ItunesConfig {
+        ItunesConfig {
+            api: "https://itunes.apple.com".to_string(),
+            charts: "https://rss.applemarketingtools.com/api/v2/us/podcasts".to_string(),
+        }
+    }
+}
+
+/// Podcasts asked of each directory for trending lists.
+const TRENDING: usize = 25;
+
+/// API credentials from <https://api.podcastindex.org>. Podcast Index
+/// lookups need these even when enabled in `discovery`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct PodcastIndexConfig {
+    pub key: String,
+    pub secret: String,
+    #[serde(default = "podcast_index_api")]
+    pub api: String,
+}
+
+fn podcast_index_api() -> String {
+    "https://api.podcastindex.org/api/1.0".to_string()
+}
+
+/// An API key from <https://www.listennotes.com/api/>, likewise needed
+/// before Listen Notes is used.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ListenNotesConfig {
+    pub key: String,
+    #[serde(default = "listen_notes_api")]
+    pub api: String,
+}
+
+fn listen_notes_api() -> String {
+    "https://listen-api.listennotes.com/api/v2".to_string()
+}
+
+/// What every directory answers, whichever one is asked.
+trait Directory {
+    const PROVIDER: DiscoveryProvider;
+
+    /// The feed URL of one of the directory's podcast IDs.
+    async fn lookup(&self, http: &Fetcher, id: &str) -> Result<String, Error>;
+
+    /// `Error::Unavailable` for kinds of search the directory can't do.
+    async fn search(
+        &self,
+        http: &Fetcher,
+        q: &str,
+        kind: SearchKind,
+    ) -> Result<Vec<DirectoryHit>, Error>;
+
+    /// Popular podcasts, most popular first.
+    async fn trending(&self, http: &Fetcher) -> Result<Vec<DirectoryHit>, Error>;
+}
+
+/// An enabled directory with what it needs to be asked.
+enum Configured {
+    Itunes(ItunesConfig),
+    PodcastIndex(PodcastIndexConfig),
+    ListenNotes(ListenNotesConfig),
+}
+
+impl Configured {
+    fn new(config: &Config, provider: DiscoveryProvider) -> Option<Configured> {
+        match provider {
+            DiscoveryProvider::Itunes => Some(Configured::Itunes(config.itunes.clone())),
+            DiscoveryProvider::PodcastIndex => {
+                config.podcast_index.clone().map(Configured::PodcastIndex)
+            }
+            DiscoveryProvider::ListenNotes => {
+                config.listen_notes.clone().map(Configured::ListenNotes)
+            }
+            _ => None,
+        }
+    }
+
+    async fn lookup(&self, http: &Fetcher, id: &str) -> Result<String, Error> {
+        match self {
+            Configured::Itunes(d) => d.lookup(http, id).await,
+            Configured::PodcastIndex(d) => d.lookup(http, id).await,
+            Configured::ListenNotes(d) => d.lookup(http, id).await,
+        }
+    }
+
+    async fn search(
+        &self,
+        http: &Fetcher,
+        q: &str,
+        kind: SearchKind,
+    ) -> Result<Vec<DirectoryHit>, Error> {
+        match self {
+            Configured::Itunes(d) => d.search(http, q, kind).await,
+            Configured::PodcastIndex(d) => d.search(http, q, kind).await,
+            Configured::ListenNotes(d) => d.search(http, q, kind).await,
+        }
+    }
+
+    async fn trending(&self, http: &Fetcher) -> Result<Vec<DirectoryHit>, Error> {
+        match self {
+            Configured::Itunes(d) => d.trending(http).await,
+            Configured::PodcastIndex(d) => d.trending(http).await,
+            Configured::ListenNotes(d) => d.trending(http).await,
+        }
+    }
+}
+
+/// The enabled directories that are configured, in `discovery` order, or
+/// only `provider`. `Error::Unavailable` if that leaves none.
+async fn directories<D: DB>(
+    state: &Mutex<AppState<D>>,
+    provider: Option<DiscoveryProvider>,
+) -> Result<(Fetcher, Vec<Configured>), Error> {
+    let s = state.lock().await;
+    let directories: Vec<Configured> = instance::effective(&s)?
+        .discovery
+        .into_iter()
+        .filter(|p| provider.is_none_or(|wanted| wanted == *p))
+        .filter_map(|p| Configured::new(&s.config, p))
+        .collect();
+    if directories.is_empty() {
+        return Err(Error::Unavailable);
+    }
+    Ok((s.http.clone(), directories))
+}
+
+/// The feed URL for a directory ID. `Error::Unavailable` if the provider is
+/// switched off or missing credentials.
+pub async fn feed_url<D: DB>(
+    state: &Mutex<AppState<D>>,
+    provider: DiscoveryProvider,
+    id: &str,
+) -> Result<String, Error> {
+    let (http, directories) = directories(state, Some(provider)).await?;
+    directories[0].lookup(&http, id).await
+}
+
+/// Alternates between the directories' answers, best first, skipping
+/// podcasts (or on episode searches, episodes) already in. Fails only if
+/// every directory did.
+fn merge(answers: Vec<Result<Vec<DirectoryHit>, Error>>) -> Result<Vec<DirectoryHit>, Error> {
+    let mut error = None;
+    let mut lists = vec![];
+    for answer in answers {
+        match answer {
+            Ok(hits) => lists.push(hits.into_iter()),
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    if lists.is_empty() {
+        return Err(error.unwrap_or(Error::Unavailable));
+    }
+    let mut merged: Vec<DirectoryHit> = vec![];
+    let mut seen: HashMap<_, usize> = HashMap::new();
+    loop {
+        let mut any = false;
+        for hit in lists.iter_mut().filter_map(Iterator::next) {
+            any = true;
+            // Without a feed URL there's nothing to tell duplicates by
+            let Some(key) = dedup_key(&hit) else {
+                merged.push(hit);
+                continue;
+            };
+            match seen.get(&key) {
+                Some(&i) => {
+                    let first = &mut merged[i];
+                    first.author = first.author.take().or(hit.author);
+                    first.artwork = first.artwork.take().or(hit.artwork);
+                    first.language = first.language.take().or(hit.language);
+                    first.explicit |= hit.explicit;
+                }
+                None => {
+                    seen.insert(key, merged.len());
+                    merged.push(hit);
+                }
+            }
+        }
+        if !any {
+            return Ok(merged);
+        }
+    }
+}
+
+/// Feed URLs differing only in scheme or a trailing slash are the same feed.
+fn dedup_key(hit: &DirectoryHit) -> Option<(String, Option<String>)> {
+    let rss = hit.rss.as_deref()?;
+    let feed = rss
+        .split_once("://")
+        .map_or(rss, |(_, rest)| rest)
+        .trim_end_matches('/')
+        .to_string();
+    let episode = hit
+        .episode
+        .as_ref()
+        .map(|e| e.audio.clone().unwrap_or_else(|| e.title.clone()));
+    Some((feed, episode))
+}
+
+pub async fn search<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Acting(user): Acting,
+    Query(query): Query<DirectorySearch>,
+) -> impl IntoResponse {
+    if query.q.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    }
+    let Ok(lang) = language::filter(query.lang.as_deref()) else {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    };
+    let Ok(hide) = hide_explicit(&state, user).await else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(None));
+    };
+    let hits = match directories(&state, query.provider).await {
+        Ok((http, directories)) => {
+            let pending = directories
+                .iter()
+                .map(|d| d.search(&http, &query.q, query.kind));
+            merge(future::join_all(pending).await)
+        }
+        Err(e) => Err(e),
+    };
+    respond(hits, lang, hide)
+}
+
+pub async fn trending<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Acting(user): Acting,
+    Query(query): Query<TrendingQuery>,
+) -> impl IntoResponse {
+    let Ok(lang) = language::filter(query.lang.as_deref()) else {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    };
+    let Ok(hide) = hide_explicit(&state, user).await else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(None));
+    };
+    let hits = match directories(&state, query.provider).await {
+        Ok((http, directories)) => {
+            let pending = directories.iter().map(|d| d.trending(&http));
+            merge(future::join_all(pending).await)
+        }
+        Err(e) => Err(e),
+    };
+    respond(hits, lang, hide)
+}
+
+/// Whether the user, or failing that the instance, hides explicit hits.
+async fn hide_explicit<D: DB>(
+    state: &Mutex<AppState<D>>,
+    user: Option<Uuid>,
+) -> Result<bool, Error> {
+    explicit::hidden(&*state.lock().await, user)
+}
+
+fn respond(
+    hits: Result<Vec<DirectoryHit>, Error>,
+    lang: Option<String>,
+    hide_explicit: bool,
+) -> (StatusCode, Json<Option<Vec<DirectoryHit>>>) {
+    match hits {
+        Ok(mut hits) => {
+            if hide_explicit {
+                hits.retain(|h| !h.explicit);
+            }
+            if let Some(lang) = lang {
+                hits.retain(|h| language::matches(&lang, h.language.as_deref()));
+            }
+            (StatusCode::OK, Json(Some(hits)))
+        }
+        Err(Error::Unavailable) => (StatusCode::BAD_REQUEST, Json(None)),
+        Err(_) => (StatusCode::BAD_GATEWAY, Json(None)),
+    }
+}
+
+async fn get_json(req: reqwest::RequestBuilder) -> Result<Value, Error> {
+    let resp = req.send().await.map_err(|_| Error::Upstream)?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(Error::NotFound);
+    }
+    if !resp.status().is_success() {
+        return Err(Error::Upstream);
+    }
+    resp.json().await.map_err(|_| Error::Upstream)
+}
+
+/// A string field, or a numeric one as a string.
+fn text(v: &Value) -> Option<String> {
+    match v {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn lang_of(v: &Value) -> Option<String> {
+    v.as_str().and_then(language::normalize)
+}
+
+/// <https://performance-partners.apple.com/search-api>
+impl Directory for ItunesConfig {
+    const PROVIDER: DiscoveryProvider = DiscoveryProvider::Itunes;
+
+    async fn lookup(&self, http: &Fetcher, id: &str) -> Result<String, Error> {
+        let url = format!("{}/lookup", self.api);
+        let req = http.get(&url).map_err(|_| Error::Blocked)?;
+        let body = get_json(req.query(&[("id", id), ("entity", "podcast")])).await?;
+        body["results"]
+            .as_array()
+            .and_then(|r| r.iter().find_map(|r| r["feedUrl"].as_str()))
+            .map(|url| url.to_string())
+            .ok_or(Error::NotFound)
+    }
+
+    async fn search(
+        &self,
+        http: &Fetcher,
+        q: &str,
+        kind: SearchKind,
+    ) -> Result<Vec<DirectoryHit>, Error> {
+        let entity = match kind {
+            SearchKind::Podcast => "podcast",
+            SearchKind::Episode => "podcastEpisode",
+            _ => return Err(Error::Unavailable),
+        };
+        let url = format!("{}/search", self.api);
+        let req = http.get(&url).map_err(|_| Error::Blocked)?;
+        let params = [("term", q), ("media", "podcast"), ("entity", entity)];
+        let body = get_json(req.query(&params)).await?;
+        let results = body["results"].as_array().cloned().unwrap_or_default();
+        Ok(results
+            .iter()
+            .filter_map(|r| {
+                let episode = match kind {
+                    SearchKind::Episode => Some(DirectoryEpisode {
+                        id: text(&r["trackId"])?,
+                        title: text(&r["trackName"])?,
+                        audio: text(&r["episodeUrl"]),
+                        published: r["releaseDate"]
+                            .as_str()
+                            .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+                            .map(|d| d.with_timezone(&Utc)),
+                    }),
+                    _ => None,
+                };
+                Some(DirectoryHit {
+                    provider: Self::PROVIDER,
+                    id: text(&r["collectionId"])?,
+                    title: text(&r["collectionName"])?,
+                    author: text(&r["artistName"]),
+                    rss: text(&r["feedUrl"]),
+                    artwork: text(&r["artworkUrl600"]),
+                    explicit: r["collectionExplicitness"] == "explicit"
+                        || r["trackExplicitness"] == "explicit",
+                    language: None,
+                    episode,
+                })
+            })
+            .collect())
+    }
+
+    /// <https://rss.applemarketingtools.com>. Charts leave out feed URLs.
+    async fn trending(&self, http: &Fetcher) -> Result<Vec<DirectoryHit>, Error> {
+        let url = format!("{}/top/{}/podcasts.json", self.charts, TRENDING);
+        let body = get_json(http.get(&url).map_err(|_| Error::Blocked)?).await?;
+        let results = body["feed"]["results"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        Ok(results
+            .iter()
+            .filter_map(|r| {
+                Some(DirectoryHit {
+                    provider: Self::PROVIDER,
+                    id: text(&r["id"])?,
+                    title: text(&r["name"])?,
+                    author: text(&r["artistName"]),
+                    rss: None,
+                    artwork: text(&r["artworkUrl100"]),
+                    explicit: r["contentAdvisoryRating"] == "Explicit",
+                    language: None,
+                    episode: None,
+                })
+            })
+            .collect())
+    }
+}
+
+impl PodcastIndexConfig {
+    fn get(&self, http: &Fetcher, path: &str) -> Result<reqwest::RequestBuilder, Error> {
+        let url = format!("{}{}", self.api, path);
+        let now = Utc::now().timestamp().to_string();
+        let auth = Sha1::digest(format!("{}{}{}", self.key, self.secret, now));
+        let auth: String = auth.iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(http
+            .get(&url)
+            .map_err(|_| Error::Blocked)?
+            .header("X-Auth-Key", &self.key)
+            .header("X-Auth-Date", now)
+            .header("Authorization", auth))
+    }
+}
+
+/// <https://podcastindex-org.github.io/docs-api/>
+impl Directory for PodcastIndexConfig {
+    const PROVIDER: DiscoveryProvider = DiscoveryProvider::PodcastIndex;
+
+    async fn lookup(&self, http: &Fetcher, id: &str) -> Result<String, Error> {
+        let req = self.get(http, "/podcasts/byfeedid")?.query(&[("id", id)]);
+        let body = get_json(req).await?;
+        // Unknown IDs come back with `"feed": []`
+        body["feed"]["url"]
+            .as_str()
+            .filter(|url| !url.is_empty())
+            .map(|url| url.to_string())
+            .ok_or(Error::NotFound)
+    }
+
+    /// Podcasts only; Podcast Index has no episode search by term.
+    async fn search(
+        &self,
+        http: &Fetcher,
+        q: &str,
+        kind: SearchKind,
+    ) -> Result<Vec<DirectoryHit>, Error> {
+        if kind != SearchKind::Podcast {
+            return Err(Error::Unavailable);
+        }
+        let req = self.get(http, "/search/byterm")?.query(&[("q", q)]);
+        Ok(podcast_index_feeds(&get_json(req).await?))
+    }
+
+    async fn trending(&self, http: &Fetcher) -> Result<Vec<DirectoryHit>, Error> {
+        let max = TRENDING.to_string();
+        let req = self.get(http, "/podcasts/trending")?.query(&[("max", max)]);
+        Ok(podcast_index_feeds(&get_json(req).await?))
+    }
+}
+
+fn podcast_index_feeds(body: &Value) -> Vec<DirectoryHit> {
+    let feeds = body["feeds"].as_array().cloned().unwrap_or_default();
+    feeds
+        .iter()
+        .filter_map(|f| {
+            Some(DirectoryHit {
+                provider: DiscoveryProvider::PodcastIndex,
+                id: text(&f["id"])?,
+                title: text(&f["title"])?,
+                author: text(&f["author"]),
+                rss: text(&f["url"]),
+                artwork: text(&f["artwork"]).or_else(|| text(&f["image"])),
+                explicit: f["explicit"].as_bool().unwrap_or(false),
+                language: lang_of(&f["language"]),
+                episode: None,
+            })
+        })
+        .collect()
+}
+
+/// <https://www.listennotes.com/api/docs/>
+impl Directory for ListenNotesConfig {
+    const PROVIDER: DiscoveryProvider = DiscoveryProvider::ListenNotes;
+
+    async fn lookup(&self, http: &Fetcher, id: &str) -> Result<String, Error> {
+        let url = format!("{}/podcasts/{}", self.api, id);
+        let req = http.get(&url).map_err(|_| Error::Blocked)?;
+        let body = get_json(req.header("X-ListenAPI-Key", &self.key)).await?;
+        text(&body["rss"]).ok_or(Error::NotFound)
+    }
+
+    async fn search(
+        &self,
+        http: &Fetcher,
+        q: &str,
+        kind: SearchKind,
+    ) -> Result<Vec<DirectoryHit>, Error> {
+        let kind_param = match kind {
+            SearchKind::Podcast => "podcast",
+            SearchKind::Episode => "episode",
+            _ => return Err(Error::Unavailable),
+        };
+        let url = format!("{}/search", self.api);
+        let req = http
+            .get(&url)
+            .map_err(|_| Error::Blocked)?
+            .header("X-ListenAPI-Key", &self.key)
+            .query(&[("q", q), ("type", kind_param)]);
+        let body = get_json(req).await?;
+        let results = body["results"].as_array().cloned().unwrap_or_default();
+        Ok(results
+            .iter()
+            .filter_map(|r| {
+                // Episode results nest their podcast
+                let (podcast, episode) = match kind {
+                    SearchKind::Episode => (
+                        &r["podcast"],
+                        Some(DirectoryEpisode {
+                            id: text(&r["id"])?,
+                            title: text(&r["title_original"])?,
+                            audio: text(&r["audio"]),
+                            published: r["pub_date_ms"]
+                                .as_i64()
+                                .and_then(DateTime::from_timestamp_millis),
+                        }),
+                    ),
+                    _ => (r, None),
+                };
+                Some(DirectoryHit {
+                    provider: Self::PROVIDER,
+                    id: text(&podcast["id"])?,
+                    title: text(&podcast["title_original"])?,
+                    author: text(&podcast["publisher_original"]),
+                    rss: text(&podcast["rss"]).or_else(|| text(&r["rss"])),
+                    artwork: text(&podcast["image"]),
+                    explicit: r["explicit_content"].as_bool().unwrap_or(false)
+                        || podcast["explicit_content"].as_bool().unwrap_or(false),
+                    language: lang_of(&podcast["language"]).or_else(|| lang_of(&r["language"])),
+                    episode,
+                })
+            })
+            .collect())
+    }
+
+    async fn trending(&self, http: &Fetcher) -> Result<Vec<DirectoryHit>, Error> {
+        let url = format!("{}/best_podcasts", self.api);
+        let req = http
+            .get(&url)
+            .map_err(|_| Error::Blocked)?
+            .header("X-ListenAPI-Key", &self.key);
+        let body = get_json(req).await?;
+        let podcasts = body["podcasts"].as_array().cloned().unwrap_or_default();
+        Ok(podcasts
+            .iter()
+            .filter_map(|p| {
+                Some(DirectoryHit {
+                    provider: Self::PROVIDER,
+                    id: text(&p["id"])?,
+                    title: text(&p["title"])?,
+                    author: text(&p["publisher"]),
+                    rss: text(&p["rss"]),
+                    artwork: text(&p["image"]),
+                    explicit: p["explicit_content"].as_bool().unwrap_or(false),
+                    language: lang_of(&p["language"]),
+                    episode: None,
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,642 @@
+//! A minimal UPnP/DLNA media server, so living-room players (Sonos, smart
+//! TVs, network receivers) can browse one user's subscriptions and play
+//! their episodes directly. It has what renderers need to find and browse
+//! it: SSDP announcements, a device description, and the ContentDirectory
+//! and ConnectionManager services, answering `Browse` over SOAP. The
+//! library is one container per subscribed podcast with its episodes newest
+//! first, each carrying the user's resume position as
+//! `upnp:lastPlaybackPosition`.
+//!
+//! Renderers can't log in, so these routes need none. When `[media_links]`
+//! is configured, episodes point at signed links to their audio.
+
+use std::{net::Ipv4Addr, sync::Arc, time::Duration};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use quick_xml::escape::escape;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::{net::UdpSocket, sync::Mutex};
+use uuid::Uuid;
+
+use crate::{media_links, resume, subscriptions, AppState, Error, DB};
+
+const SSDP_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+/// How long an announcement is good for, and how often it's repeated.
+const MAX_AGE_SECS: u64 = 1800;
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(MAX_AGE_SECS / 2);
+
+const DEVICE_TYPE: &str = "urn:schemas-upnp-org:device:MediaServer:1";
+const CONTENT_DIRECTORY: &str = "urn:schemas-upnp-org:service:ContentDirectory:1";
+const CONNECTION_MANAGER: &str = "urn:schemas-upnp-org:service:ConnectionManager:1";
+
+/// What renderers can ask for.
+const PROTOCOL_INFO: &str = "http-get:*:audio/mpeg:*,http-get:*:audio/mp4:*,\
+    http-get:*:audio/x-m4a:*,http-get:*:audio/aac:*,http-get:*:audio/ogg:*";
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct DlnaConfig {
+    /// Name of the user whose subscriptions are shared, and whose resume
+    /// positions the episodes carry.
+    pub user: String,
+    /// Where players on the network reach the API, e.g.
+    /// `http://192.168.1.10:3000`. Sent in announcements and used for
+    /// episodes' audio.
+    pub url: String,
+    /// The name players list the server under.
+    #[serde(default = "default_name")]
+    pub name: String,
+    /// Announce the server on the network and answer searches for it.
+    /// Without it, players have to be pointed at the description.
+    #[serde(default = "default_ssdp")]
+    pub ssdp: bool,
+}
+
+fn default_name() -> String {
+    "pods".to_string()
+}
+
+fn default_ssdp() -> bool {
+    true
+}
+
+impl DlnaConfig {
+    /// The device's UPnP ID, the same across restarts for the same user, so
+    /// players keep it as one server.
+    fn udn(&self) -> String {
+        let hash = Sha256::digest(format!("pods dlna {}", self.user).as_bytes());
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&hash[..16]);
+        format!("uuid:{}", Uuid::from_bytes(bytes))
+    }
+
+    /// The user named `user`; accounts, not profiles.
+    fn user<D: DB>(&self, db: &D) -> Result<Uuid, Error> {
+        let (_, users) = db.users(Some(&self.user), 0, usize::MAX)?;
+        users
+            .into_iter()
+            .find(|u| u.name == self.user && u.profile_of.is_none())
+            .map(|u| u.id)
+            .ok_or(Error::NotFound)
+    }
+
+    fn base(&self) -> &str {
+        self.url.trim_end_matches('/')
+    }
+
+    fn location(&self) -> String {
+        format!("{}/dlna/description.xml", self.base())
+    }
+}
+
+fn xml(body: String) -> Response {
+    (
+        [(header::CONTENT_TYPE, r#"text/xml; charset="utf-8""#)],
+        body,
+    )
+        .into_response()
+}
+
+fn config<D: DB>(s: &AppState<D>) -> Result<DlnaConfig, StatusCode> {
+    s.config.dlna.clone().ok_or(StatusCode::NOT_FOUND)
+}
+
+pub async fn description<D: DB>(State(state): State<Arc<Mutex<AppState<D>>>>) -> Response {
+    let config = match config(&*state.lock().await) {
+        Ok(c) => c,
+        Err(status) => return status.into_response(),
+    };
+    let service = |kind: &str, name: &str| {
+        format!(
+            "<service><serviceType>{}</serviceType>\
+             <serviceId>urn:upnp-org:serviceId:{}</serviceId>\
+             <SCPDURL>/dlna/{}.xml</SCPDURL>\
+             <controlURL>/dlna/control/{}</controlURL>\
+             <eventSubURL>/dlna/events/{}</eventSubURL></service>",
+            kind, name, name, name, name
+        )
+    };
+    xml(format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0" xmlns:dlna="urn:schemas-dlna-org:device-1-0">
+<specVersion><major>1</major><minor>0</minor></specVersion>
+<URLBase>{}</URLBase>
+<device>
+<deviceType>{}</deviceType>
+<friendlyName>{}</friendlyName>
+<manufacturer>pods</manufacturer>
+<modelName>pods</modelName>
+<UDN>{}</UDN>
+<dlna:X_DLNADOC>DMS-1.50</dlna:X_DLNADOC>
+<serviceList>{}{}</serviceList>
+</device>
+</root>
+"#,
+        escape(config.base()),
+        DEVICE_TYPE,
+        escape(config.name.as_str()),
+        config.udn(),
+        service(CONTENT_DIRECTORY, "ContentDirectory"),
+        service(CONNECTION_MANAGER, "ConnectionManager"),
+    ))
+}
+
+/// An action's argument: name, direction and state variable.
+type Argument<'a> = (&'a str, &'a str, &'a str);
+
+/// A service description listing `actions` with their arguments, and the
+/// state variables they use as `(name, type)`.
+fn scpd(actions: &[(&str, &[Argument])], variables: &[(&str, &str)]) -> String {
+    let mut out = String::from(concat!(
+        r#"<?xml version="1.0" encoding="utf-8"?>"#,
+        "\n",
+        r#"<scpd xmlns="urn:schemas-upnp-org:service-1-0">"#,
+        "<specVersion><major>1</major><minor>0</minor></specVersion><actionList>",
+    ));
+    for (name, args) in actions {
+        out += &format!("<action><name>{}</name><argumentList>", name);
+        for (arg, direction, variable) in *args {
+            out += &format!(
+                "<argument><name>{}</name><direction>{}</direction>\
+                 <relatedStateVariable>{}</relatedStateVariable></argument>",
+                arg, direction, variable
+            );
+        }
+        out += "</argumentList></action>";
+    }
+    out += "</actionList><serviceStateTable>";
+    for (name, kind) in variables {
+        out += &format!(
+            r#"<stateVariable sendEvents="no"><name>{}</name><dataType>{}</dataType></stateVariable>"#,
+            name, kind
+        );
+    }
+    out += "</serviceStateTable></scpd>\n";
+    out
+}
+
+pub async fn content_directory<D: DB>(State(state): State<Arc<Mutex<AppState<D>>>>) -> Response {
+    if let Err(status) = config(&*state.lock().await) {
+        return status.into_response();
+    }
+    xml(scpd(
+        &[
+            (
+                "Browse",
+                &[
+                    ("ObjectID", "in", "A_ARG_TYPE_ObjectID"),
+                    ("BrowseFlag", "in", "A_ARG_TYPE_BrowseFlag"),
+                    ("Filter", "in", "A_ARG_TYPE_Filter"),
+                    ("StartingIndex", "in", "A_ARG_TYPE_Index"),
+                    ("RequestedCount", "in", "A_ARG_TYPE_Count"),
+                    ("SortCriteria", "in", "A_ARG_TYPE_SortCriteria"),
+                    ("Result", "out", "A_ARG_TYPE_Result"),
+                    ("NumberReturned", "out", "A_ARG_TYPE_Count"),
+                    ("TotalMatches", "out", "A_ARG_TYPE_Count"),
+                    ("UpdateID", "out", "A_ARG_TYPE_UpdateID"),
+                ],
+            ),
+            (
+                "GetSearchCapabilities",
+                &[("SearchCaps", "out", "SearchCapabilities")],
+            ),
+            (
+                "GetSortCapabilities",
+                &[("SortCaps", "out", "SortCapabilities")],
+            ),
+            ("GetSystemUpdateID", &[("Id", "out", "SystemUpdateID")]),
+        ],
+        &[
+            ("A_ARG_TYPE_ObjectID", "string"),
+            ("A_ARG_TYPE_BrowseFlag", "string"),
+            ("A_ARG_TYPE_Filter", "string"),
+            ("A_ARG_TYPE_Index", "ui4"),
+            ("A_ARG_TYPE_Count", "ui4"),
+            ("A_ARG_TYPE_SortCriteria", "string"),
+            ("A_ARG_TYPE_Result", "string"),
+            ("A_ARG_TYPE_UpdateID", "ui4"),
+            ("SearchCapabilities", "string"),
+            ("SortCapabilities", "string"),
+            ("SystemUpdateID", "ui4"),
+        ],
+    ))
+}
+
+pub async fn connection_manager<D: DB>(State(state): State<Arc<Mutex<AppState<D>>>>) -> Response {
+    if let Err(status) = config(&*state.lock().await) {
+        return status.into_response();
+    }
+    xml(scpd(
+        &[
+            (
+                "GetProtocolInfo",
+                &[
+                    ("Source", "out", "SourceProtocolInfo"),
+                    ("Sink", "out", "SinkProtocolInfo"),
+                ],
+            ),
+            (
+                "GetCurrentConnectionIDs",
+                &[("ConnectionIDs", "out", "CurrentConnectionIDs")],
+            ),
+        ],
+        &[
+            ("SourceProtocolInfo", "string"),
+            ("SinkProtocolInfo", "string"),
+            ("CurrentConnectionIDs", "string"),
+        ],
+    ))
+}
+
+/// Reads the action and its arguments from a SOAP request.
+fn soap_call(headers: &HeaderMap, body: &str) -> Option<(String, Vec<(String, String)>)> {
+    let action = headers
+        .get("soapaction")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.trim_matches('"').rsplit_once('#'))
+        .map(|(_, action)| action.to_string());
+    let doc = roxmltree::Document::parse(body).ok()?;
+    let call = doc
+        .descendants()
+        .find(|n| n.tag_name().name() == "Body")?
+        .children()
+        .find(|n| n.is_element())?;
+    let args = call
+        .children()
+        .filter(|n| n.is_element())
+        .map(|n| {
+            let value = n.text().unwrap_or_default().to_string();
+            (n.tag_name().name().to_string(), value)
+        })
+        .collect();
+    Some((
+        action.unwrap_or_else(|| call.tag_name().name().to_string()),
+        args,
+    ))
+}
+
+fn soap_response(service: &str, action: &str, out: &[(&str, String)]) -> Response {
+    let mut args = String::new();
+    for (name, value) in out {
+        args += &format!("<{}>{}</{}>", name, escape(value.as_str()), name);
+    }
+    xml(format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+<s:Body><u:{}Response xmlns:u="{}">{}</u:{}Response></s:Body>
+</s:Envelope>
+"#,
+        action, service, args, action
+    ))
+}
+
+/// A UPnP error, like `401 Invalid Action` or `701 No such object`.
+fn soap_fault(code: u16, description: &str) -> Response {
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+<s:Body><s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring>
+<detail><UPnPError xmlns="urn:schemas-upnp-org:control-1-0"><errorCode>{}</errorCode><errorDescription>{}</errorDescription></UPnPError></detail>
+</s:Fault></s:Body>
+</s:Envelope>
+"#,
+        code, description
+    );
+    (StatusCode::INTERNAL_SERVER_ERROR, xml(body)).into_response()
+}
+
+/// `H:MM:SS`, as DIDL-Lite writes times.
+fn duration(secs: u32) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// A podcast's container, shown as an album with its artwork, or the root
+/// when there's no `parent`.
+fn container(
+    id: &str,
+    parent: Option<&str>,
+    title: &str,
+    children: usize,
+    art: Option<&str>,
+) -> String {
+    let class = match parent {
+        Some(_) => "object.container.album.musicAlbum",
+        None => "object.container.storageFolder",
+    };
+    let art = art.map(|a| format!("<upnp:albumArtURI>{}</upnp:albumArtURI>", escape(a)));
+    format!(
+        r#"<container id="{}" parentID="{}" restricted="1" childCount="{}"><dc:title>{}</dc:title><upnp:class>{}</upnp:class>{}</container>"#,
+        id,
+        parent.unwrap_or("-1"),
+        children,
+        escape(title),
+        class,
+        art.unwrap_or_default()
+    )
+}
+
+/// The DIDL-Lite item for an episode, or `None` if it has no audio.
+fn item<D: DB>(
+    s: &AppState<D>,
+    config: &DlnaConfig,
+    user: Uuid,
+    podcast: &str,
+    e: Uuid,
+) -> Option<String> {
+    let e = s.db.get_episode(e).ok()?;
+    let podcast_name = s.db.get_podcast(e.podcast.clone()).ok()?.name;
+    let enclosure = e.enclosure?;
+    let path = format!("/episodes/{}/audio", e.id);
+    let query = match &s.config.media_links {
+        Some(links) => {
+            let expires = Utc::now().timestamp() + links.max_ttl_secs as i64;
+            format!("?{}", media_links::sign(&links.secret, &path, "", expires))
+        }
+        None => String::new(),
+    };
+    let mime = enclosure
+        .mime_type
+        .unwrap_or_else(|| "audio/mpeg".to_string());
+    let size = enclosure
+        .length
+        .map(|l| format!(r#" size="{}""#, l))
+        .unwrap_or_default();
+    let mut extra = String::new();
+    if let Some(published) = e.published {
+        extra += &format!(
+            "<dc:date>{}</dc:date>",
+            published.format("%Y-%m-%dT%H:%M:%S")
+        );
+    }
+    let now = Utc::now().naive_utc();
+    if let Ok(r) = resume::resume_position(&s.db, user, e.id, now) {
+        extra += &format!(
+            "<upnp:lastPlaybackPosition>{}</upnp:lastPlaybackPosition>",
+            duration(r.position)
+        );
+    }
+    Some(format!(
+        r#"<item id="e:{}" parentID="{}" restricted="1"><dc:title>{}</dc:title><upnp:album>{}</upnp:album><upnp:class>object.item.audioItem.musicTrack</upnp:class>{}<res protocolInfo="http-get:*:{}:*"{}>{}</res></item>"#,
+        e.id,
+        podcast,
+        escape(e.title.as_str()),
+        escape(podcast_name.as_str()),
+        extra,
+        escape(mime.as_str()),
+        size,
+        escape(format!("{}{}{}", config.base(), path, query).as_str()),
+    ))
+}
+
+/// The objects `Browse` answers with, as DIDL-Lite fragments, and how many
+/// there are before paging.
+fn browse<D: DB>(
+    s: &AppState<D>,
+    config: &DlnaConfig,
+    object: &str,
+    children: bool,
+    start: usize,
+    count: usize,
+) -> Result<(Vec<String>, usize), Error> {
+    let user = config.user(&s.db)?;
+    let podcasts = subscriptions(&s.db, user)?;
+    let page = |all: Vec<String>| {
+        let total = all.len();
+        let take = if count == 0 { usize::MAX } else { count };
+        (all.into_iter().skip(start).take(take).collect(), total)
+    };
+    let podcast_container = |p: &pods_types::PodcastChannel| -> Result<String, Error> {
+        let episodes = s.db.episodes(p.rss.clone())?.len();
+        let id = format!("p:{}", p.id);
+        Ok(container(
+            &id,
+            Some("0"),
+            &p.name,
+            episodes,
+            p.artwork.as_deref(),
+        ))
+    };
+    if object == "0" {
+        if !children {
+            let root = container("0", None, &config.name, podcasts.len(), None);
+            return Ok((vec![root], 1));
+        }
+        let all = podcasts
+            .iter()
+            .map(podcast_container)
+            .collect::<Result<Vec<_>, Error>>()?;
+        return Ok(page(all));
+    }
+    if let Some(id) = object.strip_prefix("p:") {
+        let id: Uuid = id.parse().map_err(|_| Error::NotFound)?;
+        let p = podcasts
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or(Error::NotFound)?;
+        if !children {
+            return Ok((vec![podcast_container(p)?], 1));
+        }
+        let all =
+            s.db.episodes(p.rss.clone())?
+                .iter()
+                .filter_map(|e| item(s, config, user, object, e.id))
+                .collect();
+        return Ok(page(all));
+    }
+    if let Some(id) = object.strip_prefix("e:") {
+        let id: Uuid = id.parse().map_err(|_| Error::NotFound)?;
+        let e = s.db.get_episode(id)?;
+        let p = podcasts
+            .iter()
+            .find(|p| p.rss == e.podcast)
+            .ok_or(Error::NotFound)?;
+        if children {
+            return Ok((vec![], 0));
+        }
+        let parent = format!("p:{}", p.id);
+        let found = item(s, config, user, &parent, id).ok_or(Error::NotFound)?;
+        return Ok((vec![found], 1));
+    }
+    Err(Error::NotFound)
+}
+
+pub async fn control_content_directory<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let s = state.lock().await;
+    let config = match config(&s) {
+        Ok(c) => c,
+        Err(status) => return status.into_response(),
+    };
+    let Some((action, args)) = soap_call(&headers, &body) else {
+        return soap_fault(401, "Invalid Action");
+    };
+    let arg = |name: &str| {
+        args.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_default()
+    };
+    match action.as_str() {
+        "Browse" => {
+            let children = match arg("BrowseFlag") {
+                "BrowseDirectChildren" => true,
+                "BrowseMetadata" => false,
+                _ => return soap_fault(402, "Invalid Args"),
+            };
+            let start = arg("StartingIndex").parse().unwrap_or(0);
+            let count = arg("RequestedCount").parse().unwrap_or(0);
+            let (objects, total) =
+                match browse(&s, &config, arg("ObjectID"), children, start, count) {
+                    Ok(found) => found,
+                    Err(Error::NotFound) => return soap_fault(701, "No such object"),
+                    Err(_) => return soap_fault(501, "Action Failed"),
+                };
+            let didl = format!(
+                r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">{}</DIDL-Lite>"#,
+                objects.concat()
+            );
+            soap_response(
+                CONTENT_DIRECTORY,
+                "Browse",
+                &[
+                    ("Result", didl),
+                    ("NumberReturned", objects.len().to_string()),
+                    ("TotalMatches", total.to_string()),
+                    ("UpdateID", "0".to_string()),
+                ],
+            )
+        }
+        "GetSearchCapabilities" => {
+            soap_response(CONTENT_DIRECTORY, &action, &[("SearchCaps", String::new())])
+        }
+        "GetSortCapabilities" => {
+            soap_response(CONTENT_DIRECTORY, &action, &[("SortCaps", String::new())])
+        }
+        "GetSystemUpdateID" => {
+            soap_response(CONTENT_DIRECTORY, &action, &[("Id", "0".to_string())])
+        }
+        _ => soap_fault(401, "Invalid Action"),
+    }
+}
+
+pub async fn control_connection_manager<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if let Err(status) = config(&*state.lock().await) {
+        return status.into_response();
+    }
+    let Some((action, _)) = soap_call(&headers, &body) else {
+        return soap_fault(401, "Invalid Action");
+    };
+    match action.as_str() {
+        "GetProtocolInfo" => soap_response(
+            CONNECTION_MANAGER,
+            &action,
+            &[
+                ("Source", PROTOCOL_INFO.to_string()),
+                ("Sink", String::new()),
+            ],
+        ),
+        "GetCurrentConnectionIDs" => soap_response(
+            CONNECTION_MANAGER,
+            &action,
+            &[("ConnectionIDs", "0".to_string())],
+        ),
+        _ => soap_fault(401, "Invalid Action"),
+    }
+}
+
+/// The search targets the server answers to, with the USN for each.
+fn targets(config: &DlnaConfig) -> Vec<(String, String)> {
+    let udn = config.udn();
+    let mut targets = vec![
+        (
+            "upnp:rootdevice".to_string(),
+            format!("{}::upnp:rootdevice", udn),
+        ),
+        (udn.clone(), udn.clone()),
+    ];
+    for t in [DEVICE_TYPE, CONTENT_DIRECTORY, CONNECTION_MANAGER] {
+        targets.push((t.to_string(), format!("{}::{}", udn, t)));
+    }
+    targets
+}
+
+/// Announces the server on the network and answers `M-SEARCH` requests
+/// for it, if `[dlna]` is configured with `ssdp`.
+pub async fn worker<D: DB + Send + 'static>(state: Arc<Mutex<AppState<D>>>) {
+    let Some(config) = state.lock().await.config.dlna.clone() else {
+        return;
+    };
+    if !config.ssdp {
+        return;
+    }
+    let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, SSDP_PORT)).await {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("listening for SSDP on port {} failed: {}", SSDP_PORT, e);
+            return;
+        }
+    };
+    if let Err(e) = socket.join_multicast_v4(SSDP_ADDR, Ipv4Addr::UNSPECIFIED) {
+        eprintln!("joining the SSDP multicast group failed: {}", e);
+        return;
+    }
+    let targets = targets(&config);
+    let location = config.location();
+    let mut announce = tokio::time::interval(ANNOUNCE_INTERVAL);
+    let mut buf = [0; 2048];
+    loop {
+        tokio::select! {
+            _ = announce.tick() => {
+                for (nt, usn) in &targets {
+                    let notify = format!(
+                        "NOTIFY * HTTP/1.1\r\nHOST: {}:{}\r\nCACHE-CONTROL: max-age={}\r\n\
+                         LOCATION: {}\r\nNT: {}\r\nNTS: ssdp:alive\r\nSERVER: pods UPnP/1.0\r\n\
+                         USN: {}\r\n\r\n",
+                        SSDP_ADDR, SSDP_PORT, MAX_AGE_SECS, location, nt, usn
+                    );
+                    let _ = socket.send_to(notify.as_bytes(), (SSDP_ADDR, SSDP_PORT)).await;
+                }
+            }
+            received = socket.recv_from(&mut buf) => {
+                let Ok((n, from)) = received else {
+                    continue;
+                };
+                let request = String::from_utf8_lossy(&buf[..n]);
+                if !request.starts_with("M-SEARCH") {
+                    continue;
+                }
+                let st = request.lines().find_map(|l| {
+                    let (name, value) = l.split_once(':')?;
+                    name.eq_ignore_ascii_case("st").then(|| value.trim().to_string())
+                });
+                let Some(st) = st else {
+                    continue;
+                };
+                for (target, usn) in &targets {
+                    if st != "ssdp:all" && st != *target {
+                        continue;
+                    }
+                    let reply = format!(
+                        "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age={}\r\nEXT:\r\n\
+                         LOCATION: {}\r\nSERVER: pods UPnP/1.0\r\nST: {}\r\nUSN: {}\r\n\r\n",
+                        MAX_AGE_SECS, location, target, usn
+                    );
+                    let _ = socket.send_to(reply.as_bytes(), from).await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
+use uuid::Uuid;
+
+pub use pods_types::downloads::{Download, DownloadLatest, DownloadStatus, EnqueueDownload};
+
+use crate::{
+    bandwidth::Throttle,
+    dates,
+    fetcher::Fetcher,
+    i18n::{self, Message, UserLang},
+    integrity::{self, Integrity},
+    media, quota, ssrf, tags,
+    validation::Valid,
+    AppState, Enclosure, Error, DB,
+};
+
+/// Most episodes one request may queue by recency.
+pub(crate) const MAX_LATEST: usize = 50;
+
+pub async fn enqueue<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+    UserLang(lang): UserLang,
+    Json(payload): Json<EnqueueDownload>,
+) -> Response {
+    let fail = |status| (status, Json(None::<Download>)).into_response();
+    let state = &mut state.lock().await;
+    let episode = match state.db.get_episode(payload.episode) {
+        Ok(e) => e,
+        Err(Error::NotFound) => return fail(StatusCode::NOT_FOUND),
+        Err(_) => return fail(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    let Some(enclosure) = episode.enclosure else {
+        return fail(StatusCode::BAD_REQUEST);
+    };
+    match queue(state, uid, episode.id, enclosure, payload.background) {
+        Ok(d) => (StatusCode::CREATED, Json(Some(d))).into_response(),
+        Err(Error::NotFound) => fail(StatusCode::NOT_FOUND),
+        Err(Error::QuotaExceeded) => i18n::error(
+            StatusCode::INSUFFICIENT_STORAGE,
+            Message::QuotaExceeded,
+            lang,
+        ),
+        Err(_) => fail(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Queues a download if the user's quota has room for it.
+fn queue<D: DB>(
+    state: &mut AppState<D>,
+    user: Uuid,
+    episode: Uuid,
+    enclosure: Enclosure,
+    background: bool,
+) -> Result<Download, Error> {
+    quota::check(state, user, enclosure.length.unwrap_or(0))?;
+    let download = state.db.save_download(Download {
+        id: Uuid::new_v4(),
+        user,
+        episode,
+        url: enclosure.url,
+        status: DownloadStatus::Queued,
+        expected_bytes: enclosure.length,
+        bytes: 0,
+        background,
+        attempts: 0,
+        retry_after: None,
+        sha256: None,
+        integrity: None,
+        path: None,
+    })?;
+    state.download_notify.notify_one();
+    Ok(download)
+}
+
+/// Queues downloads of the podcast's newest `count` episodes, leaving out
+/// ones the user already has (or is getting) and stopping where the quota
+/// runs out. `Error::QuotaExceeded` only if nothing fit.
+pub fn queue_latest<D: DB>(
+    state: &mut AppState<D>,
+    user: Uuid,
+    rss: &str,
+    count: usize,
+) -> Result<Vec<Download>, Error> {
+    let have: HashSet<Uuid> = state
+        .db
+        .downloads_for_user(user)?
+        .into_iter()
+        .filter(|d| d.status != DownloadStatus::Failed)
+        .map(|d| d.episode)
+        .collect();
+    let mut queued = vec![];
+    let latest = state.db.episodes(rss.to_string())?.into_iter();
+    for e in latest.filter(|e| e.enclosure.is_some()).take(count) {
+        let Some(enclosure) = e.enclosure.filter(|_| !have.contains(&e.id)) else {
+            continue;
+        };
+        match queue(state, user, e.id, enclosure, false) {
+            Ok(d) => queued.push(d),
+            Err(Error::QuotaExceeded) if !queued.is_empty() => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(queued)
+}
+
+/// Gets the podcast's newest episodes ready offline, like subscribing with
+/// `?download_latest=`.
+pub async fn download_latest<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((uid, podcast)): Path<(Uuid, Uuid)>,
+    UserLang(lang): UserLang,
+    Valid(Json(payload)): Valid<Json<DownloadLatest>>,
+) -> Response {
+    let fail = |status| (status, Json(None::<Vec<Download>>)).into_response();
+    let state = &mut *state.lock().await;
+    let queued = state
+        .db
+        .get_podcast_by_id(podcast)
+        .and_then(|p| queue_latest(state, uid, &p.rss, payload.latest));
+    match queued {
+        Ok(d) => (StatusCode::CREATED, Json(Some(d))).into_response(),
+        Err(Error::NotFound) => fail(StatusCode::NOT_FOUND),
+        Err(Error::QuotaExceeded) => i18n::error(
+            StatusCode::INSUFFICIENT_STORAGE,
+            Message::QuotaExceeded,
+            lang,
+        ),
+        Err(_) => fail(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn list<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> impl IntoResponse {
+    match state.lock().await.db.downloads_for_user(uid) {
+        Ok(d) => (StatusCode::OK, Json(Some(d))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Background task draining the download queue, one download at a time.
+pub async fn worker<D: DB>(state: Arc<Mutex<AppState<D>>>) {
+    loop {
+        let (next, notify, media_dir, http, throttle) = {
+            let s = &mut state.lock().await;
+            let in_window = s
+                .config
+                .download_window
+                .is_none_or(|w| w.contains(dates::local(Utc::now(), s.config.timezone).time()));
+            (
+                s.db.next_queued_download(in_window, Utc::now()),
+                s.download_notify.clone(),
+                s.config.media_dir.clone(),
+                s.http.clone(),
+                s.download_throttle.clone(),
+            )
+        };
+        let Some(mut download) = next else {
+            // Wake up on enqueue, with a periodic re-check as a fallback and
+            // so we notice the download window opening
+            let _ = tokio::time::timeout(Duration::from_secs(60), notify.notified()).await;
+            continue;
+        };
+
+        // Someone already has this episode; share their file
+        {
+            let s = &mut *state.lock().await;
+            if let Some(done) = media::find(&s.db, download.episode) {
+                if let Some(partial) = &download.path {
+                    let _ = fs::remove_file(partial).await;
+                }
+                download.status = DownloadStatus::Done;
+                download.bytes = done.bytes;
+                download.sha256 = done.sha256;
+                download.integrity = done.integrity;
+                download.path = done.path;
+                let _ = s.db.save_download(download);
+                continue;
+            }
+        }
+
+        download.status = DownloadStatus::Downloading;
+        download.attempts += 1;
+        download.retry_after = None;
+        let path = download.path.clone().unwrap_or_else(|| {
+            media_dir
+                .join(download.user.to_string())
+                .join(download.episode.to_string())
+        });
+        download.path = Some(path.clone());
+        let _ = state.lock().await.db.save_download(download.clone());
+
+        let fetched = fetch(&http, &download.url, &path, &mut download.bytes, &throttle).await;
+        download.status = match fetched {
+            Ok(()) => verify(&state, &mut download, &path).await,
+            // Keep the partial file and pick up where we left off next time
+            Err(FetchError::Interrupted) if download.attempts < MAX_ATTEMPTS => {
+                retry_later(&mut download)
+            }
+            Err(_) => DownloadStatus::Failed,
+        };
+        let stored = {
+            let s = &mut *state.lock().await;
+            if let (DownloadStatus::Done, Some(sha256)) = (download.status, &download.sha256) {
+                // Left where it is, unshared, if it can't be moved
+                if let Ok(blob) = media::store(&media_dir, &path, sha256).await {
+                    download.path = Some(blob);
+                }
+            }
+            let _ = s.db.save_download(download.clone());
+            download
+                .path
+                .filter(|_| download.status == DownloadStatus::Done)
+        };
+        if let Some(path) = stored {
+            tags::backfill(&state, download.episode, path).await;
+        }
+    }
+}
+
+/// Checks a finished download against the feed, returning its final status.
+async fn verify<D: DB>(
+    state: &Mutex<AppState<D>>,
+    download: &mut Download,
+    path: &std::path::Path,
+) -> DownloadStatus {
+    let sri = state
+        .lock()
+        .await
+        .db
+        .get_episode(download.episode)
+        .ok()
+        .and_then(|e| e.enclosure)
+        .and_then(|e| e.integrity);
+    match integrity::verify_download(path, download.expected_bytes, sri.as_deref()).await {
+        Ok((_, Integrity::HashMismatch)) => {
+            // Not what the feed published; don't keep it around
+            let _ = fs::remove_file(path).await;
+            download.bytes = 0;
+            download.integrity = Some(Integrity::HashMismatch);
+            DownloadStatus::Failed
+        }
+        Ok((sha256, i)) => {
+            download.sha256 = Some(sha256);
+            download.integrity = Some(i);
+            DownloadStatus::Done
+        }
+        // Reading it back failed, which says nothing about the file; keep
+        // it and check again on the next attempt
+        Err(e) => {
+            eprintln!("verifying download {} failed: {}", download.id, e);
+            download.integrity = None;
+            match download.attempts < MAX_ATTEMPTS {
+                true => retry_later(download),
+                false => DownloadStatus::Failed,
+            }
+        }
+    }
+}
+
+/// How many times an interrupted download is resumed before giving up.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// The wait before the first retry, doubling with each one after.
+const RETRY_BASE: Duration = Duration::from_secs(30);
+
+/// Queues the download again once a wait for its attempt so far has passed,
+/// so a host that's down doesn't use up the attempts at once.
+fn retry_later(download: &mut Download) -> DownloadStatus {
+    let wait = RETRY_BASE * 2u32.pow(download.attempts.saturating_sub(1));
+    download.retry_after = chrono::Duration::from_std(wait)
+        .ok()
+        .map(|wait| Utc::now() + wait);
+    DownloadStatus::Queued
+}
+
+#[derive(Debug)]
+enum FetchError {
+    /// The host answered with an error status, or isn't one we may fetch
+    /// from; retrying won't help.
+    Http,
+    /// The connection failed or dropped part way through.
+    Interrupted,
+    Io,
+}
+
+/// Downloads `url` to `path`, continuing from `written` bytes with a `Range`
+/// request if we already have part of the file. `written` is kept up to date
+/// as chunks land, so it is accurate even when this fails.
+async fn fetch(
+    http: &Fetcher,
+    url: &str,
+    path: &std::path::Path,
+    written: &mut u64,
+    throttle: &Throttle,
+) -> Result<(), FetchError> {
+    let mut resp = send(http, url, *written).await?;
+    if resp.status() == StatusCode::PARTIAL_CONTENT && range_start(&resp) != Some(*written) {
+        // Not the bytes we asked for; splicing them in would corrupt the
+        // file, so start over
+        *written = 0;
+        resp = send(http, url, 0).await?;
+    }
+    let resume = match resp.status() {
+        StatusCode::PARTIAL_CONTENT if range_start(&resp) == Some(*written) => *written > 0,
+        StatusCode::PARTIAL_CONTENT => return Err(FetchError::Http),
+        // Either a fresh download or the host ignored our Range
+        s if s.is_success() => false,
+        // We already have the whole file
+        StatusCode::RANGE_NOT_SATISFIABLE if *written > 0 => return Ok(()),
+        _ => return Err(FetchError::Http),
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await.map_err(|_| FetchError::Io)?;
+    }
+    let mut file = if resume {
+        fs::OpenOptions::new().append(true).open(path).await
+    } else {
+        *written = 0;
+        fs::File::create(path).await
+    }
+    .map_err(|_| FetchError::Io)?;
+
+    let conn = throttle.connection();
+    while let Some(chunk) = resp.chunk().await.map_err(|_| FetchError::Interrupted)? {
+        conn.take(chunk.len()).await;
+        file.write_all(&chunk).await.map_err(|_| FetchError::Io)?;
+        *written += chunk.len() as u64;
+    }
+    file.flush().await.map_err(|_| FetchError::Io)?;
+    Ok(())
+}
+
+/// Requests `url`, from byte `from` on.
+async fn send(http: &Fetcher, url: &str, from: u64) -> Result<reqwest::Response, FetchError> {
+    let mut req = http.get(url).map_err(|_| FetchError::Http)?;
+    if from > 0 {
+        req = req.header(header::RANGE, format!("bytes={}-", from));
+    }
+    req.send().await.map_err(|e| {
+        if ssrf::is_blocked(&e) {
+            FetchError::Http
+        } else {
+            FetchError::Interrupted
+        }
+    })
+}
+
+/// Where a `206`'s `Content-Range: bytes <start>-<end>/<total>` starts.
+fn range_start(resp: &reqwest::Response) -> Option<u64> {
+    let value = resp.headers().get(header::CONTENT_RANGE)?.to_str().ok()?;
+    let (start, _) = value.strip_prefix("bytes ")?.split_once('-')?;
+    start.parse().ok()
+}
@@ -0,0 +1,222 @@
+//! Per-podcast engagement for admins, added up from every user's `play`
+//! actions: who listens, how far they get, and with which apps and
+//! connections.
+
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::engagement::{
+    ClientShare, ConnectionShare, Engagement, EngagementQuery, EpisodeEngagement,
+    PodcastEngagement, PodcastEngagementReport,
+};
+
+use crate::{
+    gpodder::{Connection, EpisodeAction},
+    service_accounts::{self, Service},
+    stats, AppState, Error, PodcastChannel, DB,
+};
+
+/// At least this far through counts as finishing an episode.
+pub(crate) const COMPLETE_PERCENT: f32 = 90.0;
+
+/// How far through the episode a play action ended, in percent.
+pub(crate) fn completion(a: &EpisodeAction) -> Option<f32> {
+    let from_total = match (a.position, a.total) {
+        (Some(position), Some(total)) if total > 0 => Some(position as f32 * 100.0 / total as f32),
+        _ => None,
+    };
+    a.completion.or(from_total).map(|c| c.clamp(0.0, 100.0))
+}
+
+#[derive(Default)]
+struct Tally {
+    plays: usize,
+    listened_secs: u64,
+    listeners: HashSet<Uuid>,
+    /// Furthest completion per listener and episode.
+    furthest: HashMap<(Uuid, String), f32>,
+}
+
+impl Tally {
+    fn add(&mut self, user: Uuid, a: &EpisodeAction, secs: u32) {
+        self.plays += 1;
+        self.listened_secs += u64::from(secs);
+        self.listeners.insert(user);
+        if let Some(c) = completion(a) {
+            let furthest = self.furthest.entry((user, a.episode.clone())).or_default();
+            *furthest = furthest.max(c);
+        }
+    }
+
+    fn engagement(&self) -> Engagement {
+        let known = self.furthest.len();
+        Engagement {
+            listeners: self.listeners.len(),
+            plays: self.plays,
+            listened_secs: self.listened_secs,
+            avg_completion: (known > 0).then(|| self.furthest.values().sum::<f32>() / known as f32),
+            completed: self
+                .furthest
+                .values()
+                .filter(|c| **c >= COMPLETE_PERCENT)
+                .count(),
+        }
+    }
+}
+
+/// Plays and seconds by some detail of the play, most played first.
+fn shares<K: Clone + Eq + Hash>(counts: &HashMap<K, (usize, u64)>) -> Vec<(K, usize, u64)> {
+    let mut shares: Vec<_> = counts
+        .iter()
+        .map(|(k, (plays, secs))| (k.clone(), *plays, *secs))
+        .collect();
+    shares.sort_by_key(|(_, plays, secs)| Reverse((*plays, *secs)));
+    shares
+}
+
+#[derive(Default)]
+struct PodcastTally {
+    totals: Tally,
+    clients: HashMap<Option<String>, (usize, u64)>,
+    connections: HashMap<Option<Connection>, (usize, u64)>,
+    episodes: HashMap<String, Tally>,
+}
+
+/// Tallies of every podcast listened to since `query.since`, by feed URL.
+fn tally<D: DB>(db: &D, query: &EngagementQuery) -> Result<HashMap<String, PodcastTally>, Error> {
+    let since = query.since.map(|t| t.naive_utc());
+    let (_, users) = db.users(None, 0, usize::MAX)?;
+    let mut podcasts: HashMap<String, PodcastTally> = HashMap::new();
+    for u in users {
+        let actions = db.episode_actions(u.id)?;
+        // Worked out over the whole history, so `started` can come from a
+        // play before `since`
+        for (a, secs) in stats::listened(&actions) {
+            if since.is_some_and(|since| a.timestamp <= since) {
+                continue;
+            }
+            let p = podcasts.entry(a.podcast.clone()).or_default();
+            p.totals.add(u.id, a, secs);
+            p.episodes
+                .entry(a.episode.clone())
+                .or_default()
+                .add(u.id, a, secs);
+            let client = p.clients.entry(a.client.clone()).or_default();
+            *client = (client.0 + 1, client.1 + u64::from(secs));
+            let connection = p.connections.entry(a.connection).or_default();
+            *connection = (connection.0 + 1, connection.1 + u64::from(secs));
+        }
+    }
+    Ok(podcasts)
+}
+
+fn podcast_engagement(podcast: &PodcastChannel, t: &PodcastTally) -> PodcastEngagement {
+    PodcastEngagement {
+        podcast: podcast.id,
+        rss: podcast.rss.clone(),
+        name: podcast.name.clone(),
+        totals: t.totals.engagement(),
+        by_client: shares(&t.clients)
+            .into_iter()
+            .map(|(client, plays, listened_secs)| ClientShare {
+                client,
+                plays,
+                listened_secs,
+            })
+            .collect(),
+        by_connection: shares(&t.connections)
+            .into_iter()
+            .map(|(connection, plays, listened_secs)| ConnectionShare {
+                connection,
+                plays,
+                listened_secs,
+            })
+            .collect(),
+    }
+}
+
+/// `GET /admin/engagement`: every podcast that was played, most listened
+/// first.
+pub async fn list<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Query(query): Query<EngagementQuery>,
+    service: Option<Extension<Service>>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    if let Err(status) = service_accounts::admin_or_service(&s, service) {
+        return (status, Json(None));
+    }
+    let listed = tally(&s.db, &query).map(|tallies| {
+        let mut listed: Vec<PodcastEngagement> = tallies
+            .iter()
+            // Actions for feeds the server doesn't know are left out
+            .filter_map(|(rss, t)| {
+                let podcast = s.db.get_podcast(rss.clone()).ok()?;
+                Some(podcast_engagement(&podcast, t))
+            })
+            .collect();
+        listed.sort_by_key(|p| Reverse(p.totals.listened_secs));
+        listed
+    });
+    match listed {
+        Ok(l) => (StatusCode::OK, Json(Some(l))),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// `GET /admin/podcasts/<podcast ID>/engagement`, with a line per episode,
+/// most listened first.
+pub async fn podcast<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<EngagementQuery>,
+    service: Option<Extension<Service>>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    if let Err(status) = service_accounts::admin_or_service(&s, service) {
+        return (status, Json(None));
+    }
+    let report = s.db.get_podcast_by_id(id).and_then(|podcast| {
+        let mut tallies = tally(&s.db, &query)?;
+        let t = tallies.remove(&podcast.rss).unwrap_or_default();
+        let known = s.db.episodes(podcast.rss.clone())?;
+        let mut episodes: Vec<EpisodeEngagement> = t
+            .episodes
+            .iter()
+            .map(|(audio, e)| {
+                let found = known
+                    .iter()
+                    .find(|k| k.enclosure.as_ref().is_some_and(|x| x.url == *audio));
+                EpisodeEngagement {
+                    episode: found.map(|k| k.id),
+                    audio: audio.clone(),
+                    title: found.map(|k| k.title.clone()),
+                    totals: e.engagement(),
+                }
+            })
+            .collect();
+        episodes.sort_by_key(|e| Reverse(e.totals.listened_secs));
+        Ok(PodcastEngagementReport {
+            podcast: podcast_engagement(&podcast, &t),
+            episodes,
+        })
+    });
+    match report {
+        Ok(r) => (StatusCode::OK, Json(Some(r))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
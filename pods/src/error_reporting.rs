@@ -0,0 +1,143 @@
+//! Reports handler panics and internal errors to a Sentry-compatible
+//! service (Sentry, GlitchTip, ...) using its store API.
+
+use std::{any::Any, panic::AssertUnwindSafe, sync::Arc};
+
+use axum::{
+    extract::State,
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use futures_util::FutureExt;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{media_links::redact, AppState, DB};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ErrorReportingConfig {
+    /// `https://<key>@<host>/<project id>`
+    pub dsn: String,
+    #[serde(default)]
+    pub environment: Option<String>,
+}
+
+pub struct Reporter {
+    client: reqwest::Client,
+    store_url: String,
+    auth: String,
+    environment: Option<String>,
+}
+
+impl Reporter {
+    pub fn new(config: &ErrorReportingConfig) -> Result<Reporter, String> {
+        let dsn = Url::parse(&config.dsn).map_err(|e| e.to_string())?;
+        let key = dsn.username().to_string();
+        // Self-hosted installs may live under a path prefix
+        let (prefix, project) = dsn.path().rsplit_once('/').unwrap_or_default();
+        if key.is_empty() || project.is_empty() {
+            return Err("DSN needs a key and a project id".to_string());
+        }
+        let mut store_url = dsn.clone();
+        store_url.set_path(&format!("{}/api/{}/store/", prefix, project));
+        let _ = store_url.set_username("");
+        let _ = store_url.set_password(None);
+        Ok(Reporter {
+            client: reqwest::Client::new(),
+            store_url: store_url.to_string(),
+            auth: format!(
+                "Sentry sentry_version=7, sentry_client=pods/{}, sentry_key={}",
+                env!("CARGO_PKG_VERSION"),
+                key
+            ),
+            environment: config.environment.clone(),
+        })
+    }
+
+    /// Sends an event in the background; reporting must never hold up or
+    /// fail a request.
+    fn capture(&self, kind: &str, message: String, request: RequestContext) {
+        let event = json!({
+            "event_id": Uuid::new_v4().simple().to_string(),
+            "timestamp": Utc::now().to_rfc3339(),
+            "platform": "other",
+            "level": "error",
+            "logger": "pods",
+            "release": format!("pods@{}", env!("CARGO_PKG_VERSION")),
+            "environment": self.environment,
+            "exception": {"values": [{"type": kind, "value": message}]},
+            "request": {
+                "method": request.method,
+                "url": request.url,
+                "headers": {"User-Agent": request.user_agent},
+            },
+            "user": request.user.map(|u| json!({"id": u})),
+        });
+        let req = self
+            .client
+            .post(&self.store_url)
+            .header("X-Sentry-Auth", &self.auth)
+            .json(&event);
+        tokio::spawn(async move {
+            let _ = req.send().await;
+        });
+    }
+}
+
+struct RequestContext {
+    method: String,
+    url: String,
+    user_agent: Option<String>,
+    user: Option<Uuid>,
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "handler panicked".to_string()
+    }
+}
+
+/// The reporter, plus app state for the logged in user.
+type ReportState<D> = (Arc<Reporter>, Arc<Mutex<AppState<D>>>);
+
+/// Turns handler panics into 500s, and reports them along with any other
+/// 500 response (the status handlers give `Error::DbError` and friends).
+pub async fn report<D: DB, B>(
+    State((reporter, state)): State<ReportState<D>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let context = RequestContext {
+        method: req.method().to_string(),
+        url: redact(&req.uri().to_string()),
+        user_agent: req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from),
+        user: state.lock().await.current_user,
+    };
+
+    match AssertUnwindSafe(next.run(req)).catch_unwind().await {
+        Ok(resp) => {
+            if resp.status() == StatusCode::INTERNAL_SERVER_ERROR {
+                let message = format!("{} {} returned 500", context.method, context.url);
+                reporter.capture("InternalError", message, context);
+            }
+            resp
+        }
+        Err(payload) => {
+            reporter.capture("Panic", panic_message(&*payload), context);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Hiding podcasts and episodes marked `<itunes:explicit>` from people who
+//! asked not to see them, or on instances that default to it.
+
+use uuid::Uuid;
+
+use crate::{instance, AppState, Episode, Error, PodcastChannel, DB};
+
+/// An `<itunes:explicit>` value. Apple's spec says `true`/`false`; older
+/// feeds write `yes`, `explicit`, `no` or `clean`.
+pub fn parse(raw: &str) -> Option<bool> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "explicit" => Some(true),
+        "false" | "no" | "clean" => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether to leave explicit content out for `user`: their own setting, or
+/// the instance's when they have none or nobody is logged in.
+pub fn hidden<D: DB>(state: &AppState<D>, user: Option<Uuid>) -> Result<bool, Error> {
+    let own = match user {
+        Some(u) => state.db.get_user(u)?.settings.hide_explicit,
+        None => None,
+    };
+    match own {
+        Some(hide) => Ok(hide),
+        None => Ok(instance::effective(state)?.hide_explicit),
+    }
+}
+
+/// Drops explicit episodes, and episodes of explicit podcasts, if `hide`.
+pub fn filter_episodes<D: DB>(db: &D, hide: bool, episodes: &mut Vec<Episode>) {
+    if hide {
+        episodes.retain(|e| !e.explicit && !podcast_explicit(db, &e.podcast));
+    }
+}
+
+pub fn filter_podcasts(hide: bool, podcasts: &mut Vec<PodcastChannel>) {
+    if hide {
+        podcasts.retain(|p| !p.explicit);
+    }
+}
+
+fn podcast_explicit<D: DB>(db: &D, rss: &str) -> bool {
+    db.get_podcast(rss.to_string()).is_ok_and(|p| p.explicit)
+}
@@ -0,0 +1,224 @@
+//! Servers that mirror each other's feeds. A server with `secret` set shares
+//! its catalog at `GET /federation/catalog`; a server with `peers` fetches
+//! theirs on an interval, adds podcasts it hasn't seen and ingests new
+//! episodes as if it had refreshed the feed itself.
+//!
+//! A mirrored feed takes the peer's `last_refreshed`, so the local refresh
+//! leaves it alone while the peer keeps it fresh and picks it back up if
+//! the peer stops.
+//!
+//! Requests to a peer are signed like outgoing events (see
+//! [`pods_types::signing`]), over the query string instead of a body: the
+//! path can differ behind a reverse proxy.
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, Uri},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use pods_types::signing::{self, DEFAULT_TOLERANCE_SECS, HEADER};
+use reqwest::Url;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::federation::{Catalog, CatalogEntry, CatalogQuery};
+
+use crate::{refresh, AppState, Episode, Error, Feed, DB};
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FederationConfig {
+    /// Followers sign their requests with this. Unset means the catalog
+    /// isn't shared.
+    pub secret: Option<String>,
+    /// Servers to mirror.
+    pub peers: Vec<PeerConfig>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PeerConfig {
+    /// Where the peer's API is mounted, e.g. `http://pods-a:3000`.
+    pub url: String,
+    /// The peer's `secret`.
+    pub secret: String,
+    #[serde(default = "default_interval")]
+    pub interval_secs: u64,
+}
+
+fn default_interval() -> u64 {
+    300
+}
+
+pub async fn catalog<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    headers: HeaderMap,
+    uri: Uri,
+    Query(query): Query<CatalogQuery>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let Some(secret) = &s.config.federation.secret else {
+        return (StatusCode::NOT_FOUND, Json(None));
+    };
+    let signature = headers.get(HEADER).and_then(|h| h.to_str().ok());
+    let signed = uri.query().unwrap_or_default();
+    let verified = signature.is_some_and(|sig| {
+        let now = Utc::now().timestamp();
+        signing::verify(
+            secret.as_bytes(),
+            sig,
+            signed.as_bytes(),
+            now,
+            DEFAULT_TOLERANCE_SECS,
+        )
+        .is_ok()
+    });
+    if !verified {
+        return (StatusCode::UNAUTHORIZED, Json(None));
+    }
+    let listed = s.db.podcasts().and_then(|podcasts| {
+        let mut entries = vec![];
+        for podcast in podcasts {
+            let last_refreshed = s.db.last_refreshed(podcast.rss.clone())?;
+            if query
+                .since
+                .is_some_and(|since| last_refreshed.is_none_or(|t| t <= since))
+            {
+                continue;
+            }
+            entries.push(CatalogEntry {
+                episodes: s.db.episodes(podcast.rss.clone())?,
+                podcast,
+                last_refreshed,
+            });
+        }
+        Ok(entries)
+    });
+    match listed {
+        Ok(podcasts) => {
+            let cursor = podcasts
+                .iter()
+                .filter_map(|e| e.last_refreshed)
+                .max()
+                .or(query.since);
+            (StatusCode::OK, Json(Some(Catalog { cursor, podcasts })))
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Starts following each configured peer.
+pub async fn worker<D: DB + Send + 'static>(state: Arc<Mutex<AppState<D>>>) {
+    let peers = state.lock().await.config.federation.peers.clone();
+    for peer in peers {
+        tokio::spawn(follow(state.clone(), peer));
+    }
+}
+
+async fn follow<D: DB + Send + 'static>(state: Arc<Mutex<AppState<D>>>, peer: PeerConfig) {
+    // Not the fetcher: peers are the operator's, and usually on a private
+    // address the SSRF guard would refuse
+    let client = reqwest::Client::new();
+    let mut since = None;
+    loop {
+        match fetch(&client, &peer, since).await {
+            Ok(catalog) => {
+                let s = &mut *state.lock().await;
+                for entry in catalog.podcasts {
+                    if let Err(e) = mirror(s, &peer, entry) {
+                        eprintln!("mirroring from {} failed: {:?}", peer.url, e);
+                    }
+                }
+                since = catalog.cursor.or(since);
+            }
+            Err(e) => eprintln!("fetching the catalog of {} failed: {}", peer.url, e),
+        }
+        tokio::time::sleep(Duration::from_secs(peer.interval_secs.max(1))).await;
+    }
+}
+
+async fn fetch(
+    client: &reqwest::Client,
+    peer: &PeerConfig,
+    since: Option<DateTime<Utc>>,
+) -> Result<Catalog, Box<dyn std::error::Error + Send + Sync>> {
+    let mut url = peer_url(&peer.url)?.join("federation/catalog")?;
+    if let Some(since) = since {
+        url.query_pairs_mut()
+            .append_pair("since", &since.to_rfc3339());
+    }
+    let signature = signing::sign(
+        &[peer.secret.as_bytes()],
+        Utc::now().timestamp(),
+        url.query().unwrap_or_default().as_bytes(),
+    );
+    let resp = client
+        .get(url)
+        .header(HEADER, signature)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(resp.json().await?)
+}
+
+/// The peer's base URL, with a trailing slash so routes join under it.
+fn peer_url(base: &str) -> Result<Url, url::ParseError> {
+    let mut url = Url::parse(base)?;
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    Ok(url)
+}
+
+fn mirror<D: DB>(s: &mut AppState<D>, peer: &PeerConfig, entry: CatalogEntry) -> Result<(), Error> {
+    let p = entry.podcast;
+    // Artwork the peer serves itself, like `/media/<sha256>/artwork`
+    let artwork = p.artwork.and_then(|a| {
+        let base = peer_url(&peer.url).ok()?;
+        Some(base.join(a.trim_start_matches('/')).ok()?.to_string())
+    });
+    match s.db.get_podcast(p.rss.clone()) {
+        Ok(_) => {}
+        Err(Error::NotFound) => {
+            s.db.create_podcast(
+                p.rss.clone(),
+                p.name.clone(),
+                p.description.clone(),
+                artwork.clone(),
+                p.language.clone(),
+                p.explicit,
+            )?;
+        }
+        Err(e) => return Err(e),
+    }
+    let episodes = entry
+        .episodes
+        .into_iter()
+        .map(|e| Episode {
+            id: Uuid::new_v4(),
+            podcast: p.rss.clone(),
+            ..e
+        })
+        .collect();
+    let feed = Feed {
+        title: p.name,
+        description: p.description,
+        artwork,
+        language: p.language,
+        explicit: p.explicit,
+        episodes,
+    };
+    refresh::ingest(s, &p.rss, feed);
+    // Counts as refreshed when the peer refreshed it
+    let local = s.db.last_refreshed(p.rss.clone())?;
+    if let Some(theirs) = entry.last_refreshed {
+        if local.is_none_or(|t| t < theirs) {
+            s.db.set_last_refreshed(p.rss, theirs)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,297 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use hyper::client::connect::dns::Name;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    redirect, Client, Method, NoProxy, Proxy, RequestBuilder, Response, StatusCode, Url,
+};
+use serde::Deserialize;
+
+use crate::ssrf::{Blocked, FilteringResolver, Policy, SsrfConfig};
+
+/// Most redirects followed from one URL.
+const MAX_REDIRECTS: usize = 10;
+
+/// Settings for outbound feed and media fetches.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FetchConfig {
+    /// Proxy for every outbound fetch: `http://`, `https://`, `socks5://`, or
+    /// `socks5h://` to also resolve hostnames through the proxy (e.g. Tor).
+    /// Hostnames sent through a proxy never reach the SSRF resolver, so
+    /// this needs `ssrf.enabled = false`, with the proxy doing the refusing.
+    pub proxy: Option<String>,
+    /// Hosts fetched directly instead of through the proxy. `.example.com`
+    /// also matches subdomains; IP addresses and CIDR ranges work too.
+    pub no_proxy: Vec<String>,
+    /// Defaults to `pods/<version>`.
+    pub user_agent: Option<String>,
+    /// Extra headers per host, e.g. auth for a private CDN. Hosts match like
+    /// `no_proxy` entries.
+    pub headers: HashMap<String, HashMap<String, String>>,
+    pub dns: DnsConfig,
+    pub ssrf: SsrfConfig,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct DnsConfig {
+    /// A DNS-over-HTTPS endpoint speaking the JSON API, e.g.
+    /// `https://1.1.1.1/dns-query`. Unset means the system resolver.
+    pub doh: Option<String>,
+    /// Fixed addresses for particular hosts, bypassing the resolver.
+    pub hosts: HashMap<String, IpAddr>,
+}
+
+/// The HTTP client shared by everything that fetches from podcast hosts.
+#[derive(Clone, Debug)]
+pub struct Fetcher {
+    client: Client,
+    /// The same, leaving redirects to `get_traced`.
+    unfollowed: Client,
+    host_headers: Vec<(String, HeaderMap)>,
+    policy: Arc<Policy>,
+}
+
+impl Fetcher {
+    pub fn new(config: &FetchConfig) -> Fetcher {
+        let user_agent = config
+            .user_agent
+            .clone()
+            .unwrap_or_else(|| format!("pods/{}", env!("CARGO_PKG_VERSION")));
+        let mut proxy = None;
+        if let Some(url) = &config.proxy {
+            if config.ssrf.enabled {
+                panic!(
+                    "proxy {} would bypass SSRF protection; set fetch.ssrf.enabled = false",
+                    url
+                );
+            }
+            proxy = Some(
+                Proxy::all(url)
+                    .unwrap_or_else(|e| panic!("invalid proxy {}: {}", url, e))
+                    .no_proxy(NoProxy::from_string(&config.no_proxy.join(","))),
+            );
+        }
+
+        let policy = Arc::new(Policy::new(&config.ssrf));
+        let doh = config.dns.doh.as_ref().map(|url| {
+            Arc::new(DohResolver {
+                client: Client::new(),
+                url: url.clone(),
+            })
+        });
+        let filtering = policy.enabled().then(|| {
+            Arc::new(FilteringResolver {
+                inner: doh.clone().map(|d| d as Arc<dyn Resolve>),
+                policy: policy.clone(),
+            })
+        });
+        let build = |redirects: redirect::Policy| {
+            let mut builder = Client::builder()
+                .user_agent(&user_agent)
+                .redirect(redirects);
+            if let Some(proxy) = &proxy {
+                builder = builder.proxy(proxy.clone());
+            }
+            if let Some(filtering) = &filtering {
+                builder = builder.dns_resolver(filtering.clone());
+            } else if let Some(doh) = &doh {
+                builder = builder.dns_resolver(doh.clone());
+            }
+            for (host, ip) in &config.dns.hosts {
+                // The port is ignored; the URL's port is used
+                builder = builder.resolve(host, SocketAddr::new(*ip, 0));
+            }
+            builder.build().unwrap()
+        };
+        let client = match policy.enabled() {
+            true => {
+                let redirects = policy.clone();
+                build(redirect::Policy::custom(move |attempt| {
+                    if attempt.previous().len() >= MAX_REDIRECTS {
+                        attempt.error("too many redirects")
+                    } else if let Err(e) = redirects.check_url(attempt.url()) {
+                        attempt.error(e)
+                    } else {
+                        attempt.follow()
+                    }
+                }))
+            }
+            false => build(redirect::Policy::default()),
+        };
+
+        let host_headers = config
+            .headers
+            .iter()
+            .map(|(host, headers)| {
+                let map = headers
+                    .iter()
+                    .map(|(name, value)| {
+                        match (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+                            (Ok(n), Ok(v)) => (n, v),
+                            _ => panic!("invalid header {} for host {}", name, host),
+                        }
+                    })
+                    .collect();
+                (host.to_lowercase(), map)
+            })
+            .collect();
+
+        Fetcher {
+            client,
+            unfollowed: build(redirect::Policy::none()),
+            host_headers,
+            policy,
+        }
+    }
+
+    /// Starts a GET, refusing URLs that point at a non-public address.
+    pub fn get(&self, url: &str) -> Result<RequestBuilder, Blocked> {
+        self.request(Method::GET, url)
+    }
+
+    /// Like `get`, for any method.
+    pub fn request(&self, method: Method, url: &str) -> Result<RequestBuilder, Blocked> {
+        let parsed = Url::parse(url).ok();
+        if let Some(u) = &parsed {
+            self.policy.check_url(u)?;
+        }
+        Ok(self.with_headers(self.client.request(method, url), parsed))
+    }
+
+    /// Like `get` with `headers`, following redirects here rather than in
+    /// the client, so the URLs and statuses on the way are known.
+    pub async fn get_traced(
+        &self,
+        url: &str,
+        headers: HeaderMap,
+    ) -> Result<(Response, Vec<Hop>), TracedError> {
+        let mut url = Url::parse(url).map_err(|_| TracedError::BadUrl)?;
+        let mut hops = vec![];
+        loop {
+            self.policy
+                .check_url(&url)
+                .map_err(|_| TracedError::Blocked)?;
+            let req = self.unfollowed.get(url.clone()).headers(headers.clone());
+            let resp = self
+                .with_headers(req, Some(url.clone()))
+                .send()
+                .await
+                .map_err(TracedError::Request)?;
+            let location = resp
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .and_then(|l| url.join(l).ok());
+            let Some(next) = location.filter(|_| resp.status().is_redirection()) else {
+                return Ok((resp, hops));
+            };
+            if hops.len() >= MAX_REDIRECTS {
+                return Err(TracedError::TooManyRedirects);
+            }
+            hops.push(Hop {
+                url: url.to_string(),
+                status: resp.status(),
+            });
+            url = next;
+        }
+    }
+
+    fn with_headers(&self, mut req: RequestBuilder, parsed: Option<Url>) -> RequestBuilder {
+        let host = parsed.and_then(|u| u.host_str().map(|h| h.to_lowercase()));
+        if let Some(host) = host {
+            for (pattern, headers) in &self.host_headers {
+                if host_matches(pattern, &host) {
+                    req = req.headers(headers.clone());
+                }
+            }
+        }
+        req
+    }
+}
+
+/// A redirect `get_traced` followed.
+#[derive(Clone, Debug)]
+pub struct Hop {
+    /// The URL that answered with the redirect.
+    pub url: String,
+    pub status: StatusCode,
+}
+
+#[derive(Debug)]
+pub enum TracedError {
+    BadUrl,
+    /// The URL or a redirect points at a non-public address.
+    Blocked,
+    TooManyRedirects,
+    Request(reqwest::Error),
+}
+
+/// `.example.com` matches `example.com` and any subdomain of it; anything
+/// else must match exactly.
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix('.') {
+        Some(domain) => host == domain || host.ends_with(pattern),
+        None => host == pattern,
+    }
+}
+
+/// Resolves hostnames with DNS-over-HTTPS, using the JSON flavour of the
+/// protocol that Cloudflare, Google and others serve.
+struct DohResolver {
+    client: Client,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data: String,
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let client = self.client.clone();
+        let url = self.url.clone();
+        Box::pin(async move {
+            let mut addrs = vec![];
+            for record_type in ["A", "AAAA"] {
+                let resp: DohResponse = client
+                    .get(&url)
+                    .query(&[("name", name.as_str()), ("type", record_type)])
+                    .header(header::ACCEPT, "application/dns-json")
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                // Skip CNAMEs and the like; the addresses they lead to are
+                // in the answer too
+                addrs.extend(
+                    resp.answer
+                        .iter()
+                        .filter(|a| a.record_type == 1 || a.record_type == 28)
+                        .filter_map(|a| a.data.parse::<IpAddr>().ok())
+                        .map(|ip| SocketAddr::new(ip, 0)),
+                );
+            }
+            if addrs.is_empty() {
+                return Err(format!("no addresses found for {}", name).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
@@ -0,0 +1,50 @@
+//! Import/export in the formats used by gPodder and gpodder.net, so users can
+//! move between this server and the rest of the gpodder ecosystem.
+//!
+//! See <https://gpoddernet.readthedocs.io/en/latest/api/reference/events.html>
+//! for the episode action format.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::gpodder::{ActionKind, Connection, EpisodeAction, GpodderExport};
+
+use crate::{AppState, Error, DB};
+
+pub async fn upload_episode_actions<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+    Json(actions): Json<Vec<EpisodeAction>>,
+) -> impl IntoResponse {
+    match state.lock().await.db.record_episode_actions(uid, actions) {
+        Ok(()) => StatusCode::OK,
+        Err(Error::NotFound) => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub async fn export_gpodder<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> impl IntoResponse {
+    let db = &state.lock().await.db;
+    let export = db.get_user(uid).and_then(|u| {
+        Ok(GpodderExport {
+            subscriptions: u.subscribed,
+            episode_actions: db.episode_actions(uid)?,
+        })
+    });
+    match export {
+        Ok(e) => (StatusCode::OK, Json(Some(e))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
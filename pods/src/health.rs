@@ -0,0 +1,162 @@
+//! A record of each feed's recent fetches and of what's wrong with its
+//! latest usable copy, served as a report for debugging a show that isn't
+//! updating: whether the host answers, how fast, where it redirects and
+//! whether items have what's needed to tell them apart.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::health::{
+    FeedFetch, FeedHealth, FeedWarning, FetchOutcome, Redirect, WarningKind,
+};
+
+use crate::{dates, explicit, profiles::Acting, AppState, Error, DB};
+
+/// Fetches kept per feed.
+const HISTORY: usize = 20;
+/// Item titles given per warning.
+const EXAMPLES: usize = 5;
+
+/// How a fetch went, and the warnings if it gave a usable feed.
+pub struct Report {
+    pub fetch: FeedFetch,
+    pub warnings: Option<Vec<FeedWarning>>,
+}
+
+/// Keeps the report for a podcast that's stored.
+pub fn record<D: DB>(db: &mut D, rss: &str, report: Report) {
+    let _ = db.add_feed_fetch(rss.to_string(), report.fetch, HISTORY);
+    if let Some(warnings) = report.warnings {
+        let _ = db.set_feed_warnings(rss.to_string(), warnings);
+    }
+}
+
+/// Problems with the feed's items, by kind. `body` already parsed as a feed.
+pub fn warnings(body: &str) -> Vec<FeedWarning> {
+    let Ok(xml) = roxmltree::Document::parse(body) else {
+        return vec![];
+    };
+    let items = xml
+        .descendants()
+        .filter(|n| n.tag_name().name() == "channel")
+        .take(1)
+        .flat_map(|c| c.children().filter(|n| n.tag_name().name() == "item"));
+    let mut found: Vec<FeedWarning> = vec![];
+    let mut warn = |kind, title: &str| {
+        let i = match found.iter().position(|w| w.kind == kind) {
+            Some(i) => i,
+            None => {
+                found.push(FeedWarning {
+                    kind,
+                    count: 0,
+                    examples: vec![],
+                });
+                found.len() - 1
+            }
+        };
+        let w = &mut found[i];
+        w.count += 1;
+        if w.examples.len() < EXAMPLES {
+            w.examples.push(title.to_string());
+        }
+    };
+    let mut guids: HashMap<String, usize> = HashMap::new();
+    for item in items {
+        let text = |name: &str| {
+            item.children()
+                .find(|n| n.tag_name().name() == name)
+                .and_then(|n| n.text())
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+        };
+        let title = text("title").unwrap_or("Untitled");
+        match text("guid") {
+            Some(guid) => {
+                let seen = guids.entry(guid.to_string()).or_default();
+                *seen += 1;
+                if *seen == 2 {
+                    warn(WarningKind::DuplicateGuid, title);
+                }
+            }
+            None => warn(WarningKind::MissingGuid, title),
+        }
+        match text("pubDate").or_else(|| text("date")) {
+            Some(d) if dates::parse(d).is_none() => warn(WarningKind::BadDate, title),
+            Some(_) => {}
+            None => warn(WarningKind::MissingDate, title),
+        }
+        let length = item
+            .children()
+            .find(|n| n.tag_name().name() == "enclosure" && n.has_attribute("url"))
+            .map(|n| {
+                n.attribute("length")
+                    .and_then(|l| l.trim().parse::<u64>().ok())
+            });
+        match length {
+            None => warn(WarningKind::MissingEnclosure, title),
+            // Some feeds put 0 for a length they don't know
+            Some(None | Some(0)) => warn(WarningKind::MissingLength, title),
+            Some(Some(_)) => {}
+        }
+    }
+    found.sort_by_key(|w| w.kind);
+    found
+}
+
+/// Whether the fetch got the feed, or found it unchanged.
+pub fn succeeded(outcome: FetchOutcome) -> bool {
+    matches!(outcome, FetchOutcome::Ok | FetchOutcome::NotModified)
+}
+
+/// The latest fetch that got the feed, whose `ETag` and `Last-Modified`
+/// make the next one conditional.
+pub fn validators<D: DB>(db: &D, rss: &str) -> Option<FeedFetch> {
+    db.feed_fetches(rss.to_string())
+        .ok()?
+        .into_iter()
+        .find(|f| succeeded(f.outcome) && (f.etag.is_some() || f.last_modified.is_some()))
+}
+
+fn report<D: DB>(s: &AppState<D>, user: Option<Uuid>, id: Uuid) -> Result<FeedHealth, Error> {
+    let p = s.db.get_podcast_by_id(id)?;
+    if p.explicit && explicit::hidden(s, user)? {
+        return Err(Error::NotFound);
+    }
+    let fetches = s.db.feed_fetches(p.rss.clone())?;
+    let answered: Vec<&FeedFetch> = fetches.iter().filter(|f| f.status.is_some()).collect();
+    let average_ms = match answered.len() as u64 {
+        0 => None,
+        n => Some(answered.iter().map(|f| f.elapsed_ms).sum::<u64>() / n),
+    };
+    let latest = answered.first();
+    Ok(FeedHealth {
+        podcast: p.id,
+        last_success: fetches.iter().find(|f| succeeded(f.outcome)).map(|f| f.at),
+        average_ms,
+        etag: latest.and_then(|f| f.etag.clone()),
+        last_modified: latest.and_then(|f| f.last_modified.clone()),
+        warnings: s.db.feed_warnings(p.rss.clone())?,
+        rss: p.rss,
+        fetches,
+    })
+}
+
+pub async fn get_health<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Acting(user): Acting,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match report(&*state.lock().await, user, id) {
+        Ok(h) => (StatusCode::OK, Json(Some(h))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
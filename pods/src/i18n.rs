@@ -0,0 +1,346 @@
+//! Translations of user-facing messages. The language comes from the
+//! settings of the profile the request switched to or the logged in user,
+//! then `Accept-Language`, then English.
+
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use pods_types::ApiError;
+use tokio::sync::Mutex;
+
+use crate::{profiles::Switched, AppState, DB};
+
+pub use pods_types::i18n::Lang;
+
+/// The most preferred supported language in an `Accept-Language` header.
+pub fn negotiate(headers: &HeaderMap) -> Option<Lang> {
+    let accept = headers.get(header::ACCEPT_LANGUAGE)?.to_str().ok()?;
+    let mut best: Option<(f32, Lang)> = None;
+    for range in accept.split(',') {
+        let mut parts = range.split(';');
+        let Some(lang) = parts.next().and_then(Lang::from_tag) else {
+            continue;
+        };
+        let q = parts
+            .find_map(|p| p.trim().strip_prefix("q="))
+            .and_then(|q| q.parse().ok())
+            .unwrap_or(1.0);
+        // Ties go to the earlier entry
+        if q > 0.0 && best.is_none_or(|(best_q, _)| q > best_q) {
+            best = Some((q, lang));
+        }
+    }
+    best.map(|(_, lang)| lang)
+}
+
+/// Extracts the language to answer in.
+pub struct UserLang(pub Lang);
+
+#[async_trait]
+impl<D: DB + Send> FromRequestParts<Arc<Mutex<AppState<D>>>> for UserLang {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<Mutex<AppState<D>>>,
+    ) -> Result<UserLang, Self::Rejection> {
+        let s = state.lock().await;
+        let acting = parts.extensions.get().map(|Switched(p)| *p);
+        let setting = acting
+            .or(s.current_user)
+            .and_then(|u| s.db.get_user(u).ok())
+            .and_then(|u| u.settings.language);
+        Ok(UserLang(
+            setting
+                .or_else(|| negotiate(&parts.headers))
+                .unwrap_or_default(),
+        ))
+    }
+}
+
+pub enum Message {
+    Timeout {
+        secs: u64,
+    },
+    QuotaExceeded,
+    /// Over the daily API request quota.
+    RequestQuota,
+    Blocked,
+    Upstream,
+    /// Some fields of the request broke the rules below.
+    Invalid,
+    Required,
+    TooLong {
+        max: usize,
+    },
+    NotUrl,
+    UrlScheme,
+    /// Not a tenant slug.
+    Slug,
+    /// Not a Listen Notes podcast ID.
+    ListenNotesId,
+    OutOfRange {
+        min: usize,
+        max: usize,
+    },
+    IdleWarningSubject,
+    IdleWarning {
+        months: u32,
+        days: u32,
+        delete: bool,
+    },
+    /// What a voice assistant says as it starts an episode.
+    VoicePlaying {
+        episode: String,
+        podcast: String,
+    },
+    /// No subscription matched the podcast a voice assistant heard.
+    VoiceNoPodcast {
+        podcast: String,
+    },
+    VoiceNoEpisodes {
+        podcast: String,
+    },
+    VoiceQueueEmpty,
+}
+
+impl Message {
+    /// Stable identifier for clients to match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Message::Timeout { .. } => "timeout",
+            Message::QuotaExceeded => "quota_exceeded",
+            Message::RequestQuota => "request_quota",
+            Message::Blocked => "blocked",
+            Message::Upstream => "upstream",
+            Message::Invalid => "invalid",
+            Message::Required => "required",
+            Message::TooLong { .. } => "too_long",
+            Message::NotUrl => "not_url",
+            Message::UrlScheme => "url_scheme",
+            Message::Slug => "slug",
+            Message::ListenNotesId => "listennotes_id",
+            Message::OutOfRange { .. } => "out_of_range",
+            Message::IdleWarningSubject | Message::IdleWarning { .. } => "idle_warning",
+            Message::VoicePlaying { .. } => "voice_playing",
+            Message::VoiceNoPodcast { .. } => "voice_no_podcast",
+            Message::VoiceNoEpisodes { .. } => "voice_no_episodes",
+            Message::VoiceQueueEmpty => "voice_queue_empty",
+        }
+    }
+
+    pub fn text(&self, lang: Lang) -> String {
+        match (self, lang) {
+            (Message::IdleWarningSubject, Lang::En) => "Your podcast account is inactive".to_string(),
+            (Message::IdleWarningSubject, Lang::De) => "Dein Podcast-Konto ist inaktiv".to_string(),
+            (Message::IdleWarningSubject, Lang::Es) => "Tu cuenta de podcasts está inactiva".to_string(),
+            (Message::IdleWarningSubject, Lang::Fr) => "Votre compte de podcasts est inactif".to_string(),
+            (Message::IdleWarning { months, days, delete }, Lang::En) => format!(
+                "You haven't used your podcast account in {} months. Unless you log in within {} days, {}.",
+                months,
+                days,
+                if *delete {
+                    "it will be deleted along with your downloads"
+                } else {
+                    "your downloads will be removed; your subscriptions and history are kept"
+                }
+            ),
+            (Message::IdleWarning { months, days, delete }, Lang::De) => format!(
+                "Du hast dein Podcast-Konto seit {} Monaten nicht benutzt. Wenn du dich nicht innerhalb von {} Tagen anmeldest, {}.",
+                months,
+                days,
+                if *delete {
+                    "wird es mitsamt deinen Downloads gelöscht"
+                } else {
+                    "werden deine Downloads entfernt; Abos und Verlauf bleiben erhalten"
+                }
+            ),
+            (Message::IdleWarning { months, days, delete }, Lang::Es) => format!(
+                "No has usado tu cuenta de podcasts en {} meses. Si no inicias sesión en {} días, {}.",
+                months,
+                days,
+                if *delete {
+                    "se eliminará junto con tus descargas"
+                } else {
+                    "se borrarán tus descargas; se conservan tus suscripciones y tu historial"
+                }
+            ),
+            (Message::IdleWarning { months, days, delete }, Lang::Fr) => format!(
+                "Vous n'avez pas utilisé votre compte de podcasts depuis {} mois. Sans connexion d'ici {} jours, {}.",
+                months,
+                days,
+                if *delete {
+                    "il sera supprimé avec vos téléchargements"
+                } else {
+                    "vos téléchargements seront supprimés ; vos abonnements et votre historique sont conservés"
+                }
+            ),
+            (Message::Timeout { secs }, Lang::En) => {
+                format!("The request took longer than {} seconds.", secs)
+            }
+            (Message::Timeout { secs }, Lang::De) => {
+                format!("Die Anfrage hat länger als {} Sekunden gedauert.", secs)
+            }
+            (Message::Timeout { secs }, Lang::Es) => {
+                format!("La solicitud tardó más de {} segundos.", secs)
+            }
+            (Message::Timeout { secs }, Lang::Fr) => {
+                format!("La requête a pris plus de {} secondes.", secs)
+            }
+            (Message::QuotaExceeded, Lang::En) => {
+                "This download would exceed your storage quota.".to_string()
+            }
+            (Message::QuotaExceeded, Lang::De) => {
+                "Dieser Download würde dein Speicherkontingent überschreiten.".to_string()
+            }
+            (Message::QuotaExceeded, Lang::Es) => {
+                "Esta descarga superaría tu cuota de almacenamiento.".to_string()
+            }
+            (Message::QuotaExceeded, Lang::Fr) => {
+                "Ce téléchargement dépasserait votre quota de stockage.".to_string()
+            }
+            (Message::RequestQuota, Lang::En) => {
+                "You've used up today's API requests. They reset at midnight UTC.".to_string()
+            }
+            (Message::RequestQuota, Lang::De) => {
+                "Du hast die API-Anfragen für heute aufgebraucht. Sie werden um Mitternacht UTC zurückgesetzt."
+                    .to_string()
+            }
+            (Message::RequestQuota, Lang::Es) => {
+                "Has agotado las solicitudes a la API de hoy. Se reinician a medianoche UTC."
+                    .to_string()
+            }
+            (Message::RequestQuota, Lang::Fr) => {
+                "Vous avez épuisé les requêtes API d'aujourd'hui. Elles repartent à zéro à minuit UTC."
+                    .to_string()
+            }
+            (Message::Blocked, Lang::En) => {
+                "This address points at a private network and can't be fetched.".to_string()
+            }
+            (Message::Blocked, Lang::De) => {
+                "Diese Adresse zeigt auf ein privates Netzwerk und kann nicht abgerufen werden."
+                    .to_string()
+            }
+            (Message::Blocked, Lang::Es) => {
+                "Esta dirección apunta a una red privada y no se puede obtener.".to_string()
+            }
+            (Message::Blocked, Lang::Fr) => {
+                "Cette adresse pointe vers un réseau privé et ne peut pas être récupérée."
+                    .to_string()
+            }
+            (Message::Upstream, Lang::En) => "The podcast host couldn't be reached.".to_string(),
+            (Message::Upstream, Lang::De) => "Der Podcast-Host ist nicht erreichbar.".to_string(),
+            (Message::Upstream, Lang::Es) => {
+                "No se pudo contactar con el servidor del podcast.".to_string()
+            }
+            (Message::Upstream, Lang::Fr) => "L'hébergeur du podcast est injoignable.".to_string(),
+            (Message::Invalid, Lang::En) => "Some fields are invalid.".to_string(),
+            (Message::Invalid, Lang::De) => "Einige Felder sind ungültig.".to_string(),
+            (Message::Invalid, Lang::Es) => "Algunos campos no son válidos.".to_string(),
+            (Message::Invalid, Lang::Fr) => "Certains champs sont invalides.".to_string(),
+            (Message::Required, Lang::En) => "This can't be empty.".to_string(),
+            (Message::Required, Lang::De) => "Das darf nicht leer sein.".to_string(),
+            (Message::Required, Lang::Es) => "Esto no puede estar vacío.".to_string(),
+            (Message::Required, Lang::Fr) => "Ce champ ne peut pas être vide.".to_string(),
+            (Message::TooLong { max }, Lang::En) => format!("At most {} characters.", max),
+            (Message::TooLong { max }, Lang::De) => format!("Höchstens {} Zeichen.", max),
+            (Message::TooLong { max }, Lang::Es) => format!("Como máximo {} caracteres.", max),
+            (Message::TooLong { max }, Lang::Fr) => format!("{} caractères au maximum.", max),
+            (Message::NotUrl, Lang::En) => "This isn't a valid URL.".to_string(),
+            (Message::NotUrl, Lang::De) => "Das ist keine gültige URL.".to_string(),
+            (Message::NotUrl, Lang::Es) => "Esta URL no es válida.".to_string(),
+            (Message::NotUrl, Lang::Fr) => "Cette URL n'est pas valide.".to_string(),
+            (Message::UrlScheme, Lang::En) => "Only http and https URLs work.".to_string(),
+            (Message::UrlScheme, Lang::De) => "Nur http- und https-URLs gehen.".to_string(),
+            (Message::UrlScheme, Lang::Es) => "Solo sirven URLs http y https.".to_string(),
+            (Message::UrlScheme, Lang::Fr) => {
+                "Seules les URL http et https sont acceptées.".to_string()
+            }
+            (Message::Slug, Lang::En) => "Only lowercase letters, digits and dashes.".to_string(),
+            (Message::Slug, Lang::De) => "Nur Kleinbuchstaben, Ziffern und Bindestriche.".to_string(),
+            (Message::Slug, Lang::Es) => "Solo minúsculas, dígitos y guiones.".to_string(),
+            (Message::Slug, Lang::Fr) => {
+                "Uniquement des minuscules, des chiffres et des tirets.".to_string()
+            }
+            (Message::ListenNotesId, Lang::En) => "32 lowercase hex digits.".to_string(),
+            (Message::ListenNotesId, Lang::De) => {
+                "32 hexadezimale Ziffern in Kleinbuchstaben.".to_string()
+            }
+            (Message::ListenNotesId, Lang::Es) => "32 dígitos hexadecimales en minúscula.".to_string(),
+            (Message::ListenNotesId, Lang::Fr) => {
+                "32 chiffres hexadécimaux en minuscules.".to_string()
+            }
+            (Message::OutOfRange { min, max }, Lang::En) => {
+                format!("Must be between {} and {}.", min, max)
+            }
+            (Message::OutOfRange { min, max }, Lang::De) => {
+                format!("Muss zwischen {} und {} liegen.", min, max)
+            }
+            (Message::OutOfRange { min, max }, Lang::Es) => {
+                format!("Debe estar entre {} y {}.", min, max)
+            }
+            (Message::OutOfRange { min, max }, Lang::Fr) => {
+                format!("Doit être entre {} et {}.", min, max)
+            }
+            (Message::VoicePlaying { episode, podcast }, Lang::En) => {
+                format!("Playing {} from {}.", episode, podcast)
+            }
+            (Message::VoicePlaying { episode, podcast }, Lang::De) => {
+                format!("Ich spiele {} von {}.", episode, podcast)
+            }
+            (Message::VoicePlaying { episode, podcast }, Lang::Es) => {
+                format!("Reproduciendo {} de {}.", episode, podcast)
+            }
+            (Message::VoicePlaying { episode, podcast }, Lang::Fr) => {
+                format!("Lecture de {} de {}.", episode, podcast)
+            }
+            (Message::VoiceNoPodcast { podcast }, Lang::En) => {
+                format!("You're not subscribed to {}.", podcast)
+            }
+            (Message::VoiceNoPodcast { podcast }, Lang::De) => {
+                format!("Du hast {} nicht abonniert.", podcast)
+            }
+            (Message::VoiceNoPodcast { podcast }, Lang::Es) => {
+                format!("No estás suscrito a {}.", podcast)
+            }
+            (Message::VoiceNoPodcast { podcast }, Lang::Fr) => {
+                format!("Vous n'êtes pas abonné à {}.", podcast)
+            }
+            (Message::VoiceNoEpisodes { podcast }, Lang::En) => {
+                format!("{} has no episodes to play yet.", podcast)
+            }
+            (Message::VoiceNoEpisodes { podcast }, Lang::De) => {
+                format!("{} hat noch keine Folgen zum Abspielen.", podcast)
+            }
+            (Message::VoiceNoEpisodes { podcast }, Lang::Es) => {
+                format!("{} aún no tiene episodios para reproducir.", podcast)
+            }
+            (Message::VoiceNoEpisodes { podcast }, Lang::Fr) => {
+                format!("{} n'a pas encore d'épisode à lire.", podcast)
+            }
+            (Message::VoiceQueueEmpty, Lang::En) => "Your queue is empty.".to_string(),
+            (Message::VoiceQueueEmpty, Lang::De) => "Deine Warteschlange ist leer.".to_string(),
+            (Message::VoiceQueueEmpty, Lang::Es) => "Tu cola está vacía.".to_string(),
+            (Message::VoiceQueueEmpty, Lang::Fr) => "Votre file d'attente est vide.".to_string(),
+            // A language pods-types knows but nobody has translated yet
+            _ => self.text(Lang::En),
+        }
+    }
+}
+
+/// An error response with a translated `message` next to its code.
+pub fn error(status: StatusCode, message: Message, lang: Lang) -> Response {
+    let body = ApiError {
+        error: message.code().to_string(),
+        message: message.text(lang),
+        timeout_secs: None,
+        fields: vec![],
+    };
+    (status, Json(body)).into_response()
+}
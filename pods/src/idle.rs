@@ -0,0 +1,160 @@
+//! Flags accounts nobody has used in a while and, after a grace period,
+//! archives or deletes them.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::{Months, Utc};
+use serde::Deserialize;
+use tokio::{fs, sync::Mutex};
+
+use crate::{
+    i18n::{Lang, Message},
+    mail::Mailer,
+    media, profiles, AppState, DB,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct IdlePolicy {
+    /// Months without a logged in request before an account is flagged.
+    pub months: u32,
+    /// Email the user (if they have an address in their settings) when their
+    /// account is flagged for archiving or deletion.
+    pub warn: bool,
+    /// Days between flagging and `action`.
+    pub grace_days: u32,
+    pub action: IdleAction,
+}
+
+impl Default for IdlePolicy {
+    fn default() -> IdlePolicy {
+        IdlePolicy {
+            months: 12,
+            warn: true,
+            grace_days: 30,
+            action: IdleAction::Flag,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleAction {
+    /// Only flag, for admins to look at in the user listing.
+    Flag,
+    /// Delete the account's downloads but keep the account, subscriptions
+    /// and history. Using the account again lifts it.
+    Archive,
+    /// Delete the account and everything in it.
+    Delete,
+}
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub async fn worker<D: DB + Send + 'static>(state: Arc<Mutex<AppState<D>>>) {
+    let (policy, mailer) = {
+        let s = state.lock().await;
+        let Some(policy) = s.config.idle_accounts.clone() else {
+            return;
+        };
+        let mailer = s
+            .config
+            .mail
+            .as_ref()
+            .map(|m| Mailer::new(m).unwrap_or_else(|e| panic!("invalid mail config: {}", e)));
+        (policy, mailer)
+    };
+    loop {
+        let warnings = sweep(&state, &policy).await;
+        if let Some(mailer) = &mailer {
+            for (to, lang) in warnings {
+                let subject = Message::IdleWarningSubject.text(lang);
+                let body = Message::IdleWarning {
+                    months: policy.months,
+                    days: policy.grace_days,
+                    delete: policy.action == IdleAction::Delete,
+                }
+                .text(lang);
+                if let Err(e) = mailer.send(&to, &subject, body).await {
+                    eprintln!("idle account warning to {} failed: {}", to, e);
+                }
+            }
+        }
+        tokio::time::sleep(SWEEP_INTERVAL).await;
+    }
+}
+
+/// Applies the policy to every account, returning who to warn and in which
+/// language.
+async fn sweep<D: DB>(state: &Mutex<AppState<D>>, policy: &IdlePolicy) -> Vec<(String, Lang)> {
+    let s = &mut *state.lock().await;
+    let now = Utc::now();
+    let Some(cutoff) = now.checked_sub_months(Months::new(policy.months)) else {
+        return vec![];
+    };
+    let grace = chrono::Duration::days(policy.grace_days.into());
+    let Ok((_, users)) = s.db.users(None, 0, usize::MAX) else {
+        return vec![];
+    };
+
+    let mut warnings = vec![];
+    for mut u in users {
+        // Never lock out whoever runs the instance. Profiles go with their
+        // account.
+        if u.admin || u.profile_of.is_some() {
+            continue;
+        }
+        let idle = u.last_active.unwrap_or(u.created) < cutoff;
+        match u.flagged_idle {
+            Some(_) if !idle => {
+                for mut p in profiles::of(&s.db, u.id).unwrap_or_default() {
+                    p.archived = false;
+                    let _ = s.db.update_user(p);
+                }
+                u.flagged_idle = None;
+                u.archived = false;
+                let _ = s.db.update_user(u);
+            }
+            None if idle => {
+                u.flagged_idle = Some(now);
+                if policy.warn && policy.action != IdleAction::Flag {
+                    if let Some(email) = &u.settings.email {
+                        let lang = u.settings.language.unwrap_or_default();
+                        warnings.push((email.clone(), lang));
+                    }
+                }
+                let _ = s.db.update_user(u);
+            }
+            Some(flagged) if now - flagged >= grace && !u.archived => {
+                if policy.action == IdleAction::Flag {
+                    continue;
+                }
+                let mut members = profiles::of(&s.db, u.id).unwrap_or_default();
+                members.push(u);
+                for mut m in members {
+                    let dir = s.config.media_dir.join(m.id.to_string());
+                    let downloads = s.db.downloads_for_user(m.id).unwrap_or_default();
+                    match policy.action {
+                        IdleAction::Archive => {
+                            for d in &downloads {
+                                let _ = s.db.delete_download(d.id);
+                            }
+                            m.archived = true;
+                            let _ = s.db.update_user(m);
+                        }
+                        IdleAction::Delete => {
+                            let _ = s.db.delete_user(m.id);
+                        }
+                        IdleAction::Flag => unreachable!(),
+                    }
+                    for d in &downloads {
+                        media::release(&s.db, d).await;
+                    }
+                    let _ = fs::remove_dir_all(dir).await;
+                }
+            }
+            _ => {}
+        }
+    }
+    warnings
+}
@@ -0,0 +1,158 @@
+//! Triage of newly ingested episodes: each lands in its subscribers' inbox
+//! until they queue it, mark it played or dismiss it.
+
+use std::{cmp::Reverse, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Timelike, Utc};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::inbox::Inbox;
+
+use crate::{
+    explicit,
+    gpodder::{ActionKind, EpisodeAction},
+    AppState, Episode, Error, DB,
+};
+
+/// Puts episodes just added to `rss` in the inbox of everyone subscribed,
+/// and says who that was.
+pub fn deliver<D: DB>(db: &mut D, rss: &str, episodes: &[Episode]) -> Result<Vec<Uuid>, Error> {
+    let ids: Vec<Uuid> = episodes.iter().map(|e| e.id).collect();
+    let (_, users) = db.users(None, 0, usize::MAX)?;
+    let mut delivered = vec![];
+    for u in users
+        .into_iter()
+        .filter(|u| u.subscribed.iter().any(|s| s == rss))
+    {
+        db.add_to_inbox(u.id, ids.clone())?;
+        delivered.push(u.id);
+    }
+    Ok(delivered)
+}
+
+fn inbox<D: DB>(db: &D, user: Uuid, hide_explicit: bool) -> Result<Inbox, Error> {
+    let mut episodes: Vec<Episode> = db
+        .inbox(user)?
+        .into_iter()
+        .filter_map(|id| db.get_episode(id).ok())
+        .collect();
+    explicit::filter_episodes(db, hide_explicit, &mut episodes);
+    episodes.sort_by_key(|e| Reverse(e.published));
+    Ok(Inbox {
+        count: episodes.len(),
+        episodes,
+    })
+}
+
+pub async fn get_inbox<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    match explicit::hidden(&s, Some(uid)).and_then(|hide| inbox(&s.db, uid, hide)) {
+        Ok(i) => (StatusCode::OK, Json(Some(i))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+enum Triage {
+    Queue,
+    Played,
+    Dismiss,
+}
+
+/// Takes an episode out of the inbox, doing what `action` says with it, and
+/// responds with what's left.
+async fn triage<D: DB>(
+    state: &Mutex<AppState<D>>,
+    uid: Uuid,
+    episode: Uuid,
+    action: Triage,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    let hide = explicit::hidden(s, Some(uid));
+    let db = &mut s.db;
+    let result = db.get_episode(episode).and_then(|e| {
+        db.remove_from_inbox(uid, episode)?;
+        match action {
+            Triage::Queue => db.add_to_queue(uid, episode)?,
+            Triage::Played => {
+                // gPodder identifies episodes by their media URL
+                if let Some(enclosure) = e.enclosure {
+                    let played = EpisodeAction {
+                        podcast: e.podcast,
+                        episode: enclosure.url,
+                        device: None,
+                        action: ActionKind::Play,
+                        timestamp: Utc::now().naive_utc().with_nanosecond(0).unwrap(),
+                        started: None,
+                        position: None,
+                        total: None,
+                        completion: None,
+                        client: None,
+                        connection: None,
+                    };
+                    db.record_episode_actions(uid, vec![played])?;
+                }
+            }
+            Triage::Dismiss => {}
+        }
+        inbox(db, uid, hide?)
+    });
+    match result {
+        Ok(i) => (StatusCode::OK, Json(Some(i))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn queue<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((uid, episode)): Path<(Uuid, Uuid)>,
+) -> impl IntoResponse {
+    triage(&state, uid, episode, Triage::Queue).await
+}
+
+pub async fn played<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((uid, episode)): Path<(Uuid, Uuid)>,
+) -> impl IntoResponse {
+    triage(&state, uid, episode, Triage::Played).await
+}
+
+pub async fn dismiss<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((uid, episode)): Path<(Uuid, Uuid)>,
+) -> impl IntoResponse {
+    triage(&state, uid, episode, Triage::Dismiss).await
+}
+
+/// The user's up-next list, in the order episodes were queued.
+pub async fn get_queue<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let queue = explicit::hidden(&s, Some(uid)).and_then(|hide| {
+        let mut episodes: Vec<Episode> =
+            s.db.queue(uid)?
+                .into_iter()
+                .filter_map(|id| s.db.get_episode(id).ok())
+                .collect();
+        explicit::filter_episodes(&s.db, hide, &mut episodes);
+        Ok(episodes)
+    });
+    match queue {
+        Ok(q) => (StatusCode::OK, Json(Some(q))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
@@ -0,0 +1,64 @@
+//! Instance options an admin can change at runtime, stored in the DB over
+//! the config file.
+
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use tokio::sync::Mutex;
+
+pub use pods_types::instance::{
+    DiscoveryProvider, EffectiveSettings, InstanceSettings, InstanceSettingsReport,
+};
+
+use crate::{current_admin, AppState, Error, DB};
+
+/// The stored overrides applied over the config file.
+pub fn effective<D: DB>(state: &AppState<D>) -> Result<EffectiveSettings, Error> {
+    let o = state.db.instance_settings()?;
+    let c = &state.config;
+    Ok(EffectiveSettings {
+        registration_open: o.registration_open.unwrap_or(c.registration_open),
+        refresh_interval_mins: o.refresh_interval_mins.unwrap_or(c.refresh_interval_mins),
+        max_feed_bytes: o.max_feed_bytes.unwrap_or(c.max_feed_bytes),
+        discovery: o.discovery.unwrap_or_else(|| c.discovery.clone()),
+        hide_explicit: o.hide_explicit.unwrap_or(c.hide_explicit),
+    })
+}
+
+fn report<D: DB>(state: &AppState<D>) -> Result<InstanceSettingsReport, Error> {
+    Ok(InstanceSettingsReport {
+        overrides: state.db.instance_settings()?,
+        effective: effective(state)?,
+    })
+}
+
+pub async fn get_settings<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    if let Err(status) = current_admin(&s) {
+        return (status, Json(None));
+    }
+    match report(&s) {
+        Ok(r) => (StatusCode::OK, Json(Some(r))),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Replaces the stored overrides; `null` fields go back to the config file.
+pub async fn put_settings<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Json(settings): Json<InstanceSettings>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    if let Err(status) = current_admin(s) {
+        return (status, Json(None));
+    }
+    if settings.refresh_interval_mins == Some(0) {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    }
+    match s.db.set_instance_settings(settings).and_then(|_| report(s)) {
+        Ok(r) => (StatusCode::OK, Json(Some(r))),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
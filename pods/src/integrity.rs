@@ -0,0 +1,167 @@
+use std::{path::Path, sync::Arc};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use tokio::{fs, io::AsyncReadExt, sync::Mutex};
+
+pub use pods_types::integrity::{Integrity, VerifyReport};
+
+use crate::{current_admin, downloads::DownloadStatus, AppState, DB};
+
+/// Digests of a file's contents, covering the algorithms SRI strings use.
+struct Digests {
+    sha256: Vec<u8>,
+    sha384: Vec<u8>,
+    sha512: Vec<u8>,
+    len: u64,
+}
+
+async fn digest(path: &Path) -> std::io::Result<Digests> {
+    let mut file = fs::File::open(path).await?;
+    let (mut a, mut b, mut c) = (Sha256::new(), Sha384::new(), Sha512::new());
+    let mut buf = vec![0; 64 * 1024];
+    let mut len = 0;
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        a.update(&buf[..n]);
+        b.update(&buf[..n]);
+        c.update(&buf[..n]);
+        len += n as u64;
+    }
+    Ok(Digests {
+        sha256: a.finalize().to_vec(),
+        sha384: b.finalize().to_vec(),
+        sha512: c.finalize().to_vec(),
+        len,
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Checks a Subresource Integrity string (`sha384-<base64> ...`) as used by
+/// `<podcast:integrity type="sri">`. `None` if it has no algorithm we know.
+fn sri_matches(sri: &str, d: &Digests) -> Option<bool> {
+    let mut known = false;
+    for hash in sri.split_whitespace() {
+        let Some((algo, value)) = hash.split_once('-') else {
+            continue;
+        };
+        let ours = match algo {
+            "sha256" => &d.sha256,
+            "sha384" => &d.sha384,
+            "sha512" => &d.sha512,
+            _ => continue,
+        };
+        known = true;
+        if STANDARD.decode(value).is_ok_and(|v| &v == ours) {
+            return Some(true);
+        }
+    }
+    known.then_some(false)
+}
+
+/// Verifies a freshly downloaded file against what the feed told us.
+/// Returns our own hex SHA-256 alongside the result.
+pub async fn verify_download(
+    path: &Path,
+    expected_len: Option<u64>,
+    sri: Option<&str>,
+) -> std::io::Result<(String, Integrity)> {
+    let d = digest(path).await?;
+    let integrity = if sri.and_then(|s| sri_matches(s, &d)) == Some(false) {
+        Integrity::HashMismatch
+    } else if expected_len.is_some_and(|l| l > 0 && l != d.len) {
+        Integrity::LengthMismatch
+    } else {
+        Integrity::Ok
+    };
+    Ok((hex(&d.sha256), integrity))
+}
+
+/// Re-checks a stored file against the length and hash we recorded.
+async fn reverify(path: &Path, len: u64, sha256: Option<&str>) -> Integrity {
+    match digest(path).await {
+        Err(_) => Integrity::Missing,
+        Ok(d) if d.len != len => Integrity::LengthMismatch,
+        Ok(d) if sha256.is_some_and(|h| h != hex(&d.sha256)) => Integrity::HashMismatch,
+        Ok(_) => Integrity::Ok,
+    }
+}
+
+/// Starts re-verifying every completed download in the background.
+pub async fn start_verify<D: DB + Send + 'static>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+) -> impl IntoResponse {
+    let s = &mut state.lock().await;
+    if let Err(status) = current_admin(s) {
+        return (status, Json(None));
+    }
+    if s.verify_report
+        .as_ref()
+        .is_some_and(|r| r.finished.is_none())
+    {
+        return (StatusCode::CONFLICT, Json(s.verify_report.clone()));
+    }
+    let report = VerifyReport {
+        started: Utc::now(),
+        finished: None,
+        checked: 0,
+        ok: 0,
+        problems: vec![],
+    };
+    s.verify_report = Some(report.clone());
+    tokio::spawn(verify_all(state.clone()));
+    (StatusCode::ACCEPTED, Json(Some(report)))
+}
+
+pub async fn verify_status<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    if let Err(status) = current_admin(&s) {
+        return (status, Json(None));
+    }
+    match &s.verify_report {
+        Some(r) => (StatusCode::OK, Json(Some(r.clone()))),
+        None => (StatusCode::NOT_FOUND, Json(None)),
+    }
+}
+
+async fn verify_all<D: DB>(state: Arc<Mutex<AppState<D>>>) {
+    let downloads = state.lock().await.db.all_downloads();
+    for d in downloads {
+        let Some(path) = d.path.clone().filter(|_| d.status == DownloadStatus::Done) else {
+            continue;
+        };
+        let integrity = reverify(&path, d.bytes, d.sha256.as_deref()).await;
+
+        let s = &mut state.lock().await;
+        // It may have been deleted, moved or re-downloaded while this hashed
+        // it; the result is only about the file that was hashed
+        let Ok(mut fresh) = s.db.get_download(d.id) else {
+            continue;
+        };
+        if fresh.status != DownloadStatus::Done || fresh.path.as_ref() != Some(&path) {
+            continue;
+        }
+        fresh.integrity = Some(integrity);
+        if let Some(r) = s.verify_report.as_mut() {
+            r.checked += 1;
+            match integrity {
+                Integrity::Ok => r.ok += 1,
+                i => r.problems.push((d.id, i)),
+            }
+        }
+        let _ = s.db.save_download(fresh);
+    }
+    if let Some(r) = state.lock().await.verify_report.as_mut() {
+        r.finished = Some(Utc::now());
+    }
+}
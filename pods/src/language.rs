@@ -0,0 +1,120 @@
+//! Podcast languages as lowercase BCP 47 tags (`en`, `pt-br`): read from
+//! feeds and directories, guessed from the text when a feed doesn't say, and
+//! matched against `?lang=` filters.
+
+use whatlang::Lang;
+
+/// ISO 639-1 codes for the languages whatlang can tell apart.
+const CODES: &[(Lang, &str)] = &[
+    (Lang::Afr, "af"),
+    (Lang::Aka, "ak"),
+    (Lang::Amh, "am"),
+    (Lang::Ara, "ar"),
+    (Lang::Aze, "az"),
+    (Lang::Bel, "be"),
+    (Lang::Ben, "bn"),
+    (Lang::Bul, "bg"),
+    (Lang::Cat, "ca"),
+    (Lang::Ces, "cs"),
+    (Lang::Cmn, "zh"),
+    (Lang::Cym, "cy"),
+    (Lang::Dan, "da"),
+    (Lang::Deu, "de"),
+    (Lang::Ell, "el"),
+    (Lang::Eng, "en"),
+    (Lang::Epo, "eo"),
+    (Lang::Est, "et"),
+    (Lang::Fin, "fi"),
+    (Lang::Fra, "fr"),
+    (Lang::Guj, "gu"),
+    (Lang::Heb, "he"),
+    (Lang::Hin, "hi"),
+    (Lang::Hrv, "hr"),
+    (Lang::Hun, "hu"),
+    (Lang::Hye, "hy"),
+    (Lang::Ind, "id"),
+    (Lang::Ita, "it"),
+    (Lang::Jav, "jv"),
+    (Lang::Jpn, "ja"),
+    (Lang::Kan, "kn"),
+    (Lang::Kat, "ka"),
+    (Lang::Khm, "km"),
+    (Lang::Kor, "ko"),
+    (Lang::Lat, "la"),
+    (Lang::Lav, "lv"),
+    (Lang::Lit, "lt"),
+    (Lang::Mal, "ml"),
+    (Lang::Mar, "mr"),
+    (Lang::Mkd, "mk"),
+    (Lang::Mya, "my"),
+    (Lang::Nep, "ne"),
+    (Lang::Nld, "nl"),
+    (Lang::Nob, "nb"),
+    (Lang::Ori, "or"),
+    (Lang::Pan, "pa"),
+    (Lang::Pes, "fa"),
+    (Lang::Pol, "pl"),
+    (Lang::Por, "pt"),
+    (Lang::Ron, "ro"),
+    (Lang::Rus, "ru"),
+    (Lang::Sin, "si"),
+    (Lang::Slk, "sk"),
+    (Lang::Slv, "sl"),
+    (Lang::Sna, "sn"),
+    (Lang::Spa, "es"),
+    (Lang::Srp, "sr"),
+    (Lang::Swe, "sv"),
+    (Lang::Tam, "ta"),
+    (Lang::Tel, "te"),
+    (Lang::Tgl, "tl"),
+    (Lang::Tha, "th"),
+    (Lang::Tuk, "tk"),
+    (Lang::Tur, "tr"),
+    (Lang::Ukr, "uk"),
+    (Lang::Urd, "ur"),
+    (Lang::Uzb, "uz"),
+    (Lang::Vie, "vi"),
+    (Lang::Yid, "yi"),
+    (Lang::Zul, "zu"),
+];
+
+/// A language as feeds and directories write it: a tag in any case, maybe
+/// with `_` for `-`, or an English name like `English`.
+pub fn normalize(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    if raw.len() > 3 && raw.chars().all(|c| c.is_ascii_alphabetic()) {
+        return CODES
+            .iter()
+            .find(|(lang, _)| lang.eng_name().eq_ignore_ascii_case(raw))
+            .map(|(_, code)| code.to_string());
+    }
+    let tag = raw.to_ascii_lowercase().replace('_', "-");
+    let valid = tag
+        .split('-')
+        .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()));
+    valid.then_some(tag)
+}
+
+/// The language of some text, if whatlang is confident about it.
+pub fn detect(text: &str) -> Option<String> {
+    let info = whatlang::detect(text).filter(|i| i.is_reliable())?;
+    CODES
+        .iter()
+        .find(|(lang, _)| *lang == info.lang())
+        .map(|(_, code)| code.to_string())
+}
+
+/// A `?lang=` filter, normalized. `Err` if it isn't a language.
+pub fn filter(lang: Option<&str>) -> Result<Option<String>, ()> {
+    lang.map(|l| normalize(l).ok_or(())).transpose()
+}
+
+/// Whether a podcast in `language` passes a normalized `filter`: the same
+/// tag, or one of its regional variants. Podcasts of unknown language pass.
+pub fn matches(filter: &str, language: Option<&str>) -> bool {
+    language.is_none_or(|l| {
+        l == filter
+            || l.strip_prefix(filter)
+                .is_some_and(|rest| rest.starts_with('-'))
+    })
+}
@@ -0,0 +1,2049 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    str::FromStr,
+    sync::Arc,
+    time::Instant,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, Request, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Json, Router, ServiceExt,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use tokio::{
+    sync::{Mutex, Notify},
+    task::JoinHandle,
+};
+use tower::Layer;
+use uuid::Uuid;
+
+pub use pods_types::{CreateUser, DbStats, Enclosure, Episode, PodcastChannel, User};
+
+mod access_log;
+mod admin;
+mod annotations;
+mod archive;
+mod bandwidth;
+mod bundles;
+mod chapters;
+mod config;
+mod dates;
+mod discovery;
+mod dlna;
+mod downloads;
+mod engagement;
+mod error_reporting;
+mod explicit;
+mod federation;
+mod fetcher;
+mod gpodder;
+mod health;
+mod i18n;
+mod idle;
+mod inbox;
+mod instance;
+mod integrity;
+mod language;
+mod mail;
+mod maintenance;
+mod media;
+mod media_links;
+mod merge;
+mod metrics_export;
+mod negotiation;
+mod poll;
+mod profiles;
+mod public;
+mod quota;
+mod refresh;
+mod request_quota;
+mod resume;
+mod retention;
+mod scrobble;
+mod service_accounts;
+mod settings;
+mod ssrf;
+mod stats;
+mod stream;
+mod stream_cache;
+mod subscriptions;
+mod tags;
+mod tenancy;
+mod timeout;
+mod torrent;
+mod transcode;
+mod transcription;
+mod validation;
+mod voice;
+
+use access_log::AccessLog;
+use annotations::Annotation;
+use archive::{Archive, FeedSnapshot};
+use bandwidth::Throttle;
+use bundles::Bundle;
+use config::Config;
+use downloads::{Download, DownloadStatus};
+use error_reporting::Reporter;
+use fetcher::{Fetcher, TracedError};
+use gpodder::EpisodeAction;
+use health::{FeedFetch, FeedWarning, FetchOutcome, Redirect};
+use instance::{DiscoveryProvider, InstanceSettings};
+use integrity::VerifyReport;
+use maintenance::MaintenanceReport;
+use negotiation::{Format, Negotiated};
+use pods_types::{EpisodeFilter, Subscribe, SubscribeQuery, Subscribed, Today, UserStatus};
+use profiles::{Acting, Switched};
+use refresh::{RefreshRun, RefreshRunReport};
+use retention::Tombstone;
+use scrobble::{ScrobbleTarget, Scrobbler};
+use service_accounts::ServiceAccount;
+use settings::UserSettings;
+use stream_cache::StreamCache;
+use subscriptions::SubscriptionOverride;
+use tags::EmbeddedTags;
+use tenancy::Tenants;
+use transcode::Transcoder;
+use transcription::{Transcript, TranscriptHit, TranscriptionJob};
+use validation::Valid;
+
+#[derive(Clone)]
+struct AppState<D: DB> {
+    current_user: Option<Uuid>,
+    db: D,
+    config: Config,
+    download_notify: Arc<Notify>,
+    transcription_notify: Arc<Notify>,
+    /// Client for all outbound fetches, set up from `config.fetch`.
+    http: Fetcher,
+    download_throttle: Throttle,
+    stream_throttle: Throttle,
+    stream_cache: Arc<StreamCache>,
+    transcoder: Arc<Transcoder>,
+    /// Progress of the latest media re-verification job.
+    verify_report: Option<VerifyReport>,
+    /// What the latest nightly maintenance run did.
+    maintenance: Option<MaintenanceReport>,
+    /// What `GET /poll` answers with.
+    events: poll::Events,
+    /// Offline bundles until they expire, by ID.
+    bundles: HashMap<Uuid, Bundle>,
+    /// The tenants, on the main instance when tenancy is on.
+    tenants: Option<Arc<Tenants>>,
+}
+
+fn routes(config: &Config) -> Router<Arc<Mutex<AppState<InMemoryStore>>>> {
+    let mut router = Router::new()
+        .route("/", get(handler))
+        .route("/users", post(add_user))
+        .route("/users/:id", get(get_user))
+        .route(
+            "/users/:id/episode_actions",
+            post(gpodder::upload_episode_actions),
+        )
+        .route("/users/:id/podcasts", get(get_subscriptions))
+        .route("/users/:id/podcasts/order", put(subscriptions::reorder))
+        .route(
+            "/users/:id/podcasts/:podcast",
+            put(subscriptions::set_override),
+        )
+        .route(
+            "/users/:id/podcasts/:podcast/downloads",
+            post(downloads::download_latest),
+        )
+        .route("/users/:id/today", get(get_today))
+        .route("/users/:id/stats", get(stats::get_stats))
+        .route(
+            "/users/:id/episodes/:episode/resume",
+            get(resume::get_resume),
+        )
+        .route(
+            "/users/:id/episodes/:episode/playback",
+            get(resume::get_playback),
+        )
+        .route(
+            "/users/:id/episodes/:episode/chapters",
+            get(chapters::get_chapters),
+        )
+        .route(
+            "/users/:id/profiles",
+            get(profiles::list).post(profiles::create),
+        )
+        .route("/users/:id/profiles/:profile", delete(profiles::delete))
+        .route("/users/:id/inbox", get(inbox::get_inbox))
+        .route("/users/:id/inbox/:episode/queue", post(inbox::queue))
+        .route("/users/:id/inbox/:episode/played", post(inbox::played))
+        .route("/users/:id/inbox/:episode/dismiss", post(inbox::dismiss))
+        .route("/users/:id/queue", get(inbox::get_queue))
+        .route("/poll", get(poll::poll))
+        .route("/federation/catalog", get(federation::catalog))
+        .route("/users/:id/export/gpodder", get(gpodder::export_gpodder))
+        .route("/export/bundle", post(bundles::create))
+        .route("/export/bundle/:id", get(bundles::status))
+        .route("/export/bundle/:id/download", get(bundles::download))
+        .route(
+            "/users/:id/export/markdown",
+            get(annotations::export_markdown),
+        )
+        .route(
+            "/users/:id/episodes/:episode/annotations",
+            get(annotations::list).post(annotations::create),
+        )
+        .route(
+            "/users/:id/annotations/:annotation",
+            delete(annotations::delete),
+        )
+        .route(
+            "/users/:id/downloads",
+            get(downloads::list).post(downloads::enqueue),
+        )
+        .route("/users/:id/usage", get(quota::get_usage))
+        .route("/users/:id/quota", get(request_quota::get_quota))
+        .route(
+            "/users/:id/settings",
+            get(settings::get_settings).put(settings::put_settings),
+        )
+        .route(
+            "/users/:id/scrobbler",
+            get(scrobble::get_scrobbler)
+                .put(scrobble::put_scrobbler)
+                .delete(scrobble::delete_scrobbler),
+        )
+        .route("/admin/users/:id/quota", put(quota::set_override))
+        .route("/admin/users", get(admin::users))
+        .route("/admin/users/:id/merge", post(merge::merge_user))
+        .route("/admin/storage", get(admin::storage))
+        .route("/admin/podcasts", post(service_accounts::add_podcast))
+        .route(
+            "/admin/service_accounts",
+            get(service_accounts::list).post(service_accounts::create),
+        )
+        .route(
+            "/admin/service_accounts/:id",
+            delete(service_accounts::delete),
+        )
+        .route("/admin/engagement", get(engagement::list))
+        .route("/admin/podcasts/:id/engagement", get(engagement::podcast))
+        .route(
+            "/admin/settings",
+            get(instance::get_settings).put(instance::put_settings),
+        )
+        .route("/admin/refresh-runs", get(refresh::runs))
+        .route("/admin/refresh-runs/:id", get(refresh::run))
+        .route(
+            "/admin/podcasts/:id/refresh",
+            get(refresh::get_schedule).put(refresh::put_schedule),
+        )
+        .route(
+            "/admin/podcasts/:id/archive",
+            get(archive::get_archive).put(archive::put_archive),
+        )
+        .route(
+            "/admin/podcasts/:id/archive/snapshots/:snapshot",
+            get(archive::get_snapshot),
+        )
+        .route(
+            "/admin/podcasts/:id/archive/torrent",
+            get(torrent::get_torrent),
+        )
+        .route(
+            "/admin/media/verify",
+            get(integrity::verify_status).post(integrity::start_verify),
+        )
+        .route(
+            "/admin/episodes/:id/transcribe",
+            post(transcription::transcribe_episode),
+        )
+        .route(
+            "/admin/podcasts/:id/transcribe",
+            post(transcription::transcribe_podcast),
+        )
+        .route("/admin/transcriptions", get(transcription::jobs))
+        .route("/login", get(user_status))
+        .route("/login/:id", post(login))
+        .route("/podcast", post(subscribe_to_podcast))
+        .route("/podcasts/:id/episodes", get(get_episodes))
+        .route("/podcasts/:id/health", get(health::get_health))
+        .route("/episodes/:id/audio", get(stream::audio))
+        .route("/episodes/:id/audio/link", post(media_links::create))
+        .route("/episodes/:id/tags", get(tags::get_tags))
+        .route("/episodes/:id/artwork", get(tags::artwork))
+        .route("/media/:sha256", get(stream::blob))
+        .route("/media/:sha256/artwork", get(tags::blob_artwork))
+        .route("/webseed/:id/*path", get(torrent::webseed))
+        .route("/voice", post(voice::webhook))
+        .route(
+            "/episodes/:id/transcript",
+            get(transcription::get_transcript),
+        )
+        .route("/transcripts/search", get(transcription::search))
+        .route("/discover/search", get(discovery::search))
+        .route("/discover/trending", get(discovery::trending));
+    if let Some(public) = &config.public_api {
+        router = router.nest("/public", public::routes(public));
+    }
+    if config.dlna.is_some() {
+        router = router
+            .route("/dlna/description.xml", get(dlna::description))
+            .route("/dlna/ContentDirectory.xml", get(dlna::content_directory))
+            .route("/dlna/ConnectionManager.xml", get(dlna::connection_manager))
+            .route(
+                "/dlna/control/ContentDirectory",
+                post(dlna::control_content_directory),
+            )
+            .route(
+                "/dlna/control/ConnectionManager",
+                post(dlna::control_connection_manager),
+            );
+    }
+    if config.tenancy.is_some() {
+        router = router
+            .route("/admin/tenants", get(tenancy::list).post(tenancy::create))
+            .route(
+                "/admin/tenants/:slug",
+                get(tenancy::get).delete(tenancy::delete),
+            );
+    }
+    router.route_layer(middleware::from_fn_with_state(
+        Arc::new(config.timeouts.clone()),
+        timeout::enforce,
+    ))
+}
+
+/// Loads the config and serves the API until the process is stopped.
+pub async fn run() {
+    let config = Config::load();
+    let listen = config.listen.parse().unwrap();
+    let server = config.server.clone();
+    let access_log = config.access_log.as_ref().map(|c| {
+        Arc::new(AccessLog::open(c).unwrap_or_else(|e| panic!("can't open access log: {}", e)))
+    });
+    let reporter = config.error_reporting.as_ref().map(|c| {
+        Arc::new(Reporter::new(c).unwrap_or_else(|e| panic!("invalid error reporting DSN: {}", e)))
+    });
+    let tenants = config.tenancy.clone().map(|t| {
+        Arc::new(Tenants::new(
+            t,
+            config.clone(),
+            access_log.clone(),
+            reporter.clone(),
+        ))
+    });
+    let state = new_state(config, tenants.clone());
+    spawn_workers(&state);
+    let app = app(state, access_log, reporter).await;
+    // Outermost, so a tenant's requests get its own copy of all the above
+    let app = middleware::from_fn_with_state(tenants, tenancy::route).layer(app);
+
+    let secs = |s: u64| std::time::Duration::from_secs(s);
+    axum::Server::bind(&listen)
+        .http1_keepalive(server.keep_alive)
+        .http1_only(!server.http2)
+        .tcp_keepalive(server.tcp_keepalive_secs.map(secs))
+        .http2_keep_alive_interval(server.http2_keep_alive_interval_secs.map(secs))
+        .http2_keep_alive_timeout(secs(server.http2_keep_alive_timeout_secs))
+        .http2_max_concurrent_streams(server.http2_max_concurrent_streams)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
+}
+
+/// An instance with nothing stored yet.
+fn new_state(config: Config, tenants: Option<Arc<Tenants>>) -> Arc<Mutex<AppState<InMemoryStore>>> {
+    Arc::new(Mutex::new(AppState {
+        db: InMemoryStore::new(),
+        current_user: None,
+        download_throttle: Throttle::new(config.bandwidth.download),
+        stream_throttle: Throttle::new(config.bandwidth.stream),
+        stream_cache: Arc::new(StreamCache::new(config.stream_cache.clone())),
+        transcoder: Arc::new(Transcoder::new(config.transcode.clone())),
+        http: Fetcher::new(&config.fetch),
+        config,
+        download_notify: Arc::new(Notify::new()),
+        transcription_notify: Arc::new(Notify::new()),
+        verify_report: None,
+        maintenance: None,
+        events: poll::Events::default(),
+        bundles: HashMap::new(),
+        tenants,
+    }))
+}
+
+/// Starts the instance's background work, returning the tasks so a
+/// tenant's can be stopped.
+fn spawn_workers(state: &Arc<Mutex<AppState<InMemoryStore>>>) -> Vec<JoinHandle<()>> {
+    vec![
+        tokio::spawn(archive::worker(state.clone())),
+        tokio::spawn(dlna::worker(state.clone())),
+        tokio::spawn(downloads::worker(state.clone())),
+        tokio::spawn(federation::worker(state.clone())),
+        tokio::spawn(idle::worker(state.clone())),
+        tokio::spawn(maintenance::worker(state.clone())),
+        tokio::spawn(metrics_export::worker(state.clone())),
+        tokio::spawn(refresh::worker(state.clone())),
+        tokio::spawn(retention::worker(state.clone())),
+        tokio::spawn(scrobble::worker(state.clone())),
+        tokio::spawn(transcription::worker(state.clone())),
+    ]
+}
+
+/// The API over `state`, with its middleware.
+async fn app(
+    state: Arc<Mutex<AppState<InMemoryStore>>>,
+    access_log: Option<Arc<AccessLog>>,
+    reporter: Option<Arc<Reporter>>,
+) -> Router {
+    let mut routes = routes(&state.lock().await.config);
+    // Inside authentication, which says which service account is calling
+    routes = routes.route_layer(middleware::from_fn_with_state(
+        state.clone(),
+        request_quota::enforce,
+    ));
+    // Per route, since scopes are checked against the matched pattern
+    routes = routes.route_layer(middleware::from_fn_with_state(
+        state.clone(),
+        service_accounts::authenticate,
+    ));
+    routes = routes.layer(middleware::from_fn_with_state(
+        state.clone(),
+        track_activity,
+    ));
+    if let Some(reporter) = reporter {
+        routes = routes.layer(middleware::from_fn_with_state(
+            (reporter, state.clone()),
+            error_reporting::report,
+        ));
+    }
+    if let Some(log) = access_log {
+        routes = routes.layer(middleware::from_fn_with_state(
+            (log, state.clone()),
+            access_log::log,
+        ));
+    }
+    // build our application with a route
+    let routes = routes.with_state(state.clone());
+    // Outside the router, since switching profiles rewrites the path
+    let app = middleware::from_fn_with_state(state, profiles::switch).layer(routes);
+    Router::new().fallback_service(app)
+}
+
+async fn handler() -> Json<&'static str> {
+    Json("hello world")
+}
+
+async fn add_user<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Valid(Json(payload)): Valid<Json<CreateUser>>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    // Closing registration still lets the first user (the admin) in
+    let open = instance::effective(s).map(|i| i.registration_open);
+    let first = s.db.stats().map(|d| d.users == 0);
+    match (open, first) {
+        (Ok(true), _) | (_, Ok(true)) => {}
+        _ if current_admin(s).is_ok() => {}
+        (Err(_), _) | (_, Err(_)) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+        _ => return (StatusCode::FORBIDDEN, Json(None)),
+    }
+    let user = s.db.create_user(payload).unwrap();
+    // Presumably store somewhere?
+    (StatusCode::CREATED, Json(Some(user)))
+}
+
+async fn get_user<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> impl IntoResponse {
+    let x = state.lock().await.db.get_user(uid);
+    // (StatusCode::OK, Json(x))
+    match x {
+        Ok(u) => (StatusCode::OK, Json(Some(u))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+async fn get_subscriptions<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+    format: Format,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let listed = explicit::hidden(&s, Some(uid)).and_then(|hide| {
+        let mut podcasts = subscriptions(&s.db, uid)?;
+        explicit::filter_podcasts(hide, &mut podcasts);
+        Ok(podcasts)
+    });
+    match listed {
+        Ok(p) => (StatusCode::OK, Negotiated(format, Some(p))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Negotiated(format, None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Negotiated(format, None)),
+    }
+}
+
+/// The podcasts a user is subscribed to, with their overrides applied, in
+/// their preferred order.
+pub fn subscriptions<D: DB>(db: &D, user: Uuid) -> Result<Vec<PodcastChannel>, Error> {
+    let u = db.get_user(user)?;
+    let mut podcasts = u
+        .subscribed
+        .iter()
+        .map(|rss| {
+            let channel = db.get_podcast(rss.clone())?;
+            Ok(match db.subscription_override(user, rss.clone())? {
+                Some(o) => o.apply(channel),
+                None => channel,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    subscriptions::sort(db, &u, &mut podcasts)?;
+    Ok(podcasts)
+}
+
+async fn login<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> impl IntoResponse {
+    let mut s = state.lock().await;
+    match s.db.get_user(uid) {
+        // Profiles are switched to, not logged in to
+        Ok(u) if u.profile_of.is_some() => StatusCode::FORBIDDEN,
+        Ok(u) => {
+            s.current_user = Some(u.id);
+            StatusCode::OK
+        }
+        Err(_) => StatusCode::NOT_EXTENDED,
+    }
+}
+
+/// The logged in user, if they are an admin.
+fn current_admin<D: DB>(state: &AppState<D>) -> Result<Uuid, StatusCode> {
+    let uid = state.current_user.ok_or(StatusCode::UNAUTHORIZED)?;
+    match state.db.get_user(uid) {
+        Ok(u) if u.admin => Ok(uid),
+        _ => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/// Stamps the logged in user's `last_active` after each request, and the
+/// profile's if it switched to one.
+async fn track_activity<D: DB, B>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let profile = req.extensions().get::<Switched>().copied();
+    let resp = next.run(req).await;
+    let s = &mut *state.lock().await;
+    let now = Utc::now();
+    if let Some(uid) = s.current_user {
+        let _ = s.db.touch_user(uid, now);
+    }
+    if let Some(Switched(uid)) = profile {
+        let _ = s.db.touch_user(uid, now);
+    }
+    resp
+}
+
+async fn user_status<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    profile: Option<axum::Extension<Switched>>,
+) -> impl IntoResponse {
+    let profile = profile.map(|axum::Extension(Switched(p))| p);
+    match &state.lock().await.current_user {
+        Some(u) => Json(UserStatus {
+            user: Some(*u),
+            logged_in: true,
+            profile,
+        }),
+        None => Json(UserStatus {
+            user: None,
+            logged_in: false,
+            profile,
+        }),
+    }
+}
+
+async fn subscribe_to_podcast<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Acting(logged_in): Acting,
+    Valid(Query(options)): Valid<Query<SubscribeQuery>>,
+    Valid(Json(req)): Valid<Json<Subscribe>>,
+) -> impl IntoResponse {
+    let rss = match req {
+        Subscribe::Rss { rss } => Ok(rss),
+        Subscribe::Itunes { itunes_id } => {
+            let id = itunes_id.to_string();
+            discovery::feed_url(&state, DiscoveryProvider::Itunes, &id).await
+        }
+        Subscribe::PodcastIndex { podcastindex_id } => {
+            let id = podcastindex_id.to_string();
+            discovery::feed_url(&state, DiscoveryProvider::PodcastIndex, &id).await
+        }
+        Subscribe::ListenNotes { listennotes_id } => {
+            discovery::feed_url(&state, DiscoveryProvider::ListenNotes, &listennotes_id).await
+        }
+        _ => Err(Error::Unavailable),
+    };
+    let rss = match rss {
+        Ok(rss) => rss,
+        Err(Error::NotFound) => return (StatusCode::NOT_FOUND, Json(None)),
+        Err(Error::Unavailable) => return (StatusCode::BAD_REQUEST, Json(None)),
+        Err(_) => return (StatusCode::BAD_GATEWAY, Json(None)),
+    };
+    match Uri::from_str(&rss) {
+        Ok(url) => {
+            let state = &mut *state.lock().await;
+            let http = state.http.clone();
+            let max_bytes = match instance::effective(state) {
+                Ok(i) => i.max_feed_bytes,
+                Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+            };
+            let db = &mut state.db;
+
+            match db.get_podcast(rss) {
+                Ok(p) => {
+                    if let Some(u) = logged_in {
+                        let subs = db.subscribe(u, p.rss.clone());
+                        match subs {
+                            Ok(s) => {
+                                let warnings = db.feed_warnings(p.rss.clone()).unwrap_or_default();
+                                bootstrap(state, u, &p.rss, options.download_latest);
+                                let subscribed = Subscribed {
+                                    podcast: p,
+                                    subscriptions: s,
+                                    warnings,
+                                };
+                                (StatusCode::CREATED, Json(Some(subscribed)))
+                            }
+                            Err(_) => (StatusCode::BAD_REQUEST, Json(None))
+                        }
+                    } else {
+                        (StatusCode::NOT_FOUND, Json(None))
+                    }
+                },
+                Err(Error::NotFound) => {
+                    // Podcast not found, so let's create it
+                    let (fetched, report) =
+                        parse_rss(&http, url.to_string(), max_bytes, None).await;
+                    let created = fetched.and_then(|feed| {
+                        db.create_podcast(
+                            url.to_string(),
+                            feed.title,
+                            feed.description,
+                            feed.artwork,
+                            feed.language,
+                            feed.explicit,
+                        )
+                        .and_then(|p| db.add_episodes(p.rss.clone(), feed.episodes).map(|_| p))
+                    });
+                    match created {
+                        Ok(p) => {
+                            let warnings = report.warnings.clone().unwrap_or_default();
+                            health::record(db, &p.rss, report);
+                            if let Some(u) = logged_in {
+                                let subs = db.subscribe(u, p.rss.clone());
+                                match subs {
+                                    Ok(s) => {
+                                        bootstrap(state, u, &p.rss, options.download_latest);
+                                        let subscribed = Subscribed {
+                                            podcast: p,
+                                            subscriptions: s,
+                                            warnings,
+                                        };
+                                        (StatusCode::CREATED, Json(Some(subscribed)))
+                                    }
+                                    Err(_) => (StatusCode::BAD_REQUEST, Json(None))
+                                }
+                            } else {
+                                (StatusCode::NOT_FOUND, Json(None))
+                            }
+                        },
+                        Err(_) => (StatusCode::BAD_REQUEST, Json(None))
+                    }
+                },
+                Err(_) => (StatusCode::BAD_REQUEST, Json(None))
+            }
+
+        }
+        // try with https://revolutionspodcast.libsyn.com/rss/
+        Err(_) => (StatusCode::BAD_REQUEST, Json(None)),
+    }
+}
+
+/// Queues the downloads `?download_latest=` asked for. The subscription
+/// stands even if they don't fit the quota.
+fn bootstrap<D: DB>(state: &mut AppState<D>, user: Uuid, rss: &str, latest: Option<usize>) {
+    if let Some(count) = latest {
+        if let Err(e) = downloads::queue_latest(state, user, rss, count) {
+            eprintln!("queueing downloads of {} failed: {:?}", rss, e);
+        }
+    }
+}
+
+async fn get_episodes<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Acting(user): Acting,
+    Path(id): Path<Uuid>,
+    Query(filter): Query<EpisodeFilter>,
+    format: Format,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let hide = match explicit::hidden(&s, user) {
+        Ok(hide) => hide,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Negotiated(format, None)),
+    };
+    let db = &s.db;
+    let episodes = db
+        .get_podcast_by_id(id)
+        .and_then(|p| match p.explicit && hide {
+            true => Err(Error::NotFound),
+            false => db.episodes(p.rss),
+        });
+    match episodes {
+        Ok(mut e) => {
+            explicit::filter_episodes(db, hide, &mut e);
+            if let Some(since) = filter.since {
+                e.retain(|e| e.published.is_some_and(|p| p > since));
+            }
+            (StatusCode::OK, Negotiated(format, Some(e)))
+        }
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Negotiated(format, None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Negotiated(format, None)),
+    }
+}
+
+/// Episodes from the user's subscriptions published today, in their
+/// timezone.
+async fn get_today<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let today = s.db.get_user(uid).and_then(|u| {
+        let timezone = u.settings.timezone.or(s.config.timezone);
+        let date = dates::local(Utc::now(), timezone).date();
+        let mut episodes = vec![];
+        for rss in u.subscribed {
+            episodes.extend(s.db.episodes(rss)?.into_iter().filter(|e| {
+                e.published
+                    .is_some_and(|p| dates::local(p, timezone).date() == date)
+            }));
+        }
+        explicit::filter_episodes(&s.db, explicit::hidden(&s, Some(uid))?, &mut episodes);
+        episodes.sort_by_key(|e| Reverse(e.published));
+        Ok(Today {
+            date,
+            timezone,
+            episodes,
+        })
+    });
+    match today {
+        Ok(t) => (StatusCode::OK, Json(Some(t))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub struct Feed {
+    pub title: String,
+    pub description: String,
+    pub artwork: Option<String>,
+    pub language: Option<String>,
+    pub explicit: bool,
+    pub episodes: Vec<Episode>,
+}
+
+/// Fetches and parses a feed, giving up on documents over `max_bytes`, and
+/// reports how it went for [`health::record`].
+/// With `since`, an earlier fetch, it asks the host to answer `304` if the
+/// feed hasn't changed from that, which is `Error::NotModified`.
+async fn parse_rss(
+    http: &Fetcher,
+    rss_url: String,
+    max_bytes: u64,
+    since: Option<&FeedFetch>,
+) -> (Result<Feed, Error>, health::Report) {
+    let started = Instant::now();
+    let mut fetch = FeedFetch {
+        at: Utc::now(),
+        outcome: FetchOutcome::Ok,
+        status: None,
+        elapsed_ms: 0,
+        bytes: None,
+        redirects: vec![],
+        etag: None,
+        last_modified: None,
+    };
+    let mut warnings = None;
+    let mut conditional = HeaderMap::new();
+    if let Some(since) = since {
+        let validators = [
+            (header::IF_NONE_MATCH, &since.etag),
+            (header::IF_MODIFIED_SINCE, &since.last_modified),
+        ];
+        for (name, value) in validators {
+            if let Some(v) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                conditional.insert(name, v);
+            }
+        }
+    }
+    let feed = async {
+        let (mut resp, hops) = match http.get_traced(&rss_url, conditional).await {
+            Ok(r) => r,
+            Err(TracedError::Blocked) => return Err((FetchOutcome::Blocked, Error::Blocked)),
+            Err(TracedError::Request(e)) if ssrf::is_blocked(&e) => {
+                return Err((FetchOutcome::Blocked, Error::Blocked))
+            }
+            Err(TracedError::TooManyRedirects) => {
+                return Err((FetchOutcome::TooManyRedirects, Error::Upstream))
+            }
+            Err(_) => return Err((FetchOutcome::Unreachable, Error::Upstream)),
+        };
+        let header = |name: header::HeaderName| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        fetch.status = Some(resp.status().as_u16());
+        fetch.etag = header(header::ETAG);
+        fetch.last_modified = header(header::LAST_MODIFIED);
+        fetch.redirects = hops
+            .into_iter()
+            .map(|h| Redirect {
+                url: h.url,
+                status: h.status.as_u16(),
+            })
+            .collect();
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            // Hosts needn't repeat the validators on a `304`
+            if let Some(since) = since {
+                fetch.etag = fetch.etag.take().or(since.etag.clone());
+                fetch.last_modified = fetch.last_modified.take().or(since.last_modified.clone());
+            }
+            return Err((FetchOutcome::NotModified, Error::NotModified));
+        }
+        if !resp.status().is_success() {
+            return Err((FetchOutcome::HttpError, Error::Upstream));
+        }
+        if resp.content_length().is_some_and(|l| l > max_bytes) {
+            return Err((FetchOutcome::TooLarge, Error::Upstream));
+        }
+        let mut body = vec![];
+        loop {
+            match resp.chunk().await {
+                Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+                Ok(None) => break,
+                Err(_) => return Err((FetchOutcome::Unreachable, Error::Upstream)),
+            }
+            if body.len() as u64 > max_bytes {
+                return Err((FetchOutcome::TooLarge, Error::Upstream));
+            }
+        }
+        fetch.bytes = Some(body.len() as u64);
+        let body = String::from_utf8_lossy(&body);
+        let feed = parse_feed(&rss_url, &body).map_err(|e| (FetchOutcome::Invalid, e))?;
+        warnings = Some(health::warnings(&body));
+        Ok(feed)
+    }
+    .await;
+    fetch.elapsed_ms = started.elapsed().as_millis() as u64;
+    let feed = feed.map_err(|(outcome, e)| {
+        fetch.outcome = outcome;
+        e
+    });
+    (feed, health::Report { fetch, warnings })
+}
+
+/// Episode titles to add to the channel's own text when guessing its
+/// language.
+const DETECT_EPISODES: usize = 20;
+
+/// Parses an RSS document fetched from `rss_url`. Anything that isn't a
+/// feed with a title and description is `Error::Upstream`.
+pub fn parse_feed(rss_url: &str, body: &str) -> Result<Feed, Error> {
+    let xml = roxmltree::Document::parse(body).map_err(|_| Error::Upstream)?;
+    let rss = xml
+        .root()
+        .children()
+        .find(|n| n.tag_name().name() == "rss")
+        .ok_or(Error::Upstream)?;
+    let channel = rss
+        .children()
+        .find(|n| n.tag_name().name() == "channel")
+        .ok_or(Error::Upstream)?;
+    let title = channel
+        .children()
+        .find(|n| n.tag_name().name() == "title")
+        .and_then(|n| n.text())
+        .ok_or(Error::Upstream)?;
+    let description = channel
+        .children()
+        .find(|n| n.tag_name().name() == "description")
+        .and_then(|n| n.text())
+        .ok_or(Error::Upstream)?;
+    // `<itunes:image href>` is usually the bigger one; RSS's own `<image>`
+    // has a `<url>` child
+    let images: Vec<_> = channel
+        .children()
+        .filter(|n| n.tag_name().name() == "image")
+        .collect();
+    let artwork = images
+        .iter()
+        .find_map(|n| n.attribute("href"))
+        .or_else(|| {
+            images.iter().find_map(|n| {
+                n.children()
+                    .find(|c| c.tag_name().name() == "url")
+                    .and_then(|c| c.text())
+            })
+        })
+        .map(|url| url.trim().to_string());
+    let explicit_of = |node: roxmltree::Node| {
+        node.children()
+            .find(|n| n.tag_name().name() == "explicit")
+            .and_then(|n| n.text())
+            .and_then(explicit::parse)
+    };
+    let channel_explicit = explicit_of(channel).unwrap_or(false);
+    let episodes: Vec<Episode> = channel
+        .children()
+        .filter(|n| n.tag_name().name() == "item")
+        .map(|item| {
+            let child_text = |name: &str| {
+                item.children()
+                    .find(|n| n.tag_name().name() == name)
+                    .and_then(|n| n.text())
+                    .map(|t| t.trim().to_string())
+            };
+            let enclosure = item
+                .children()
+                .find(|n| n.tag_name().name() == "enclosure")
+                .and_then(|n| {
+                    Some(Enclosure {
+                        url: n.attribute("url")?.to_string(),
+                        length: n.attribute("length").and_then(|l| l.parse().ok()),
+                        mime_type: n.attribute("type").map(|t| t.to_string()),
+                        integrity: item
+                            .descendants()
+                            .find(|n| {
+                                n.tag_name().name() == "integrity"
+                                    && n.attribute("type") == Some("sri")
+                            })
+                            .and_then(|n| n.attribute("value"))
+                            .map(|v| v.to_string()),
+                    })
+                });
+            Episode {
+                id: Uuid::new_v4(),
+                podcast: rss_url.to_string(),
+                guid: child_text("guid"),
+                title: child_text("title").unwrap_or_default(),
+                // `dc:date` is the ISO 8601 alternative some feeds use
+                published: child_text("pubDate")
+                    .or_else(|| child_text("date"))
+                    .and_then(|d| dates::parse(&d)),
+                enclosure,
+                explicit: explicit_of(item).unwrap_or(channel_explicit),
+            }
+        })
+        .collect();
+    let language = channel
+        .children()
+        .find(|n| n.tag_name().name() == "language")
+        .and_then(|n| n.text())
+        .and_then(language::normalize)
+        .or_else(|| {
+            let mut text = format!("{}\n{}", title, description);
+            for e in episodes.iter().take(DETECT_EPISODES) {
+                text.push('\n');
+                text.push_str(&e.title);
+            }
+            language::detect(&text)
+        });
+
+    Ok(Feed {
+        title: title.to_string(),
+        description: description.to_string(),
+        artwork,
+        language,
+        explicit: channel_explicit,
+        episodes,
+    })
+}
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    NotFound,
+    #[allow(dead_code)]
+    DbError,
+    QuotaExceeded,
+    /// A fetch was refused because the URL points at a non-public address.
+    Blocked,
+    /// A podcast host couldn't be reached or sent something unusable.
+    Upstream,
+    /// Work in progress (e.g. a running download) stands in the way.
+    Busy,
+    /// The feature is switched off or not configured.
+    Unavailable,
+    /// A conditional fetch found nothing changed.
+    NotModified,
+}
+
+pub trait DB {
+    fn get_user(&self, id: Uuid) -> Result<User, Error>;
+
+    /// A page of users sorted by name, optionally filtered by a name
+    /// substring, with the total number of matches. Both ignore case.
+    fn users(
+        &self,
+        name: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(usize, Vec<User>), Error>;
+
+    fn touch_user(&mut self, id: Uuid, at: DateTime<Utc>) -> Result<(), Error>;
+
+    /// Replaces a user's stored fields, keyed by `user.id`.
+    fn update_user(&mut self, user: User) -> Result<User, Error>;
+
+    /// Removes a user with their episode actions, downloads and quota
+    /// override. Media files are the caller's job.
+    fn delete_user(&mut self, id: Uuid) -> Result<(), Error>;
+
+    fn create_user(&mut self, name: CreateUser) -> Result<User, Error>;
+
+    fn get_podcast(&self, rss: String) -> Result<PodcastChannel, Error>;
+
+    fn get_podcast_by_id(&self, id: Uuid) -> Result<PodcastChannel, Error>;
+
+    fn create_podcast(
+        &mut self,
+        rss: String,
+        title: String,
+        description: String,
+        artwork: Option<String>,
+        language: Option<String>,
+        explicit: bool,
+    ) -> Result<PodcastChannel, Error>;
+
+    /// Replaces a podcast's stored fields, keyed by `podcast.rss`.
+    fn update_podcast(&mut self, podcast: PodcastChannel) -> Result<(), Error>;
+
+    fn podcasts(&self) -> Result<Vec<PodcastChannel>, Error>;
+
+    /// When the feed was last fetched, successfully or not.
+    fn last_refreshed(&self, rss: String) -> Result<Option<DateTime<Utc>>, Error>;
+
+    fn set_last_refreshed(&mut self, rss: String, at: DateTime<Utc>) -> Result<(), Error>;
+
+    /// Recent fetches of the feed, newest first.
+    fn feed_fetches(&self, rss: String) -> Result<Vec<FeedFetch>, Error>;
+
+    /// Keeps the newest `keep`.
+    fn add_feed_fetch(&mut self, rss: String, fetch: FeedFetch, keep: usize) -> Result<(), Error>;
+
+    /// Problems in the latest usable copy of the feed.
+    fn feed_warnings(&self, rss: String) -> Result<Vec<FeedWarning>, Error>;
+
+    fn set_feed_warnings(&mut self, rss: String, warnings: Vec<FeedWarning>) -> Result<(), Error>;
+
+    /// `None` unless the podcast is in archive mode.
+    fn archive(&self, rss: String) -> Result<Option<Archive>, Error>;
+
+    /// `None` takes the podcast out of archive mode.
+    fn set_archive(&mut self, rss: String, archive: Option<Archive>) -> Result<(), Error>;
+
+    /// Newest first.
+    fn feed_snapshots(&self, rss: String) -> Result<Vec<FeedSnapshot>, Error>;
+
+    fn add_feed_snapshot(&mut self, rss: String, snapshot: FeedSnapshot) -> Result<(), Error>;
+
+    /// Minutes between refreshes an admin set for this feed.
+    fn refresh_override(&self, rss: String) -> Result<Option<u32>, Error>;
+
+    /// Summaries of the kept refresh runs, newest first.
+    fn refresh_runs(&self) -> Result<Vec<RefreshRun>, Error>;
+
+    fn refresh_run(&self, id: Uuid) -> Result<RefreshRunReport, Error>;
+
+    /// Keeps the newest `keep`.
+    fn add_refresh_run(&mut self, run: RefreshRunReport, keep: usize) -> Result<(), Error>;
+
+    /// `None` removes the override.
+    fn set_refresh_override(&mut self, rss: String, mins: Option<u32>) -> Result<(), Error>;
+
+    fn subscribe(&mut self, user: Uuid, rss: String) -> Result<Vec<String>, Error>;
+
+    /// Replaces the order of a user's subscriptions; `order` holds the same
+    /// feeds as before.
+    fn reorder_subscriptions(&mut self, user: Uuid, order: Vec<String>) -> Result<(), Error>;
+
+    fn subscription_override(
+        &self,
+        user: Uuid,
+        rss: String,
+    ) -> Result<Option<SubscriptionOverride>, Error>;
+
+    /// `None` removes the override.
+    fn set_subscription_override(
+        &mut self,
+        user: Uuid,
+        rss: String,
+        value: Option<SubscriptionOverride>,
+    ) -> Result<(), Error>;
+
+    fn record_episode_actions(
+        &mut self,
+        user: Uuid,
+        actions: Vec<EpisodeAction>,
+    ) -> Result<(), Error>;
+
+    fn episode_actions(&self, user: Uuid) -> Result<Vec<EpisodeAction>, Error>;
+
+    fn add_episodes(&mut self, rss: String, episodes: Vec<Episode>) -> Result<(), Error>;
+
+    /// Newest first; undated episodes go last, in feed order.
+    fn episodes(&self, rss: String) -> Result<Vec<Episode>, Error>;
+
+    fn get_episode(&self, id: Uuid) -> Result<Episode, Error>;
+
+    /// Replaces an episode's stored fields, keyed by `episode.id`.
+    fn update_episode(&mut self, episode: Episode) -> Result<(), Error>;
+
+    /// Deletes the feed's episodes `ids` with what hangs off them (inbox and
+    /// queue entries, tags, transcripts), leaving a tombstone for each.
+    /// Returns how many there were.
+    fn prune_episodes(&mut self, rss: String, ids: Vec<Uuid>) -> Result<usize, Error>;
+
+    /// What's left of the feed's pruned episodes.
+    fn tombstones(&self, rss: String) -> Result<Vec<Tombstone>, Error>;
+
+    /// What the episode's downloaded file is tagged with, once one finished.
+    fn episode_tags(&self, episode: Uuid) -> Result<Option<EmbeddedTags>, Error>;
+
+    fn set_episode_tags(&mut self, episode: Uuid, tags: EmbeddedTags) -> Result<(), Error>;
+
+    /// Inserts or replaces the episode's job, which then goes last.
+    fn save_transcription_job(&mut self, job: TranscriptionJob) -> Result<(), Error>;
+
+    /// Oldest first.
+    fn transcription_jobs(&self) -> Result<Vec<TranscriptionJob>, Error>;
+
+    fn transcript(&self, episode: Uuid) -> Result<Option<Transcript>, Error>;
+
+    /// Replaces any transcript the episode had, and indexes it for search.
+    fn save_transcript(&mut self, transcript: Transcript) -> Result<(), Error>;
+
+    /// Episodes whose transcripts contain every word of `query`, newest
+    /// first.
+    fn search_transcripts(&self, query: &str) -> Result<Vec<TranscriptHit>, Error>;
+
+    /// Episode IDs in the user's inbox, oldest arrival first.
+    fn inbox(&self, user: Uuid) -> Result<Vec<Uuid>, Error>;
+
+    /// Adds episodes to the inbox, skipping ones already there.
+    fn add_to_inbox(&mut self, user: Uuid, episodes: Vec<Uuid>) -> Result<(), Error>;
+
+    /// `Error::NotFound` if the episode isn't in the inbox.
+    fn remove_from_inbox(&mut self, user: Uuid, episode: Uuid) -> Result<(), Error>;
+
+    fn queue(&self, user: Uuid) -> Result<Vec<Uuid>, Error>;
+
+    /// Appends to the queue unless the episode is already on it.
+    fn add_to_queue(&mut self, user: Uuid, episode: Uuid) -> Result<(), Error>;
+
+    /// The user's bookmarks and notes, oldest first.
+    fn annotations(&self, user: Uuid) -> Result<Vec<Annotation>, Error>;
+
+    fn add_annotation(&mut self, user: Uuid, annotation: Annotation) -> Result<Annotation, Error>;
+
+    fn delete_annotation(&mut self, user: Uuid, id: Uuid) -> Result<(), Error>;
+
+    /// Inserts or replaces a download, keyed by its id.
+    fn save_download(&mut self, download: Download) -> Result<Download, Error>;
+
+    fn get_download(&self, id: Uuid) -> Result<Download, Error>;
+
+    fn downloads_for_user(&self, user: Uuid) -> Result<Vec<Download>, Error>;
+
+    fn all_downloads(&self) -> Vec<Download>;
+
+    fn delete_download(&mut self, id: Uuid) -> Result<(), Error>;
+
+    /// The oldest queued download not waiting to retry at `now`, skipping
+    /// background ones unless `include_background` is set.
+    fn next_queued_download(
+        &self,
+        include_background: bool,
+        now: DateTime<Utc>,
+    ) -> Option<Download>;
+
+    fn quota_override(&self, user: Uuid) -> Result<Option<u64>, Error>;
+
+    fn set_quota_override(&mut self, user: Uuid, bytes: Option<u64>) -> Result<(), Error>;
+
+    fn stats(&self) -> Result<DbStats, Error>;
+
+    /// Nightly upkeep: drops rows nothing refers to any more, and returns
+    /// how many went.
+    fn maintain(&mut self, now: DateTime<Utc>) -> Result<usize, Error>;
+
+    fn update_settings(
+        &mut self,
+        user: Uuid,
+        settings: UserSettings,
+    ) -> Result<UserSettings, Error>;
+
+    fn instance_settings(&self) -> Result<InstanceSettings, Error>;
+
+    fn set_instance_settings(&mut self, settings: InstanceSettings) -> Result<(), Error>;
+
+    /// Stores a new service account with the SHA-256 of its token.
+    fn create_service_account(
+        &mut self,
+        account: ServiceAccount,
+        token_sha256: String,
+    ) -> Result<ServiceAccount, Error>;
+
+    fn service_accounts(&self) -> Result<Vec<ServiceAccount>, Error>;
+
+    fn service_account_by_token(&self, token_sha256: &str) -> Result<ServiceAccount, Error>;
+
+    fn touch_service_account(&mut self, id: Uuid, at: DateTime<Utc>) -> Result<(), Error>;
+
+    fn delete_service_account(&mut self, id: Uuid) -> Result<(), Error>;
+
+    /// Requests a user or service account made on `day`, by method and
+    /// route pattern. Earlier days aren't kept.
+    fn request_counts(&self, caller: Uuid, day: NaiveDate) -> Result<HashMap<String, u64>, Error>;
+
+    fn count_request(&mut self, caller: Uuid, day: NaiveDate, route: String) -> Result<(), Error>;
+
+    /// The user's scrobbler with where it sends to, if they set one.
+    fn scrobbler(&self, user: Uuid) -> Result<Option<(ScrobbleTarget, Scrobbler)>, Error>;
+
+    fn scrobblers(&self) -> Result<Vec<(Uuid, ScrobbleTarget, Scrobbler)>, Error>;
+
+    /// Sets the user's scrobbler, or with `None` removes it.
+    fn set_scrobbler(
+        &mut self,
+        user: Uuid,
+        scrobbler: Option<(ScrobbleTarget, Scrobbler)>,
+    ) -> Result<(), Error>;
+
+    /// Records a run of the user's scrobbler: `sent` listens went, the last
+    /// finished at `up_to`, and `error` is why the rest didn't. `since`
+    /// never moves back, in case the scrobbler was replaced meanwhile.
+    fn scrobbled(
+        &mut self,
+        user: Uuid,
+        sent: u64,
+        up_to: Option<NaiveDateTime>,
+        at: DateTime<Utc>,
+        error: Option<String>,
+    ) -> Result<(), Error>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryStore {
+    users: HashMap<Uuid, User>,
+    podcasts: HashMap<String, PodcastChannel>,
+    episode_actions: HashMap<Uuid, Vec<EpisodeAction>>,
+    episodes: HashMap<String, Vec<Episode>>,
+    /// Pruned episodes, by feed.
+    tombstones: HashMap<String, Vec<Tombstone>>,
+    downloads: Vec<Download>,
+    quota_overrides: HashMap<Uuid, u64>,
+    subscription_overrides: HashMap<(Uuid, String), SubscriptionOverride>,
+    refreshed: HashMap<String, DateTime<Utc>>,
+    refresh_overrides: HashMap<String, u32>,
+    feed_fetches: HashMap<String, Vec<FeedFetch>>,
+    feed_warnings: HashMap<String, Vec<FeedWarning>>,
+    archives: HashMap<String, Archive>,
+    /// Newest first.
+    feed_snapshots: HashMap<String, Vec<FeedSnapshot>>,
+    /// Newest first.
+    refresh_runs: Vec<RefreshRunReport>,
+    instance_settings: InstanceSettings,
+    inboxes: HashMap<Uuid, Vec<Uuid>>,
+    queues: HashMap<Uuid, Vec<Uuid>>,
+    annotations: HashMap<Uuid, Vec<Annotation>>,
+    episode_tags: HashMap<Uuid, EmbeddedTags>,
+    transcription_jobs: Vec<TranscriptionJob>,
+    transcripts: HashMap<Uuid, Transcript>,
+    /// Word to the episodes whose transcripts contain it.
+    transcript_index: HashMap<String, HashSet<Uuid>>,
+    /// With the SHA-256 of each one's token.
+    service_accounts: HashMap<Uuid, (ServiceAccount, String)>,
+    /// Today's requests by user or service account, by route.
+    request_counts: HashMap<Uuid, (NaiveDate, HashMap<String, u64>)>,
+    scrobblers: HashMap<Uuid, (ScrobbleTarget, Scrobbler)>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> InMemoryStore {
+        InMemoryStore {
+            users: HashMap::new(),
+            podcasts: HashMap::new(),
+            episode_actions: HashMap::new(),
+            episodes: HashMap::new(),
+            tombstones: HashMap::new(),
+            downloads: Vec::new(),
+            quota_overrides: HashMap::new(),
+            subscription_overrides: HashMap::new(),
+            refreshed: HashMap::new(),
+            refresh_overrides: HashMap::new(),
+            feed_fetches: HashMap::new(),
+            feed_warnings: HashMap::new(),
+            archives: HashMap::new(),
+            feed_snapshots: HashMap::new(),
+            refresh_runs: vec![],
+            instance_settings: InstanceSettings::default(),
+            inboxes: HashMap::new(),
+            queues: HashMap::new(),
+            annotations: HashMap::new(),
+            episode_tags: HashMap::new(),
+            transcription_jobs: Vec::new(),
+            transcripts: HashMap::new(),
+            transcript_index: HashMap::new(),
+            service_accounts: HashMap::new(),
+            request_counts: HashMap::new(),
+            scrobblers: HashMap::new(),
+        }
+    }
+}
+
+impl DB for InMemoryStore {
+    fn get_user(&self, id: Uuid) -> Result<User, Error> {
+        self.users.get(&id).cloned().ok_or(Error::NotFound)
+    }
+
+    fn users(
+        &self,
+        name: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(usize, Vec<User>), Error> {
+        let needle = name.map(|n| n.to_lowercase());
+        let mut users: Vec<_> = self
+            .users
+            .values()
+            .filter(|u| {
+                needle
+                    .as_ref()
+                    .is_none_or(|n| u.name.to_lowercase().contains(n))
+            })
+            .cloned()
+            .collect();
+        users.sort_by_cached_key(|u| (u.name.to_lowercase(), u.id));
+        let total = users.len();
+        Ok((total, users.into_iter().skip(offset).take(limit).collect()))
+    }
+
+    fn touch_user(&mut self, id: Uuid, at: DateTime<Utc>) -> Result<(), Error> {
+        let u = self.users.get_mut(&id).ok_or(Error::NotFound)?;
+        u.last_active = Some(at);
+        Ok(())
+    }
+
+    fn update_user(&mut self, user: User) -> Result<User, Error> {
+        let u = self.users.get_mut(&user.id).ok_or(Error::NotFound)?;
+        *u = user.clone();
+        Ok(user)
+    }
+
+    fn delete_user(&mut self, id: Uuid) -> Result<(), Error> {
+        self.users.remove(&id).ok_or(Error::NotFound)?;
+        self.episode_actions.remove(&id);
+        self.quota_overrides.remove(&id);
+        self.request_counts.remove(&id);
+        self.scrobblers.remove(&id);
+        self.inboxes.remove(&id);
+        self.queues.remove(&id);
+        self.annotations.remove(&id);
+        self.subscription_overrides
+            .retain(|(user, _), _| *user != id);
+        self.downloads.retain(|d| d.user != id);
+        Ok(())
+    }
+
+    fn create_user(&mut self, user: CreateUser) -> Result<User, Error> {
+        let uuid = Uuid::new_v4();
+        let u = User {
+            name: user.name,
+            id: uuid,
+            subscribed: vec![],
+            // The first account on an instance administers it
+            admin: self.users.is_empty(),
+            settings: UserSettings::default(),
+            last_active: None,
+            created: Utc::now(),
+            flagged_idle: None,
+            archived: false,
+            profile_of: None,
+        };
+        let _ = self.users.insert(uuid, u.clone());
+        Ok(u)
+    }
+
+    fn get_podcast(&self, rss: String) -> Result<PodcastChannel, Error> {
+        self.podcasts.get(&rss).cloned().ok_or(Error::NotFound)
+    }
+
+    fn get_podcast_by_id(&self, id: Uuid) -> Result<PodcastChannel, Error> {
+        self.podcasts
+            .values()
+            .find(|p| p.id == id)
+            .cloned()
+            .ok_or(Error::NotFound)
+    }
+
+    fn create_podcast(
+        &mut self,
+        rss: String,
+        title: String,
+        description: String,
+        artwork: Option<String>,
+        language: Option<String>,
+        explicit: bool,
+    ) -> Result<PodcastChannel, Error> {
+        let id = Uuid::new_v4();
+        let p = PodcastChannel {
+            rss: rss.clone(),
+            name: title,
+            description,
+            id,
+            artwork,
+            language,
+            explicit,
+        };
+        let _ = self.podcasts.insert(rss.clone(), p.clone());
+        // Podcasts are created from a feed that was just fetched
+        let _ = self.refreshed.insert(rss, Utc::now());
+        Ok(p)
+    }
+
+    fn update_podcast(&mut self, podcast: PodcastChannel) -> Result<(), Error> {
+        let p = self.podcasts.get_mut(&podcast.rss).ok_or(Error::NotFound)?;
+        *p = podcast;
+        Ok(())
+    }
+
+    fn podcasts(&self) -> Result<Vec<PodcastChannel>, Error> {
+        Ok(self.podcasts.values().cloned().collect())
+    }
+
+    fn last_refreshed(&self, rss: String) -> Result<Option<DateTime<Utc>>, Error> {
+        self.get_podcast(rss.clone())?;
+        Ok(self.refreshed.get(&rss).copied())
+    }
+
+    fn set_last_refreshed(&mut self, rss: String, at: DateTime<Utc>) -> Result<(), Error> {
+        self.get_podcast(rss.clone())?;
+        let _ = self.refreshed.insert(rss, at);
+        Ok(())
+    }
+
+    fn feed_fetches(&self, rss: String) -> Result<Vec<FeedFetch>, Error> {
+        self.get_podcast(rss.clone())?;
+        Ok(self.feed_fetches.get(&rss).cloned().unwrap_or_default())
+    }
+
+    fn add_feed_fetch(&mut self, rss: String, fetch: FeedFetch, keep: usize) -> Result<(), Error> {
+        self.get_podcast(rss.clone())?;
+        let fetches = self.feed_fetches.entry(rss).or_default();
+        fetches.insert(0, fetch);
+        fetches.truncate(keep);
+        Ok(())
+    }
+
+    fn feed_warnings(&self, rss: String) -> Result<Vec<FeedWarning>, Error> {
+        self.get_podcast(rss.clone())?;
+        Ok(self.feed_warnings.get(&rss).cloned().unwrap_or_default())
+    }
+
+    fn set_feed_warnings(&mut self, rss: String, warnings: Vec<FeedWarning>) -> Result<(), Error> {
+        self.get_podcast(rss.clone())?;
+        let _ = self.feed_warnings.insert(rss, warnings);
+        Ok(())
+    }
+
+    fn archive(&self, rss: String) -> Result<Option<Archive>, Error> {
+        self.get_podcast(rss.clone())?;
+        Ok(self.archives.get(&rss).cloned())
+    }
+
+    fn set_archive(&mut self, rss: String, archive: Option<Archive>) -> Result<(), Error> {
+        self.get_podcast(rss.clone())?;
+        match archive {
+            Some(a) => {
+                let _ = self.archives.insert(rss, a);
+            }
+            None => {
+                let _ = self.archives.remove(&rss);
+            }
+        }
+        Ok(())
+    }
+
+    fn feed_snapshots(&self, rss: String) -> Result<Vec<FeedSnapshot>, Error> {
+        self.get_podcast(rss.clone())?;
+        Ok(self.feed_snapshots.get(&rss).cloned().unwrap_or_default())
+    }
+
+    fn add_feed_snapshot(&mut self, rss: String, snapshot: FeedSnapshot) -> Result<(), Error> {
+        self.get_podcast(rss.clone())?;
+        self.feed_snapshots
+            .entry(rss)
+            .or_default()
+            .insert(0, snapshot);
+        Ok(())
+    }
+
+    fn refresh_override(&self, rss: String) -> Result<Option<u32>, Error> {
+        self.get_podcast(rss.clone())?;
+        Ok(self.refresh_overrides.get(&rss).copied())
+    }
+
+    fn refresh_runs(&self) -> Result<Vec<RefreshRun>, Error> {
+        Ok(self.refresh_runs.iter().map(|r| r.run.clone()).collect())
+    }
+
+    fn refresh_run(&self, id: Uuid) -> Result<RefreshRunReport, Error> {
+        self.refresh_runs
+            .iter()
+            .find(|r| r.run.id == id)
+            .cloned()
+            .ok_or(Error::NotFound)
+    }
+
+    fn add_refresh_run(&mut self, run: RefreshRunReport, keep: usize) -> Result<(), Error> {
+        self.refresh_runs.insert(0, run);
+        self.refresh_runs.truncate(keep);
+        Ok(())
+    }
+
+    fn set_refresh_override(&mut self, rss: String, mins: Option<u32>) -> Result<(), Error> {
+        self.get_podcast(rss.clone())?;
+        match mins {
+            Some(m) => self.refresh_overrides.insert(rss, m),
+            None => self.refresh_overrides.remove(&rss),
+        };
+        Ok(())
+    }
+
+    fn subscribe(&mut self, user: Uuid, rss: String) -> Result<Vec<String>, Error> {
+        let p = self.get_podcast(rss)?;
+        let u = self.users.get_mut(&user).ok_or(Error::NotFound)?;
+        if !u.subscribed.contains(&p.rss) {
+            u.subscribed.push(p.rss.clone());
+        }
+        Ok(u.subscribed.clone())
+    }
+
+    fn reorder_subscriptions(&mut self, user: Uuid, order: Vec<String>) -> Result<(), Error> {
+        let u = self.users.get_mut(&user).ok_or(Error::NotFound)?;
+        u.subscribed = order;
+        Ok(())
+    }
+
+    fn subscription_override(
+        &self,
+        user: Uuid,
+        rss: String,
+    ) -> Result<Option<SubscriptionOverride>, Error> {
+        self.get_user(user)?;
+        Ok(self.subscription_overrides.get(&(user, rss)).cloned())
+    }
+
+    fn set_subscription_override(
+        &mut self,
+        user: Uuid,
+        rss: String,
+        value: Option<SubscriptionOverride>,
+    ) -> Result<(), Error> {
+        self.get_user(user)?;
+        match value {
+            Some(o) => self.subscription_overrides.insert((user, rss), o),
+            None => self.subscription_overrides.remove(&(user, rss)),
+        };
+        Ok(())
+    }
+
+    fn record_episode_actions(
+        &mut self,
+        user: Uuid,
+        actions: Vec<EpisodeAction>,
+    ) -> Result<(), Error> {
+        self.get_user(user)?;
+        self.episode_actions
+            .entry(user)
+            .or_default()
+            .extend(actions);
+        Ok(())
+    }
+
+    fn episode_actions(&self, user: Uuid) -> Result<Vec<EpisodeAction>, Error> {
+        self.get_user(user)?;
+        Ok(self.episode_actions.get(&user).cloned().unwrap_or_default())
+    }
+
+    fn add_episodes(&mut self, rss: String, episodes: Vec<Episode>) -> Result<(), Error> {
+        self.get_podcast(rss.clone())?;
+        self.episodes.entry(rss).or_default().extend(episodes);
+        Ok(())
+    }
+
+    fn episodes(&self, rss: String) -> Result<Vec<Episode>, Error> {
+        self.get_podcast(rss.clone())?;
+        let mut episodes = self.episodes.get(&rss).cloned().unwrap_or_default();
+        episodes.sort_by_key(|e| Reverse(e.published));
+        Ok(episodes)
+    }
+
+    fn get_episode(&self, id: Uuid) -> Result<Episode, Error> {
+        self.episodes
+            .values()
+            .flatten()
+            .find(|e| e.id == id)
+            .cloned()
+            .ok_or(Error::NotFound)
+    }
+
+    fn update_episode(&mut self, episode: Episode) -> Result<(), Error> {
+        let e = self
+            .episodes
+            .values_mut()
+            .flatten()
+            .find(|e| e.id == episode.id)
+            .ok_or(Error::NotFound)?;
+        *e = episode;
+        Ok(())
+    }
+
+    fn prune_episodes(&mut self, rss: String, ids: Vec<Uuid>) -> Result<usize, Error> {
+        self.get_podcast(rss.clone())?;
+        let ids: HashSet<Uuid> = ids.into_iter().collect();
+        let episodes = self.episodes.entry(rss.clone()).or_default();
+        let (pruned, kept): (Vec<Episode>, Vec<Episode>) =
+            episodes.drain(..).partition(|e| ids.contains(&e.id));
+        *episodes = kept;
+        for list in self.inboxes.values_mut().chain(self.queues.values_mut()) {
+            list.retain(|e| !ids.contains(e));
+        }
+        self.episode_tags.retain(|e, _| !ids.contains(e));
+        self.transcription_jobs
+            .retain(|j| !ids.contains(&j.episode));
+        self.transcripts.retain(|e, _| !ids.contains(e));
+        for episodes in self.transcript_index.values_mut() {
+            episodes.retain(|e| !ids.contains(e));
+        }
+        self.transcript_index
+            .retain(|_, episodes| !episodes.is_empty());
+        self.tombstones
+            .entry(rss)
+            .or_default()
+            .extend(pruned.iter().map(Tombstone::of));
+        Ok(pruned.len())
+    }
+
+    fn tombstones(&self, rss: String) -> Result<Vec<Tombstone>, Error> {
+        self.get_podcast(rss.clone())?;
+        Ok(self.tombstones.get(&rss).cloned().unwrap_or_default())
+    }
+
+    fn episode_tags(&self, episode: Uuid) -> Result<Option<EmbeddedTags>, Error> {
+        self.get_episode(episode)?;
+        Ok(self.episode_tags.get(&episode).cloned())
+    }
+
+    fn set_episode_tags(&mut self, episode: Uuid, tags: EmbeddedTags) -> Result<(), Error> {
+        self.get_episode(episode)?;
+        let _ = self.episode_tags.insert(episode, tags);
+        Ok(())
+    }
+
+    fn save_transcription_job(&mut self, job: TranscriptionJob) -> Result<(), Error> {
+        self.get_episode(job.episode)?;
+        self.transcription_jobs.retain(|j| j.episode != job.episode);
+        self.transcription_jobs.push(job);
+        Ok(())
+    }
+
+    fn transcription_jobs(&self) -> Result<Vec<TranscriptionJob>, Error> {
+        Ok(self.transcription_jobs.clone())
+    }
+
+    fn transcript(&self, episode: Uuid) -> Result<Option<Transcript>, Error> {
+        self.get_episode(episode)?;
+        Ok(self.transcripts.get(&episode).cloned())
+    }
+
+    fn save_transcript(&mut self, transcript: Transcript) -> Result<(), Error> {
+        let episode = transcript.episode;
+        self.get_episode(episode)?;
+        if let Some(old) = self.transcripts.remove(&episode) {
+            for s in &old.segments {
+                for w in transcription::words(&s.text) {
+                    if let Some(episodes) = self.transcript_index.get_mut(&w) {
+                        episodes.remove(&episode);
+                    }
+                }
+            }
+            self.transcript_index
+                .retain(|_, episodes| !episodes.is_empty());
+        }
+        for s in &transcript.segments {
+            for w in transcription::words(&s.text) {
+                self.transcript_index.entry(w).or_default().insert(episode);
+            }
+        }
+        self.transcripts.insert(episode, transcript);
+        Ok(())
+    }
+
+    fn search_transcripts(&self, query: &str) -> Result<Vec<TranscriptHit>, Error> {
+        let terms: HashSet<String> = transcription::words(query).collect();
+        let mut matches: Option<HashSet<Uuid>> = None;
+        for t in &terms {
+            let found = self.transcript_index.get(t).cloned().unwrap_or_default();
+            matches = Some(match matches {
+                Some(m) => m.intersection(&found).copied().collect(),
+                None => found,
+            });
+        }
+        let mut hits = vec![];
+        for id in matches.unwrap_or_default() {
+            let (Ok(e), Some(t)) = (self.get_episode(id), self.transcripts.get(&id)) else {
+                continue;
+            };
+            let segment = t
+                .segments
+                .iter()
+                .find(|s| transcription::words(&s.text).any(|w| terms.contains(&w)));
+            if let Some(segment) = segment {
+                let hit = TranscriptHit {
+                    episode: id,
+                    podcast: e.podcast,
+                    title: e.title,
+                    segment: segment.clone(),
+                };
+                hits.push((e.published, hit));
+            }
+        }
+        hits.sort_by_key(|(published, _)| Reverse(*published));
+        Ok(hits.into_iter().map(|(_, h)| h).collect())
+    }
+
+    fn inbox(&self, user: Uuid) -> Result<Vec<Uuid>, Error> {
+        self.get_user(user)?;
+        Ok(self.inboxes.get(&user).cloned().unwrap_or_default())
+    }
+
+    fn add_to_inbox(&mut self, user: Uuid, episodes: Vec<Uuid>) -> Result<(), Error> {
+        self.get_user(user)?;
+        let inbox = self.inboxes.entry(user).or_default();
+        for e in episodes {
+            if !inbox.contains(&e) {
+                inbox.push(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_from_inbox(&mut self, user: Uuid, episode: Uuid) -> Result<(), Error> {
+        let inbox = self.inboxes.get_mut(&user).ok_or(Error::NotFound)?;
+        let before = inbox.len();
+        inbox.retain(|e| *e != episode);
+        if inbox.len() == before {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    fn queue(&self, user: Uuid) -> Result<Vec<Uuid>, Error> {
+        self.get_user(user)?;
+        Ok(self.queues.get(&user).cloned().unwrap_or_default())
+    }
+
+    fn add_to_queue(&mut self, user: Uuid, episode: Uuid) -> Result<(), Error> {
+        self.get_user(user)?;
+        let queue = self.queues.entry(user).or_default();
+        if !queue.contains(&episode) {
+            queue.push(episode);
+        }
+        Ok(())
+    }
+
+    fn annotations(&self, user: Uuid) -> Result<Vec<Annotation>, Error> {
+        self.get_user(user)?;
+        Ok(self.annotations.get(&user).cloned().unwrap_or_default())
+    }
+
+    fn add_annotation(&mut self, user: Uuid, annotation: Annotation) -> Result<Annotation, Error> {
+        self.get_user(user)?;
+        self.annotations
+            .entry(user)
+            .or_default()
+            .push(annotation.clone());
+        Ok(annotation)
+    }
+
+    fn delete_annotation(&mut self, user: Uuid, id: Uuid) -> Result<(), Error> {
+        let annotations = self.annotations.get_mut(&user).ok_or(Error::NotFound)?;
+        let before = annotations.len();
+        annotations.retain(|a| a.id != id);
+        if annotations.len() == before {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    fn save_download(&mut self, download: Download) -> Result<Download, Error> {
+        match self.downloads.iter_mut().find(|d| d.id == download.id) {
+            Some(d) => *d = download.clone(),
+            None => self.downloads.push(download.clone()),
+        }
+        Ok(download)
+    }
+
+    fn get_download(&self, id: Uuid) -> Result<Download, Error> {
+        self.downloads
+            .iter()
+            .find(|d| d.id == id)
+            .cloned()
+            .ok_or(Error::NotFound)
+    }
+
+    fn downloads_for_user(&self, user: Uuid) -> Result<Vec<Download>, Error> {
+        self.get_user(user)?;
+        Ok(self
+            .downloads
+            .iter()
+            .filter(|d| d.user == user)
+            .cloned()
+            .collect())
+    }
+
+    fn all_downloads(&self) -> Vec<Download> {
+        self.downloads.clone()
+    }
+
+    fn delete_download(&mut self, id: Uuid) -> Result<(), Error> {
+        let before = self.downloads.len();
+        self.downloads.retain(|d| d.id != id);
+        if self.downloads.len() == before {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    fn next_queued_download(
+        &self,
+        include_background: bool,
+        now: DateTime<Utc>,
+    ) -> Option<Download> {
+        self.downloads
+            .iter()
+            .filter(|d| d.status == DownloadStatus::Queued && (include_background || !d.background))
+            .find(|d| d.retry_after.is_none_or(|at| at <= now))
+            .cloned()
+    }
+
+    fn quota_override(&self, user: Uuid) -> Result<Option<u64>, Error> {
+        self.get_user(user)?;
+        Ok(self.quota_overrides.get(&user).copied())
+    }
+
+    fn set_quota_override(&mut self, user: Uuid, bytes: Option<u64>) -> Result<(), Error> {
+        self.get_user(user)?;
+        match bytes {
+            Some(b) => self.quota_overrides.insert(user, b),
+            None => self.quota_overrides.remove(&user),
+        };
+        Ok(())
+    }
+
+    fn stats(&self) -> Result<DbStats, Error> {
+        Ok(DbStats {
+            bytes: None,
+            users: self.users.len(),
+            podcasts: self.podcasts.len(),
+            episodes: self.episodes.values().map(|e| e.len()).sum(),
+            downloads: self.downloads.len(),
+        })
+    }
+
+    fn update_settings(
+        &mut self,
+        user: Uuid,
+        settings: UserSettings,
+    ) -> Result<UserSettings, Error> {
+        let u = self.users.get_mut(&user).ok_or(Error::NotFound)?;
+        u.settings = settings;
+        Ok(u.settings.clone())
+    }
+
+    fn instance_settings(&self) -> Result<InstanceSettings, Error> {
+        Ok(self.instance_settings.clone())
+    }
+
+    fn set_instance_settings(&mut self, settings: InstanceSettings) -> Result<(), Error> {
+        self.instance_settings = settings;
+        Ok(())
+    }
+
+    fn create_service_account(
+        &mut self,
+        account: ServiceAccount,
+        token_sha256: String,
+    ) -> Result<ServiceAccount, Error> {
+        self.service_accounts
+            .insert(account.id, (account.clone(), token_sha256));
+        Ok(account)
+    }
+
+    fn service_accounts(&self) -> Result<Vec<ServiceAccount>, Error> {
+        let mut accounts: Vec<ServiceAccount> = self
+            .service_accounts
+            .values()
+            .map(|(a, _)| a.clone())
+            .collect();
+        accounts.sort_by_key(|a| a.created);
+        Ok(accounts)
+    }
+
+    fn service_account_by_token(&self, token_sha256: &str) -> Result<ServiceAccount, Error> {
+        self.service_accounts
+            .values()
+            .find(|(_, hash)| hash == token_sha256)
+            .map(|(a, _)| a.clone())
+            .ok_or(Error::NotFound)
+    }
+
+    fn touch_service_account(&mut self, id: Uuid, at: DateTime<Utc>) -> Result<(), Error> {
+        let (a, _) = self.service_accounts.get_mut(&id).ok_or(Error::NotFound)?;
+        a.last_used = Some(at);
+        Ok(())
+    }
+
+    fn delete_service_account(&mut self, id: Uuid) -> Result<(), Error> {
+        self.request_counts.remove(&id);
+        self.service_accounts
+            .remove(&id)
+            .map(|_| ())
+            .ok_or(Error::NotFound)
+    }
+
+    fn request_counts(&self, caller: Uuid, day: NaiveDate) -> Result<HashMap<String, u64>, Error> {
+        match self.request_counts.get(&caller) {
+            Some((d, counts)) if *d == day => Ok(counts.clone()),
+            _ => Ok(HashMap::new()),
+        }
+    }
+
+    fn count_request(&mut self, caller: Uuid, day: NaiveDate, route: String) -> Result<(), Error> {
+        let (d, counts) = self
+            .request_counts
+            .entry(caller)
+            .or_insert_with(|| (day, HashMap::new()));
+        if *d != day {
+            *d = day;
+            counts.clear();
+        }
+        *counts.entry(route).or_default() += 1;
+        Ok(())
+    }
+
+    /// The rows are past days' request counts and inbox, queue, tag and
+    /// transcript entries for episodes that were pruned. Spare capacity is
+    /// given back too.
+    fn maintain(&mut self, now: DateTime<Utc>) -> Result<usize, Error> {
+        let today = now.date_naive();
+        let episodes: HashSet<Uuid> = self.episodes.values().flatten().map(|e| e.id).collect();
+        let mut removed = 0;
+        let before = self.request_counts.len();
+        self.request_counts.retain(|_, (day, _)| *day == today);
+        removed += before - self.request_counts.len();
+        for ids in self.inboxes.values_mut().chain(self.queues.values_mut()) {
+            let before = ids.len();
+            ids.retain(|id| episodes.contains(id));
+            removed += before - ids.len();
+        }
+        let before = self.episode_tags.len() + self.transcripts.len();
+        self.episode_tags.retain(|id, _| episodes.contains(id));
+        self.transcripts.retain(|id, _| episodes.contains(id));
+        removed += before - self.episode_tags.len() - self.transcripts.len();
+        for found in self.transcript_index.values_mut() {
+            found.retain(|id| episodes.contains(id));
+        }
+        self.transcript_index.retain(|_, found| !found.is_empty());
+        self.users.shrink_to_fit();
+        self.podcasts.shrink_to_fit();
+        self.episodes.shrink_to_fit();
+        self.downloads.shrink_to_fit();
+        self.request_counts.shrink_to_fit();
+        self.episode_tags.shrink_to_fit();
+        self.transcripts.shrink_to_fit();
+        self.transcript_index.shrink_to_fit();
+        Ok(removed)
+    }
+
+    fn scrobbler(&self, user: Uuid) -> Result<Option<(ScrobbleTarget, Scrobbler)>, Error> {
+        self.get_user(user)?;
+        Ok(self.scrobblers.get(&user).cloned())
+    }
+
+    fn scrobblers(&self) -> Result<Vec<(Uuid, ScrobbleTarget, Scrobbler)>, Error> {
+        Ok(self
+            .scrobblers
+            .iter()
+            .map(|(user, (target, scrobbler))| (*user, target.clone(), scrobbler.clone()))
+            .collect())
+    }
+
+    fn set_scrobbler(
+        &mut self,
+        user: Uuid,
+        scrobbler: Option<(ScrobbleTarget, Scrobbler)>,
+    ) -> Result<(), Error> {
+        self.get_user(user)?;
+        match scrobbler {
+            Some(s) => {
+                self.scrobblers.insert(user, s);
+            }
+            None => {
+                self.scrobblers.remove(&user).ok_or(Error::NotFound)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn scrobbled(
+        &mut self,
+        user: Uuid,
+        sent: u64,
+        up_to: Option<NaiveDateTime>,
+        at: DateTime<Utc>,
+        error: Option<String>,
+    ) -> Result<(), Error> {
+        let (_, s) = self.scrobblers.get_mut(&user).ok_or(Error::NotFound)?;
+        if let Some(up_to) = up_to {
+            s.since = s.since.max(up_to);
+        }
+        if sent > 0 {
+            s.submitted += sent;
+            s.last_submitted = Some(at);
+        }
+        s.last_error = error;
+        Ok(())
+    }
+}
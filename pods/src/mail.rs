@@ -0,0 +1,74 @@
+//! Outgoing email over SMTP, for notices to users.
+
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct MailConfig {
+    pub host: String,
+    /// Defaults to the usual port for `tls`.
+    pub port: Option<u16>,
+    /// e.g. `"pods <pods@example.com>"`
+    pub from: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(default)]
+    pub tls: Tls,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Tls {
+    #[default]
+    Starttls,
+    /// TLS from the start, usually port 465.
+    Implicit,
+    /// Plain text, for a relay on localhost.
+    None,
+}
+
+#[derive(Clone)]
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    pub fn new(config: &MailConfig) -> Result<Mailer, String> {
+        let mut builder = match config.tls {
+            Tls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host),
+            Tls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host),
+            Tls::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                &config.host,
+            )),
+        }
+        .map_err(|e| e.to_string())?;
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        if let (Some(user), Some(pass)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(user.clone(), pass.clone()));
+        }
+        Ok(Mailer {
+            transport: builder.build(),
+            from: config.from.parse().map_err(|e| format!("from: {}", e))?,
+        })
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> Result<(), String> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse().map_err(|e| format!("{}: {}", to, e))?)
+            .subject(subject)
+            .body(body)
+            .map_err(|e| e.to_string())?;
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,148 @@
+//! Nightly maintenance, so instances that run for months stay healthy
+//! without anyone looking after them: the database drops rows nothing
+//! refers to any more and compacts itself, expired offline bundles are
+//! deleted, and with `sweep_media`, so are media files no download refers
+//! to. The latest run's report is on `GET /admin/storage`. Off unless
+//! configured.
+
+use std::{
+    io,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{NaiveTime, Utc};
+use serde::Deserialize;
+use tokio::{fs, sync::Mutex};
+use uuid::Uuid;
+
+pub use pods_types::admin::MaintenanceReport;
+
+use crate::{admin, bundles, config::hh_mm, dates, media, AppState, Download, DB};
+
+/// How often the worker checks whether it's time to run.
+const TICK: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct MaintenanceConfig {
+    /// When to run each night, as `HH:MM` in the instance's timezone.
+    #[serde(default = "default_at", deserialize_with = "hh_mm")]
+    pub at: NaiveTime,
+    /// Also delete media files no download refers to. With the in-memory
+    /// store, that's everything downloaded before the last restart.
+    #[serde(default)]
+    pub sweep_media: bool,
+    /// Files changed more recently than this are never swept, so nothing
+    /// being written is.
+    #[serde(default = "default_grace")]
+    pub media_grace_hours: u32,
+}
+
+fn default_at() -> NaiveTime {
+    NaiveTime::from_hms_opt(3, 30, 0).unwrap()
+}
+
+fn default_grace() -> u32 {
+    24
+}
+
+pub async fn worker<D: DB + Send + 'static>(state: Arc<Mutex<AppState<D>>>) {
+    let (config, timezone) = {
+        let s = state.lock().await;
+        let Some(config) = s.config.maintenance.clone() else {
+            return;
+        };
+        (config, s.config.timezone)
+    };
+    // Started after tonight's time, the first run is tomorrow's
+    let now = dates::local(Utc::now(), timezone);
+    let mut last = (now.time() >= config.at).then_some(now.date());
+    loop {
+        tokio::time::sleep(TICK).await;
+        let now = dates::local(Utc::now(), timezone);
+        if now.time() < config.at || last == Some(now.date()) {
+            continue;
+        }
+        last = Some(now.date());
+        let report = run(&state, &config).await;
+        for e in &report.errors {
+            eprintln!("maintenance failed: {}", e);
+        }
+        state.lock().await.maintenance = Some(report);
+    }
+}
+
+async fn run<D: DB>(state: &Mutex<AppState<D>>, config: &MaintenanceConfig) -> MaintenanceReport {
+    let started = Utc::now();
+    let mut errors = vec![];
+    let (database_rows, expired_bundles, media_dir, downloads) = {
+        let s = &mut *state.lock().await;
+        let rows = s.db.maintain(started).unwrap_or_else(|e| {
+            errors.push(format!("database: {:?}", e));
+            0
+        });
+        let before = s.bundles.len();
+        bundles::purge(s);
+        let expired = before - s.bundles.len();
+        (
+            rows,
+            expired,
+            s.config.media_dir.clone(),
+            s.db.all_downloads(),
+        )
+    };
+    let (mut orphaned_files, mut orphaned_bytes) = (0, 0);
+    if config.sweep_media {
+        let grace = Duration::from_secs(u64::from(config.media_grace_hours) * 60 * 60);
+        match sweep(&media_dir, &downloads, grace).await {
+            Ok((files, bytes)) => (orphaned_files, orphaned_bytes) = (files, bytes),
+            Err(e) => errors.push(format!("media sweep: {}", e)),
+        }
+    }
+    MaintenanceReport {
+        started,
+        finished: Utc::now(),
+        database_rows,
+        expired_bundles,
+        orphaned_files,
+        orphaned_bytes,
+        errors,
+    }
+}
+
+/// Deletes blobs and partial downloads that no download refers to and that
+/// haven't changed for `grace`. Anything else in the media directory is
+/// left alone. Returns how many files went and their size.
+async fn sweep(
+    media_dir: &Path,
+    downloads: &[Download],
+    grace: Duration,
+) -> io::Result<(usize, u64)> {
+    let cutoff = SystemTime::now()
+        .checked_sub(grace)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let (mut files, mut bytes) = (0, 0);
+    for (dir, file, size) in admin::media_files(media_dir).await? {
+        let ours = if dir == media::BLOBS {
+            file.len() == 64 && file.bytes().all(|b| b.is_ascii_hexdigit())
+        } else {
+            dir.parse::<Uuid>().is_ok() && file.parse::<Uuid>().is_ok()
+        };
+        let path = media_dir.join(&dir).join(&file);
+        let referred = downloads.iter().any(|d| {
+            d.path.as_ref() == Some(&path)
+                || (d.user.to_string() == dir && d.episode.to_string() == file)
+        });
+        if !ours || referred {
+            continue;
+        }
+        if fs::metadata(&path).await?.modified()? > cutoff {
+            continue;
+        }
+        fs::remove_file(&path).await?;
+        files += 1;
+        bytes += size;
+    }
+    Ok((files, bytes))
+}
@@ -0,0 +1,71 @@
+//! Finished downloads are kept once per content hash, at
+//! `<media dir>/blobs/<sha256>`, however many users downloaded them. The
+//! downloads pointing at a blob are its references; it is deleted with the
+//! last one.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::{
+    downloads::{Download, DownloadStatus},
+    DB,
+};
+
+/// Directory under the media dir holding the blobs. Never a user ID.
+pub const BLOBS: &str = "blobs";
+
+/// `Cache-Control` for the `/media/<sha256>` routes. Their URLs change
+/// whenever the content does, so caches can keep responses for good.
+pub const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// Moves a verified file into the blob store, or drops it if the blob is
+/// already there, and returns the blob's path. Call with the state locked so
+/// a concurrent `release` can't remove the blob in between.
+pub async fn store(media_dir: &Path, file: &Path, sha256: &str) -> io::Result<PathBuf> {
+    let dir = media_dir.join(BLOBS);
+    let blob = dir.join(sha256);
+    if fs::try_exists(&blob).await? {
+        fs::remove_file(file).await?;
+    } else {
+        fs::create_dir_all(&dir).await?;
+        fs::rename(file, &blob).await?;
+    }
+    Ok(blob)
+}
+
+/// How many downloads reference the file at `path`.
+pub fn refs<D: DB>(db: &D, path: &Path) -> usize {
+    db.all_downloads()
+        .iter()
+        .filter(|d| d.path.as_deref() == Some(path))
+        .count()
+}
+
+/// A finished download of `episode` whose file can be shared.
+pub fn find<D: DB>(db: &D, episode: Uuid) -> Option<Download> {
+    db.all_downloads()
+        .into_iter()
+        .find(|d| d.episode == episode && d.status == DownloadStatus::Done && d.path.is_some())
+}
+
+/// A finished download whose blob has this hash.
+pub fn by_hash<D: DB>(db: &D, sha256: &str) -> Option<Download> {
+    db.all_downloads().into_iter().find(|d| {
+        d.sha256.as_deref() == Some(sha256) && d.status == DownloadStatus::Done && d.path.is_some()
+    })
+}
+
+/// Deletes the file of a download that was just removed from the DB, unless
+/// other downloads still reference it.
+pub async fn release<D: DB>(db: &D, download: &Download) {
+    if let Some(path) = &download.path {
+        if refs(db, path) == 0 {
+            let _ = fs::remove_file(path).await;
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Signed, expiring links to an episode's audio, for players that can't log
+//! in, like smart speakers. The link's query carries its expiry and an
+//! HMAC-SHA256 of its path and query keyed with `secret`, so nothing
+//! long-lived ends up in the URL and it can't be changed to reach other
+//! media. With `require` set, the audio and `/media` routes only answer
+//! signed links or a logged in user.
+
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, OriginalUri, Path, State},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::media_links::{CreateMediaLink, MediaLink};
+
+use crate::{profiles::Acting, stream::Quality, AppState, Error, DB};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct MediaLinkConfig {
+    /// Keys the links' signatures. Changing it breaks every link handed out.
+    pub secret: String,
+    /// Where players reach the server, e.g. `https://pods.example.org`. Unset
+    /// means the `Host` the link was asked for.
+    #[serde(default)]
+    pub public_url: Option<String>,
+    /// Refuse unsigned media requests from anyone not logged in.
+    #[serde(default)]
+    pub require: bool,
+    #[serde(default = "default_ttl")]
+    pub default_ttl_secs: u64,
+    #[serde(default = "max_ttl")]
+    pub max_ttl_secs: u64,
+}
+
+fn default_ttl() -> u64 {
+    60 * 60
+}
+
+fn max_ttl() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn signature(secret: &str, path: &str, query: &str) -> String {
+    // HMAC takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length");
+    mac.update(format!("{}?{}", path, query).as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// `query` with `expires` and the signature of `path` added.
+pub fn sign(secret: &str, path: &str, query: &str, expires: i64) -> String {
+    let query = match query {
+        "" => format!("expires={}", expires),
+        q => format!("{}&expires={}", q, expires),
+    };
+    let sig = signature(secret, path, &query);
+    format!("{}&sig={}", query, sig)
+}
+
+/// A request URI with any signature masked, for logs and error reports, so
+/// reading them doesn't hand out working links.
+pub(crate) fn redact(uri: &str) -> String {
+    let Some((path, query)) = uri.split_once('?') else {
+        return uri.to_string();
+    };
+    let query: Vec<_> = query
+        .split('&')
+        .map(|pair| match pair.starts_with("sig=") {
+            true => "sig=redacted",
+            false => pair,
+        })
+        .collect();
+    format!("{}?{}", path, query.join("&"))
+}
+
+/// Who a media request acts for: the user a signed link was made for, or
+/// else as with [`Acting`]. `expires` is set when it came with a signed
+/// link.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MediaAccess {
+    pub user: Option<Uuid>,
+    pub expires: Option<i64>,
+}
+
+/// The link's `expires` and `user`, if its signature is ours and it hasn't
+/// expired.
+fn verify(secret: &str, path: &str, query: &str) -> Option<MediaAccess> {
+    let mut signed = vec![];
+    let mut sig = None;
+    let mut access = MediaAccess::default();
+    for pair in query.split('&') {
+        match pair.split_once('=') {
+            Some(("sig", s)) => sig = Some(s),
+            Some(("expires", e)) => {
+                access.expires = e.parse().ok();
+                signed.push(pair);
+            }
+            Some(("user", u)) => {
+                access.user = u.parse().ok();
+                signed.push(pair);
+            }
+            _ => signed.push(pair),
+        }
+    }
+    let expected = signature(secret, path, &signed.join("&"));
+    // In constant time, so timing doesn't give away how much of a guess matched
+    let matches = sig.is_some_and(|s| {
+        s.len() == expected.len()
+            && s.bytes()
+                .zip(expected.bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    });
+    let fresh = access.expires.is_some_and(|e| e >= Utc::now().timestamp());
+    (matches && fresh).then_some(access)
+}
+
+#[async_trait]
+impl<D: DB + Send> FromRequestParts<Arc<Mutex<AppState<D>>>> for MediaAccess {
+    type Rejection = StatusCode;
+
+    /// `403` for a bad or expired signature, and `401` for an unsigned
+    /// request when links are required and no one is logged in.
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<Mutex<AppState<D>>>,
+    ) -> Result<MediaAccess, StatusCode> {
+        let Ok(Acting(user)) = Acting::from_request_parts(parts, state).await;
+        let unsigned = MediaAccess {
+            user,
+            expires: None,
+        };
+        let s = state.lock().await;
+        let Some(config) = &s.config.media_links else {
+            return Ok(unsigned);
+        };
+        let query = parts.uri.query().unwrap_or_default();
+        if query.split('&').any(|pair| pair.starts_with("sig=")) {
+            return verify(&config.secret, parts.uri.path(), query).ok_or(StatusCode::FORBIDDEN);
+        }
+        if config.require && s.current_user.is_none() {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Ok(unsigned)
+    }
+}
+
+/// Where the API is reached from outside: `public_url`, or else the `Host`
+/// the request came to, with any prefix it was mounted under, like a
+/// tenant's.
+pub(crate) fn base_url(
+    public_url: Option<&str>,
+    headers: &HeaderMap,
+    original: &str,
+    path: &str,
+) -> Option<String> {
+    let base = match public_url {
+        Some(url) => url.trim_end_matches('/').to_string(),
+        None => format!("http://{}", headers.get(header::HOST)?.to_str().ok()?),
+    };
+    let prefix = original.strip_suffix(path).unwrap_or_default();
+    Some(format!("{}{}", base, prefix))
+}
+
+/// A link to the episode's audio that works without logging in until it
+/// expires. `404` unless `[media_links]` is configured.
+pub async fn create<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Acting(user): Acting,
+    Path(id): Path<Uuid>,
+    OriginalUri(original): OriginalUri,
+    headers: HeaderMap,
+    Json(req): Json<CreateMediaLink>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let Some(config) = &s.config.media_links else {
+        return (StatusCode::NOT_FOUND, Json(None));
+    };
+    let Some(user) = user else {
+        return (StatusCode::UNAUTHORIZED, Json(None));
+    };
+    match s.db.get_episode(id) {
+        Ok(_) => {}
+        Err(Error::NotFound) => return (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+    let ttl = req
+        .ttl_secs
+        .unwrap_or(config.default_ttl_secs)
+        .min(config.max_ttl_secs);
+    // Whole seconds, like the link's own `expires`
+    let expires = Utc::now().timestamp() + ttl as i64;
+    let path = format!("/episodes/{}/audio", id);
+    let mut params = vec![];
+    if req.quality == Quality::Low {
+        params.push("quality=low".to_string());
+    }
+    if req.trim {
+        params.push("trim=true".to_string());
+        params.push(format!("user={}", user));
+    }
+    let query = sign(&config.secret, &path, &params.join("&"), expires);
+    let link_path = original.path().strip_suffix("/link").unwrap_or(&path);
+    let Some(base) = base_url(config.public_url.as_deref(), &headers, link_path, &path) else {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    };
+    let link = MediaLink {
+        url: format!("{}{}?{}", base, path, query),
+        expires: DateTime::from_timestamp(expires, 0).unwrap_or_default(),
+    };
+    (StatusCode::CREATED, Json(Some(link)))
+}
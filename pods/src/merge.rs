@@ -0,0 +1,206 @@
+//! Folding a duplicate account into another one.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{NaiveDateTime, Timelike, Utc};
+use tokio::{fs, sync::Mutex};
+use uuid::Uuid;
+
+pub use pods_types::merge::{MergeReport, MergeRequest, Position};
+
+use crate::{
+    current_admin,
+    downloads::DownloadStatus,
+    gpodder::{ActionKind, EpisodeAction},
+    media, profiles, AppState, Error, DB,
+};
+
+/// The furthest play position per episode in a user's history.
+fn positions(actions: &[EpisodeAction]) -> HashMap<&str, (&EpisodeAction, u32)> {
+    let mut furthest: HashMap<&str, (&EpisodeAction, u32)> = HashMap::new();
+    for a in actions.iter().filter(|a| a.action == ActionKind::Play) {
+        let Some(position) = a.position else {
+            continue;
+        };
+        let entry = furthest.entry(&a.episode).or_insert((a, position));
+        if position > entry.1 {
+            *entry = (a, position);
+        }
+    }
+    furthest
+}
+
+/// Moves `:id`'s subscriptions, history, inbox, queue, annotations,
+/// scrobbler and downloads to `into` and deletes it. Without
+/// `"dry_run": false` only the report is returned.
+pub async fn merge_user<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(from): Path<Uuid>,
+    Json(req): Json<MergeRequest>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    if let Err(status) = current_admin(s) {
+        return (status, Json(None));
+    }
+    if from == req.into {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    }
+    match merge(s, from, req.into, req.dry_run).await {
+        Ok(report) => (StatusCode::OK, Json(Some(report))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(Error::Busy) => (StatusCode::CONFLICT, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+async fn merge<D: DB>(
+    s: &mut AppState<D>,
+    from: Uuid,
+    into: Uuid,
+    dry_run: bool,
+) -> Result<MergeReport, Error> {
+    let source = s.db.get_user(from)?;
+    let target = s.db.get_user(into)?;
+    let source_downloads = s.db.downloads_for_user(from)?;
+    let target_downloads = s.db.downloads_for_user(into)?;
+    // The worker holds on to in-flight downloads; moving one under it would
+    // lose the file
+    if source_downloads
+        .iter()
+        .any(|d| d.status == DownloadStatus::Downloading)
+    {
+        return Err(Error::Busy);
+    }
+
+    let subscriptions_added: Vec<String> = source
+        .subscribed
+        .iter()
+        .filter(|rss| !target.subscribed.contains(rss))
+        .cloned()
+        .collect();
+
+    let source_actions = s.db.episode_actions(from)?;
+    let target_actions = s.db.episode_actions(into)?;
+    let theirs = positions(&target_actions);
+    let timestamp = Utc::now().naive_utc().with_nanosecond(0).unwrap();
+    let raised: Vec<EpisodeAction> = positions(&source_actions)
+        .into_values()
+        .filter(|(a, p)| theirs.get(a.episode.as_str()).is_none_or(|(_, q)| p > q))
+        .map(|(a, position)| raised_action(a, position, timestamp))
+        .collect();
+
+    let target_inbox = s.db.inbox(into)?;
+    let mut inbox = s.db.inbox(from)?;
+    inbox.retain(|e| !target_inbox.contains(e));
+    let target_queue = s.db.queue(into)?;
+    let mut queue = s.db.queue(from)?;
+    queue.retain(|e| !target_queue.contains(e));
+    let annotations = s.db.annotations(from)?;
+    let scrobbler = match s.db.scrobbler(into)? {
+        Some(_) => None,
+        None => s.db.scrobbler(from)?,
+    };
+
+    let (duplicates, moved): (Vec<_>, Vec<_>) = source_downloads
+        .into_iter()
+        .partition(|d| target_downloads.iter().any(|t| t.episode == d.episode));
+
+    let report = MergeReport {
+        from,
+        into,
+        dry_run,
+        subscriptions_added: subscriptions_added.clone(),
+        episode_actions_moved: source_actions.len(),
+        positions_raised: raised
+            .iter()
+            .map(|a| Position {
+                podcast: a.podcast.clone(),
+                episode: a.episode.clone(),
+                position: a.position.unwrap_or(0),
+            })
+            .collect(),
+        downloads_moved: moved.len(),
+        downloads_dropped: duplicates.len(),
+        inbox_moved: inbox.len(),
+        queue_moved: queue.len(),
+        annotations_moved: annotations.len(),
+        scrobbler_moved: scrobbler.is_some(),
+    };
+    if dry_run {
+        return Ok(report);
+    }
+
+    for rss in subscriptions_added {
+        s.db.subscribe(into, rss.clone())?;
+        let custom = s.db.subscription_override(from, rss.clone())?;
+        if custom.is_some() {
+            s.db.set_subscription_override(into, rss, custom)?;
+        }
+    }
+    let mut history = source_actions;
+    history.extend(raised);
+    s.db.record_episode_actions(into, history)?;
+    s.db.add_to_inbox(into, inbox)?;
+    for e in queue {
+        s.db.add_to_queue(into, e)?;
+    }
+    for a in annotations {
+        s.db.add_annotation(into, a)?;
+    }
+    if scrobbler.is_some() {
+        s.db.set_scrobbler(into, scrobbler)?;
+    }
+
+    for d in duplicates {
+        s.db.delete_download(d.id)?;
+        media::release(&s.db, &d).await;
+    }
+    let from_dir = s.config.media_dir.join(from.to_string());
+    let dir = s.config.media_dir.join(into.to_string());
+    for mut d in moved {
+        // Only partial downloads are in the user's directory; finished ones
+        // are shared blobs and stay put
+        if let Some(path) = d.path.as_ref().filter(|p| p.starts_with(&from_dir)) {
+            let dest = dir.join(d.episode.to_string());
+            let _ = fs::create_dir_all(&dir).await;
+            if fs::rename(path, &dest).await.is_ok() {
+                d.path = Some(dest);
+            }
+        }
+        d.user = into;
+        s.db.save_download(d)?;
+    }
+    let _ = fs::remove_dir(from_dir).await;
+
+    // Don't leave the instance without its admin
+    if source.admin && !target.admin {
+        let mut merged = s.db.get_user(into)?;
+        merged.admin = true;
+        s.db.update_user(merged)?;
+    }
+    // Profiles move with their account
+    for mut p in profiles::of(&s.db, from)? {
+        p.profile_of = (p.id != into).then_some(into);
+        s.db.update_user(p)?;
+    }
+    s.db.delete_user(from)?;
+    if s.current_user == Some(from) {
+        s.current_user = Some(into);
+    }
+    Ok(report)
+}
+
+fn raised_action(a: &EpisodeAction, position: u32, timestamp: NaiveDateTime) -> EpisodeAction {
+    EpisodeAction {
+        device: None,
+        timestamp,
+        position: Some(position),
+        ..a.clone()
+    }
+}
@@ -0,0 +1,150 @@
+//! Pushes listening and ingestion metrics to anything that takes InfluxDB
+//! line protocol over HTTP (InfluxDB 1.x and 2.x, VictoriaMetrics, ...), to
+//! graph next to other time series.
+//!
+//! Every push writes running totals stamped with the current time:
+//!
+//! - `pods_listening,user=<name>,user_id=<ID>,podcast=<name>` with
+//!   `seconds` and `plays`, per user and podcast, worked out like
+//!   `/users/<user ID>/stats`
+//! - `pods_ingestion` with instance-wide `users`, `podcasts`, `episodes` and
+//!   `downloads`
+//! - `pods_feed,podcast=<name>` with `episodes` and, once fetched,
+//!   `last_refreshed` in Unix seconds
+
+use std::{collections::HashMap, fmt::Write, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use reqwest::{header, Url};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::{stats, AppState, Error, DB};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct MetricsExportConfig {
+    /// The write endpoint, with any database or bucket parameters, e.g.
+    /// `http://influxdb:8086/api/v2/write?org=home&bucket=pods`.
+    pub url: String,
+    /// Sent as `Authorization: Token <token>`.
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default = "default_interval")]
+    pub interval_secs: u64,
+}
+
+fn default_interval() -> u64 {
+    60
+}
+
+/// The endpoint without its query or user info, for logs: InfluxDB 1.x
+/// takes credentials as `?u=...&p=...`.
+fn endpoint(url: &str) -> String {
+    let Ok(url) = Url::parse(url) else {
+        return "the metrics endpoint".to_string();
+    };
+    let port = url.port().map(|p| format!(":{}", p)).unwrap_or_default();
+    let host = url.host_str().unwrap_or_default();
+    format!("{}://{}{}{}", url.scheme(), host, port, url.path())
+}
+
+pub async fn worker<D: DB + Send + 'static>(state: Arc<Mutex<AppState<D>>>) {
+    let Some(config) = state.lock().await.config.metrics_export.clone() else {
+        return;
+    };
+    // Not the fetcher: the endpoint is the operator's, and usually on a
+    // private address the SSRF guard would refuse
+    let client = reqwest::Client::new();
+    let endpoint = endpoint(&config.url);
+    loop {
+        let body = lines(&*state.lock().await);
+        match body {
+            Ok(body) => {
+                let mut req = client.post(&config.url).body(body);
+                if let Some(token) = &config.token {
+                    req = req.header(header::AUTHORIZATION, format!("Token {}", token));
+                }
+                match req.send().await.and_then(|r| r.error_for_status()) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        eprintln!("metrics export to {} failed: {}", endpoint, e.without_url())
+                    }
+                }
+            }
+            Err(e) => eprintln!("metrics export skipped: {:?}", e),
+        }
+        tokio::time::sleep(Duration::from_secs(config.interval_secs.max(1))).await;
+    }
+}
+
+/// Escapes a tag value for line protocol.
+fn tag(value: &str) -> String {
+    // Empty tag values aren't allowed
+    if value.is_empty() {
+        return "-".to_string();
+    }
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' | '=' | ' ' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            // Line protocol has no escape for these
+            '\n' | '\r' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// One push's worth of metrics.
+fn lines<D: DB>(s: &AppState<D>) -> Result<String, Error> {
+    let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    let mut out = String::new();
+
+    let podcasts = s.db.podcasts()?;
+    let names: HashMap<&str, &str> = podcasts
+        .iter()
+        .map(|p| (p.rss.as_str(), p.name.as_str()))
+        .collect();
+    let (_, users) = s.db.users(None, 0, usize::MAX)?;
+    for u in &users {
+        let actions = s.db.episode_actions(u.id)?;
+        let mut by_podcast: HashMap<&str, (u64, u64)> = HashMap::new();
+        for (a, secs) in stats::listened(&actions) {
+            let totals = by_podcast.entry(a.podcast.as_str()).or_default();
+            *totals = (totals.0 + u64::from(secs), totals.1 + 1);
+        }
+        for (rss, (secs, plays)) in by_podcast {
+            // Podcasts the server doesn't know go by their feed URL
+            let podcast = names.get(rss).copied().unwrap_or(rss);
+            let _ = writeln!(
+                out,
+                "pods_listening,user={},user_id={},podcast={} seconds={}i,plays={}i {}",
+                tag(&u.name),
+                u.id,
+                tag(podcast),
+                secs,
+                plays,
+                now
+            );
+        }
+    }
+
+    let db = s.db.stats()?;
+    let _ = writeln!(
+        out,
+        "pods_ingestion users={}i,podcasts={}i,episodes={}i,downloads={}i {}",
+        db.users, db.podcasts, db.episodes, db.downloads, now
+    );
+    for p in &podcasts {
+        let episodes = s.db.episodes(p.rss.clone())?.len();
+        let mut fields = format!("episodes={}i", episodes);
+        if let Some(at) = s.db.last_refreshed(p.rss.clone())? {
+            let _ = write!(fields, ",last_refreshed={}i", at.timestamp());
+        }
+        let _ = writeln!(out, "pods_feed,podcast={} {} {}", tag(&p.name), fields, now);
+    }
+    Ok(out)
+}
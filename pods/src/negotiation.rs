@@ -0,0 +1,139 @@
+//! Response bodies in the format the `Accept` header asks for: JSON unless
+//! the client prefers XML or MessagePack, for embedded players without a
+//! JSON parser to spare.
+
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use crate::{Episode, PodcastChannel};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Json,
+    Xml,
+    MessagePack,
+}
+
+impl Format {
+    fn from_media_type(media_type: &str) -> Option<Format> {
+        match media_type.trim().to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" | "*/*" => Some(Format::Json),
+            "application/xml" | "text/xml" => Some(Format::Xml),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Format::MessagePack)
+            }
+            _ => None,
+        }
+    }
+
+    /// The most preferred supported format in an `Accept` header. Anything
+    /// unsupported gets JSON rather than a `406`.
+    pub fn negotiate(headers: &HeaderMap) -> Format {
+        let Some(accept) = headers.get(header::ACCEPT).and_then(|a| a.to_str().ok()) else {
+            return Format::Json;
+        };
+        let mut best: Option<(f32, Format)> = None;
+        for range in accept.split(',') {
+            let mut parts = range.split(';');
+            let Some(format) = parts.next().and_then(Format::from_media_type) else {
+                continue;
+            };
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            // Ties go to the earlier entry
+            if q > 0.0 && best.is_none_or(|(best_q, _)| q > best_q) {
+                best = Some((q, format));
+            }
+        }
+        best.map(|(_, format)| format).unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Format {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Format, Infallible> {
+        Ok(Format::negotiate(&parts.headers))
+    }
+}
+
+/// How a body is laid out as XML, which unlike JSON needs element names.
+pub trait Xml {
+    fn to_xml(&self) -> Result<String, quick_xml::SeError>;
+}
+
+/// A `<list>` root with an `<item>` element per entry.
+fn xml_list<T: Serialize>(
+    list: &str,
+    item: &str,
+    items: &[T],
+) -> Result<String, quick_xml::SeError> {
+    let mut xml = format!("<{}>", list);
+    for i in items {
+        xml.push_str(&quick_xml::se::to_string_with_root(item, i)?);
+    }
+    xml.push_str(&format!("</{}>", list));
+    Ok(xml)
+}
+
+impl Xml for Vec<PodcastChannel> {
+    fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        xml_list("podcasts", "podcast", self)
+    }
+}
+
+impl Xml for Vec<Episode> {
+    fn to_xml(&self) -> Result<String, quick_xml::SeError> {
+        xml_list("episodes", "episode", self)
+    }
+}
+
+/// Like `Json(Option<T>)`, in the request's [`Format`]. `None` is `null` in
+/// JSON and MessagePack, and an empty body in XML.
+pub struct Negotiated<T>(pub Format, pub Option<T>);
+
+impl<T: Serialize + Xml> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> Response {
+        let Negotiated(format, body) = self;
+        let mut resp = match format {
+            Format::Json => Json(body).into_response(),
+            Format::Xml => {
+                let xml = match body.as_ref().map(Xml::to_xml).transpose() {
+                    Ok(xml) => xml.unwrap_or_default(),
+                    Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                };
+                let xml = match xml.is_empty() {
+                    true => xml,
+                    false => format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>{}", xml),
+                };
+                ([(header::CONTENT_TYPE, "application/xml")], xml).into_response()
+            }
+            Format::MessagePack => {
+                let mut bytes = vec![];
+                // Human readable, so IDs and dates are strings as in JSON
+                let mut ser = rmp_serde::Serializer::new(&mut bytes)
+                    .with_struct_map()
+                    .with_human_readable();
+                if body.serialize(&mut ser).is_err() {
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+                ([(header::CONTENT_TYPE, "application/msgpack")], bytes).into_response()
+            }
+        };
+        resp.headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("accept"));
+        resp
+    }
+}
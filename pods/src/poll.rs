@@ -0,0 +1,123 @@
+//! `GET /poll`: holds the request until there are events for the user or
+//! the wait runs out, for clients that can't keep a stream open. Events are
+//! kept in memory, the latest [`KEEP`] of them.
+
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use tokio::{
+    sync::{Mutex, Notify},
+    time::Instant,
+};
+use uuid::Uuid;
+
+pub use pods_types::poll::{Event, EventKind, Poll, PollQuery};
+
+use crate::{explicit, profiles::Acting, AppState, Episode, DB};
+
+/// Events kept for polls to catch up on.
+const KEEP: usize = 1000;
+const DEFAULT_WAIT_SECS: u32 = 25;
+const MAX_WAIT_SECS: u32 = 60;
+
+/// Recent events and who they're for.
+#[derive(Clone, Default)]
+pub struct Events {
+    log: VecDeque<(Uuid, Event)>,
+    /// Cursor of the latest event; they count up from 1.
+    latest: u64,
+    notify: Arc<Notify>,
+}
+
+impl Events {
+    pub fn push(&mut self, user: Uuid, kind: EventKind) {
+        self.latest += 1;
+        let event = Event {
+            cursor: self.latest,
+            at: Utc::now(),
+            kind,
+        };
+        self.log.push_back((user, event));
+        if self.log.len() > KEEP {
+            self.log.pop_front();
+        }
+        self.notify.notify_waiters();
+    }
+
+    /// `user`'s events after `since`, and whether some were dropped.
+    fn after(&self, user: Uuid, since: u64) -> (Vec<Event>, bool) {
+        let oldest = self.log.front().map_or(self.latest + 1, |(_, e)| e.cursor);
+        // A cursor from before a restart is ahead of the count
+        let missed = since + 1 < oldest || since > self.latest;
+        let events = self
+            .log
+            .iter()
+            .filter(|(u, e)| *u == user && e.cursor > since)
+            .map(|(_, e)| e.clone())
+            .collect();
+        (events, missed)
+    }
+}
+
+/// Tells each of `users` about the episodes a refresh put in their inbox,
+/// leaving out ones they hide.
+pub fn new_episodes<D: DB>(s: &mut AppState<D>, users: &[Uuid], rss: &str, episodes: &[Episode]) {
+    let Ok(podcast) = s.db.get_podcast(rss.to_string()) else {
+        return;
+    };
+    for user in users {
+        let Ok(hide) = explicit::hidden(s, Some(*user)) else {
+            continue;
+        };
+        let mut shown = episodes.to_vec();
+        explicit::filter_episodes(&s.db, hide, &mut shown);
+        if !shown.is_empty() {
+            let episodes = shown.iter().map(|e| e.id).collect();
+            let kind = EventKind::NewEpisodes {
+                podcast: podcast.id,
+                episodes,
+            };
+            s.events.push(*user, kind);
+        }
+    }
+}
+
+pub async fn poll<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Acting(user): Acting,
+    Query(query): Query<PollQuery>,
+) -> impl IntoResponse {
+    let Some(user) = user else {
+        return (StatusCode::UNAUTHORIZED, Json(None));
+    };
+    let wait = query.wait.unwrap_or(DEFAULT_WAIT_SECS).min(MAX_WAIT_SECS);
+    let deadline = Instant::now() + Duration::from_secs(wait.into());
+    let mut since = query.since;
+    loop {
+        let s = state.lock().await;
+        let since = *since.get_or_insert(s.events.latest);
+        let (events, missed) = s.events.after(user, since);
+        if !events.is_empty() || missed || Instant::now() >= deadline {
+            let poll = Poll {
+                cursor: s.events.latest,
+                events,
+                missed,
+            };
+            return (StatusCode::OK, Json(Some(poll)));
+        }
+        // Events for anyone wake every poll; the loop sorts out whose they
+        // are. Registered before the lock is released so none slip by
+        let notify = s.events.notify.clone();
+        let notified = notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        drop(s);
+        let _ = tokio::time::timeout_at(deadline, notified).await;
+    }
+}
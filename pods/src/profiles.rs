@@ -0,0 +1,183 @@
+//! Profiles under one account, each with its own subscriptions, queue and
+//! playback state, like a media server's household profiles.
+//!
+//! A profile is a [`User`] whose `profile_of` names its account. It can't
+//! log in; a request sent with the account logged in (or to the account's
+//! `/users/<id>/…` routes) switches to it with the [`HEADER`] header or the
+//! `profile` query parameter. [`switch`] checks the profile belongs to the
+//! account and points the request's user routes at it before routing, so
+//! the per-user handlers don't need to know about profiles. Handlers that
+//! act for "the logged in user" take [`Acting`] instead.
+
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path, Query, State},
+    http::{request::Parts, Request, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use tokio::{fs, sync::Mutex};
+use uuid::Uuid;
+
+pub use pods_types::profiles::{CreateProfile, HEADER};
+
+use crate::{media, validation::Valid, AppState, CreateUser, Error, User, DB};
+
+/// Set on requests that switched to a profile.
+#[derive(Clone, Copy, Debug)]
+pub struct Switched(pub Uuid);
+
+/// Who a request acts for: the profile it switched to, or else whoever is
+/// logged in.
+pub struct Acting(pub Option<Uuid>);
+
+#[async_trait]
+impl<D: DB + Send> FromRequestParts<Arc<Mutex<AppState<D>>>> for Acting {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<Mutex<AppState<D>>>,
+    ) -> Result<Acting, Self::Rejection> {
+        if let Some(Switched(profile)) = parts.extensions.get() {
+            return Ok(Acting(Some(*profile)));
+        }
+        Ok(Acting(state.lock().await.current_user))
+    }
+}
+
+/// An account's profiles, by name.
+pub fn of<D: DB>(db: &D, account: Uuid) -> Result<Vec<User>, Error> {
+    let (_, users) = db.users(None, 0, usize::MAX)?;
+    Ok(users
+        .into_iter()
+        .filter(|u| u.profile_of == Some(account))
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct SwitchQuery {
+    profile: Option<String>,
+}
+
+/// The user ID a `/users/<id>/…` path starts with, and the rest of it.
+fn path_user(path: &str) -> Option<(Uuid, &str)> {
+    let rest = path.strip_prefix("/users/")?;
+    let (id, rest) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    Some((id.parse().ok()?, rest))
+}
+
+/// Switches the request to the profile it asks for. Runs before routing, so
+/// the path it rewrites is the one that gets routed.
+pub async fn switch<D: DB, B>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let header = req.headers().get(HEADER).map(|v| v.to_str().ok());
+    let wanted = match header {
+        Some(v) => v.map(str::to_string),
+        None => match Query::<SwitchQuery>::try_from_uri(req.uri()) {
+            Ok(Query(q)) => match q.profile {
+                Some(p) => Some(p),
+                None => return next.run(req).await,
+            },
+            Err(_) => return next.run(req).await,
+        },
+    };
+    let Some(profile) = wanted.and_then(|p| p.parse::<Uuid>().ok()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let in_path = path_user(req.uri().path());
+    {
+        let s = state.lock().await;
+        let Some(account) = in_path.map(|(id, _)| id).or(s.current_user) else {
+            return StatusCode::UNAUTHORIZED.into_response();
+        };
+        let belongs = profile == account
+            || s.db
+                .get_user(profile)
+                .is_ok_and(|p| p.profile_of == Some(account));
+        if !belongs {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+    }
+
+    // Managing profiles stays with the account
+    if let Some((account, rest)) = in_path.filter(|(_, rest)| !rest.starts_with("/profiles")) {
+        if account != profile {
+            let query = req.uri().query().map(|q| format!("?{}", q));
+            let rewritten = format!("/users/{}{}{}", profile, rest, query.unwrap_or_default());
+            match rewritten.parse::<Uri>() {
+                Ok(uri) => *req.uri_mut() = uri,
+                Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+            }
+        }
+    }
+    req.extensions_mut().insert(Switched(profile));
+    next.run(req).await
+}
+
+pub async fn list<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(account): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    match s.db.get_user(account).and_then(|_| of(&s.db, account)) {
+        Ok(profiles) => (StatusCode::OK, Json(Some(profiles))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn create<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(account): Path<Uuid>,
+    Valid(Json(req)): Valid<Json<CreateProfile>>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    match s.db.get_user(account) {
+        // Profiles don't nest
+        Ok(u) if u.profile_of.is_some() => return (StatusCode::BAD_REQUEST, Json(None)),
+        Ok(_) => {}
+        Err(Error::NotFound) => return (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+    let user = CreateUser { name: req.name };
+    let created = s.db.create_user(user).and_then(|mut u| {
+        u.admin = false;
+        u.profile_of = Some(account);
+        s.db.update_user(u)
+    });
+    match created {
+        Ok(u) => (StatusCode::CREATED, Json(Some(u))),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Deletes a profile with its history and downloads.
+pub async fn delete<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((account, profile)): Path<(Uuid, Uuid)>,
+) -> StatusCode {
+    let s = &mut *state.lock().await;
+    match s.db.get_user(profile) {
+        Ok(p) if p.profile_of == Some(account) => {}
+        Ok(_) | Err(Error::NotFound) => return StatusCode::NOT_FOUND,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    }
+    let downloads = s.db.downloads_for_user(profile).unwrap_or_default();
+    if s.db.delete_user(profile).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    for d in &downloads {
+        media::release(&s.db, d).await;
+    }
+    let _ = fs::remove_dir_all(s.config.media_dir.join(profile.to_string())).await;
+    StatusCode::NO_CONTENT
+}
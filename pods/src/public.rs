@@ -0,0 +1,157 @@
+//! A read-only slice of the API that needs no login, for a public website
+//! to show the instance's catalog: podcasts, search and episodes. Nothing
+//! here reads users, so subscriptions, history and settings stay behind the
+//! rest of the API. Only routed when `[public_api]` is configured.
+
+use std::{cmp::Reverse, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderValue, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::public::{PodcastPage, PodcastQuery};
+
+use crate::{explicit, validation::Valid, AppState, Error, PodcastChannel, DB};
+
+const DEFAULT_PAGE: usize = 50;
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct PublicApiConfig {
+    /// Origins whose pages may call the public API from the browser, like
+    /// `https://podcasts.example.org`, or `*` for any.
+    pub allow_origins: Vec<String>,
+}
+
+pub fn routes<D: DB + Send + 'static>(config: &PublicApiConfig) -> Router<Arc<Mutex<AppState<D>>>> {
+    Router::new()
+        .route("/podcasts", get(podcasts))
+        .route("/podcasts/:id", get(podcast))
+        .route("/podcasts/:id/episodes", get(episodes))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(config.clone()),
+            cors,
+        ))
+}
+
+/// Lets the configured origins read responses. The routes are all simple
+/// `GET`s, so browsers don't send a preflight.
+async fn cors<B>(
+    State(config): State<Arc<PublicApiConfig>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let origin = req.headers().get(header::ORIGIN).cloned();
+    let mut resp = next.run(req).await;
+    let allowed = match origin {
+        _ if config.allow_origins.iter().any(|o| o == "*") => Some(HeaderValue::from_static("*")),
+        Some(origin)
+            if config
+                .allow_origins
+                .iter()
+                .any(|o| o.as_bytes() == origin.as_bytes()) =>
+        {
+            Some(origin)
+        }
+        _ => None,
+    };
+    if let Some(origin) = allowed {
+        let headers = resp.headers_mut();
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        headers.append(header::VARY, HeaderValue::from_static("origin"));
+    }
+    resp
+}
+
+/// Whether the podcast's name or description has all the words in `q`.
+fn matches(podcast: &PodcastChannel, q: &str) -> bool {
+    let text = format!("{} {}", podcast.name, podcast.description).to_lowercase();
+    q.to_lowercase()
+        .split_whitespace()
+        .all(|word| text.contains(word))
+}
+
+/// Podcasts on the instance sorted by name, a page at a time, leaving out
+/// explicit ones if the instance hides them.
+async fn podcasts<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Valid(Query(query)): Valid<Query<PodcastQuery>>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE);
+    let listed = explicit::hidden(&s, None).and_then(|hide| {
+        let mut podcasts = s.db.podcasts()?;
+        explicit::filter_podcasts(hide, &mut podcasts);
+        if let Some(q) = &query.q {
+            podcasts.retain(|p| matches(p, q));
+        }
+        podcasts.sort_by_key(|p| p.name.to_lowercase());
+        Ok(podcasts)
+    });
+    match listed {
+        Ok(podcasts) => {
+            let page = PodcastPage {
+                total: podcasts.len(),
+                offset: query.offset,
+                limit,
+                podcasts: podcasts
+                    .into_iter()
+                    .skip(query.offset)
+                    .take(limit)
+                    .collect(),
+            };
+            (StatusCode::OK, Json(Some(page)))
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+async fn podcast<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let found = explicit::hidden(&s, None).and_then(|hide| {
+        let p = s.db.get_podcast_by_id(id)?;
+        match p.explicit && hide {
+            true => Err(Error::NotFound),
+            false => Ok(p),
+        }
+    });
+    match found {
+        Ok(p) => (StatusCode::OK, Json(Some(p))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Newest first.
+async fn episodes<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let listed = explicit::hidden(&s, None).and_then(|hide| {
+        let p = s.db.get_podcast_by_id(id)?;
+        if p.explicit && hide {
+            return Err(Error::NotFound);
+        }
+        let mut episodes = s.db.episodes(p.rss)?;
+        explicit::filter_episodes(&s.db, hide, &mut episodes);
+        episodes.sort_by_key(|e| Reverse(e.published));
+        Ok(episodes)
+    });
+    match listed {
+        Ok(e) => (StatusCode::OK, Json(Some(e))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::quota::{QuotaOverride, Usage};
+
+use crate::{current_admin, AppState, Error, DB};
+
+fn usage<D: DB>(state: &AppState<D>, user: Uuid) -> Result<Usage, Error> {
+    let used_bytes = state
+        .db
+        .downloads_for_user(user)?
+        .iter()
+        .map(|d| d.reserved_bytes())
+        .sum();
+    let quota_bytes = state
+        .db
+        .quota_override(user)?
+        .or(state.config.quota.default_bytes);
+    Ok(Usage {
+        user,
+        used_bytes,
+        quota_bytes,
+    })
+}
+
+/// Checks that `user` has room for another `bytes` of media. Enclosures
+/// without an advertised length are checked as zero bytes.
+pub fn check<D: DB>(state: &AppState<D>, user: Uuid, bytes: u64) -> Result<(), Error> {
+    let u = usage(state, user)?;
+    match u.quota_bytes {
+        Some(q) if u.used_bytes + bytes > q => Err(Error::QuotaExceeded),
+        _ => Ok(()),
+    }
+}
+
+pub async fn get_usage<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> impl IntoResponse {
+    match usage(&*state.lock().await, uid) {
+        Ok(u) => (StatusCode::OK, Json(Some(u))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn set_override<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+    Json(payload): Json<QuotaOverride>,
+) -> impl IntoResponse {
+    let state = &mut state.lock().await;
+    if let Err(status) = current_admin(state) {
+        return (status, Json(None));
+    }
+    let updated = state
+        .db
+        .set_quota_override(uid, payload.bytes)
+        .and_then(|_| usage(state, uid));
+    match updated {
+        Ok(u) => (StatusCode::OK, Json(Some(u))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
@@ -0,0 +1,357 @@
+//! Re-fetches feeds on an interval and stores episodes that weren't there
+//! before, delivering them to subscribers' inboxes.
+//!
+//! Each feed's interval is an admin override if it has one, otherwise derived
+//! from how often it publishes: a fraction of the typical gap between
+//! episodes, never more often than the instance interval nor less than daily.
+//!
+//! Feeds that publish at regular times also get publish windows, learned
+//! from the hours and weekdays of recent episodes. Inside a window the feed
+//! is checked at the instance interval; outside it, no more than every
+//! `OFF_WINDOW_MINS`.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Datelike, Timelike, Utc, Weekday};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::refresh::{
+    FeedRun, IntervalSource, PublishWindows, RefreshOverride, RefreshRun, RefreshRunReport,
+    RefreshSchedule,
+};
+
+use crate::{
+    archive, current_admin,
+    health::{self, FeedFetch, FetchOutcome},
+    inbox, instance, parse_rss, poll,
+    service_accounts::{admin_or_service, Service},
+    AppState, Episode, Error, Feed, PodcastChannel, DB,
+};
+
+/// How often to look for feeds that are due.
+const TICK: Duration = Duration::from_secs(60);
+
+/// Recent episodes looked at for a feed's cadence, and the fewest it takes.
+const CADENCE_SAMPLE: usize = 10;
+const CADENCE_MIN_EPISODES: usize = 3;
+/// Checks per typical gap between episodes.
+const CHECKS_PER_GAP: i64 = 24;
+const MAX_CADENCE_MINS: u32 = 24 * 60;
+
+/// Recent episodes looked at for publish windows, and the fewest it takes.
+const WINDOW_SAMPLE: usize = 20;
+const WINDOW_MIN_EPISODES: usize = 4;
+/// An hour is a window if at least two sampled episodes and one in this many
+/// were published in it.
+const WINDOW_HOUR_SHARE: usize = 5;
+/// How long a window stays open, covering late episodes and DST shifts.
+const WINDOW_HOURS: i64 = 2;
+const OFF_WINDOW_MINS: u32 = 6 * 60;
+
+/// Refresh runs kept for `GET /admin/refresh-runs`.
+const RUN_HISTORY: usize = 500;
+
+pub async fn worker<D: DB + Send + 'static>(state: Arc<Mutex<AppState<D>>>) {
+    loop {
+        tokio::time::sleep(TICK).await;
+        refresh_due(&state).await;
+    }
+}
+
+/// The median gap between recent dated episodes, spread over
+/// `CHECKS_PER_GAP` checks.
+fn cadence_mins(episodes: &[Episode]) -> Option<u32> {
+    // `episodes` is newest first
+    let dates: Vec<_> = episodes
+        .iter()
+        .filter_map(|e| e.published)
+        .take(CADENCE_SAMPLE)
+        .collect();
+    if dates.len() < CADENCE_MIN_EPISODES {
+        return None;
+    }
+    let mut gaps: Vec<i64> = dates
+        .windows(2)
+        .map(|w| (w[0] - w[1]).num_minutes())
+        .collect();
+    gaps.sort_unstable();
+    let median = gaps[gaps.len() / 2];
+    u32::try_from(median / CHECKS_PER_GAP).ok()
+}
+
+fn publish_windows(episodes: &[Episode]) -> Option<PublishWindows> {
+    let dates: Vec<DateTime<Utc>> = episodes
+        .iter()
+        .filter_map(|e| e.published)
+        .take(WINDOW_SAMPLE)
+        .collect();
+    if dates.len() < WINDOW_MIN_EPISODES {
+        return None;
+    }
+    let mut per_hour = [0; 24];
+    for d in &dates {
+        per_hour[d.hour() as usize] += 1;
+    }
+    let hours: Vec<u32> = (0..24)
+        .filter(|&h| {
+            let n = per_hour[h as usize];
+            n >= 2 && n * WINDOW_HOUR_SHARE >= dates.len()
+        })
+        .collect();
+    if hours.is_empty() {
+        return None;
+    }
+    let mut weekdays: Vec<Weekday> = dates
+        .iter()
+        .filter(|d| hours.contains(&d.hour()))
+        .map(|d| d.weekday())
+        .collect();
+    weekdays.sort_by_key(|d| d.num_days_from_monday());
+    weekdays.dedup();
+    Some(PublishWindows { weekdays, hours })
+}
+
+fn in_window(windows: &PublishWindows, at: DateTime<Utc>) -> bool {
+    (0..WINDOW_HOURS).any(|back| {
+        let t = at - chrono::Duration::hours(back);
+        windows.weekdays.contains(&t.weekday()) && windows.hours.contains(&t.hour())
+    })
+}
+
+/// How often `rss` is checked at `now`, given the instance interval.
+pub fn interval<D: DB>(
+    db: &D,
+    rss: &str,
+    instance_mins: u32,
+    now: DateTime<Utc>,
+) -> Result<(u32, IntervalSource), Error> {
+    if let Some(mins) = db.refresh_override(rss.to_string())? {
+        return Ok((mins, IntervalSource::Override));
+    }
+    let episodes = db.episodes(rss.to_string())?;
+    let cadence = cadence_mins(&episodes).map(|m| m.min(MAX_CADENCE_MINS).max(instance_mins));
+    Ok(match (publish_windows(&episodes), cadence) {
+        (Some(w), _) if in_window(&w, now) => (instance_mins, IntervalSource::Window),
+        (Some(_), c) => (
+            c.unwrap_or(instance_mins).max(OFF_WINDOW_MINS),
+            IntervalSource::Cadence,
+        ),
+        (None, Some(c)) => (c, IntervalSource::Cadence),
+        (None, None) => (instance_mins, IntervalSource::Instance),
+    })
+}
+
+async fn refresh_due<D: DB>(state: &Mutex<AppState<D>>) {
+    let (due, http, max_bytes) = {
+        let s = state.lock().await;
+        let Ok(settings) = instance::effective(&s) else {
+            return;
+        };
+        let now = Utc::now();
+        let due: Vec<(PodcastChannel, Option<FeedFetch>)> = s
+            .db
+            .podcasts()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| {
+                let Ok((mins, _)) = interval(&s.db, &p.rss, settings.refresh_interval_mins, now)
+                else {
+                    return false;
+                };
+                let cutoff = now - chrono::Duration::minutes(mins.into());
+                s.db.last_refreshed(p.rss.clone())
+                    .ok()
+                    .flatten()
+                    .is_none_or(|t| t < cutoff)
+            })
+            .map(|p| {
+                let since = health::validators(&s.db, &p.rss);
+                (p, since)
+            })
+            .collect();
+        (due, s.http.clone(), settings.max_feed_bytes)
+    };
+    if due.is_empty() {
+        return;
+    }
+
+    let started = Utc::now();
+    let clock = Instant::now();
+    let mut feeds = vec![];
+    // Fetch without holding the lock; a slow host shouldn't stall the API
+    for (p, since) in due {
+        let rss = p.rss;
+        let (feed, report) = parse_rss(&http, rss.clone(), max_bytes, since.as_ref()).await;
+        let s = &mut *state.lock().await;
+        // Failed fetches count too, so a dead host waits a full interval
+        let _ = s.db.set_last_refreshed(rss.clone(), Utc::now());
+        let mut run = FeedRun {
+            podcast: p.id,
+            rss: rss.clone(),
+            outcome: report.fetch.outcome,
+            status: report.fetch.status,
+            elapsed_ms: report.fetch.elapsed_ms,
+            new_episodes: 0,
+        };
+        health::record(&mut s.db, &rss, report);
+        if let Ok(feed) = feed {
+            run.new_episodes = ingest(s, &rss, feed);
+        }
+        feeds.push(run);
+    }
+
+    let count = |keep: fn(&FeedRun) -> bool| feeds.iter().filter(|f| keep(f)).count();
+    let run = RefreshRun {
+        id: Uuid::new_v4(),
+        started,
+        duration_ms: clock.elapsed().as_millis() as u64,
+        attempted: feeds.len(),
+        not_modified: count(|f| f.outcome == FetchOutcome::NotModified),
+        failed: count(|f| !health::succeeded(f.outcome)),
+        new_episodes: feeds.iter().map(|f| f.new_episodes).sum(),
+    };
+    let _ = state
+        .lock()
+        .await
+        .db
+        .add_refresh_run(RefreshRunReport { run, feeds }, RUN_HISTORY);
+}
+
+/// Stores what a fresh read of `rss` says: changes to its language and
+/// explicit flag, and episodes that weren't there before, which go to
+/// subscribers' inboxes. Returns how many episodes were new.
+pub fn ingest<D: DB>(s: &mut AppState<D>, rss: &str, feed: Feed) -> usize {
+    // Feeds gain or change `<language>` and `<itunes:explicit>` now and
+    // then
+    if let Ok(mut p) = s.db.get_podcast(rss.to_string()) {
+        let language = feed.language.clone().or(p.language.clone());
+        if p.language != language || p.explicit != feed.explicit {
+            p.language = language;
+            p.explicit = feed.explicit;
+            let _ = s.db.update_podcast(p);
+        }
+    }
+    let known = s.db.episodes(rss.to_string()).unwrap_or_default();
+    // Archived podcasts keep everything, including what was pruned before
+    let pruned = match archive::is_on(&s.db, rss) {
+        true => vec![],
+        false => s.db.tombstones(rss.to_string()).unwrap_or_default(),
+    };
+    let new: Vec<Episode> = feed
+        .episodes
+        .into_iter()
+        .filter(|e| !known.iter().any(|k| same_episode(k, e)))
+        .filter(|e| !pruned.iter().any(|t| t.matches(e)))
+        .collect();
+    if new.is_empty() || s.db.add_episodes(rss.to_string(), new.clone()).is_err() {
+        return 0;
+    }
+    if let Ok(users) = inbox::deliver(&mut s.db, rss, &new) {
+        poll::new_episodes(s, &users, rss, &new);
+    }
+    new.len()
+}
+
+/// Episodes are matched by GUID, falling back to the enclosure URL for feeds
+/// without GUIDs.
+pub(crate) fn same_episode(a: &Episode, b: &Episode) -> bool {
+    match (&a.guid, &b.guid) {
+        (Some(x), Some(y)) => x == y,
+        _ => match (&a.enclosure, &b.enclosure) {
+            (Some(x), Some(y)) => x.url == y.url,
+            _ => a.title == b.title,
+        },
+    }
+}
+
+fn schedule<D: DB>(s: &AppState<D>, podcast: Uuid) -> Result<RefreshSchedule, Error> {
+    let p = s.db.get_podcast_by_id(podcast)?;
+    let instance_mins = instance::effective(s)?.refresh_interval_mins;
+    let (interval_mins, source) = interval(&s.db, &p.rss, instance_mins, Utc::now())?;
+    Ok(RefreshSchedule {
+        podcast,
+        interval_mins,
+        source,
+        last_refreshed: s.db.last_refreshed(p.rss.clone())?,
+        windows: publish_windows(&s.db.episodes(p.rss)?),
+    })
+}
+
+pub async fn get_schedule<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(podcast): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    if let Err(status) = current_admin(&s) {
+        return (status, Json(None));
+    }
+    match schedule(&s, podcast) {
+        Ok(r) => (StatusCode::OK, Json(Some(r))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn put_schedule<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(podcast): Path<Uuid>,
+    Json(payload): Json<RefreshOverride>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    if let Err(status) = current_admin(s) {
+        return (status, Json(None));
+    }
+    if payload.interval_mins == Some(0) {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    }
+    let updated =
+        s.db.get_podcast_by_id(podcast)
+            .and_then(|p| s.db.set_refresh_override(p.rss, payload.interval_mins))
+            .and_then(|_| schedule(s, podcast));
+    match updated {
+        Ok(r) => (StatusCode::OK, Json(Some(r))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Newest first.
+pub async fn runs<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    service: Option<Extension<Service>>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    if let Err(status) = admin_or_service(&s, service) {
+        return (status, Json(None));
+    }
+    match s.db.refresh_runs() {
+        Ok(r) => (StatusCode::OK, Json(Some(r))),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn run<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    service: Option<Extension<Service>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    if let Err(status) = admin_or_service(&s, service) {
+        return (status, Json(None));
+    }
+    match s.db.refresh_run(id) {
+        Ok(r) => (StatusCode::OK, Json(Some(r))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
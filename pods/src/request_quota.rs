@@ -0,0 +1,183 @@
+//! Counts each user's and service account's API requests per UTC day, by
+//! route, and answers `429` once a configured quota is used up. Requests
+//! without a login or token aren't counted.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{MatchedPath, Path, State},
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::request_quota::{RequestQuota, RouteUsage};
+
+use crate::{
+    i18n::{self, Message},
+    service_accounts::Service,
+    AppState, Error, DB,
+};
+
+/// Where callers check their quota, which isn't counted so it answers even
+/// when the quota is used up.
+const QUOTA_ROUTE: &str = "/users/:id/quota";
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct RequestQuotaConfig {
+    /// Requests a day per user. `None` means unlimited.
+    pub daily: Option<u64>,
+    /// Requests a day per service account. `None` means unlimited.
+    pub service_daily: Option<u64>,
+    /// Requests a day per user or service account to one route, keyed by
+    /// method and route pattern, e.g. `"GET /podcasts/:id/episodes"`.
+    pub routes: HashMap<String, u64>,
+}
+
+fn key(method: &str, route: &str) -> String {
+    format!("{} {}", method, route)
+}
+
+/// Midnight UTC after `day`.
+fn resets(day: NaiveDate) -> DateTime<Utc> {
+    let next = day.checked_add_days(Days::new(1)).unwrap_or(day);
+    next.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
+
+fn quota<D: DB>(state: &AppState<D>, id: Uuid, service: bool) -> Result<RequestQuota, Error> {
+    let config = &state.config.request_quota;
+    let day = Utc::now().date_naive();
+    let counts = state.db.request_counts(id, day)?;
+    let mut routes: Vec<RouteUsage> = counts
+        .iter()
+        .map(|(k, used)| {
+            let (method, route) = k.split_once(' ').unwrap_or(("", k));
+            RouteUsage {
+                method: method.to_string(),
+                route: route.to_string(),
+                used: *used,
+                limit: config.routes.get(k).copied(),
+            }
+        })
+        .collect();
+    routes.sort_by(|a, b| b.used.cmp(&a.used).then_with(|| a.route.cmp(&b.route)));
+    Ok(RequestQuota {
+        id,
+        day,
+        used: counts.values().sum(),
+        limit: if service {
+            config.service_daily
+        } else {
+            config.daily
+        },
+        resets: resets(day),
+        routes,
+    })
+}
+
+/// Counts the request against whoever made it, or turns it away with `429`
+/// until midnight UTC if that would go over their daily quota or the
+/// route's.
+pub async fn enforce<D: DB, B>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(route) = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+    else {
+        return next.run(req).await;
+    };
+    let service = req.extensions().get::<Service>().copied();
+    let key = key(req.method().as_str(), &route);
+    let over = {
+        let s = &mut *state.lock().await;
+        let caller = match service {
+            Some(Service(id)) => Some((id, true)),
+            None => s.current_user.map(|id| (id, false)),
+        };
+        match caller {
+            Some((id, service)) => over(s, id, service, &route, &key),
+            None => Ok(None),
+        }
+    };
+    match over {
+        Ok(None) => next.run(req).await,
+        Ok(Some(wait)) => {
+            let lang = i18n::negotiate(req.headers()).unwrap_or_default();
+            let mut resp = i18n::error(StatusCode::TOO_MANY_REQUESTS, Message::RequestQuota, lang);
+            resp.headers_mut()
+                .insert(header::RETRY_AFTER, wait.to_string().parse().unwrap());
+            resp
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Counts the request, or returns the seconds until the caller may make it
+/// if they're over a quota.
+fn over<D: DB>(
+    s: &mut AppState<D>,
+    id: Uuid,
+    service: bool,
+    route: &str,
+    key: &str,
+) -> Result<Option<i64>, Error> {
+    if route == QUOTA_ROUTE {
+        return Ok(None);
+    }
+    let q = quota(s, id, service)?;
+    let route_used = q
+        .routes
+        .iter()
+        .find(|r| key == self::key(&r.method, &r.route))
+        .map_or(0, |r| r.used);
+    let over = q.limit.is_some_and(|l| q.used >= l)
+        || s.config
+            .request_quota
+            .routes
+            .get(key)
+            .is_some_and(|l| route_used >= *l);
+    if over {
+        return Ok(Some((q.resets - Utc::now()).num_seconds().max(1)));
+    }
+    s.db.count_request(id, Utc::now().date_naive(), key.to_string())?;
+    Ok(None)
+}
+
+/// Today's requests by a user, or by a service account for admins. Users
+/// can only see their own.
+pub async fn get_quota<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let Some(uid) = s.current_user else {
+        return (StatusCode::UNAUTHORIZED, Json(None));
+    };
+    let admin = s.db.get_user(uid).map(|u| u.admin).unwrap_or(false);
+    if id != uid && !admin {
+        return (StatusCode::FORBIDDEN, Json(None));
+    }
+    let service = match s.db.get_user(id) {
+        Ok(_) => false,
+        Err(Error::NotFound) => match s.db.service_accounts() {
+            Ok(accounts) if accounts.iter().any(|a| a.id == id) => true,
+            Ok(_) => return (StatusCode::NOT_FOUND, Json(None)),
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+        },
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    };
+    match quota(&s, id, service) {
+        Ok(q) => (StatusCode::OK, Json(Some(q))),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
@@ -0,0 +1,116 @@
+//! The canonical resume position of an episode, so every client picks up in
+//! the same place, with the user's "rewind on resume" rules applied, and
+//! the intro and outro they skip for the podcast.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{NaiveDateTime, Utc};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::resume::{Playback, ResumePosition, RewindRule};
+
+use crate::{gpodder::ActionKind, AppState, Error, DB};
+
+/// Seconds to jump back after playback has been stopped for `idle_secs`.
+fn rewind(rules: &[RewindRule], idle_secs: i64) -> u32 {
+    rules
+        .iter()
+        .filter(|r| i64::from(r.after_mins) * 60 <= idle_secs)
+        .max_by_key(|r| r.after_mins)
+        .map_or(0, |r| r.secs)
+}
+
+pub(crate) fn resume_position<D: DB>(
+    db: &D,
+    user: Uuid,
+    episode: Uuid,
+    now: NaiveDateTime,
+) -> Result<ResumePosition, Error> {
+    let rules = db.get_user(user)?.settings.rewind_on_resume;
+    let e = db.get_episode(episode)?;
+    let audio = e.enclosure.ok_or(Error::NotFound)?.url;
+    let latest = db
+        .episode_actions(user)?
+        .into_iter()
+        .filter(|a| a.action == ActionKind::Play && a.episode == audio)
+        .filter_map(|a| Some((a.timestamp, a.position?, a.total)))
+        .max_by_key(|(timestamp, _, _)| *timestamp);
+    let (stopped, stopped_at, total) = latest.ok_or(Error::NotFound)?;
+    // Don't rewind a finished episode back into its last seconds
+    let rewound_secs = match total {
+        Some(total) if stopped_at >= total => 0,
+        _ => rewind(&rules, (now - stopped).num_seconds()).min(stopped_at),
+    };
+    Ok(ResumePosition {
+        episode,
+        position: stopped_at - rewound_secs,
+        stopped_at,
+        rewound_secs,
+        stopped,
+        total,
+    })
+}
+
+/// `404` until the user has a `play` action with a position for the episode.
+pub async fn get_resume<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((uid, episode)): Path<(Uuid, Uuid)>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    match resume_position(&s.db, uid, episode, Utc::now().naive_utc()) {
+        Ok(r) => (StatusCode::OK, Json(Some(r))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub(crate) fn playback<D: DB>(
+    db: &D,
+    user: Uuid,
+    episode: Uuid,
+    now: NaiveDateTime,
+) -> Result<Playback, Error> {
+    let trims = db
+        .subscription_override(user, db.get_episode(episode)?.podcast)?
+        .unwrap_or_default();
+    let resume = match resume_position(db, user, episode, now) {
+        Ok(r) => Some(r),
+        Err(Error::NotFound) => None,
+        Err(e) => return Err(e),
+    };
+    let skip_intro_secs = trims.skip_intro_secs.unwrap_or(0);
+    Ok(Playback {
+        episode,
+        skip_intro_secs,
+        skip_outro_secs: trims.skip_outro_secs.unwrap_or(0),
+        start_at: resume
+            .as_ref()
+            .map_or(0, |r| r.position)
+            .max(skip_intro_secs),
+        resume,
+    })
+}
+
+pub async fn get_playback<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((uid, episode)): Path<(Uuid, Uuid)>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let now = Utc::now().naive_utc();
+    match s
+        .db
+        .get_user(uid)
+        .and_then(|_| playback(&s.db, uid, episode, now))
+    {
+        Ok(p) => (StatusCode::OK, Json(Some(p))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
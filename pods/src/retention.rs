@@ -0,0 +1,110 @@
+//! Prunes old episode rows, independently of media cleanup, so a long-lived
+//! instance's database doesn't carry every episode a feed ever listed.
+//! Pruned episodes leave a tombstone so the next refresh doesn't add them
+//! back while the feed still lists them.
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use chrono::{Months, Utc};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{archive, AppState, Episode, DB};
+
+/// Either limit, or both. An episode goes once it's past either one.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct EpisodeRetention {
+    /// Episodes kept per podcast, newest first.
+    pub keep_latest: Option<usize>,
+    /// Episodes published longer ago than this go. Undated ones are only
+    /// subject to `keep_latest`.
+    pub keep_years: Option<u32>,
+}
+
+/// What's left of a pruned episode: enough to recognize it in the feed.
+#[derive(Clone, Debug)]
+pub struct Tombstone {
+    pub guid: Option<String>,
+    pub enclosure: Option<String>,
+    pub title: String,
+}
+
+impl Tombstone {
+    pub fn of(e: &Episode) -> Tombstone {
+        Tombstone {
+            guid: e.guid.clone(),
+            enclosure: e.enclosure.as_ref().map(|enc| enc.url.clone()),
+            title: e.title.clone(),
+        }
+    }
+
+    /// Matches the way refreshes match episodes: by GUID, then enclosure URL,
+    /// then title.
+    pub fn matches(&self, e: &Episode) -> bool {
+        match (&self.guid, &e.guid) {
+            (Some(x), Some(y)) => x == y,
+            _ => match (&self.enclosure, &e.enclosure) {
+                (Some(x), Some(y)) => *x == y.url,
+                _ => self.title == e.title,
+            },
+        }
+    }
+}
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub async fn worker<D: DB + Send + 'static>(state: Arc<Mutex<AppState<D>>>) {
+    let Some(policy) = state.lock().await.config.episode_retention.clone() else {
+        return;
+    };
+    loop {
+        sweep(&mut *state.lock().await, &policy);
+        tokio::time::sleep(SWEEP_INTERVAL).await;
+    }
+}
+
+/// Applies the policy to every podcast not in archive mode.
+fn sweep<D: DB>(s: &mut AppState<D>, policy: &EpisodeRetention) {
+    let cutoff = policy
+        .keep_years
+        .and_then(|y| Utc::now().checked_sub_months(Months::new(y.saturating_mul(12))));
+    let kept = in_use(&s.db);
+    for p in s.db.podcasts().unwrap_or_default() {
+        if archive::is_on(&s.db, &p.rss) {
+            continue;
+        }
+        let episodes = s.db.episodes(p.rss.clone()).unwrap_or_default();
+        let old: Vec<Uuid> = episodes
+            .iter()
+            .enumerate()
+            .filter(|(i, e)| {
+                policy.keep_latest.is_some_and(|n| *i >= n)
+                    || matches!((cutoff, e.published), (Some(c), Some(d)) if d < c)
+            })
+            .map(|(_, e)| e.id)
+            .filter(|id| !kept.contains(id))
+            .collect();
+        if !old.is_empty() {
+            let _ = s.db.prune_episodes(p.rss, old);
+        }
+    }
+}
+
+/// Episodes someone still has a hold on: downloaded, queued or annotated.
+/// These are kept whatever their age.
+fn in_use<D: DB>(db: &D) -> HashSet<Uuid> {
+    let mut kept: HashSet<Uuid> = db.all_downloads().iter().map(|d| d.episode).collect();
+    let (_, users) = db.users(None, 0, usize::MAX).unwrap_or_default();
+    for u in users {
+        kept.extend(db.queue(u.id).unwrap_or_default());
+        kept.extend(
+            db.annotations(u.id)
+                .unwrap_or_default()
+                .iter()
+                .map(|a| a.episode),
+        );
+    }
+    kept
+}
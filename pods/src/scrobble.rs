@@ -0,0 +1,286 @@
+//! Scrobbling: each episode a user finishes is sent to ListenBrainz, or to a
+//! webhook they set, with the podcast and episode it was. An episode counts
+//! as finished when its play actions first reach [`COMPLETE_PERCENT`], so
+//! later progress updates past the mark aren't sent again, but playing it
+//! through again is. Listens that fail to send are tried again on the next
+//! run. Off unless `[scrobbling]` is configured.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{NaiveDateTime, Utc};
+use pods_types::signing;
+use reqwest::header;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::scrobble::{Scrobble, ScrobbleKind, ScrobbleTarget, Scrobbler};
+
+use crate::{
+    engagement::{completion, COMPLETE_PERCENT},
+    fetcher::Fetcher,
+    gpodder::ActionKind,
+    validation::Valid,
+    AppState, Error, DB,
+};
+
+/// Most listens in one ListenBrainz submission.
+const BATCH: usize = 100;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ScrobblingConfig {
+    /// ListenBrainz, or a server with its API, like a self-hosted one.
+    pub listenbrainz_url: String,
+    pub interval_secs: u64,
+}
+
+impl Default for ScrobblingConfig {
+    fn default() -> ScrobblingConfig {
+        ScrobblingConfig {
+            listenbrainz_url: "https://api.listenbrainz.org".to_string(),
+            interval_secs: 60,
+        }
+    }
+}
+
+/// The episodes the user finished after `since`, oldest first.
+fn listens<D: DB>(db: &D, user: Uuid, since: NaiveDateTime) -> Result<Vec<Scrobble>, Error> {
+    let mut plays: Vec<_> = db
+        .episode_actions(user)?
+        .into_iter()
+        .filter(|a| a.action == ActionKind::Play)
+        .collect();
+    plays.sort_by_key(|a| a.timestamp);
+    // Whether each episode's latest play with a position was past the mark
+    let mut finished: HashMap<String, bool> = HashMap::new();
+    let mut listens = vec![];
+    for a in plays {
+        let Some(c) = completion(&a) else {
+            continue;
+        };
+        let done = c >= COMPLETE_PERCENT;
+        let was = finished.insert(a.episode.clone(), done).unwrap_or(false);
+        if !done || was || a.timestamp <= since {
+            continue;
+        }
+        let Ok(episodes) = db.episodes(a.podcast.clone()) else {
+            continue;
+        };
+        let found = episodes
+            .into_iter()
+            .find(|e| e.enclosure.as_ref().is_some_and(|en| en.url == a.episode));
+        let (Some(e), Ok(p)) = (found, db.get_podcast(a.podcast.clone())) else {
+            continue;
+        };
+        listens.push(Scrobble {
+            user,
+            listened_at: a.timestamp,
+            episode: e.id,
+            title: e.title,
+            podcast: p.name,
+            rss: p.rss,
+            audio: a.episode,
+            published: e.published,
+            duration_secs: a.total,
+        });
+    }
+    Ok(listens)
+}
+
+pub async fn worker<D: DB + Send + 'static>(state: Arc<Mutex<AppState<D>>>) {
+    let Some(config) = state.lock().await.config.scrobbling.clone() else {
+        return;
+    };
+    // Not the fetcher for ListenBrainz: it's the operator's, and may be
+    // self-hosted on a private address. Webhooks are users' URLs, so they
+    // go through it
+    let client = reqwest::Client::new();
+    loop {
+        tokio::time::sleep(Duration::from_secs(config.interval_secs.max(1))).await;
+        let (due, http) = {
+            let s = state.lock().await;
+            let scrobblers = match s.db.scrobblers() {
+                Ok(scrobblers) => scrobblers,
+                Err(e) => {
+                    eprintln!("scrobbling skipped: {:?}", e);
+                    continue;
+                }
+            };
+            let due: Vec<_> = scrobblers
+                .into_iter()
+                .filter_map(|(user, target, scrobbler)| {
+                    let listens = listens(&s.db, user, scrobbler.since).ok()?;
+                    (!listens.is_empty()).then_some((user, target, listens))
+                })
+                .collect();
+            (due, s.http.clone())
+        };
+        for (user, target, listens) in due {
+            let (sent, error) = submit(&client, &http, &config, &target, &listens).await;
+            if let Some(e) = &error {
+                eprintln!("scrobbling for {} failed: {}", user, e);
+            }
+            let up_to = sent.checked_sub(1).map(|i| listens[i].listened_at);
+            let _ = state
+                .lock()
+                .await
+                .db
+                .scrobbled(user, sent as u64, up_to, Utc::now(), error);
+        }
+    }
+}
+
+/// Sends `listens` in order, stopping at the first failure. Returns how
+/// many went, and why the rest didn't.
+async fn submit(
+    client: &reqwest::Client,
+    http: &Fetcher,
+    config: &ScrobblingConfig,
+    target: &ScrobbleTarget,
+    listens: &[Scrobble],
+) -> (usize, Option<String>) {
+    let mut sent = 0;
+    match target {
+        ScrobbleTarget::ListenBrainz { token } => {
+            let url = format!(
+                "{}/1/submit-listens",
+                config.listenbrainz_url.trim_end_matches('/')
+            );
+            for batch in listens.chunks(BATCH) {
+                let req = client
+                    .post(&url)
+                    .header(header::AUTHORIZATION, format!("Token {}", token))
+                    .json(&listenbrainz(batch));
+                if let Err(e) = req.send().await.and_then(|r| r.error_for_status()) {
+                    return (sent, Some(e.to_string()));
+                }
+                sent += batch.len();
+            }
+        }
+        ScrobbleTarget::Webhook { url, secret } => {
+            for listen in listens {
+                let body = match serde_json::to_vec(listen) {
+                    Ok(body) => body,
+                    Err(e) => return (sent, Some(e.to_string())),
+                };
+                let mut req = match http.request(reqwest::Method::POST, url) {
+                    Ok(req) => req,
+                    Err(blocked) => return (sent, Some(blocked.to_string())),
+                };
+                if let Some(secret) = secret {
+                    let signature =
+                        signing::sign(&[secret.as_bytes()], Utc::now().timestamp(), &body);
+                    req = req.header(signing::HEADER, signature);
+                }
+                let req = req
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(body);
+                if let Err(e) = req.send().await.and_then(|r| r.error_for_status()) {
+                    return (sent, Some(e.to_string()));
+                }
+                sent += 1;
+            }
+        }
+    }
+    (sent, None)
+}
+
+/// A ListenBrainz submission, with the podcast as the artist and album.
+fn listenbrainz(listens: &[Scrobble]) -> serde_json::Value {
+    let payload: Vec<_> = listens
+        .iter()
+        .map(|l| {
+            json!({
+                "listened_at": l.listened_at.and_utc().timestamp(),
+                "track_metadata": {
+                    "artist_name": l.podcast,
+                    "track_name": l.title,
+                    "release_name": l.podcast,
+                    "additional_info": {
+                        "media_player": "pods",
+                        "submission_client": "pods",
+                        "origin_url": l.audio,
+                        "duration": l.duration_secs,
+                    },
+                },
+            })
+        })
+        .collect();
+    json!({
+        "listen_type": if listens.len() == 1 { "single" } else { "import" },
+        "payload": payload,
+    })
+}
+
+fn status(target: &ScrobbleTarget, since: NaiveDateTime) -> Scrobbler {
+    let (kind, url) = match target {
+        ScrobbleTarget::ListenBrainz { .. } => (ScrobbleKind::ListenBrainz, None),
+        ScrobbleTarget::Webhook { url, .. } => (ScrobbleKind::Webhook, Some(url.clone())),
+    };
+    Scrobbler {
+        kind,
+        url,
+        since,
+        submitted: 0,
+        last_submitted: None,
+        last_error: None,
+    }
+}
+
+/// `404` if the user has none, or scrobbling is off.
+pub async fn get_scrobbler<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    if s.config.scrobbling.is_none() {
+        return (StatusCode::NOT_FOUND, Json(None));
+    }
+    match s.db.scrobbler(uid) {
+        Ok(Some((_, scrobbler))) => (StatusCode::OK, Json(Some(scrobbler))),
+        Ok(None) | Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Sets where the user's listens go, starting with what they finish from
+/// now on.
+pub async fn put_scrobbler<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+    Valid(Json(target)): Valid<Json<ScrobbleTarget>>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    if s.config.scrobbling.is_none() {
+        return (StatusCode::NOT_FOUND, Json(None));
+    }
+    let scrobbler = status(&target, Utc::now().naive_utc());
+    match s.db.set_scrobbler(uid, Some((target, scrobbler.clone()))) {
+        Ok(()) => (StatusCode::OK, Json(Some(scrobbler))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn delete_scrobbler<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> StatusCode {
+    let s = &mut *state.lock().await;
+    if s.config.scrobbling.is_none() {
+        return StatusCode::NOT_FOUND;
+    }
+    match s.db.set_scrobbler(uid, None) {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(Error::NotFound) => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,215 @@
+//! Accounts for automation, like a cron job adding feeds or a dashboard
+//! reading stats. Admins create them; each gets a token sent as
+//! `Authorization: Bearer <token>` that only opens the routes of its scopes
+//! and nothing a user can do. Only the token's SHA-256 is kept.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{MatchedPath, Path, State},
+    http::{header, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::service_accounts::{
+    AddPodcast, CreateServiceAccount, CreatedServiceAccount, Scope, ServiceAccount,
+};
+
+use crate::{current_admin, health, instance, parse_rss, validation::Valid, AppState, Error, DB};
+
+/// Marks a request let in with a service account's token, by the account's
+/// ID.
+#[derive(Clone, Copy, Debug)]
+pub struct Service(pub Uuid);
+
+/// Routes each scope opens, by method and route pattern.
+fn routes(scope: Scope) -> &'static [(Method, &'static str)] {
+    match scope {
+        Scope::Ingest => &[(Method::POST, "/admin/podcasts")],
+        Scope::Stats => &[
+            (Method::GET, "/admin/engagement"),
+            (Method::GET, "/admin/podcasts/:id/engagement"),
+            (Method::GET, "/admin/refresh-runs"),
+            (Method::GET, "/admin/refresh-runs/:id"),
+            (Method::GET, "/admin/storage"),
+        ],
+        Scope::Voice => &[(Method::POST, "/voice")],
+        _ => &[],
+    }
+}
+
+fn sha256(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Checks bearer tokens against the route. Requests without one pass
+/// through untouched.
+pub async fn authenticate<D: DB, B>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    let Some(token) = bearer else {
+        return next.run(req).await;
+    };
+    let hash = sha256(token.trim());
+    let path = req.extensions().get::<MatchedPath>().map(|p| p.as_str());
+    {
+        let s = &mut *state.lock().await;
+        let account = match s.db.service_account_by_token(&hash) {
+            Ok(a) => a,
+            Err(Error::NotFound) => return StatusCode::UNAUTHORIZED.into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+        let allowed = account.scopes.iter().any(|scope| {
+            routes(*scope)
+                .iter()
+                .any(|(m, p)| m == req.method() && Some(*p) == path)
+        });
+        if !allowed {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+        let _ = s.db.touch_service_account(account.id, Utc::now());
+        req.extensions_mut().insert(Service(account.id));
+    }
+    next.run(req).await
+}
+
+/// Lets in a service account the middleware passed, or else the logged in
+/// admin.
+pub fn admin_or_service<D: DB>(
+    state: &AppState<D>,
+    service: Option<Extension<Service>>,
+) -> Result<(), StatusCode> {
+    match service {
+        Some(_) => Ok(()),
+        None => current_admin(state).map(|_| ()),
+    }
+}
+
+pub async fn list<D: DB>(State(state): State<Arc<Mutex<AppState<D>>>>) -> impl IntoResponse {
+    let s = state.lock().await;
+    if let Err(status) = current_admin(&s) {
+        return (status, Json(None));
+    }
+    match s.db.service_accounts() {
+        Ok(a) => (StatusCode::OK, Json(Some(a))),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn create<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Valid(Json(req)): Valid<Json<CreateServiceAccount>>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    if let Err(status) = current_admin(s) {
+        return (status, Json(None));
+    }
+    let mut scopes = vec![];
+    for scope in req.scopes {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    let account = ServiceAccount {
+        id: Uuid::new_v4(),
+        name: req.name,
+        scopes,
+        created: Utc::now(),
+        last_used: None,
+    };
+    let token = format!(
+        "pods_{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    );
+    match s.db.create_service_account(account, sha256(&token)) {
+        Ok(account) => (
+            StatusCode::CREATED,
+            Json(Some(CreatedServiceAccount { account, token })),
+        ),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Revokes the account's token.
+pub async fn delete<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(id): Path<Uuid>,
+) -> StatusCode {
+    let s = &mut *state.lock().await;
+    if let Err(status) = current_admin(s) {
+        return status;
+    }
+    match s.db.delete_service_account(id) {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(Error::NotFound) => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Adds a feed to the catalog without subscribing anyone, so it's already
+/// there when users look for it. Answers `200` if it was known.
+pub async fn add_podcast<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    service: Option<Extension<Service>>,
+    Valid(Json(req)): Valid<Json<AddPodcast>>,
+) -> impl IntoResponse {
+    let (http, max_bytes) = {
+        let s = state.lock().await;
+        if let Err(status) = admin_or_service(&s, service) {
+            return (status, Json(None));
+        }
+        match s.db.get_podcast(req.rss.clone()) {
+            Ok(p) => return (StatusCode::OK, Json(Some(p))),
+            Err(Error::NotFound) => {}
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+        }
+        match instance::effective(&s) {
+            Ok(i) => (s.http.clone(), i.max_feed_bytes),
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+        }
+    };
+    let (feed, report) = parse_rss(&http, req.rss.clone(), max_bytes, None).await;
+    let feed = match feed {
+        Ok(f) => f,
+        Err(_) => return (StatusCode::BAD_GATEWAY, Json(None)),
+    };
+    let db = &mut state.lock().await.db;
+    // Another request may have added it while the feed was fetched
+    if let Ok(p) = db.get_podcast(req.rss.clone()) {
+        return (StatusCode::OK, Json(Some(p)));
+    }
+    let created = db
+        .create_podcast(
+            req.rss,
+            feed.title,
+            feed.description,
+            feed.artwork,
+            feed.language,
+            feed.explicit,
+        )
+        .and_then(|p| db.add_episodes(p.rss.clone(), feed.episodes).map(|_| p));
+    match created {
+        Ok(p) => {
+            health::record(db, &p.rss, report);
+            (StatusCode::CREATED, Json(Some(p)))
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::settings::UserSettings;
+
+use crate::{AppState, Error, DB};
+
+pub async fn get_settings<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+) -> impl IntoResponse {
+    match state.lock().await.db.get_user(uid) {
+        Ok(u) => (StatusCode::OK, Json(Some(u.settings))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn put_settings<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+    Json(settings): Json<UserSettings>,
+) -> impl IntoResponse {
+    match state.lock().await.db.update_settings(uid, settings) {
+        Ok(s) => (StatusCode::OK, Json(Some(s))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
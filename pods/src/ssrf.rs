@@ -0,0 +1,206 @@
+//! Keeps user-supplied URLs (feeds, enclosures) from reaching the server's
+//! own network: loopback, private, link-local and similar ranges are refused
+//! unless allow-listed.
+//!
+//! Hostnames are checked at resolution time, so a feed host can't pass a
+//! check and then re-resolve somewhere internal. URLs with a literal IP skip
+//! DNS and are checked up front, including on redirects. A proxy resolves
+//! hostnames itself, so the two can't be combined.
+
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+
+use hyper::client::connect::dns::Name;
+use ipnet::IpNet;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    Url,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SsrfConfig {
+    pub enabled: bool,
+    /// Hosts (`.example.com` includes subdomains) and CIDR ranges that may
+    /// be fetched even though they are internal.
+    pub allow: Vec<String>,
+}
+
+impl Default for SsrfConfig {
+    fn default() -> SsrfConfig {
+        SsrfConfig {
+            enabled: true,
+            allow: vec![],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Blocked(pub String);
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not a public address", self.0)
+    }
+}
+
+impl std::error::Error for Blocked {}
+
+/// Whether a failed request was refused by this policy.
+pub fn is_blocked(err: &reqwest::Error) -> bool {
+    let mut source: Option<&dyn std::error::Error> = Some(err);
+    while let Some(e) = source {
+        if e.is::<Blocked>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+#[derive(Debug, Default)]
+pub struct Policy {
+    enabled: bool,
+    hosts: Vec<String>,
+    nets: Vec<IpNet>,
+}
+
+impl Policy {
+    pub fn new(config: &SsrfConfig) -> Policy {
+        let mut policy = Policy {
+            enabled: config.enabled,
+            hosts: vec![],
+            nets: vec![],
+        };
+        for entry in &config.allow {
+            match entry.parse::<IpNet>() {
+                Ok(net) => policy.nets.push(net),
+                Err(_) => match entry.parse::<IpAddr>() {
+                    Ok(ip) => policy.nets.push(ip.into()),
+                    Err(_) => policy.hosts.push(entry.to_lowercase()),
+                },
+            }
+        }
+        policy
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn host_allowed(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        self.hosts
+            .iter()
+            .any(|pattern| crate::fetcher::host_matches(pattern, &host))
+    }
+
+    fn ip_allowed(&self, ip: IpAddr) -> bool {
+        is_public(ip) || self.nets.iter().any(|n| n.contains(&ip))
+    }
+
+    /// Checks a URL before fetching it. Only literal IPs can be judged here;
+    /// hostnames are checked by the resolver.
+    pub fn check_url(&self, url: &Url) -> Result<(), Blocked> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let host = url.host_str().unwrap_or_default();
+        let Ok(ip) = host.trim_matches(['[', ']']).parse::<IpAddr>() else {
+            return Ok(());
+        };
+        if self.ip_allowed(ip) {
+            Ok(())
+        } else {
+            Err(Blocked(ip.to_string()))
+        }
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // "this network" and carrier-grade NAT
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+                // Protocol assignments, benchmarking, and reserved
+                || (a == 192 && b == 0 && c == 0)
+                || (a == 198 && (b == 18 || b == 19))
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match embedded_v4(ip) {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                let [first, second, third, ..] = ip.segments();
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    // Local-use NAT64, whose translators are on the local network
+                    || (first == 0x64 && second == 0xff9b && third == 1))
+            }
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 one reaches: IPv4-mapped `::ffff:a.b.c.d`,
+/// IPv4-compatible `::a.b.c.d`, NAT64 `64:ff9b::a.b.c.d` and 6to4
+/// `2002:aabb:ccdd::`. `::` and `::1` come out as `0.0.0.0` and `0.0.0.1`,
+/// which aren't public either.
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return Some(v4);
+    }
+    let [.., a, b, c, d] = ip.octets();
+    match ip.segments() {
+        [0, 0, 0, 0, 0, 0, _, _] | [0x64, 0xff9b, 0, 0, 0, 0, _, _] => {
+            Some(Ipv4Addr::new(a, b, c, d))
+        }
+        [0x2002, high, low, ..] => Some(Ipv4Addr::from(u32::from(high) << 16 | u32::from(low))),
+        _ => None,
+    }
+}
+
+/// Resolves with `inner` (or the system resolver) and drops any address the
+/// policy doesn't allow, failing if none are left.
+pub struct FilteringResolver {
+    pub inner: Option<Arc<dyn Resolve>>,
+    pub policy: Arc<Policy>,
+}
+
+impl Resolve for FilteringResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let inner = self.inner.clone();
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = match inner {
+                Some(r) => r.resolve(name.clone()).await?.collect(),
+                None => tokio::net::lookup_host((name.as_str(), 0)).await?.collect(),
+            };
+            if !policy.enabled || policy.host_allowed(name.as_str()) {
+                return Ok(Box::new(addrs.into_iter()) as Addrs);
+            }
+            let allowed: Vec<_> = addrs
+                .into_iter()
+                .filter(|a| policy.ip_allowed(a.ip()))
+                .collect();
+            if allowed.is_empty() {
+                return Err(Box::new(Blocked(name.as_str().to_string())) as _);
+            }
+            Ok(Box::new(allowed.into_iter()) as Addrs)
+        })
+    }
+}
@@ -0,0 +1,173 @@
+//! Weekly listening time and streaks, worked out from the `play` actions in
+//! a user's gPodder history whenever they're asked for.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Datelike, Days, NaiveDate, Utc};
+use chrono_tz::Tz;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::stats::{ListeningStats, StatsQuery, WeekStats};
+
+use crate::{
+    dates,
+    gpodder::{ActionKind, EpisodeAction},
+    AppState, Error, DB,
+};
+
+const DEFAULT_WEEKS: u32 = 12;
+/// Ten years.
+const MAX_WEEKS: u32 = 520;
+
+/// One user's `play` actions with a position, oldest first, with the
+/// seconds each one covers.
+pub fn listened(actions: &[EpisodeAction]) -> Vec<(&EpisodeAction, u32)> {
+    let mut plays: Vec<&EpisodeAction> = actions
+        .iter()
+        .filter(|a| a.action == ActionKind::Play)
+        .collect();
+    plays.sort_by_key(|a| a.timestamp);
+    let mut last: HashMap<&str, u32> = HashMap::new();
+    let mut listened = vec![];
+    for a in plays {
+        let Some(position) = a.position else {
+            continue;
+        };
+        // Without `started`, the client is taken to have resumed where the
+        // episode's previous action left off
+        let started = a
+            .started
+            .or_else(|| last.get(a.episode.as_str()).copied())
+            .unwrap_or(0);
+        last.insert(&a.episode, position);
+        listened.push((a, position.saturating_sub(started)));
+    }
+    listened
+}
+
+/// Seconds listened on each local day.
+fn daily(actions: &[EpisodeAction], tz: Option<Tz>) -> BTreeMap<NaiveDate, u64> {
+    let mut days = BTreeMap::new();
+    for (a, secs) in listened(actions) {
+        if secs > 0 {
+            let day = dates::local(a.timestamp.and_utc(), tz).date();
+            *days.entry(day).or_default() += u64::from(secs);
+        }
+    }
+    days
+}
+
+fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Days::new(u64::from(day.weekday().num_days_from_monday()))
+}
+
+/// The current and the longest run of periods that were `met`, oldest
+/// first. The last period is still going, so it only adds to the current
+/// run once met rather than breaking it.
+fn streaks(met: &[bool]) -> (u32, u32) {
+    let (mut run, mut before_last, mut longest) = (0, 0, 0);
+    for &m in met {
+        before_last = run;
+        run = if m { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let current = if met.last() == Some(&true) {
+        run
+    } else {
+        before_last
+    };
+    (current, longest)
+}
+
+fn listening_stats(
+    actions: &[EpisodeAction],
+    timezone: Option<Tz>,
+    weekly_goal_mins: Option<u32>,
+    weeks: u32,
+    now: DateTime<Utc>,
+) -> ListeningStats {
+    let days = daily(actions, timezone);
+    let today = dates::local(now, timezone).date();
+    let this_week = week_start(today);
+
+    let mut by_week: BTreeMap<NaiveDate, u64> = BTreeMap::new();
+    for (day, secs) in &days {
+        *by_week.entry(week_start(*day)).or_default() += secs;
+    }
+    let minutes = |week: NaiveDate| by_week.get(&week).copied().unwrap_or(0) / 60;
+    let met = |week| weekly_goal_mins.map(|goal| minutes(week) >= u64::from(goal));
+
+    let first = days.keys().next().copied().filter(|d| *d <= today);
+    let (day_streak, longest_day_streak) = match first {
+        Some(first) => {
+            let listened: Vec<bool> = first
+                .iter_days()
+                .take_while(|d| *d <= today)
+                .map(|d| days.contains_key(&d))
+                .collect();
+            streaks(&listened)
+        }
+        None => (0, 0),
+    };
+    let (goal_streak, longest_goal_streak) = match first.zip(weekly_goal_mins) {
+        Some((first, _)) => {
+            let weeks_met: Vec<bool> = week_start(first)
+                .iter_weeks()
+                .take_while(|w| *w <= this_week)
+                .map(|w| met(w) == Some(true))
+                .collect();
+            streaks(&weeks_met)
+        }
+        None => (0, 0),
+    };
+
+    ListeningStats {
+        timezone,
+        weekly_goal_mins,
+        weeks: this_week
+            .iter_weeks()
+            .rev()
+            .take(weeks as usize)
+            .map(|start| WeekStats {
+                start,
+                minutes: minutes(start),
+                goal_met: met(start),
+            })
+            .collect(),
+        goal_streak,
+        longest_goal_streak,
+        day_streak,
+        longest_day_streak,
+        total_mins: days.values().sum::<u64>() / 60,
+    }
+}
+
+pub async fn get_stats<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+    Query(query): Query<StatsQuery>,
+) -> impl IntoResponse {
+    let weeks = query.weeks.unwrap_or(DEFAULT_WEEKS).min(MAX_WEEKS);
+    let s = state.lock().await;
+    let stats = s.db.get_user(uid).and_then(|u| {
+        let actions = s.db.episode_actions(uid)?;
+        let timezone = u.settings.timezone.or(s.config.timezone);
+        let goal = u.settings.weekly_goal_mins;
+        Ok(listening_stats(&actions, timezone, goal, weeks, Utc::now()))
+    });
+    match stats {
+        Ok(stats) => (StatusCode::OK, Json(Some(stats))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
@@ -0,0 +1,570 @@
+use std::{
+    io,
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use axum::{
+    body::StreamBody,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::{stream, Stream, StreamExt};
+use tokio::{fs, sync::Mutex};
+use uuid::Uuid;
+
+pub use pods_types::stream::{AudioQuery, Quality};
+
+use crate::{
+    bandwidth::Throttle,
+    fetcher::Fetcher,
+    i18n::{self, Lang, Message, UserLang},
+    media,
+    media_links::{self, MediaAccess},
+    ssrf,
+    stream_cache::{self, Meta},
+    transcode::Transcoder,
+    AppState, Error, DB,
+};
+
+/// Headers passed through from the enclosure host to the client.
+const PASSTHROUGH: [header::HeaderName; 6] = [
+    header::CONTENT_TYPE,
+    header::CONTENT_LENGTH,
+    header::CONTENT_RANGE,
+    header::ACCEPT_RANGES,
+    header::ETAG,
+    header::LAST_MODIFIED,
+];
+
+/// Headers passed through from the client to the enclosure host.
+const FORWARDED: [header::HeaderName; 3] = [
+    header::RANGE,
+    header::IF_NONE_MATCH,
+    header::IF_MODIFIED_SINCE,
+];
+
+/// Proxies an episode's enclosure, forwarding `Range` so clients can seek.
+/// Bytes already in the stream cache are served from there. `?quality=low`
+/// serves a low-bitrate rendition instead, with `&trim=true` starting after
+/// the acting user's intro skip for the podcast. `HEAD` and conditional
+/// requests are answered from what's known about the file when possible, and
+/// never fetch its body. Once the episode is downloaded, this redirects to the
+/// file's `/media/<sha256>` URL, signed like the request if it was.
+pub async fn audio<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    access: MediaAccess,
+    Path(id): Path<Uuid>,
+    Query(query): Query<AudioQuery>,
+    UserLang(lang): UserLang,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    let head = method == Method::HEAD;
+    let low = query.quality == Quality::Low;
+    let (episode, http, throttle, cache, transcoder, stored, skip_secs, secret) = {
+        let s = state.lock().await;
+        let skip_secs = match (query.trim, access.user) {
+            (true, Some(user)) => skip_intro(&s.db, user, id),
+            _ => 0,
+        };
+        (
+            s.db.get_episode(id),
+            s.http.clone(),
+            s.stream_throttle.clone(),
+            s.stream_cache.clone(),
+            s.transcoder.clone(),
+            media::find(&s.db, id),
+            skip_secs,
+            s.config.media_links.as_ref().map(|l| l.secret.clone()),
+        )
+    };
+    let enclosure = match episode {
+        Ok(e) => match e.enclosure {
+            Some(enclosure) => enclosure,
+            None => return StatusCode::NOT_FOUND.into_response(),
+        },
+        Err(Error::NotFound) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if low {
+        let source = Source {
+            url: enclosure.url,
+            // A finished download saves fetching the enclosure to transcode it
+            local: stored.and_then(|d| d.path),
+            http,
+        };
+        let rendition = Rendition {
+            id,
+            skip_secs,
+            source,
+        };
+        return serve_rendition(&transcoder, rendition, &headers, head, &throttle, lang).await;
+    }
+    if let Some(sha256) = stored.and_then(|d| d.sha256) {
+        // Relative, so it works wherever the API is mounted
+        let mut to = format!("../../media/{}", sha256);
+        if let (Some(expires), Some(secret)) = (access.expires, secret) {
+            let path = format!("/media/{}", sha256);
+            to = format!("{}?{}", to, media_links::sign(&secret, &path, "", expires));
+        }
+        return Redirect::temporary(&to).into_response();
+    }
+
+    let range = headers.get(header::RANGE);
+    if let Some(meta) = cache.meta(id) {
+        if not_modified(&headers, &meta) {
+            return unchanged(meta);
+        }
+        if head {
+            match span(range, meta.total) {
+                Span::Part(start, end) => return replay(meta, start, end, true).into_response(),
+                Span::Whole if meta.total > 0 => {
+                    let last = meta.total - 1;
+                    return replay(meta, 0, last, false).into_response();
+                }
+                // Left to the host
+                _ => {}
+            }
+        }
+    }
+    if let Some((start, end)) = requested(range) {
+        if let Some((meta, end)) = cache.lookup(id, start, end) {
+            if let Ok(body) = cache.read(id, start, end).await {
+                return cached(meta, start, end, range.is_some(), &throttle, body);
+            }
+        }
+    }
+
+    let method = if head { Method::HEAD } else { Method::GET };
+    let resp = match fetch(&http, method, &enclosure.url, &headers, lang).await {
+        Ok(r) => r,
+        Err(e) => return e,
+    };
+
+    let mut out = HeaderMap::new();
+    for name in PASSTHROUGH {
+        if let Some(value) = resp.headers().get(&name) {
+            out.insert(name, value.clone());
+        }
+    }
+    let status = resp.status();
+    if head {
+        return (status, out).into_response();
+    }
+    let writer = cache_meta(&resp).and_then(|(offset, meta)| cache.writer(id, offset, meta));
+    let body = resp.bytes_stream().inspect(move |chunk| {
+        if let (Ok(chunk), Some(writer)) = (chunk, &writer) {
+            let _ = writer.send(chunk.clone());
+        }
+    });
+    let body = StreamBody::new(throttle.connection().wrap(body));
+    (status, out, body).into_response()
+}
+
+async fn fetch(
+    http: &Fetcher,
+    method: Method,
+    url: &str,
+    headers: &HeaderMap,
+    lang: Lang,
+) -> Result<reqwest::Response, Response> {
+    let mut req = match http.request(method, url) {
+        Ok(r) => r,
+        Err(_) => return Err(i18n::error(StatusCode::FORBIDDEN, Message::Blocked, lang)),
+    };
+    for name in FORWARDED {
+        if let Some(value) = headers.get(&name) {
+            req = req.header(name, value);
+        }
+    }
+    req.send().await.map_err(|e| {
+        if ssrf::is_blocked(&e) {
+            i18n::error(StatusCode::FORBIDDEN, Message::Blocked, lang)
+        } else {
+            i18n::error(StatusCode::BAD_GATEWAY, Message::Upstream, lang)
+        }
+    })
+}
+
+/// Where to read an episode's audio from for transcoding.
+struct Source {
+    url: String,
+    local: Option<PathBuf>,
+    http: Fetcher,
+}
+
+/// Seconds of the episode the user skips at the start, from their settings
+/// for its podcast.
+fn skip_intro<D: DB>(db: &D, user: Uuid, episode: Uuid) -> u32 {
+    db.get_episode(episode)
+        .and_then(|e| db.subscription_override(user, e.podcast))
+        .ok()
+        .flatten()
+        .and_then(|o| o.skip_intro_secs)
+        .unwrap_or(0)
+}
+
+/// The low-bitrate rendition of an episode, starting `skip_secs` in.
+struct Rendition {
+    id: Uuid,
+    skip_secs: u32,
+    source: Source,
+}
+
+/// Serves the saved low-bitrate rendition, or makes it while streaming the
+/// output. `Range` and conditional requests are only honoured once the
+/// rendition is saved; a `HEAD` before then doesn't start making it.
+async fn serve_rendition(
+    transcoder: &Arc<Transcoder>,
+    rendition: Rendition,
+    headers: &HeaderMap,
+    head: bool,
+    throttle: &Throttle,
+    lang: Lang,
+) -> Response {
+    let Rendition {
+        id,
+        skip_secs,
+        source,
+    } = rendition;
+    let path = transcoder.path(id, skip_secs);
+    let content_type = HeaderValue::from_static("audio/mpeg");
+    if let Ok(saved) = fs::metadata(&path).await {
+        let modified = saved.modified().ok();
+        let meta = Meta {
+            total: saved.len(),
+            content_type: Some(content_type.clone()),
+            etag: modified.map(|m| etag(m, saved.len())),
+            last_modified: modified.map(http_date),
+        };
+        if not_modified(headers, &meta) {
+            return unchanged(meta);
+        }
+        let (start, end, partial) = match span(headers.get(header::RANGE), meta.total) {
+            Span::Part(start, end) => (start, end, true),
+            Span::Unsatisfiable => return unsatisfiable(meta.total),
+            Span::Whole => (0, meta.total.saturating_sub(1), false),
+        };
+        if head {
+            return replay(meta, start, end, partial).into_response();
+        }
+        if let Ok(body) = stream_cache::read_file(&path, start, end).await {
+            return cached(meta, start, end, partial, throttle, body);
+        }
+    }
+    let content_type = [(header::CONTENT_TYPE, content_type)];
+    if head {
+        // Of unknown length, like the GET would be
+        let body = StreamBody::new(stream::empty::<io::Result<Bytes>>());
+        return (StatusCode::OK, content_type, body).into_response();
+    }
+
+    let input = match source.local {
+        Some(local) => match fs::metadata(&local).await {
+            Ok(m) => stream_cache::read_file(&local, 0, m.len().saturating_sub(1))
+                .await
+                .map(|s| s.boxed()),
+            Err(e) => Err(e),
+        },
+        None => Err(io::ErrorKind::NotFound.into()),
+    };
+    let input = match input {
+        Ok(i) => i,
+        Err(_) => match fetch(
+            &source.http,
+            Method::GET,
+            &source.url,
+            &HeaderMap::new(),
+            lang,
+        )
+        .await
+        {
+            Ok(r) => r
+                .bytes_stream()
+                .map(|c| c.map_err(io::Error::other))
+                .boxed(),
+            Err(e) => return e,
+        },
+    };
+    let Ok(output) = transcoder.start(id, skip_secs, input) else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+    let body = stream::unfold(output, |mut rx| async move {
+        rx.recv().await.map(|c| (Ok::<_, io::Error>(c), rx))
+    });
+    let body = StreamBody::new(throttle.connection().wrap(body));
+    (StatusCode::OK, content_type, body).into_response()
+}
+
+/// A finished download by content hash, with `Range` support. The
+/// response never changes for a URL, so it's marked cacheable for good.
+pub async fn blob<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    _: MediaAccess,
+    Path(sha256): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    let (stored, throttle) = {
+        let s = state.lock().await;
+        let stored = media::by_hash(&s.db, &sha256).and_then(|d| {
+            let episode = s.db.get_episode(d.episode).ok();
+            let mime_type = episode.and_then(|e| e.enclosure?.mime_type);
+            Some((d.path?, mime_type))
+        });
+        (stored, s.stream_throttle.clone())
+    };
+    let Some((path, mime_type)) = stored else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    immutable_file(&path, mime_type, &sha256, &method, &headers, &throttle).await
+}
+
+/// A file that never changes, with `Range` support and `etag` (quoted here)
+/// as its validator, marked cacheable for good.
+pub(crate) async fn immutable_file(
+    path: &FsPath,
+    content_type: Option<String>,
+    etag: &str,
+    method: &Method,
+    headers: &HeaderMap,
+    throttle: &Throttle,
+) -> Response {
+    let Ok(file) = fs::metadata(path).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let meta = Meta {
+        total: file.len(),
+        content_type: content_type.and_then(|m| HeaderValue::from_str(&m).ok()),
+        etag: HeaderValue::from_str(&format!("\"{}\"", etag)).ok(),
+        last_modified: None,
+    };
+    let mut resp = if not_modified(headers, &meta) {
+        unchanged(meta)
+    } else {
+        let (start, end, partial) = match span(headers.get(header::RANGE), meta.total) {
+            Span::Part(start, end) => (start, end, true),
+            Span::Whole if meta.total > 0 => (0, meta.total - 1, false),
+            Span::Whole => {
+                // `replay` counts inclusive ends, which can't say zero bytes
+                let (status, mut out) = replay(meta, 0, 0, false);
+                out.insert(header::CONTENT_LENGTH, 0.into());
+                let mut resp = (status, out).into_response();
+                resp.headers_mut().insert(
+                    header::CACHE_CONTROL,
+                    HeaderValue::from_static(media::IMMUTABLE),
+                );
+                return resp;
+            }
+            Span::Unsatisfiable => return unsatisfiable(meta.total),
+        };
+        if method == Method::HEAD {
+            replay(meta, start, end, partial).into_response()
+        } else {
+            match stream_cache::read_file(path, start, end).await {
+                Ok(body) => cached(meta, start, end, partial, throttle, body),
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
+        }
+    };
+    let immutable = HeaderValue::from_static(media::IMMUTABLE);
+    resp.headers_mut().insert(header::CACHE_CONTROL, immutable);
+    resp
+}
+
+/// A validator for a saved file that changes whenever it's rewritten.
+fn etag(modified: SystemTime, len: u64) -> HeaderValue {
+    let secs = DateTime::<Utc>::from(modified).timestamp();
+    format!("\"{:x}-{:x}\"", secs, len).parse().unwrap()
+}
+
+fn http_date(time: SystemTime) -> HeaderValue {
+    let date = DateTime::<Utc>::from(time).format("%a, %d %b %Y %H:%M:%S GMT");
+    date.to_string().parse().unwrap()
+}
+
+/// Whether the client's copy is still current: its `If-None-Match` lists
+/// the ETag, or, without that header, nothing changed since its
+/// `If-Modified-Since`.
+pub fn not_modified(headers: &HeaderMap, meta: &Meta) -> bool {
+    if let Some(tags) = headers.get(header::IF_NONE_MATCH) {
+        let Ok(tags) = tags.to_str() else {
+            return false;
+        };
+        // Weak comparison, which is what RFC 9110 asks for here
+        let opaque = |tag: &str| {
+            let tag = tag.trim();
+            tag.strip_prefix("W/").unwrap_or(tag).to_string()
+        };
+        let etag = meta.etag.as_ref().and_then(|e| e.to_str().ok()).map(opaque);
+        return tags
+            .split(',')
+            .any(|t| t.trim() == "*" || Some(opaque(t)) == etag);
+    }
+    let date =
+        |value: Option<&HeaderValue>| DateTime::parse_from_rfc2822(value?.to_str().ok()?).ok();
+    match (
+        date(headers.get(header::IF_MODIFIED_SINCE)),
+        date(meta.last_modified.as_ref()),
+    ) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+/// `304`, with the validators the client should keep.
+pub fn unchanged(meta: Meta) -> Response {
+    let mut out = HeaderMap::new();
+    for (name, value) in [
+        (header::ETAG, meta.etag),
+        (header::LAST_MODIFIED, meta.last_modified),
+    ] {
+        if let Some(value) = value {
+            out.insert(name, value);
+        }
+    }
+    (StatusCode::NOT_MODIFIED, out).into_response()
+}
+
+/// The single range a request asks for from a known start, or all of it
+/// without `Range`. `None` for anything else, which goes to the host as is.
+fn requested(range: Option<&HeaderValue>) -> Option<(u64, Option<u64>)> {
+    match range {
+        None => Some((0, None)),
+        Some(r) => match parse_range(r.to_str().ok()?)? {
+            ByteRange::From(start, end) => Some((start, end)),
+            // Needs the length to know where it starts
+            ByteRange::Last(_) => None,
+        },
+    }
+}
+
+enum ByteRange {
+    /// `bytes=<start>-[<end>]`
+    From(u64, Option<u64>),
+    /// `bytes=-<len>`, the last `len` bytes.
+    Last(u64),
+}
+
+/// A `Range` header value asking for one range. `None` for anything else,
+/// multiple ranges included.
+fn parse_range(value: &str) -> Option<ByteRange> {
+    let (start, end) = value.strip_prefix("bytes=")?.split_once('-')?;
+    if start.is_empty() {
+        return Some(ByteRange::Last(end.parse().ok()?));
+    }
+    let start = start.parse().ok()?;
+    let end = match end {
+        "" => None,
+        end => Some(end.parse().ok().filter(|&end| end >= start)?),
+    };
+    Some(ByteRange::From(start, end))
+}
+
+/// What a request for a file gets.
+enum Span {
+    /// All of it, without a `Range` or with one this can't read, which RFC
+    /// 9110 says to ignore.
+    Whole,
+    /// Bytes `start..=end`.
+    Part(u64, u64),
+    /// The range starts past the end.
+    Unsatisfiable,
+}
+
+fn span(range: Option<&HeaderValue>, total: u64) -> Span {
+    let parsed = range.and_then(|r| parse_range(r.to_str().ok()?));
+    let Some(last) = total.checked_sub(1) else {
+        return match parsed {
+            Some(_) => Span::Unsatisfiable,
+            None => Span::Whole,
+        };
+    };
+    match parsed {
+        None => Span::Whole,
+        Some(ByteRange::From(start, end)) if start <= last => {
+            Span::Part(start, end.unwrap_or(last).min(last))
+        }
+        Some(ByteRange::Last(len)) if len > 0 => Span::Part(total - len.min(total), last),
+        Some(_) => Span::Unsatisfiable,
+    }
+}
+
+/// `416`, saying how long the file is.
+fn unsatisfiable(total: u64) -> Response {
+    let range = format!("bytes */{}", total);
+    let range = HeaderValue::from_str(&range).unwrap();
+    (
+        StatusCode::RANGE_NOT_SATISFIABLE,
+        [(header::CONTENT_RANGE, range)],
+    )
+        .into_response()
+}
+
+/// Where a host response starts in the enclosure, and what to replay on
+/// later cache hits. `None` if it can't be cached.
+fn cache_meta(resp: &reqwest::Response) -> Option<(u64, Meta)> {
+    let (offset, total) = match resp.status() {
+        StatusCode::OK => (0, resp.content_length()?),
+        StatusCode::PARTIAL_CONTENT => {
+            // bytes <start>-<end>/<total>
+            let value = resp.headers().get(header::CONTENT_RANGE)?.to_str().ok()?;
+            let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+            let (start, _) = range.split_once('-')?;
+            (start.parse().ok()?, total.parse().ok()?)
+        }
+        _ => return None,
+    };
+    let header = |name| resp.headers().get(name).cloned();
+    let meta = Meta {
+        total,
+        content_type: header(header::CONTENT_TYPE),
+        etag: header(header::ETAG),
+        last_modified: header(header::LAST_MODIFIED),
+    };
+    Some((offset, meta))
+}
+
+/// Status and headers for serving `start..=end` of a file we know `meta`
+/// of.
+fn replay(meta: Meta, start: u64, end: u64, partial: bool) -> (StatusCode, HeaderMap) {
+    let mut out = HeaderMap::new();
+    let replayed = [
+        (header::CONTENT_TYPE, meta.content_type),
+        (header::ETAG, meta.etag),
+        (header::LAST_MODIFIED, meta.last_modified),
+    ];
+    for (name, value) in replayed {
+        if let Some(value) = value {
+            out.insert(name, value);
+        }
+    }
+    out.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    out.insert(header::CONTENT_LENGTH, (end - start + 1).into());
+    let status = if partial {
+        let range = format!("bytes {}-{}/{}", start, end, meta.total);
+        out.insert(header::CONTENT_RANGE, range.parse().unwrap());
+        StatusCode::PARTIAL_CONTENT
+    } else {
+        StatusCode::OK
+    };
+    (status, out)
+}
+
+fn cached(
+    meta: Meta,
+    start: u64,
+    end: u64,
+    partial: bool,
+    throttle: &Throttle,
+    body: impl Stream<Item = io::Result<Bytes>> + Send + 'static,
+) -> Response {
+    let (status, out) = replay(meta, start, end, partial);
+    let body = StreamBody::new(throttle.connection().wrap(body));
+    (status, out, body).into_response()
+}
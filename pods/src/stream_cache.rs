@@ -0,0 +1,254 @@
+//! Disk cache of enclosure bytes the stream proxy fetched, so seeking back
+//! and a second listener of the same episode are served without asking the
+//! podcast host again.
+//!
+//! Each episode's bytes are written at their offset into a sparse file named
+//! after the episode, and the ranges it holds are tracked in memory. Once the
+//! cache holds more than `max_bytes`, the least recently used episodes go.
+
+use std::{
+    collections::HashMap,
+    io::{self, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use bytes::Bytes;
+use futures_util::{stream, Stream};
+use reqwest::header::HeaderValue;
+use serde::Deserialize;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    sync::mpsc,
+};
+use uuid::Uuid;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct StreamCacheConfig {
+    pub dir: PathBuf,
+    /// `0` turns the cache off.
+    pub max_bytes: u64,
+}
+
+impl Default for StreamCacheConfig {
+    fn default() -> StreamCacheConfig {
+        StreamCacheConfig {
+            dir: PathBuf::from("stream-cache"),
+            max_bytes: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// What the host said about an enclosure, replayed on cache hits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Meta {
+    pub total: u64,
+    pub content_type: Option<HeaderValue>,
+    pub etag: Option<HeaderValue>,
+    pub last_modified: Option<HeaderValue>,
+}
+
+struct Entry {
+    meta: Meta,
+    /// Sorted, non-overlapping.
+    ranges: Vec<Range<u64>>,
+    used: Instant,
+    /// Bumped when the entry is reset, so writers for the old file stop
+    /// recording.
+    generation: u64,
+}
+
+pub struct StreamCache {
+    config: StreamCacheConfig,
+    entries: Mutex<HashMap<Uuid, Entry>>,
+    generations: AtomicU64,
+}
+
+/// Bytes to read at a time when serving from the cache.
+const READ_CHUNK: usize = 64 * 1024;
+
+impl StreamCache {
+    pub fn new(config: StreamCacheConfig) -> StreamCache {
+        StreamCache {
+            config,
+            entries: Mutex::new(HashMap::new()),
+            generations: AtomicU64::new(0),
+        }
+    }
+
+    fn path(&self, episode: Uuid) -> PathBuf {
+        self.config.dir.join(episode.to_string())
+    }
+
+    /// The episode's metadata and the resolved last byte if `start..=end`
+    /// (`end` defaulting to the last byte) is all on disk.
+    pub fn lookup(&self, episode: Uuid, start: u64, end: Option<u64>) -> Option<(Meta, u64)> {
+        let mut entries = self.entries.lock().unwrap();
+        let e = entries.get_mut(&episode)?;
+        let last = e.meta.total.checked_sub(1)?;
+        let end = end.unwrap_or(last).min(last);
+        if start > end || !e.ranges.iter().any(|r| r.start <= start && end < r.end) {
+            return None;
+        }
+        e.used = Instant::now();
+        Some((e.meta.clone(), end))
+    }
+
+    /// What the host last said about the episode, however little of it is on
+    /// disk.
+    pub fn meta(&self, episode: Uuid) -> Option<Meta> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(&episode).map(|e| e.meta.clone())
+    }
+
+    /// Streams `start..=end` of the episode from the cache file.
+    pub async fn read(
+        &self,
+        episode: Uuid,
+        start: u64,
+        end: u64,
+    ) -> io::Result<impl Stream<Item = io::Result<Bytes>>> {
+        read_file(&self.path(episode), start, end).await
+    }
+
+    /// Starts caching a host response whose body begins at `offset`, and
+    /// returns where to send its chunks. `None` when the cache is off.
+    pub fn writer(
+        self: &Arc<Self>,
+        episode: Uuid,
+        offset: u64,
+        meta: Meta,
+    ) -> Option<mpsc::UnboundedSender<Bytes>> {
+        if self.config.max_bytes == 0 {
+            return None;
+        }
+        let generation = {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.get_mut(&episode) {
+                Some(e) if e.meta == meta => e.generation,
+                // The enclosure changed on the host; start over
+                _ => {
+                    let _ = std::fs::remove_file(self.path(episode));
+                    let generation = self.generations.fetch_add(1, Ordering::Relaxed) + 1;
+                    entries.insert(
+                        episode,
+                        Entry {
+                            meta,
+                            ranges: vec![],
+                            used: Instant::now(),
+                            generation,
+                        },
+                    );
+                    generation
+                }
+            }
+        };
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(self.clone().write(episode, generation, offset, rx));
+        Some(tx)
+    }
+
+    async fn write(
+        self: Arc<Self>,
+        episode: Uuid,
+        generation: u64,
+        mut offset: u64,
+        mut chunks: mpsc::UnboundedReceiver<Bytes>,
+    ) {
+        let path = self.path(episode);
+        let _ = fs::create_dir_all(&self.config.dir).await;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .await;
+        let Ok(mut file) = file else {
+            return;
+        };
+        if file.seek(SeekFrom::Start(offset)).await.is_err() {
+            return;
+        }
+        while let Some(chunk) = chunks.recv().await {
+            if file.write_all(&chunk).await.is_err() {
+                break;
+            }
+            let written = offset..offset + chunk.len() as u64;
+            offset = written.end;
+            let mut entries = self.entries.lock().unwrap();
+            match entries.get_mut(&episode) {
+                Some(e) if e.generation == generation => insert(&mut e.ranges, written),
+                // Evicted or reset while we were writing
+                _ => break,
+            }
+        }
+        self.evict();
+    }
+
+    /// Drops least recently used episodes until the cache fits.
+    fn evict(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        loop {
+            let size: u64 = entries
+                .values()
+                .flat_map(|e| &e.ranges)
+                .map(|r| r.end - r.start)
+                .sum();
+            if size <= self.config.max_bytes {
+                break;
+            }
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, e)| e.used)
+                .map(|(id, _)| *id)
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+            let _ = std::fs::remove_file(self.path(oldest));
+        }
+    }
+}
+
+/// Streams `start..=end` of a file.
+pub async fn read_file(
+    path: &Path,
+    start: u64,
+    end: u64,
+) -> io::Result<impl Stream<Item = io::Result<Bytes>>> {
+    let mut file = fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    let file = file.take(end - start + 1);
+    Ok(stream::unfold(file, |mut file| async move {
+        let mut buf = vec![0; READ_CHUNK];
+        match file.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(Bytes::from(buf)), file))
+            }
+            Err(e) => Some((Err(e), file)),
+        }
+    }))
+}
+
+/// Adds `new` to sorted, non-overlapping `ranges`, merging where they touch.
+fn insert(ranges: &mut Vec<Range<u64>>, new: Range<u64>) {
+    ranges.push(new);
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for r in ranges.drain(..) {
+        match merged.last_mut() {
+            Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+            _ => merged.push(r),
+        }
+    }
+    *ranges = merged;
+}
@@ -0,0 +1,131 @@
+use std::{cmp::Reverse, collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::subscriptions::{Reorder, SubscriptionOrder, SubscriptionOverride};
+
+use crate::{explicit, gpodder::ActionKind, AppState, Error, PodcastChannel, User, DB};
+
+/// Sorts `podcasts` (already carrying the user's overrides) by the user's
+/// preference. Sorts are stable, so ties keep the manual order.
+pub fn sort<D: DB>(db: &D, user: &User, podcasts: &mut [PodcastChannel]) -> Result<(), Error> {
+    match user.settings.subscription_order {
+        SubscriptionOrder::Manual => {}
+        SubscriptionOrder::Alphabetical => podcasts.sort_by_cached_key(|p| p.name.to_lowercase()),
+        SubscriptionOrder::RecentEpisode => {
+            let mut newest = HashMap::new();
+            for p in podcasts.iter() {
+                // Episodes come newest first
+                let published = db
+                    .episodes(p.rss.clone())?
+                    .first()
+                    .and_then(|e| e.published);
+                newest.insert(p.rss.clone(), published);
+            }
+            podcasts.sort_by_key(|p| Reverse(newest[&p.rss]));
+        }
+        SubscriptionOrder::MostListened => {
+            let mut listened: HashMap<String, u64> = HashMap::new();
+            for a in db.episode_actions(user.id)? {
+                if a.action == ActionKind::Play {
+                    let secs = a
+                        .position
+                        .unwrap_or(0)
+                        .saturating_sub(a.started.unwrap_or(0));
+                    *listened.entry(a.podcast).or_default() += u64::from(secs);
+                }
+            }
+            podcasts.sort_by_key(|p| Reverse(listened.get(&p.rss).copied().unwrap_or(0)));
+        }
+        // Orders added to pods-types before this server learned them
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Sets the manual order of a user's subscriptions, returning the list as
+/// they'll now see it. Subscriptions hidden as explicit can be left out;
+/// they keep their place after the rest.
+pub async fn reorder<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(uid): Path<Uuid>,
+    Json(payload): Json<Reorder>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    let (user, hide) = match s
+        .db
+        .get_user(uid)
+        .and_then(|u| Ok((u, explicit::hidden(s, Some(uid))?)))
+    {
+        Ok(u) => u,
+        Err(Error::NotFound) => return (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    };
+    let db = &mut s.db;
+    let order: Result<Vec<String>, _> = payload
+        .podcasts
+        .iter()
+        .map(|id| db.get_podcast_by_id(*id).map(|p| p.rss))
+        .collect();
+    let mut order = match order {
+        Ok(o) => o,
+        Err(Error::NotFound) => return (StatusCode::BAD_REQUEST, Json(None)),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    };
+    if hide {
+        for rss in &user.subscribed {
+            let hidden = db.get_podcast(rss.clone()).is_ok_and(|p| p.explicit);
+            if hidden && !order.contains(rss) {
+                order.push(rss.clone());
+            }
+        }
+    }
+    // Must be exactly the current subscriptions, rearranged
+    let mut current = user.subscribed.clone();
+    let mut proposed = order.clone();
+    current.sort();
+    proposed.sort();
+    if current != proposed {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    }
+    let listed = db.reorder_subscriptions(uid, order).and_then(|_| {
+        let mut podcasts = crate::subscriptions(&*db, uid)?;
+        explicit::filter_podcasts(hide, &mut podcasts);
+        Ok(podcasts)
+    });
+    match listed {
+        Ok(p) => (StatusCode::OK, Json(Some(p))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Sets the user's overrides for a podcast they're subscribed to, returning
+/// the podcast as they'll now see it. All-`null` removes the override.
+pub async fn set_override<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((uid, podcast)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<SubscriptionOverride>,
+) -> impl IntoResponse {
+    let db = &mut state.lock().await.db;
+    let updated = db.get_podcast_by_id(podcast).and_then(|p| {
+        if !db.get_user(uid)?.subscribed.contains(&p.rss) {
+            return Err(Error::NotFound);
+        }
+        let payload = Some(payload).filter(|o| *o != SubscriptionOverride::default());
+        db.set_subscription_override(uid, p.rss.clone(), payload.clone())?;
+        Ok(payload.unwrap_or_default().apply(p))
+    });
+    match updated {
+        Ok(p) => (StatusCode::OK, Json(Some(p))),
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
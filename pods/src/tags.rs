@@ -0,0 +1,276 @@
+//! Tags embedded in downloaded files: ID3v2 in MP3s, Vorbis comments in Ogg
+//! and FLAC, and whatever else lofty reads. They fill in what sloppy feeds
+//! leave out.
+
+use std::{
+    borrow::Cow,
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use lofty::{
+    config::ParseOptions,
+    file::{AudioFile, FileType, TaggedFile, TaggedFileExt},
+    flac::FlacFile,
+    id3::v2::{Frame, FrameId, Id3v2Tag},
+    mpeg::MpegFile,
+    ogg::{tag::VorbisComments, OpusFile, VorbisFile},
+    picture::{Picture, PictureType},
+    probe::Probe,
+    tag::{Accessor, Tag},
+};
+use tokio::{sync::Mutex, task};
+use uuid::Uuid;
+
+pub use pods_types::tags::{Chapter, EmbeddedTags};
+
+use crate::{media, stream, stream_cache::Meta, AppState, Error, DB};
+
+fn options() -> ParseOptions {
+    // Only the tags are wanted, not the audio's duration and bitrate
+    ParseOptions::new().read_properties(false)
+}
+
+fn open(path: &FsPath) -> Option<TaggedFile> {
+    // Blobs are named by hash, so the format comes from the content
+    let probe = Probe::open(path).ok()?.guess_file_type().ok()?;
+    probe.options(options()).read().ok()
+}
+
+/// Reads a file's tags. `None` if it has none lofty understands. Blocks.
+pub fn read(path: &FsPath) -> Option<EmbeddedTags> {
+    let file = open(path)?;
+    let tag = file.primary_tag().or_else(|| file.first_tag());
+    let chapters = chapters(path, file.file_type()).unwrap_or_default();
+    if tag.is_none() && chapters.is_empty() {
+        return None;
+    }
+    let text = |value: Option<Cow<str>>| Some(value?.trim().to_string()).filter(|v| !v.is_empty());
+    Some(EmbeddedTags {
+        title: text(tag.and_then(|t| t.title())),
+        artist: text(tag.and_then(|t| t.artist())),
+        artwork: tag.and_then(cover).map(mime_type),
+        chapters,
+    })
+}
+
+/// The embedded cover's MIME type and bytes. Blocks.
+pub fn read_artwork(path: &FsPath) -> Option<(String, Vec<u8>)> {
+    let file = open(path)?;
+    let tag = file.primary_tag().or_else(|| file.first_tag())?;
+    let picture = cover(tag)?;
+    Some((mime_type(picture), picture.data().to_vec()))
+}
+
+/// The front cover, or whatever picture there is.
+fn cover(tag: &Tag) -> Option<&Picture> {
+    let pictures = tag.pictures();
+    pictures
+        .iter()
+        .find(|p| p.pic_type() == PictureType::CoverFront)
+        .or(pictures.first())
+}
+
+fn mime_type(picture: &Picture) -> String {
+    match picture.mime_type() {
+        Some(m) => m.as_str().to_string(),
+        None => "application/octet-stream".to_string(),
+    }
+}
+
+/// Chapters, for the formats that have a convention for them.
+fn chapters(path: &FsPath, file_type: FileType) -> Option<Vec<Chapter>> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let chapters = match file_type {
+        FileType::Mpeg => id3_chapters(MpegFile::read_from(&mut file, options()).ok()?.id3v2()?),
+        FileType::Flac => vorbis_chapters(
+            FlacFile::read_from(&mut file, options())
+                .ok()?
+                .vorbis_comments()?,
+        ),
+        FileType::Vorbis => vorbis_chapters(
+            VorbisFile::read_from(&mut file, options())
+                .ok()?
+                .vorbis_comments(),
+        ),
+        FileType::Opus => vorbis_chapters(
+            OpusFile::read_from(&mut file, options())
+                .ok()?
+                .vorbis_comments(),
+        ),
+        _ => return None,
+    };
+    Some(chapters)
+}
+
+/// `CHAP` frames, titled by their `TIT2`.
+fn id3_chapters(tag: &Id3v2Tag) -> Vec<Chapter> {
+    let title = FrameId::Valid(Cow::Borrowed("TIT2"));
+    let mut chapters: Vec<Chapter> = tag
+        .into_iter()
+        .filter_map(|frame| match frame {
+            Frame::Chapter(c) => Some(Chapter {
+                start_ms: c.times.start.into(),
+                end_ms: Some(c.times.end.into()),
+                title: c.children.get_text(&title).map(|t| t.to_string()),
+            }),
+            _ => None,
+        })
+        .collect();
+    chapters.sort_by_key(|c| c.start_ms);
+    chapters
+}
+
+/// `CHAPTER001=00:00:00.000` with an optional `CHAPTER001NAME`.
+fn vorbis_chapters(comments: &VorbisComments) -> Vec<Chapter> {
+    let value = |key: &str| {
+        comments
+            .items()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.to_string())
+    };
+    let mut chapters: Vec<Chapter> = comments
+        .items()
+        .filter_map(|(key, start)| {
+            let n = key
+                .get(..7)?
+                .eq_ignore_ascii_case("CHAPTER")
+                .then(|| &key[7..])?;
+            if n.is_empty() || !n.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            Some(Chapter {
+                start_ms: timestamp(start)?,
+                end_ms: None,
+                title: value(&format!("{}NAME", key)),
+            })
+        })
+        .collect();
+    chapters.sort_by_key(|c| c.start_ms);
+    chapters
+}
+
+/// `HH:MM:SS.mmm` in milliseconds.
+fn timestamp(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (hms, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let mut secs = 0;
+    for part in hms.split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+    }
+    let ms = match fraction {
+        "" => 0,
+        f => format!("{:0<3}", f).get(..3)?.parse::<u64>().ok()?,
+    };
+    Some(secs * 1000 + ms)
+}
+
+/// Reads a finished download's tags, keeps them for the episode and fills
+/// in what its feed left out: an empty title, and the podcast's artwork.
+pub async fn backfill<D: DB>(state: &Mutex<AppState<D>>, episode: Uuid, path: PathBuf) {
+    let Ok(Some(tags)) = task::spawn_blocking(move || read(&path)).await else {
+        return;
+    };
+    let db = &mut state.lock().await.db;
+    let Ok(mut e) = db.get_episode(episode) else {
+        return;
+    };
+    if let (true, Some(title)) = (e.title.trim().is_empty(), &tags.title) {
+        e.title = title.clone();
+        let _ = db.update_episode(e.clone());
+    }
+    if let (Ok(mut podcast), Some(_)) = (db.get_podcast(e.podcast), &tags.artwork) {
+        if podcast.artwork.is_none() {
+            // The cover's immutable URL, when the file has its hash
+            let url = match media::find(db, episode).and_then(|d| d.sha256) {
+                Some(sha256) => format!("/media/{}/artwork", sha256),
+                None => format!("/episodes/{}/artwork", episode),
+            };
+            podcast.artwork = Some(url);
+            let _ = db.update_podcast(podcast);
+        }
+    }
+    let _ = db.set_episode_tags(episode, tags);
+}
+
+pub async fn get_tags<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match state.lock().await.db.episode_tags(id) {
+        Ok(Some(t)) => (StatusCode::OK, Json(Some(t))),
+        Ok(None) | Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// The cover embedded in the episode's downloaded file. Downloads with a
+/// hash redirect to [`blob_artwork`].
+pub async fn artwork<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Response {
+    let download = media::find(&state.lock().await.db, id);
+    let Some((path, sha256)) = download.and_then(|d| Some((d.path?, d.sha256))) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match sha256 {
+        // Relative, so it works wherever the API is mounted
+        Some(sha256) => {
+            Redirect::temporary(&format!("../../media/{}/artwork", sha256)).into_response()
+        }
+        None => serve_artwork(path, None, &headers).await,
+    }
+}
+
+/// The cover embedded in a downloaded file, by the file's hash. Its ETag is
+/// the hash, so it's answered `304` without reading the file again, and
+/// caches may keep it for good.
+pub async fn blob_artwork<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(sha256): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let download = media::by_hash(&state.lock().await.db, &sha256);
+    let Some(path) = download.and_then(|d| d.path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let mut resp = serve_artwork(path, Some(sha256), &headers).await;
+    if resp.status().is_success() || resp.status() == StatusCode::NOT_MODIFIED {
+        let immutable = HeaderValue::from_static(media::IMMUTABLE);
+        resp.headers_mut().insert(header::CACHE_CONTROL, immutable);
+    }
+    resp
+}
+
+async fn serve_artwork(path: PathBuf, sha256: Option<String>, headers: &HeaderMap) -> Response {
+    let meta = Meta {
+        total: 0,
+        content_type: None,
+        etag: sha256.and_then(|s| HeaderValue::from_str(&format!("\"{}\"", s)).ok()),
+        last_modified: None,
+    };
+    if stream::not_modified(headers, &meta) {
+        return stream::unchanged(meta);
+    }
+    match task::spawn_blocking(move || read_artwork(&path)).await {
+        Ok(Some((mime_type, bytes))) => {
+            let mut out = HeaderMap::new();
+            if let Ok(mime_type) = HeaderValue::from_str(&mime_type) {
+                out.insert(header::CONTENT_TYPE, mime_type);
+            }
+            if let Some(etag) = meta.etag {
+                out.insert(header::ETAG, etag);
+            }
+            (StatusCode::OK, out, bytes).into_response()
+        }
+        _ => StatusCode::NOT_FOUND.into_response(),
+    }
+}
@@ -0,0 +1,259 @@
+//! Tenants: separate groups on one server, like two families sharing a VPS.
+//! Each tenant is an instance of its own under `/t/<slug>/`, with its own
+//! store, so users, subscriptions and the catalog don't cross over, and its
+//! own background work and files under `<dir>/<slug>/`. The main instance's
+//! admin creates and deletes them.
+//!
+//! Federation, metrics export and the DLNA server stay with the main
+//! instance. Everything else in the config, like quotas and bandwidth caps,
+//! applies to each tenant separately.
+
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::{OriginalUri, Path, State},
+    http::{Request, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tokio::{fs, sync::Mutex, task::JoinHandle};
+use tower::ServiceExt;
+
+pub use pods_types::tenants::{CreateTenant, NewTenant, Tenant};
+
+use crate::{
+    access_log::AccessLog, app, config::Config, current_admin, error_reporting::Reporter,
+    federation::FederationConfig, new_state, spawn_workers, validation::Valid, AppState,
+    InMemoryStore, DB,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct TenancyConfig {
+    /// Where each tenant's media, caches and archive go, in a folder per
+    /// tenant.
+    pub dir: PathBuf,
+}
+
+impl Default for TenancyConfig {
+    fn default() -> TenancyConfig {
+        TenancyConfig {
+            dir: PathBuf::from("tenants"),
+        }
+    }
+}
+
+struct Running {
+    name: String,
+    created: DateTime<Utc>,
+    state: Arc<Mutex<AppState<InMemoryStore>>>,
+    app: Router,
+    workers: Vec<JoinHandle<()>>,
+}
+
+pub struct Tenants {
+    config: TenancyConfig,
+    /// What each tenant's config is made from.
+    base: Config,
+    access_log: Option<Arc<AccessLog>>,
+    reporter: Option<Arc<Reporter>>,
+    running: Mutex<HashMap<String, Running>>,
+}
+
+impl Tenants {
+    pub fn new(
+        config: TenancyConfig,
+        base: Config,
+        access_log: Option<Arc<AccessLog>>,
+        reporter: Option<Arc<Reporter>>,
+    ) -> Tenants {
+        Tenants {
+            config,
+            base,
+            access_log,
+            reporter,
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn tenant_config(&self, slug: &str) -> Config {
+        let dir = self.config.dir.join(slug);
+        let mut c = self.base.clone();
+        c.media_dir = dir.join("media");
+        c.stream_cache.dir = dir.join("stream-cache");
+        c.transcode.dir = dir.join("renditions");
+        c.bundles.dir = dir.join("bundles");
+        c.archive.dir = dir.join("archive");
+        c.federation = FederationConfig::default();
+        c.metrics_export = None;
+        c.dlna = None;
+        c.tenancy = None;
+        c
+    }
+}
+
+impl Running {
+    /// Counts are filled in by `info`, which needs the tenant's state
+    /// unlocked.
+    fn tenant(&self, slug: &str) -> (Tenant, Arc<Mutex<AppState<InMemoryStore>>>) {
+        let tenant = Tenant {
+            slug: slug.to_string(),
+            name: self.name.clone(),
+            created: self.created,
+            users: 0,
+            podcasts: 0,
+        };
+        (tenant, self.state.clone())
+    }
+}
+
+async fn info((mut tenant, state): (Tenant, Arc<Mutex<AppState<InMemoryStore>>>)) -> Tenant {
+    if let Ok(stats) = state.lock().await.db.stats() {
+        tenant.users = stats.users;
+        tenant.podcasts = stats.podcasts;
+    }
+    tenant
+}
+
+/// Sends `/t/<slug>/...` to the tenant, with the prefix taken off; anything
+/// else goes on to the main instance.
+pub async fn route(
+    State(tenants): State<Option<Arc<Tenants>>>,
+    mut req: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let Some(tenants) = tenants else {
+        return next.run(req).await;
+    };
+    let Some(rest) = req.uri().path().strip_prefix("/t/") else {
+        return next.run(req).await;
+    };
+    let (slug, path) = match rest.split_once('/') {
+        Some((slug, path)) => (slug.to_string(), format!("/{}", path)),
+        None => (rest.to_string(), "/".to_string()),
+    };
+    let app = match tenants.running.lock().await.get(&slug) {
+        Some(t) => t.app.clone(),
+        None => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let uri = match req.uri().query() {
+        Some(q) => format!("{}?{}", path, q),
+        None => path,
+    };
+    let Ok(uri) = uri.parse::<Uri>() else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    // So links the tenant hands out keep the prefix
+    let original = OriginalUri(req.uri().clone());
+    req.extensions_mut().insert(original);
+    *req.uri_mut() = uri;
+    match app.oneshot(req).await {
+        Ok(resp) => resp,
+        Err(e) => match e {},
+    }
+}
+
+/// The tenants, or the status to answer with if this isn't the main
+/// instance's admin.
+async fn tenants<D: DB>(state: &Mutex<AppState<D>>) -> Result<Arc<Tenants>, StatusCode> {
+    let s = state.lock().await;
+    current_admin(&s)?;
+    s.tenants.clone().ok_or(StatusCode::NOT_FOUND)
+}
+
+pub async fn list<D: DB>(State(state): State<Arc<Mutex<AppState<D>>>>) -> impl IntoResponse {
+    let tenants = match tenants(&state).await {
+        Ok(t) => t,
+        Err(status) => return (status, Json(None)),
+    };
+    let found: Vec<_> = {
+        let running = tenants.running.lock().await;
+        running.iter().map(|(slug, t)| t.tenant(slug)).collect()
+    };
+    let mut listed = vec![];
+    for t in found {
+        listed.push(info(t).await);
+    }
+    listed.sort_by(|a, b| a.slug.cmp(&b.slug));
+    (StatusCode::OK, Json(Some(listed)))
+}
+
+/// Starts the tenant with its first user, its admin. `409` if the slug is
+/// taken.
+pub async fn create<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Valid(Json(req)): Valid<Json<CreateTenant>>,
+) -> impl IntoResponse {
+    let tenants = match tenants(&state).await {
+        Ok(t) => t,
+        Err(status) => return (status, Json(None)),
+    };
+    let mut running = tenants.running.lock().await;
+    if running.contains_key(&req.slug) {
+        return (StatusCode::CONFLICT, Json(None));
+    }
+    let tenant_state = new_state(tenants.tenant_config(&req.slug), None);
+    let Ok(admin) = tenant_state.lock().await.db.create_user(req.admin) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(None));
+    };
+    let t = Running {
+        name: req.name,
+        created: Utc::now(),
+        app: app(
+            tenant_state.clone(),
+            tenants.access_log.clone(),
+            tenants.reporter.clone(),
+        )
+        .await,
+        workers: spawn_workers(&tenant_state),
+        state: tenant_state,
+    };
+    let found = t.tenant(&req.slug);
+    running.insert(req.slug, t);
+    drop(running);
+    let tenant = info(found).await;
+    (StatusCode::CREATED, Json(Some(NewTenant { tenant, admin })))
+}
+
+pub async fn get<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(slug): Path<String>,
+) -> impl IntoResponse {
+    let tenants = match tenants(&state).await {
+        Ok(t) => t,
+        Err(status) => return (status, Json(None)),
+    };
+    let found = tenants
+        .running
+        .lock()
+        .await
+        .get(&slug)
+        .map(|t| t.tenant(&slug));
+    match found {
+        Some(t) => (StatusCode::OK, Json(Some(info(t).await))),
+        None => (StatusCode::NOT_FOUND, Json(None)),
+    }
+}
+
+/// Stops the tenant and deletes everything in it, files included.
+pub async fn delete<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(slug): Path<String>,
+) -> StatusCode {
+    let tenants = match tenants(&state).await {
+        Ok(t) => t,
+        Err(status) => return status,
+    };
+    let Some(t) = tenants.running.lock().await.remove(&slug) else {
+        return StatusCode::NOT_FOUND;
+    };
+    for w in t.workers {
+        w.abort();
+    }
+    let _ = fs::remove_dir_all(tenants.config.dir.join(&slug)).await;
+    StatusCode::NO_CONTENT
+}
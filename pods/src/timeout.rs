@@ -0,0 +1,72 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{MatchedPath, State},
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use pods_types::ApiError;
+use serde::Deserialize;
+
+use crate::i18n::{self, Message};
+
+/// How long a handler may take to produce a response, in seconds. Streaming
+/// bodies aren't cut off once they've started.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    pub default_secs: u64,
+    /// For GET requests without a route override.
+    pub read_secs: u64,
+    /// Overrides keyed by route pattern, e.g. `"/podcast"` or
+    /// `"/podcasts/:id/episodes"`.
+    pub routes: HashMap<String, u64>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> TimeoutConfig {
+        TimeoutConfig {
+            default_secs: 30,
+            read_secs: 10,
+            // Subscribing fetches the whole feed before answering, and polls
+            // wait up to a minute on purpose
+            routes: HashMap::from([("/podcast".to_string(), 120), ("/poll".to_string(), 90)]),
+        }
+    }
+}
+
+impl TimeoutConfig {
+    fn for_route(&self, method: &Method, path: Option<&str>) -> u64 {
+        match path.and_then(|p| self.routes.get(p)) {
+            Some(secs) => *secs,
+            None if method == Method::GET => self.read_secs,
+            None => self.default_secs,
+        }
+    }
+}
+
+pub async fn enforce<B>(
+    State(config): State<Arc<TimeoutConfig>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let path = req.extensions().get::<MatchedPath>().map(|p| p.as_str());
+    let secs = config.for_route(req.method(), path);
+    // No session lookup here; the header is all there is to go on
+    let lang = i18n::negotiate(req.headers()).unwrap_or_default();
+    match tokio::time::timeout(Duration::from_secs(secs), next.run(req)).await {
+        Ok(resp) => resp,
+        Err(_) => {
+            let message = Message::Timeout { secs };
+            let body = ApiError {
+                error: message.code().to_string(),
+                message: message.text(lang),
+                timeout_secs: Some(secs),
+                fields: vec![],
+            };
+            (StatusCode::GATEWAY_TIMEOUT, Json(body)).into_response()
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! Torrents of archived podcasts, so community archives can share a show
+//! without every copy coming from its host. The torrent holds the podcast's
+//! downloaded episodes and every snapshot of its feed, and names this server
+//! as a web seed (BEP 19), which serves the same files under `/webseed`:
+//!
+//! ```text
+//! <podcast>/<date> <episode title>.mp3
+//! <podcast>/feeds/<fetched>.xml
+//! ```
+//!
+//! A torrent is made once per set of files and kept next to the podcast's
+//! snapshots.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, Read},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tokio::{fs, sync::Mutex, task};
+use uuid::Uuid;
+
+use crate::{
+    annotations::file_name, archive, bundles, current_admin, media, stream, AppState, Error, DB,
+};
+
+/// Smallest and largest piece sizes, and the piece count to stay under
+/// between them.
+const MIN_PIECE: u64 = 256 * 1024;
+const MAX_PIECE: u64 = 16 * 1024 * 1024;
+const TARGET_PIECES: u64 = 2000;
+
+/// A file in the torrent and where the server has it.
+struct SeedFile {
+    /// Under the torrent's top folder.
+    path: Vec<String>,
+    source: PathBuf,
+    len: u64,
+    sha256: String,
+    content_type: Option<String>,
+}
+
+/// The torrent's top folder, and its files: downloaded episodes oldest
+/// first, then the feed snapshots.
+fn files<D: DB>(s: &AppState<D>, podcast: Uuid) -> Result<(String, Vec<SeedFile>), Error> {
+    let p = s.db.get_podcast_by_id(podcast)?;
+    if !archive::is_on(&s.db, &p.rss) {
+        return Err(Error::NotFound);
+    }
+    let mut files = vec![];
+    let mut taken = HashSet::new();
+    let mut unique = |stem: String, ext: &str| {
+        let mut name = format!("{}.{}", stem, ext);
+        let mut n = 2;
+        while !taken.insert(name.clone()) {
+            name = format!("{} ({}).{}", stem, n, ext);
+            n += 1;
+        }
+        name
+    };
+    let mut episodes = s.db.episodes(p.rss.clone())?;
+    episodes.reverse();
+    for e in episodes {
+        let Some(d) = media::find(&s.db, e.id) else {
+            continue;
+        };
+        let (Some(path), Some(sha256)) = (d.path, d.sha256) else {
+            continue;
+        };
+        let date = e.published.map(|p| p.format("%Y-%m-%d ").to_string());
+        let stem = format!("{}{}", date.unwrap_or_default(), file_name(&e.title));
+        files.push(SeedFile {
+            path: vec![unique(stem, &bundles::extension(&e))],
+            source: path,
+            len: d.bytes,
+            sha256,
+            content_type: e.enclosure.and_then(|enc| enc.mime_type),
+        });
+    }
+    let dir = s.config.archive.dir.join(p.id.to_string());
+    let mut snapshots = s.db.feed_snapshots(p.rss.clone())?;
+    snapshots.reverse();
+    for snapshot in snapshots {
+        let stem = snapshot.fetched.format("%Y-%m-%dT%H-%M-%SZ").to_string();
+        files.push(SeedFile {
+            path: vec!["feeds".to_string(), unique(stem, "xml")],
+            source: archive::snapshot_path(&dir, snapshot.id),
+            len: snapshot.bytes,
+            sha256: snapshot.sha256,
+            content_type: Some("application/rss+xml".to_string()),
+        });
+    }
+    Ok((file_name(&p.name), files))
+}
+
+/// Bencoded data.
+enum Value<'a> {
+    Int(i64),
+    Bytes(&'a [u8]),
+    List(Vec<Value<'a>>),
+    /// Keys in sorted order, as bencoding requires.
+    Dict(Vec<(&'static str, Value<'a>)>),
+}
+
+impl Value<'_> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Int(i) => out.extend_from_slice(format!("i{}e", i).as_bytes()),
+            Value::Bytes(b) => {
+                out.extend_from_slice(format!("{}:", b.len()).as_bytes());
+                out.extend_from_slice(b);
+            }
+            Value::List(items) => {
+                out.push(b'l');
+                items.iter().for_each(|v| v.encode(out));
+                out.push(b'e');
+            }
+            Value::Dict(entries) => {
+                out.push(b'd');
+                for (k, v) in entries {
+                    Value::Bytes(k.as_bytes()).encode(out);
+                    v.encode(out);
+                }
+                out.push(b'e');
+            }
+        }
+    }
+}
+
+/// SHA-1 of each `piece`-byte piece of the files laid end to end.
+fn pieces(files: &[(PathBuf, u64)], piece: u64) -> io::Result<Vec<u8>> {
+    let mut hashes = vec![];
+    let mut hasher = Sha1::new();
+    let mut filled = 0;
+    let mut buf = vec![0; 64 * 1024];
+    for (path, len) in files {
+        // A short file would shift every piece after it
+        let mut file = File::open(path)?.take(*len);
+        let mut read = 0;
+        loop {
+            let want = buf.len().min((piece - filled) as usize);
+            let n = file.read(&mut buf[..want])?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            filled += n as u64;
+            read += n as u64;
+            if filled == piece {
+                hashes.extend_from_slice(&hasher.finalize_reset());
+                filled = 0;
+            }
+        }
+        if read != *len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+    }
+    if filled > 0 {
+        hashes.extend_from_slice(&hasher.finalize());
+    }
+    Ok(hashes)
+}
+
+fn piece_length(total: u64) -> u64 {
+    let mut piece = MIN_PIECE;
+    while total / piece > TARGET_PIECES && piece < MAX_PIECE {
+        piece *= 2;
+    }
+    piece
+}
+
+/// The `.torrent` for `files`, web seeded from `seed`.
+fn metainfo(
+    name: &str,
+    files: &[SeedFile],
+    seed: &str,
+    trackers: &[String],
+) -> io::Result<Vec<u8>> {
+    let total = files.iter().map(|f| f.len).sum();
+    let piece = piece_length(total);
+    let sources: Vec<(PathBuf, u64)> = files.iter().map(|f| (f.source.clone(), f.len)).collect();
+    let hashes = pieces(&sources, piece)?;
+    let listed = files
+        .iter()
+        .map(|f| {
+            Value::Dict(vec![
+                ("length", Value::Int(f.len as i64)),
+                (
+                    "path",
+                    Value::List(f.path.iter().map(|c| Value::Bytes(c.as_bytes())).collect()),
+                ),
+            ])
+        })
+        .collect();
+    let info = Value::Dict(vec![
+        ("files", Value::List(listed)),
+        ("name", Value::Bytes(name.as_bytes())),
+        ("piece length", Value::Int(piece as i64)),
+        ("pieces", Value::Bytes(&hashes)),
+    ]);
+    let mut top = vec![];
+    if let Some(first) = trackers.first() {
+        top.push(("announce", Value::Bytes(first.as_bytes())));
+        let tiers = trackers
+            .iter()
+            .map(|t| Value::List(vec![Value::Bytes(t.as_bytes())]))
+            .collect();
+        top.push(("announce-list", Value::List(tiers)));
+    }
+    top.push(("created by", Value::Bytes(b"pods")));
+    top.push(("creation date", Value::Int(Utc::now().timestamp())));
+    top.push(("info", info));
+    top.push(("url-list", Value::List(vec![Value::Bytes(seed.as_bytes())])));
+    let mut out = vec![];
+    Value::Dict(top).encode(&mut out);
+    Ok(out)
+}
+
+/// Where the web seed is reached, from `[archive] public_url` or else the
+/// request's `Host`.
+fn seed_url(public_url: Option<&str>, headers: &HeaderMap, podcast: Uuid) -> Option<String> {
+    let base = match public_url {
+        Some(url) => url.trim_end_matches('/').to_string(),
+        None => format!("http://{}", headers.get(header::HOST)?.to_str().ok()?),
+    };
+    Some(format!("{}/webseed/{}/", base, podcast))
+}
+
+/// `404` unless the podcast is archived and has something to share.
+pub async fn get_torrent<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(podcast): Path<Uuid>,
+    headers: HeaderMap,
+) -> Response {
+    let (name, files, seed, trackers, dir) = {
+        let s = state.lock().await;
+        if let Err(status) = current_admin(&s) {
+            return status.into_response();
+        }
+        let (name, files) = match files(&s, podcast) {
+            Ok(f) => f,
+            Err(Error::NotFound) => return StatusCode::NOT_FOUND.into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+        let archive = &s.config.archive;
+        let Some(seed) = seed_url(archive.public_url.as_deref(), &headers, podcast) else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        let dir = archive.dir.join(podcast.to_string()).join("torrents");
+        (name, files, seed, archive.trackers.clone(), dir)
+    };
+    if files.is_empty() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let mut key = Sha256::new();
+    for part in [&name, &seed].into_iter().chain(&trackers) {
+        key.update(part.as_bytes());
+        key.update([0]);
+    }
+    for f in &files {
+        key.update(format!("{}\0{}\0{}\0", f.path.join("/"), f.len, f.sha256));
+    }
+    let key: String = key
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let cached = dir.join(format!("{}.torrent", key));
+    let torrent = match fs::read(&cached).await {
+        Ok(t) => t,
+        Err(_) => {
+            let made = task::spawn_blocking({
+                let name = name.clone();
+                move || metainfo(&name, &files, &seed, &trackers)
+            })
+            .await;
+            let Ok(Ok(t)) = made else {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            };
+            if fs::create_dir_all(&dir).await.is_ok() {
+                let _ = fs::write(&cached, &t).await;
+            }
+            t
+        }
+    };
+    let disposition = format!("attachment; filename=\"{}.torrent\"", name.replace('"', ""));
+    (
+        [
+            (header::CONTENT_TYPE, "application/x-bittorrent".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        torrent,
+    )
+        .into_response()
+}
+
+/// The web seed: files of an archived podcast's torrent, by their path in
+/// it, which starts with the torrent's name. No login, since torrent
+/// clients have none.
+pub async fn webseed<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path((podcast, path)): Path<(Uuid, String)>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    let (found, throttle) = {
+        let s = state.lock().await;
+        let Ok((name, files)) = files(&s, podcast) else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        let mut parts = path.trim_start_matches('/').split('/');
+        let found = match parts.next() {
+            Some(top) if top == name => {
+                let rest: Vec<&str> = parts.collect();
+                files.into_iter().find(|f| f.path == rest)
+            }
+            _ => None,
+        };
+        (found, s.stream_throttle.clone())
+    };
+    let Some(f) = found else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    stream::immutable_file(
+        &f.source,
+        f.content_type,
+        &f.sha256,
+        &method,
+        &headers,
+        &throttle,
+    )
+    .await
+}
@@ -0,0 +1,180 @@
+//! Low-bitrate renditions for `?quality=low`. The first request pipes the
+//! enclosure through ffmpeg and streams the output while saving it to `dir`;
+//! later requests are served from the saved file. A rendition may start some
+//! seconds in, for users who skip a podcast's intro; each start is kept as
+//! its own file.
+
+use std::{
+    collections::HashSet,
+    io,
+    path::PathBuf,
+    process::Stdio,
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use futures_util::{stream::BoxStream, StreamExt};
+use serde::Deserialize;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::mpsc,
+    task::JoinHandle,
+};
+use uuid::Uuid;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct TranscodeConfig {
+    pub ffmpeg: PathBuf,
+    pub bitrate_kbps: u32,
+    /// Where finished renditions are kept.
+    pub dir: PathBuf,
+}
+
+impl Default for TranscodeConfig {
+    fn default() -> TranscodeConfig {
+        TranscodeConfig {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            bitrate_kbps: 48,
+            dir: PathBuf::from("renditions"),
+        }
+    }
+}
+
+pub struct Transcoder {
+    config: TranscodeConfig,
+    /// Episodes and starts whose rendition is being saved.
+    making: Mutex<HashSet<(Uuid, u32)>>,
+}
+
+const READ_CHUNK: usize = 64 * 1024;
+
+impl Transcoder {
+    pub fn new(config: TranscodeConfig) -> Transcoder {
+        Transcoder {
+            config,
+            making: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Where the episode's finished rendition starting `skip_secs` in is,
+    /// if it has one.
+    pub fn path(&self, episode: Uuid, skip_secs: u32) -> PathBuf {
+        self.config.dir.join(file_name(episode, skip_secs))
+    }
+
+    /// Runs ffmpeg over `source` and returns its output as it comes. Only one
+    /// run per episode saves the rendition; it finishes even if its listener
+    /// goes away.
+    pub fn start(
+        self: &Arc<Self>,
+        episode: Uuid,
+        skip_secs: u32,
+        source: BoxStream<'static, io::Result<Bytes>>,
+    ) -> io::Result<mpsc::Receiver<Bytes>> {
+        let bitrate = format!("{}k", self.config.bitrate_kbps);
+        let mut child = Command::new(&self.config.ffmpeg)
+            .args(["-hide_banner", "-loglevel", "error", "-i", "pipe:0"])
+            // After the input, since a pipe can't be seeked: decodes and
+            // drops the skipped part
+            .args(["-ss", &skip_secs.to_string()])
+            .args(["-vn", "-ac", "1", "-b:a", &bitrate, "-f", "mp3", "pipe:1"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(io::Error::other("ffmpeg has no pipes"));
+        };
+        let fed = tokio::spawn(feed(source, stdin));
+        let keep = self.making.lock().unwrap().insert((episode, skip_secs));
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(
+            self.clone()
+                .drain((episode, skip_secs), child, stdout, fed, keep, tx),
+        );
+        Ok(rx)
+    }
+
+    async fn drain(
+        self: Arc<Self>,
+        (episode, skip_secs): (Uuid, u32),
+        mut child: Child,
+        mut stdout: ChildStdout,
+        fed: JoinHandle<bool>,
+        keep: bool,
+        tx: mpsc::Sender<Bytes>,
+    ) {
+        let part = self
+            .config
+            .dir
+            .join(format!("{}.part", file_name(episode, skip_secs)));
+        let mut file = None;
+        if keep && fs::create_dir_all(&self.config.dir).await.is_ok() {
+            file = fs::File::create(&part).await.ok();
+        }
+        let mut listening = true;
+        let mut buf = vec![0; READ_CHUNK];
+        loop {
+            let n = match stdout.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            let chunk = Bytes::copy_from_slice(&buf[..n]);
+            if let Some(f) = &mut file {
+                if f.write_all(&chunk).await.is_err() {
+                    file = None;
+                }
+            }
+            if listening && tx.send(chunk).await.is_err() {
+                listening = false;
+            }
+            if !listening && file.is_none() {
+                break;
+            }
+        }
+        // Closing stdout stops an ffmpeg nobody is reading from
+        drop(stdout);
+        let complete = fed.await.unwrap_or(false);
+        let ok = child.wait().await.is_ok_and(|s| s.success());
+        if !keep {
+            return;
+        }
+        match file {
+            Some(f) if complete && ok => {
+                drop(f);
+                let _ = fs::rename(&part, self.path(episode, skip_secs)).await;
+            }
+            _ => {
+                let _ = fs::remove_file(&part).await;
+            }
+        }
+        self.making.lock().unwrap().remove(&(episode, skip_secs));
+    }
+}
+
+/// `<episode ID>.mp3`, or `<episode ID>-<skip_secs>.mp3` for one starting
+/// later, so existing renditions keep their names.
+fn file_name(episode: Uuid, skip_secs: u32) -> String {
+    match skip_secs {
+        0 => format!("{}.mp3", episode),
+        _ => format!("{}-{}.mp3", episode, skip_secs),
+    }
+}
+
+/// Copies `source` into ffmpeg, returning whether all of it got there.
+async fn feed(mut source: BoxStream<'static, io::Result<Bytes>>, mut stdin: ChildStdin) -> bool {
+    while let Some(chunk) = source.next().await {
+        let Ok(chunk) = chunk else {
+            return false;
+        };
+        if stdin.write_all(&chunk).await.is_err() {
+            return false;
+        }
+    }
+    // Dropping stdin ends ffmpeg's input
+    true
+}
@@ -0,0 +1,507 @@
+//! Episode transcripts, made by a configured backend: a local whisper.cpp
+//! command, or a remote speech-to-text API that speaks OpenAI's
+//! `/v1/audio/transcriptions`. Admins queue single episodes or whole
+//! podcasts; a worker transcribes them one at a time, and finished
+//! transcripts are indexed for search.
+
+use std::{
+    io,
+    path::{Path as FsPath, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use reqwest::{multipart, Body};
+use serde::Deserialize;
+use tokio::{fs, io::AsyncWriteExt, process::Command, sync::Mutex};
+use uuid::Uuid;
+
+pub use pods_types::transcripts::{
+    Segment, Transcript, TranscriptHit, TranscriptQuery, TranscriptionBackend, TranscriptionJob,
+    TranscriptionStatus,
+};
+
+use crate::{
+    current_admin, explicit, fetcher::Fetcher, language, media, profiles::Acting, stream_cache,
+    AppState, Episode, Error, DB,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum TranscriptionConfig {
+    WhisperCpp(WhisperCppConfig),
+    Api(ApiConfig),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct WhisperCppConfig {
+    pub command: PathBuf,
+    /// A ggml model file.
+    pub model: PathBuf,
+    /// Spoken language code; whisper.cpp's default otherwise.
+    pub language: Option<String>,
+}
+
+impl Default for WhisperCppConfig {
+    fn default() -> WhisperCppConfig {
+        WhisperCppConfig {
+            command: PathBuf::from("whisper-cli"),
+            model: PathBuf::from("ggml-base.bin"),
+            language: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ApiConfig {
+    pub url: String,
+    /// Sent as a bearer token.
+    pub key: Option<String>,
+    pub model: String,
+}
+
+impl Default for ApiConfig {
+    fn default() -> ApiConfig {
+        ApiConfig {
+            url: "https://api.openai.com/v1/audio/transcriptions".to_string(),
+            key: None,
+            model: "whisper-1".to_string(),
+        }
+    }
+}
+
+impl TranscriptionConfig {
+    fn backend(&self) -> TranscriptionBackend {
+        match self {
+            TranscriptionConfig::WhisperCpp(_) => TranscriptionBackend::WhisperCpp,
+            TranscriptionConfig::Api(_) => TranscriptionBackend::Api,
+        }
+    }
+}
+
+/// Lowercased words of `text`, as the search index keys them.
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+}
+
+/// Background task working through queued transcriptions, oldest first.
+pub async fn worker<D: DB>(state: Arc<Mutex<AppState<D>>>) {
+    // Not the fetcher for the API: it's the operator's, and usually a
+    // self-hosted one on a private address the SSRF guard would refuse.
+    // Enclosures are users' URLs, so they go through it
+    let client = reqwest::Client::new();
+    loop {
+        let (next, notify, config, ffmpeg, http) = {
+            let s = state.lock().await;
+            let next = s.db.transcription_jobs().ok().and_then(|jobs| {
+                jobs.into_iter()
+                    .find(|j| j.status == TranscriptionStatus::Queued)
+            });
+            (
+                next,
+                s.transcription_notify.clone(),
+                s.config.transcription.clone(),
+                s.config.transcode.ffmpeg.clone(),
+                s.http.clone(),
+            )
+        };
+        let (Some(mut job), Some(config)) = (next, config) else {
+            let _ = tokio::time::timeout(Duration::from_secs(60), notify.notified()).await;
+            continue;
+        };
+
+        let (episode, local) = {
+            let s = &mut *state.lock().await;
+            job.status = TranscriptionStatus::Running;
+            let _ = s.db.save_transcription_job(job.clone());
+            (
+                s.db.get_episode(job.episode),
+                media::find(&s.db, job.episode).and_then(|d| d.path),
+            )
+        };
+        let result = match episode {
+            Ok(e) => transcribe(&config, &client, &http, &ffmpeg, &e, local).await,
+            Err(_) => Err("the episode is gone".to_string()),
+        };
+        let s = &mut *state.lock().await;
+        match result {
+            Ok(segments) => {
+                let transcript = Transcript {
+                    episode: job.episode,
+                    backend: config.backend(),
+                    created: Utc::now(),
+                    segments,
+                };
+                job.status = match s.db.save_transcript(transcript) {
+                    Ok(()) => TranscriptionStatus::Done,
+                    Err(_) => TranscriptionStatus::Failed,
+                };
+                job.error = None;
+            }
+            Err(e) => {
+                job.status = TranscriptionStatus::Failed;
+                job.error = Some(e);
+            }
+        }
+        let _ = s.db.save_transcription_job(job);
+    }
+}
+
+/// Transcribes the episode's finished download, or its enclosure fetched
+/// for the purpose.
+async fn transcribe(
+    config: &TranscriptionConfig,
+    client: &reqwest::Client,
+    http: &Fetcher,
+    ffmpeg: &FsPath,
+    episode: &Episode,
+    local: Option<PathBuf>,
+) -> Result<Vec<Segment>, String> {
+    let url = match &episode.enclosure {
+        Some(e) => e.url.clone(),
+        None => return Err("the episode has no enclosure".to_string()),
+    };
+    let dir = std::env::temp_dir().join("pods-transcription");
+    fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("can't create {}: {}", dir.display(), e))?;
+    let fetched = dir.join(format!("{}.audio", episode.id));
+    let audio = match local {
+        Some(path) => path,
+        None => {
+            fetch(http, &url, &fetched).await?;
+            fetched.clone()
+        }
+    };
+    let result = match config {
+        TranscriptionConfig::WhisperCpp(c) => {
+            whisper_cpp(c, ffmpeg, &audio, &dir, episode.id).await
+        }
+        TranscriptionConfig::Api(c) => api(c, client, &audio, &url).await,
+    };
+    let _ = fs::remove_file(&fetched).await;
+    result
+}
+
+async fn fetch(http: &Fetcher, url: &str, path: &FsPath) -> Result<(), String> {
+    let req = http
+        .get(url)
+        .map_err(|_| "the enclosure's host is blocked".to_string())?;
+    let mut resp = req
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("couldn't fetch the enclosure: {}", e))?;
+    let mut file = fs::File::create(path)
+        .await
+        .map_err(|e| format!("can't write {}: {}", path.display(), e))?;
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| format!("couldn't fetch the enclosure: {}", e))?
+    {
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("can't write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Runs a command to completion with no terminal attached.
+async fn run(cmd: &mut Command, name: &str) -> Result<(), String> {
+    let status = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status()
+        .await
+        .map_err(|e| format!("couldn't start {}: {}", name, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed ({})", name, status))
+    }
+}
+
+#[derive(Deserialize)]
+struct WhisperOutput {
+    transcription: Vec<WhisperSegment>,
+}
+
+#[derive(Deserialize)]
+struct WhisperSegment {
+    offsets: WhisperOffsets,
+    text: String,
+}
+
+/// Milliseconds.
+#[derive(Deserialize)]
+struct WhisperOffsets {
+    from: u64,
+    to: u64,
+}
+
+/// whisper.cpp only reads 16 kHz WAV, so ffmpeg converts the audio first.
+async fn whisper_cpp(
+    config: &WhisperCppConfig,
+    ffmpeg: &FsPath,
+    audio: &FsPath,
+    dir: &FsPath,
+    id: Uuid,
+) -> Result<Vec<Segment>, String> {
+    let wav = dir.join(format!("{}.wav", id));
+    let out = dir.join(id.to_string());
+    let converted = run(
+        Command::new(ffmpeg)
+            .args(["-nostdin", "-loglevel", "error", "-y", "-i"])
+            .arg(audio)
+            .args(["-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le"])
+            .arg(&wav),
+        "ffmpeg",
+    )
+    .await;
+    if let Err(e) = converted {
+        let _ = fs::remove_file(&wav).await;
+        return Err(e);
+    }
+    let mut cmd = Command::new(&config.command);
+    cmd.arg("-m").arg(&config.model).arg("-f").arg(&wav);
+    // JSON output, to `<out>.json`
+    cmd.arg("-oj").arg("-of").arg(&out);
+    if let Some(language) = &config.language {
+        cmd.arg("-l").arg(language);
+    }
+    let ran = run(&mut cmd, "whisper.cpp").await;
+    let _ = fs::remove_file(&wav).await;
+    ran?;
+    let json = out.with_extension("json");
+    let body = fs::read(&json).await;
+    let _ = fs::remove_file(&json).await;
+    let output: WhisperOutput = body
+        .ok()
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        .ok_or_else(|| "whisper.cpp wrote no readable transcript".to_string())?;
+    Ok(output
+        .transcription
+        .into_iter()
+        .map(|s| Segment {
+            start_ms: s.offsets.from,
+            end_ms: s.offsets.to,
+            text: s.text.trim().to_string(),
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct ApiOutput {
+    segments: Vec<ApiSegment>,
+}
+
+/// Seconds.
+#[derive(Deserialize)]
+struct ApiSegment {
+    start: f64,
+    end: f64,
+    text: String,
+}
+
+async fn api(
+    config: &ApiConfig,
+    client: &reqwest::Client,
+    audio: &FsPath,
+    url: &str,
+) -> Result<Vec<Segment>, String> {
+    // Streamed from disk rather than read into memory, as episodes can be
+    // hundreds of megabytes
+    let unreadable = |e: io::Error| format!("can't read {}: {}", audio.display(), e);
+    let len = fs::metadata(audio).await.map_err(unreadable)?.len();
+    let body = stream_cache::read_file(audio, 0, len.saturating_sub(1))
+        .await
+        .map_err(unreadable)?;
+    // The API tells formats apart by file name; downloads are named by hash
+    let name = url
+        .rsplit('/')
+        .next()
+        .and_then(|n| n.split(['?', '#']).next())
+        .filter(|n| n.contains('.'))
+        .unwrap_or("audio.mp3")
+        .to_string();
+    let form = multipart::Form::new()
+        .text("model", config.model.clone())
+        .text("response_format", "verbose_json")
+        .part(
+            "file",
+            multipart::Part::stream_with_length(Body::wrap_stream(body), len).file_name(name),
+        );
+    let mut req = client.post(&config.url).multipart(form);
+    if let Some(key) = &config.key {
+        req = req.bearer_auth(key);
+    }
+    let output: ApiOutput = req
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("the transcription API failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("the transcription API sent something unreadable: {}", e))?;
+    Ok(output
+        .segments
+        .into_iter()
+        .map(|s| Segment {
+            start_ms: (s.start * 1000.0) as u64,
+            end_ms: (s.end * 1000.0) as u64,
+            text: s.text.trim().to_string(),
+        })
+        .collect())
+}
+
+fn queue<D: DB>(db: &mut D, episode: Uuid) -> Result<TranscriptionJob, Error> {
+    let job = TranscriptionJob {
+        episode,
+        status: TranscriptionStatus::Queued,
+        queued: Utc::now(),
+        error: None,
+    };
+    db.save_transcription_job(job.clone())?;
+    Ok(job)
+}
+
+/// Queues an episode, redoing its transcript if it has one.
+pub async fn transcribe_episode<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    if let Err(status) = current_admin(s) {
+        return (status, Json(None));
+    }
+    if s.config.transcription.is_none() {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    }
+    match s.db.get_episode(id).and_then(|_| queue(&mut s.db, id)) {
+        Ok(job) => {
+            s.transcription_notify.notify_one();
+            (StatusCode::ACCEPTED, Json(Some(vec![job])))
+        }
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+/// Queues every episode of a podcast that has no transcript and isn't
+/// queued already, responding with the new jobs.
+pub async fn transcribe_podcast<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let s = &mut *state.lock().await;
+    if let Err(status) = current_admin(s) {
+        return (status, Json(None));
+    }
+    if s.config.transcription.is_none() {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    }
+    let db = &mut s.db;
+    let queued = db.get_podcast_by_id(id).and_then(|p| {
+        let pending: Vec<Uuid> = db
+            .transcription_jobs()?
+            .into_iter()
+            .filter(|j| {
+                matches!(
+                    j.status,
+                    TranscriptionStatus::Queued | TranscriptionStatus::Running
+                )
+            })
+            .map(|j| j.episode)
+            .collect();
+        let mut jobs = vec![];
+        for e in db.episodes(p.rss)? {
+            if pending.contains(&e.id) || db.transcript(e.id)?.is_some() {
+                continue;
+            }
+            jobs.push(queue(db, e.id)?);
+        }
+        Ok(jobs)
+    });
+    match queued {
+        Ok(jobs) => {
+            s.transcription_notify.notify_one();
+            (StatusCode::ACCEPTED, Json(Some(jobs)))
+        }
+        Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn jobs<D: DB>(State(state): State<Arc<Mutex<AppState<D>>>>) -> impl IntoResponse {
+    let s = state.lock().await;
+    if let Err(status) = current_admin(&s) {
+        return (status, Json(None));
+    }
+    match s.db.transcription_jobs() {
+        Ok(j) => (StatusCode::OK, Json(Some(j))),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn get_transcript<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match state.lock().await.db.transcript(id) {
+        Ok(Some(t)) => (StatusCode::OK, Json(Some(t))),
+        Ok(None) | Err(Error::NotFound) => (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
+
+pub async fn search<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    Acting(user): Acting,
+    Query(query): Query<TranscriptQuery>,
+) -> impl IntoResponse {
+    if words(&query.q).next().is_none() {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    }
+    let Ok(lang) = language::filter(query.lang.as_deref()) else {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    };
+    let s = state.lock().await;
+    let hide = match explicit::hidden(&s, user) {
+        Ok(hide) => hide,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    };
+    match s.db.search_transcripts(&query.q) {
+        Ok(mut hits) => {
+            if hide {
+                hits.retain(|h| {
+                    let podcast = s.db.get_podcast(h.podcast.clone());
+                    let episode = s.db.get_episode(h.episode);
+                    !(podcast.is_ok_and(|p| p.explicit) || episode.is_ok_and(|e| e.explicit))
+                });
+            }
+            if let Some(lang) = lang {
+                hits.retain(|h| {
+                    let podcast = s.db.get_podcast(h.podcast.clone()).ok();
+                    language::matches(&lang, podcast.and_then(|p| p.language).as_deref())
+                });
+            }
+            (StatusCode::OK, Json(Some(hits)))
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    }
+}
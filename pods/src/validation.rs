@@ -0,0 +1,296 @@
+//! Checks request bodies and query strings before the handler sees them.
+//! Wrapping an extractor in [`Valid`] answers requests that break a rule
+//! with a `422` naming each bad field:
+//!
+//! ```json
+//! {
+//!     "error": "invalid",
+//!     "message": "Some fields are invalid.",
+//!     "fields": [{"field": "name", "error": "required", "message": "This can't be empty."}]
+//! }
+//! ```
+
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    body::HttpBody,
+    extract::{FromRequest, FromRequestParts, Query},
+    http::{request::Parts, Request, StatusCode},
+    response::{IntoResponse, Response},
+    BoxError, Json,
+};
+use pods_types::{ApiError, FieldError};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use tokio::sync::Mutex;
+
+use crate::{
+    admin::{UserQuery, MAX_PAGE},
+    annotations::CreateAnnotation,
+    bundles::{CreateBundle, MAX_EPISODES},
+    downloads::{DownloadLatest, MAX_LATEST},
+    i18n::{Lang, Message, UserLang},
+    profiles::CreateProfile,
+    public::PodcastQuery,
+    scrobble::ScrobbleTarget,
+    service_accounts::{AddPodcast, CreateServiceAccount},
+    tenancy::CreateTenant,
+    AppState, CreateUser, Subscribe, SubscribeQuery, DB,
+};
+
+/// Longest user or profile name, in characters.
+const NAME_MAX: usize = 64;
+/// Longest feed URL, in bytes.
+const URL_MAX: usize = 2048;
+/// Longest note on an episode, in characters.
+const NOTE_MAX: usize = 10_000;
+/// Longest tenant slug.
+const SLUG_MAX: usize = 32;
+
+/// Rules a request body or query string has to follow.
+pub trait Validate {
+    fn validate(&self, fields: &mut Fields);
+}
+
+/// The broken rules found so far.
+#[derive(Default)]
+pub struct Fields(Vec<(&'static str, Message)>);
+
+impl Fields {
+    pub fn add(&mut self, field: &'static str, message: Message) {
+        self.0.push((field, message));
+    }
+
+    /// Not blank, and at most [`NAME_MAX`] characters.
+    pub fn name(&mut self, field: &'static str, value: &str) {
+        if value.trim().is_empty() {
+            self.add(field, Message::Required);
+        } else if value.chars().count() > NAME_MAX {
+            self.add(field, Message::TooLong { max: NAME_MAX });
+        }
+    }
+
+    /// An absolute `http` or `https` URL.
+    pub fn url(&mut self, field: &'static str, value: &str) {
+        if value.trim().is_empty() {
+            return self.add(field, Message::Required);
+        }
+        if value.len() > URL_MAX {
+            return self.add(field, Message::TooLong { max: URL_MAX });
+        }
+        match Url::parse(value) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            Ok(_) => self.add(field, Message::UrlScheme),
+            Err(_) => self.add(field, Message::NotUrl),
+        }
+    }
+
+    pub fn range(&mut self, field: &'static str, value: usize, min: usize, max: usize) {
+        if !(min..=max).contains(&value) {
+            self.add(field, Message::OutOfRange { min, max });
+        }
+    }
+
+    fn error(self, lang: Lang) -> Option<ApiError> {
+        if self.0.is_empty() {
+            return None;
+        }
+        Some(ApiError {
+            error: Message::Invalid.code().to_string(),
+            message: Message::Invalid.text(lang),
+            timeout_secs: None,
+            fields: self
+                .0
+                .into_iter()
+                .map(|(field, m)| FieldError {
+                    field: field.to_string(),
+                    error: m.code().to_string(),
+                    message: m.text(lang),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// The `422` for a value that broke a rule.
+fn rejection<T: Validate>(value: &T, lang: Lang) -> Option<Response> {
+    let mut fields = Fields::default();
+    value.validate(&mut fields);
+    let body = fields.error(lang)?;
+    Some((StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response())
+}
+
+/// An extractor whose value passed [`Validate`].
+pub struct Valid<E>(pub E);
+
+#[async_trait]
+impl<D, T> FromRequestParts<Arc<Mutex<AppState<D>>>> for Valid<Query<T>>
+where
+    D: DB + Send,
+    T: DeserializeOwned + Validate + Send,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<Mutex<AppState<D>>>,
+    ) -> Result<Self, Response> {
+        let query = Query::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Ok(UserLang(lang)) = UserLang::from_request_parts(parts, state).await;
+        match rejection(&query.0, lang) {
+            Some(r) => Err(r),
+            None => Ok(Valid(query)),
+        }
+    }
+}
+
+#[async_trait]
+impl<D, T, B> FromRequest<Arc<Mutex<AppState<D>>>, B> for Valid<Json<T>>
+where
+    D: DB + Send,
+    T: DeserializeOwned + Validate + Send,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = Response;
+
+    async fn from_request(
+        req: Request<B>,
+        state: &Arc<Mutex<AppState<D>>>,
+    ) -> Result<Self, Response> {
+        let (mut parts, body) = req.into_parts();
+        let Ok(UserLang(lang)) = UserLang::from_request_parts(&mut parts, state).await;
+        let json = Json::<T>::from_request(Request::from_parts(parts, body), state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        match rejection(&json.0, lang) {
+            Some(r) => Err(r),
+            None => Ok(Valid(json)),
+        }
+    }
+}
+
+impl Validate for CreateUser {
+    fn validate(&self, fields: &mut Fields) {
+        fields.name("name", &self.name);
+    }
+}
+
+impl Validate for CreateTenant {
+    fn validate(&self, fields: &mut Fields) {
+        let slug = &self.slug;
+        if slug.is_empty() {
+            fields.add("slug", Message::Required);
+        } else if slug.len() > SLUG_MAX {
+            fields.add("slug", Message::TooLong { max: SLUG_MAX });
+        } else if !slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            fields.add("slug", Message::Slug);
+        }
+        fields.name("name", &self.name);
+        fields.name("admin.name", &self.admin.name);
+    }
+}
+
+impl Validate for CreateProfile {
+    fn validate(&self, fields: &mut Fields) {
+        fields.name("name", &self.name);
+    }
+}
+
+impl Validate for CreateAnnotation {
+    fn validate(&self, fields: &mut Fields) {
+        let note = self.note.as_deref().unwrap_or_default();
+        if self.position.is_none() && note.trim().is_empty() {
+            fields.add("note", Message::Required);
+        } else if note.chars().count() > NOTE_MAX {
+            fields.add("note", Message::TooLong { max: NOTE_MAX });
+        }
+    }
+}
+
+impl Validate for Subscribe {
+    fn validate(&self, fields: &mut Fields) {
+        // Numeric directory IDs are checked by looking them up
+        match self {
+            Subscribe::Rss { rss } => fields.url("rss", rss),
+            Subscribe::ListenNotes { listennotes_id } => {
+                let id = listennotes_id;
+                if id.len() != 32 || !id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+                    fields.add("listennotes_id", Message::ListenNotesId);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Validate for CreateServiceAccount {
+    fn validate(&self, fields: &mut Fields) {
+        fields.name("name", &self.name);
+        if self.scopes.is_empty() {
+            fields.add("scopes", Message::Required);
+        }
+    }
+}
+
+impl Validate for ScrobbleTarget {
+    fn validate(&self, fields: &mut Fields) {
+        match self {
+            ScrobbleTarget::ListenBrainz { token } => {
+                if token.trim().is_empty() {
+                    fields.add("token", Message::Required);
+                }
+            }
+            ScrobbleTarget::Webhook { url, .. } => fields.url("url", url),
+        }
+    }
+}
+
+impl Validate for AddPodcast {
+    fn validate(&self, fields: &mut Fields) {
+        fields.url("rss", &self.rss);
+    }
+}
+
+impl Validate for SubscribeQuery {
+    fn validate(&self, fields: &mut Fields) {
+        if let Some(latest) = self.download_latest {
+            fields.range("download_latest", latest, 0, MAX_LATEST);
+        }
+    }
+}
+
+impl Validate for DownloadLatest {
+    fn validate(&self, fields: &mut Fields) {
+        fields.range("latest", self.latest, 1, MAX_LATEST);
+    }
+}
+
+impl Validate for CreateBundle {
+    fn validate(&self, fields: &mut Fields) {
+        fields.range("episodes", self.episodes.len(), 1, MAX_EPISODES);
+    }
+}
+
+impl Validate for UserQuery {
+    fn validate(&self, fields: &mut Fields) {
+        if let Some(limit) = self.limit {
+            fields.range("limit", limit, 1, MAX_PAGE);
+        }
+    }
+}
+
+impl Validate for PodcastQuery {
+    fn validate(&self, fields: &mut Fields) {
+        if let Some(limit) = self.limit {
+            fields.range("limit", limit, 1, MAX_PAGE);
+        }
+    }
+}
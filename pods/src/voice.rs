@@ -0,0 +1,198 @@
+//! A webhook for voice-assistant skills. The skill recognizes the intent,
+//! like "play the latest episode of X" or "resume my queue", and posts it
+//! here for the user its account is linked to; the answer is what to say
+//! and a signed link to stream. Skills call it with a service account
+//! token with the `voice` scope, or as the logged in user for themselves.
+//! Answers `404` unless `[media_links]` is configured, since the stream
+//! links are signed.
+
+use std::{cmp::Reverse, sync::Arc};
+
+use axum::{
+    extract::{OriginalUri, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use pods_types::voice::{Intent, VoicePlay, VoiceRequest, VoiceResponse};
+
+use crate::{
+    explicit,
+    i18n::{self, Lang, Message},
+    media_links::{self, MediaLink},
+    resume,
+    service_accounts::Service,
+    subscriptions, AppState, Episode, Error, PodcastChannel, DB,
+};
+
+/// Lowercase words, without punctuation, so "The Daily" is heard as
+/// "the daily".
+fn words(s: &str) -> Vec<String> {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// How well a podcast name matches what was heard: all of it, the start of
+/// it, or every word somewhere in it. `None` if not at all.
+fn score(name: &str, heard: &[String]) -> Option<u8> {
+    let name = words(name);
+    if heard.is_empty() {
+        None
+    } else if name == heard {
+        Some(3)
+    } else if name.starts_with(heard) {
+        Some(2)
+    } else if heard.iter().all(|w| name.contains(w)) {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+/// The user's subscription best matching what was heard.
+fn find_podcast(podcasts: Vec<PodcastChannel>, heard: &str) -> Option<PodcastChannel> {
+    let heard = words(heard);
+    podcasts
+        .into_iter()
+        .filter_map(|p| Some((score(&p.name, &heard)?, p)))
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, p)| p)
+}
+
+/// The episode to play and the speech for it, or the speech for why there's
+/// nothing.
+fn resolve<D: DB>(
+    s: &AppState<D>,
+    user: Uuid,
+    intent: &Intent,
+) -> Result<Result<(Episode, String), Message>, Error> {
+    let hide = explicit::hidden(s, Some(user))?;
+    let mut episodes = match intent {
+        Intent::PlayLatest { podcast } => {
+            let Some(p) = find_podcast(subscriptions(&s.db, user)?, podcast) else {
+                return Ok(Err(Message::VoiceNoPodcast {
+                    podcast: podcast.clone(),
+                }));
+            };
+            let mut episodes = s.db.episodes(p.rss.clone())?;
+            episodes.retain(|e| e.enclosure.is_some());
+            explicit::filter_episodes(&s.db, hide, &mut episodes);
+            episodes.sort_by_key(|e| Reverse(e.published));
+            if episodes.is_empty() {
+                return Ok(Err(Message::VoiceNoEpisodes { podcast: p.name }));
+            }
+            episodes
+        }
+        Intent::ResumeQueue => {
+            let now = Utc::now().naive_utc();
+            let mut episodes: Vec<Episode> =
+                s.db.queue(user)?
+                    .into_iter()
+                    .filter_map(|id| s.db.get_episode(id).ok())
+                    .filter(|e| e.enclosure.is_some())
+                    .collect();
+            explicit::filter_episodes(&s.db, hide, &mut episodes);
+            episodes.retain(|e| match resume::resume_position(&s.db, user, e.id, now) {
+                Ok(r) => r.total.is_none_or(|total| r.stopped_at < total),
+                Err(_) => true,
+            });
+            if episodes.is_empty() {
+                return Ok(Err(Message::VoiceQueueEmpty));
+            }
+            episodes
+        }
+        _ => return Err(Error::NotFound),
+    };
+    let e = episodes.remove(0);
+    let podcast = s.db.get_podcast(e.podcast.clone())?.name;
+    Ok(Ok((e, podcast)))
+}
+
+/// Resolves the intent against the user's subscriptions and queue. `403`
+/// for anyone but the user or a `voice` service account, and `404` for an
+/// unknown user.
+pub async fn webhook<D: DB>(
+    State(state): State<Arc<Mutex<AppState<D>>>>,
+    service: Option<Extension<Service>>,
+    OriginalUri(original): OriginalUri,
+    headers: HeaderMap,
+    Json(req): Json<VoiceRequest>,
+) -> impl IntoResponse {
+    let s = state.lock().await;
+    let Some(config) = &s.config.media_links else {
+        return (StatusCode::NOT_FOUND, Json(None));
+    };
+    if service.is_none() {
+        match s.current_user {
+            Some(uid) if uid == req.user => {}
+            Some(_) => return (StatusCode::FORBIDDEN, Json(None)),
+            None => return (StatusCode::UNAUTHORIZED, Json(None)),
+        }
+    }
+    let setting = match s.db.get_user(req.user) {
+        Ok(u) => u.settings.language,
+        Err(Error::NotFound) => return (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    };
+    let lang = req
+        .locale
+        .as_deref()
+        .and_then(Lang::from_tag)
+        .or(setting)
+        .or_else(|| i18n::negotiate(&headers))
+        .unwrap_or_default();
+    let (e, podcast) = match resolve(&s, req.user, &req.intent) {
+        Ok(Ok(found)) => found,
+        Ok(Err(message)) => {
+            let answer = VoiceResponse {
+                speech: message.text(lang),
+                play: None,
+            };
+            return (StatusCode::OK, Json(Some(answer)));
+        }
+        Err(Error::NotFound) => return (StatusCode::NOT_FOUND, Json(None)),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    };
+    let now = Utc::now();
+    let offset_secs = match resume::playback(&s.db, req.user, e.id, now.naive_utc()) {
+        Ok(p) => p.start_at,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(None)),
+    };
+    let expires = now.timestamp() + config.default_ttl_secs.min(config.max_ttl_secs) as i64;
+    let path = format!("/episodes/{}/audio", e.id);
+    let query = media_links::sign(&config.secret, &path, "", expires);
+    let Some(base) = media_links::base_url(
+        config.public_url.as_deref(),
+        &headers,
+        original.path(),
+        "/voice",
+    ) else {
+        return (StatusCode::BAD_REQUEST, Json(None));
+    };
+    let speech = Message::VoicePlaying {
+        episode: e.title.clone(),
+        podcast: podcast.clone(),
+    }
+    .text(lang);
+    let play = VoicePlay {
+        episode: e.id,
+        title: e.title,
+        podcast,
+        offset_secs,
+        link: MediaLink {
+            url: format!("{}{}?{}", base, path, query),
+            expires: DateTime::from_timestamp(expires, 0).unwrap_or_default(),
+        },
+    };
+    let answer = VoiceResponse {
+        speech,
+        play: Some(play),
+    };
+    (StatusCode::OK, Json(Some(answer)))
+}